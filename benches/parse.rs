@@ -0,0 +1,156 @@
+//! Parse speed and the heap a parsed tree takes, on generated documents shaped
+//! like the ones size changes to `JsonValue` are measured on: many small
+//! records, and one long array of scalars.
+//! Run with `cargo bench`, optionally naming documents, e.g. `cargo bench -- flat`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use jsonp::borrowed::BorrowedValue;
+use jsonp::parse::Parser;
+use jsonp::tokenize::Tokenizer;
+
+/// The system allocator, keeping count of the bytes live and the allocations
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+            LIVE.fetch_add(new_size, Ordering::Relaxed);
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        new
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Times each parse is repeated, of which the fastest counts
+const RUNS: usize = 5;
+
+fn records(count: usize) -> String {
+    let mut text = String::from("[");
+    for i in 0..count {
+        if i > 0 {
+            text.push_str(",\n");
+        }
+        text.push_str(&format!(
+            r#"{{"id": {}, "name": "user {}", "email": "user{}@example.com", "score": {}.5, "active": {}, "tags": ["a", "b\n"], "address": {{"city": "Göteborg", "zip": "41{:03}"}}}}"#,
+            i,
+            i,
+            i,
+            i % 100,
+            i % 2 == 0,
+            i % 1000
+        ));
+    }
+    text.push(']');
+    text
+}
+
+fn flat(count: usize) -> String {
+    let mut text = String::from("[");
+    for i in 0..count {
+        if i > 0 {
+            text.push(',');
+        }
+        match i % 4 {
+            0 => text.push_str(&i.to_string()),
+            1 => text.push_str(&format!("{}.25", i)),
+            2 => text.push_str(&format!("\"s{}\"", i)),
+            _ => text.push_str("null"),
+        }
+    }
+    text.push(']');
+    text
+}
+
+/// What one way of parsing took: the fastest time, and the heap and number of
+/// allocations of a single run, counting what it kept
+struct Measured {
+    time: Duration,
+    heap: usize,
+    allocations: usize,
+}
+
+fn measure<T>(mut parse: impl FnMut() -> T) -> Measured {
+    let mut time = Duration::MAX;
+    let mut heap = 0;
+    let mut allocations = 0;
+    for _ in 0..RUNS {
+        let (live, allocated) = (
+            LIVE.load(Ordering::Relaxed),
+            ALLOCATIONS.load(Ordering::Relaxed),
+        );
+        let start = Instant::now();
+        let kept = parse();
+        time = time.min(start.elapsed());
+        heap = LIVE.load(Ordering::Relaxed).saturating_sub(live);
+        allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocated;
+        drop(kept);
+    }
+    Measured {
+        time,
+        heap,
+        allocations,
+    }
+}
+
+fn report(document: &str, text: &str, how: &str, measured: Measured) {
+    let mb = |bytes: usize| bytes as f64 / 1e6;
+    println!(
+        "{:<8} {:<9} {:>8.1} MB/s {:>9.1} MB heap {:>9} allocations",
+        document,
+        how,
+        mb(text.len()) / measured.time.as_secs_f64(),
+        mb(measured.heap),
+        measured.allocations
+    );
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, anything else names documents to run
+    let names = env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect::<Vec<_>>();
+    let documents = [("records", records(100_000)), ("flat", flat(1_000_000))];
+    for (document, text) in &documents {
+        if !names.is_empty() && !names.iter().any(|name| document.contains(name.as_str())) {
+            continue;
+        }
+        println!("{:<8} {:.1} MB", document, text.len() as f64 / 1e6);
+        let parsed = measure(|| Parser::new(Tokenizer::new().tokens(text)).parse().unwrap());
+        report(document, text, "parse", parsed);
+        let validated = measure(|| {
+            Parser::new(Tokenizer::new().tokens(text))
+                .validate()
+                .unwrap()
+        });
+        report(document, text, "validate", validated);
+        let borrowed = measure(|| BorrowedValue::parse(text).unwrap());
+        report(document, text, "borrowed", borrowed);
+    }
+}
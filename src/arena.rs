@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+
+use crate::hash::KeyHasher;
+use crate::parse::{JsonValue, Key};
+
+/// Keys beyond this many distinct ones are no longer shared, so that objects
+/// keyed by e.g. ids do not grow the table without bound
+const MAX_KEYS: usize = 4096;
+
+/// The keys seen so far, handing out one shared copy of each
+/// Arrays of records repeat the same few keys, which then take memory once.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Interner(HashSet<Key, KeyHasher>);
+
+impl Interner {
+    pub(crate) fn with_hasher(hasher: KeyHasher) -> Self {
+        Interner(HashSet::with_hasher(hasher))
+    }
+
+    /// The shared copy of `key`, if it was seen before
+    pub(crate) fn get(&self, key: &str) -> Option<Key> {
+        self.0.get(key).cloned()
+    }
+
+    pub(crate) fn hasher(&self) -> &KeyHasher {
+        self.0.hasher()
+    }
+
+    pub(crate) fn intern(&mut self, key: &str) -> Key {
+        if let Some(shared) = self.get(key) {
+            return shared;
+        }
+        let key: Key = key.into();
+        if self.0.len() < MAX_KEYS {
+            self.0.insert(key.clone());
+        }
+        key
+    }
+}
+
+/// Allocations taken back from trees that are done with, for `Parser::with_arena`
+/// to build the next ones from
+/// A service parsing many small documents in a loop hands each tree back with
+/// `reclaim`, so that after the first few documents parsing allocates little.
+/// Keys are shared across all the documents parsed with the same arena.
+///
+/// A tree keeps no spare capacity, to keep `JsonValue` at 24 bytes, so a built
+/// container or string is shrunk to its length. A reused or presized buffer
+/// with room to spare then costs a reallocation after all, which
+/// `allocations` counts: reuse pays off for documents that look alike, and
+/// less so the more their sizes differ.
+#[derive(Clone, Debug, Default)]
+pub struct Arena {
+    vecs: Vec<Vec<JsonValue>>,
+    strings: Vec<String>,
+    /// Members emptied by `reclaim`, whose boxes are the allocations to reuse
+    /// They are kept whole, as `JsonValue` implements `Drop` and so cannot be
+    /// taken apart.
+    members: Vec<JsonValue>,
+    /// Values still to be taken apart by `reclaim`, kept to reuse its allocation
+    pending: Vec<JsonValue>,
+    keys: Interner,
+    /// Vectors, strings, boxes and keys made anew rather than reused
+    allocations: usize,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An arena sharing keys through a table hashed with `hasher`, e.g.
+    /// `KeyHasher::resistant()` for documents from untrusted sources
+    pub fn with_hasher(hasher: KeyHasher) -> Self {
+        Arena {
+            keys: Interner::with_hasher(hasher),
+            ..Self::default()
+        }
+    }
+
+    /// Take back the vectors, strings and boxes of `value`
+    pub fn reclaim(&mut self, value: JsonValue) {
+        self.pending.push(value);
+        while let Some(mut value) = self.pending.pop() {
+            match &mut value {
+                JsonValue::Object(values) | JsonValue::Arr(values) => {
+                    let mut values = std::mem::take(values).into_vec();
+                    self.pending.append(&mut values);
+                    self.vecs.push(values);
+                }
+                JsonValue::KeyedObject(_, member) => {
+                    self.pending
+                        .push(std::mem::replace(&mut **member, JsonValue::Null));
+                    self.members.push(value);
+                }
+                JsonValue::Str(s) => self.string_back(std::mem::take(s)),
+                JsonValue::Float(_, raw @ Some(_)) => {
+                    self.string_back(*raw.take().unwrap());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Allocations made for the trees built so far, rather than reused, counting
+    /// each vector once however much it grew, and the reallocations shrinking
+    /// vectors and strings to fit the tree
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    /// An empty vector with room for `capacity` values, from an earlier tree if
+    /// there is one
+    pub(crate) fn vec(&mut self, capacity: usize) -> Vec<JsonValue> {
+        match self.vecs.pop() {
+            Some(mut values) => {
+                values.reserve(capacity);
+                values
+            }
+            None => {
+                self.allocations += 1;
+                Vec::with_capacity(capacity)
+            }
+        }
+    }
+
+    /// The values of a built container, shrunk to their number
+    pub(crate) fn boxed_slice(&mut self, values: Vec<JsonValue>) -> Box<[JsonValue]> {
+        if values.capacity() > values.len() {
+            self.allocations += 1;
+        }
+        values.into_boxed_slice()
+    }
+
+    /// Give back a vector that went unused
+    pub(crate) fn vec_back(&mut self, values: Vec<JsonValue>) {
+        if values.capacity() > 0 {
+            self.vecs.push(values);
+        }
+    }
+
+    /// A copy of `text`, in a reclaimed string if there is one
+    pub(crate) fn string(&mut self, text: &str) -> Box<str> {
+        match self.strings.pop() {
+            Some(mut s) => {
+                s.push_str(text);
+                if s.capacity() > s.len() {
+                    self.allocations += 1;
+                }
+                s.into_boxed_str()
+            }
+            None => {
+                self.allocations += 1;
+                text.into()
+            }
+        }
+    }
+
+    fn string_back(&mut self, s: Box<str>) {
+        let mut s = s.into_string();
+        if s.capacity() > 0 {
+            s.clear();
+            self.strings.push(s);
+        }
+    }
+
+    /// How the table of keys is hashed, for other tables of the same keys
+    pub(crate) fn hasher(&self) -> &KeyHasher {
+        self.keys.hasher()
+    }
+
+    /// `text` as a key, shared with the earlier keys spelled the same
+    pub(crate) fn key(&mut self, text: &str) -> Key {
+        if let Some(shared) = self.keys.get(text) {
+            return shared;
+        }
+        self.allocations += 1;
+        self.keys.intern(text)
+    }
+
+    /// A member of `key` and `value`, boxing the value in a reclaimed box if
+    /// there is one
+    pub(crate) fn member(&mut self, key: Key, value: JsonValue) -> JsonValue {
+        match self.members.pop() {
+            Some(mut member) => {
+                if let JsonValue::KeyedObject(k, v) = &mut member {
+                    *k = key;
+                    **v = value;
+                }
+                member
+            }
+            None => {
+                self.allocations += 1;
+                JsonValue::KeyedObject(key, Box::new(value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    /// Parse `text` with `arena`, giving the tree back, and count what it allocated
+    fn allocations(arena: &mut Arena, text: &str) -> usize {
+        let before = arena.allocations();
+        let mut parser = Parser::with_arena(Tokenizer::new().tokens(text), std::mem::take(arena));
+        let value = parser.parse().unwrap();
+        parser.reclaim(value);
+        *arena = parser.into_arena();
+        arena.allocations() - before
+    }
+
+    #[test]
+    fn reuses_what_fits() {
+        let mut arena = Arena::new();
+        let record = r#"[{"a": "x", "b": [1, 2]}, {"a": "y", "b": [3, 4]}]"#;
+        let first = allocations(&mut arena, record);
+        assert!(first > 0);
+        assert_eq!(allocations(&mut arena, record), 0);
+    }
+
+    #[test]
+    fn counts_shrinking_to_fit() {
+        let mut arena = Arena::new();
+        allocations(&mut arena, r#"[["long string", 2, 3], 4, 5]"#);
+        // Both vectors and the string are reused, each with room to spare
+        assert_eq!(allocations(&mut arena, r#"[["s"]]"#), 3);
+    }
+}
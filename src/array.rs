@@ -0,0 +1,237 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::compare::compare;
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+use crate::serialize::to_string;
+
+impl JsonValue {
+    /// Python-style slice of an array, e.g. `slice(None, None, Some(-1))` reverses it
+    /// Negative bounds count from the end and out of range bounds are clamped.
+    /// Returns `None` for non-arrays and a zero step.
+    pub fn slice(
+        &self,
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    ) -> Option<JsonValue> {
+        let values = self.as_array()?;
+        let indices = slice_indices(values.len(), start, end, step.unwrap_or(1))?;
+        Some(JsonValue::Arr(
+            indices.into_iter().map(|i| values[i].clone()).collect(),
+        ))
+    }
+
+    /// Split an array into arrays of `size` elements, the last one possibly shorter
+    /// Returns `None` for non-arrays and a zero size.
+    pub fn chunk(&self, size: usize) -> Option<JsonValue> {
+        if size == 0 {
+            return None;
+        }
+        let chunks = self
+            .as_array()?
+            .chunks(size)
+            .map(|chunk| JsonValue::Arr(chunk.into()))
+            .collect();
+        Some(JsonValue::Arr(chunks))
+    }
+
+    /// The first `n` elements of an array
+    pub fn take(&self, n: usize) -> Option<JsonValue> {
+        let values = self.as_array()?;
+        Some(JsonValue::Arr(values.iter().take(n).cloned().collect()))
+    }
+
+    /// Everything after the first `n` elements of an array
+    pub fn skip(&self, n: usize) -> Option<JsonValue> {
+        let values = self.as_array()?;
+        Some(JsonValue::Arr(values.iter().skip(n).cloned().collect()))
+    }
+
+    /// Group the elements of an array by the value at a dotted path, e.g. `group_by("country")`
+    /// Gives an object from each distinct value to the elements that have it, in
+    /// first-seen order. String values are used as-is, others as their JSON text
+    /// and elements missing the path are grouped under `"null"`.
+    /// Paths may also be JSON Pointers. Like the other aggregations, returns `None`
+    /// for non-arrays or malformed paths.
+    pub fn group_by(&self, path: &str) -> Option<JsonValue> {
+        let groups = self.groups(path)?;
+        let members = groups
+            .into_iter()
+            .map(|(key, values)| (key, JsonValue::Arr(values.into_iter().cloned().collect())))
+            .collect();
+        Some(JsonValue::from_members(members))
+    }
+
+    /// Count the elements of an array per value at a dotted path, keyed like `group_by`
+    pub fn count_by(&self, path: &str) -> Option<JsonValue> {
+        let groups = self.groups(path)?;
+        let members = groups
+            .into_iter()
+            .map(|(key, values)| (key, JsonValue::Int(values.len() as i64)))
+            .collect();
+        Some(JsonValue::from_members(members))
+    }
+
+    /// Sum the numbers found at a dotted path, ignoring elements without one
+    /// The sum stays an integer unless a float is seen or it would overflow.
+    pub fn sum(&self, path: &str) -> Option<JsonValue> {
+        let mut int = Some(0i64);
+        let mut float = 0.0;
+        for value in self.values_at(path)? {
+            match value {
+                JsonValue::Int(i) => int = int.and_then(|sum| sum.checked_add(*i)),
+                JsonValue::Float(_, _) => int = None,
+                _ => continue,
+            }
+            float += value.as_f64().unwrap_or(0.0);
+        }
+        Some(match int {
+            Some(sum) => JsonValue::Int(sum),
+            None => JsonValue::Float(float, None),
+        })
+    }
+
+    /// The smallest value found at a dotted path, in the order used by `sort`
+    /// Gives `null` if no element has the path.
+    pub fn min(&self, path: &str) -> Option<JsonValue> {
+        let values = self.values_at(path)?;
+        Some(
+            values
+                .min_by(|a, b| compare(a, b))
+                .cloned()
+                .unwrap_or(JsonValue::Null),
+        )
+    }
+
+    /// The largest value found at a dotted path, see `min`
+    pub fn max(&self, path: &str) -> Option<JsonValue> {
+        let values = self.values_at(path)?;
+        Some(
+            values
+                .max_by(|a, b| compare(a, b))
+                .cloned()
+                .unwrap_or(JsonValue::Null),
+        )
+    }
+
+    /// The values at `path` in each element of an array, skipping misses
+    fn values_at<'a>(&'a self, path: &str) -> Option<impl Iterator<Item = &'a JsonValue>> {
+        let pointer = JsonPointer::from_path(path).ok()?;
+        let values = self.as_array()?;
+        Some(values.iter().filter_map(move |v| pointer.get(v)))
+    }
+
+    fn groups(&self, path: &str) -> Option<Vec<(String, Vec<&JsonValue>)>> {
+        let pointer = JsonPointer::from_path(path).ok()?;
+        let mut groups: Vec<(String, Vec<&JsonValue>)> = vec![];
+        for value in self.as_array()? {
+            let key = match pointer.get(value) {
+                Some(JsonValue::Str(s)) => s.to_string(),
+                Some(key) => to_string(key).unwrap_or_else(|_| "null".to_string()),
+                None => "null".to_string(),
+            };
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(value),
+                None => groups.push((key, vec![value])),
+            }
+        }
+        Some(groups)
+    }
+
+    /// Drop repeated elements of an array, keeping the first of each
+    /// Elements are equal if their canonical JSON is, so `1` and `1.0` are duplicates
+    /// and object member order does not matter.
+    pub fn dedup(&self) -> Option<JsonValue> {
+        let values = self.as_array()?;
+        let mut seen = HashSet::new();
+        let values = values
+            .iter()
+            .filter(|v| seen.insert(canonical_key(v)))
+            .cloned()
+            .collect();
+        Some(JsonValue::Arr(values))
+    }
+
+    /// Drop elements whose value at `path` was already seen, e.g. `dedup_by("/id")`
+    /// Elements without the path are always kept.
+    pub fn dedup_by(&self, path: &str) -> Option<JsonValue> {
+        let pointer = JsonPointer::from_path(path).ok()?;
+        let values = self.as_array()?;
+        let mut seen = HashSet::new();
+        let values = values
+            .iter()
+            .filter(|v| {
+                pointer
+                    .get(v)
+                    .is_none_or(|key| seen.insert(canonical_key(key)))
+            })
+            .cloned()
+            .collect();
+        Some(JsonValue::Arr(values))
+    }
+
+    /// Stable sort of an array by the value at `path`, e.g. `sort_by_path("/age")`
+    /// Values are ordered by `compare`, so mixed types are fine and elements
+    /// without the path sort first, as if it were `null`.
+    pub fn sort_by_path(&self, path: &str) -> Option<JsonValue> {
+        let pointer = JsonPointer::from_path(path).ok()?;
+        let null = JsonValue::Null;
+        self.sort_by(|a, b| {
+            let a = pointer.get(a).unwrap_or(&null);
+            let b = pointer.get(b).unwrap_or(&null);
+            compare(a, b)
+        })
+    }
+
+    /// Stable sort of an array with a comparator
+    pub fn sort_by<F>(&self, mut cmp: F) -> Option<JsonValue>
+    where
+        F: FnMut(&JsonValue, &JsonValue) -> Ordering,
+    {
+        let mut values = self.as_array()?.to_vec();
+        values.sort_by(|a, b| cmp(a, b));
+        Some(JsonValue::Arr(values.into()))
+    }
+}
+
+/// A hashable stand-in for structural equality
+/// Values that cannot be canonicalized (non-finite floats) fall back to their
+/// debug form, which only equals itself.
+fn canonical_key(value: &JsonValue) -> String {
+    value
+        .to_canonical_string()
+        .unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// The indices a Python-style slice selects from `len` elements, `None` for a zero step
+pub(crate) fn slice_indices(
+    len: usize,
+    start: Option<i64>,
+    end: Option<i64>,
+    step: i64,
+) -> Option<Vec<usize>> {
+    if step == 0 {
+        return None;
+    }
+
+    let len = len as i64;
+    let (lower, upper) = if step > 0 { (0, len) } else { (-1, len - 1) };
+    let clamp = |bound: i64| {
+        if bound < 0 {
+            (bound + len).max(lower)
+        } else {
+            bound.min(upper)
+        }
+    };
+    let mut i = start.map_or(if step > 0 { lower } else { upper }, clamp);
+    let end = end.map_or(if step > 0 { upper } else { lower }, clamp);
+
+    let mut out = vec![];
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        out.push(i as usize);
+        i += step;
+    }
+    Some(out)
+}
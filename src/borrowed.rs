@@ -0,0 +1,356 @@
+use std::borrow::Cow;
+use std::cell::OnceCell;
+
+use crate::escape::{decode, escape};
+use crate::parse::{JsonValue, ParseErrorKind, SyntaxError};
+use crate::serialize::format_float;
+use crate::tokenize::Position;
+use crate::writer::is_number;
+
+/// A JSON value that borrows from the text it was parsed from
+/// Keys without escapes are slices of the input and string values are decoded
+/// only once read, so a read-mostly pass over a document allocates little
+/// beyond its containers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BorrowedValue<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// A float with its source lexeme, kept as in `JsonValue::Float`
+    Float(f64, Option<&'a str>),
+    Str(LazyStr<'a>),
+    Arr(Vec<BorrowedValue<'a>>),
+    /// Members in the order written, `{}` being an empty one
+    Object(Vec<(Cow<'a, str>, BorrowedValue<'a>)>),
+}
+
+/// Deeper nesting is rejected rather than risking the stack
+pub(crate) const MAX_DEPTH: usize = 512;
+
+impl<'a> BorrowedValue<'a> {
+    /// Parse a document
+    /// Only the strings with escapes are copied, once they are decoded.
+    pub fn parse(input: &'a str) -> Result<Self, SyntaxError> {
+        let mut parser = BorrowedParser::new(input);
+        parser.skip_whitespace();
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.pos < input.len() {
+            return Err(parser.err(
+                ParseErrorKind::TrailingCharacters,
+                "unexpected data after the document",
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Look up the first member named `key`
+    pub fn get(&self, key: &str) -> Option<&BorrowedValue<'a>> {
+        match self {
+            BorrowedValue::Object(members) => {
+                members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            BorrowedValue::Str(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Copy into an owned tree
+    pub fn into_json(self) -> JsonValue {
+        match self {
+            BorrowedValue::Null => JsonValue::Null,
+            BorrowedValue::Bool(b) => JsonValue::Bool(b),
+            BorrowedValue::Int(i) => JsonValue::Int(i),
+            BorrowedValue::Float(f, raw) => {
+                JsonValue::Float(f, raw.map(|raw| Box::new(raw.into())))
+            }
+            BorrowedValue::Str(s) => JsonValue::Str(s.into_cow().into()),
+            BorrowedValue::Arr(values) => {
+                JsonValue::Arr(values.into_iter().map(BorrowedValue::into_json).collect())
+            }
+            BorrowedValue::Object(members) => JsonValue::from_members(
+                members
+                    .into_iter()
+                    .map(|(key, value)| (key.into_owned(), value.into_json()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A string value as written between its quotes, whose escapes are decoded the
+/// first time it is read and kept from then on
+#[derive(Clone, Debug)]
+pub struct LazyStr<'a> {
+    raw: &'a str,
+    escaped: bool,
+    decoded: OnceCell<String>,
+}
+
+impl<'a> LazyStr<'a> {
+    /// The text between the quotes, escapes included
+    pub fn raw(&self) -> &'a str {
+        self.raw
+    }
+
+    pub fn as_str(&self) -> &str {
+        if !self.escaped {
+            return self.raw;
+        }
+        self.decoded.get_or_init(|| decode(self.raw))
+    }
+
+    pub fn into_cow(self) -> Cow<'a, str> {
+        match (self.escaped, self.decoded.into_inner()) {
+            (false, _) => Cow::Borrowed(self.raw),
+            (true, Some(decoded)) => Cow::Owned(decoded),
+            (true, None) => Cow::Owned(decode(self.raw)),
+        }
+    }
+}
+
+impl PartialEq for LazyStr<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw || self.as_str() == other.as_str()
+    }
+}
+
+/// Scans JSON bytes, also for `Tape::parse`
+pub(crate) struct BorrowedParser<'a> {
+    input: &'a str,
+    pub(crate) pos: usize,
+}
+
+impl<'a> BorrowedParser<'a> {
+    pub(crate) fn new(input: &'a str) -> Self {
+        BorrowedParser { input, pos: 0 }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<BorrowedValue<'a>, SyntaxError> {
+        if depth > MAX_DEPTH {
+            return Err(self.err(ParseErrorKind::DepthExceeded, "nesting too deep"));
+        }
+        let value = match self.peek() {
+            Some(b'{') => self.object(depth)?,
+            Some(b'[') => self.array(depth)?,
+            Some(b'"') => {
+                let (raw, escaped) = self.string()?;
+                BorrowedValue::Str(LazyStr {
+                    raw,
+                    escaped,
+                    decoded: OnceCell::new(),
+                })
+            }
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            Some(b't') => {
+                self.literal("true", BorrowedValue::Bool(true), "failed to parse boolean")?
+            }
+            Some(b'f') => self.literal(
+                "false",
+                BorrowedValue::Bool(false),
+                "failed to parse boolean",
+            )?,
+            Some(b'n') => self.literal("null", BorrowedValue::Null, "failed to parse null")?,
+            Some(_) => {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "unexpected character, expected a value",
+                ))
+            }
+            None => return Err(self.err(ParseErrorKind::UnexpectedEof, "unexpected end of file")),
+        };
+        Ok(value)
+    }
+
+    fn object(&mut self, depth: usize) -> Result<BorrowedValue<'a>, SyntaxError> {
+        self.pos += 1;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Ok(BorrowedValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "expected QUOTE to start a key",
+                ));
+            }
+            let key = match self.string()? {
+                (raw, false) => Cow::Borrowed(raw),
+                (raw, true) => Cow::Owned(decode(raw)),
+            };
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "expected COLON after the key",
+                ));
+            }
+            self.skip_whitespace();
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Ok(BorrowedValue::Object(members));
+            }
+            if !self.eat(b',') {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "expected COMMA or RIGHT_CURLY",
+                ));
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<BorrowedValue<'a>, SyntaxError> {
+        self.pos += 1;
+        let mut values = vec![];
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Ok(BorrowedValue::Arr(values));
+        }
+        loop {
+            self.skip_whitespace();
+            values.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Ok(BorrowedValue::Arr(values));
+            }
+            if !self.eat(b',') {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "expected COMMA or RIGHT_BRACKET",
+                ));
+            }
+        }
+    }
+
+    pub(crate) fn literal(
+        &mut self,
+        word: &str,
+        value: BorrowedValue<'a>,
+        msg: &'static str,
+    ) -> Result<BorrowedValue<'a>, SyntaxError> {
+        if !self.input[self.pos..].starts_with(word) {
+            return Err(self.err(ParseErrorKind::UnexpectedToken, msg));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    pub(crate) fn number(&mut self) -> Result<BorrowedValue<'a>, SyntaxError> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.peek() {
+            self.pos += 1;
+        }
+        let num = &self.input[start..self.pos];
+        if !is_number(num) {
+            self.pos = start;
+            return Err(SyntaxError::invalid_number(num, self.position()));
+        }
+
+        // Integers beyond `i64` are floats, as in `Parser`
+        if !num.contains(['.', 'e', 'E']) && num != "-0" {
+            if let Ok(i) = num.parse::<i64>() {
+                return Ok(BorrowedValue::Int(i));
+            }
+        }
+        let f = num
+            .parse::<f64>()
+            .map_err(|_| self.err(ParseErrorKind::InvalidNumber, "failed to parse float"))?;
+        let raw = (*format_float(f) != *num).then_some(num);
+        Ok(BorrowedValue::Float(f, raw))
+    }
+
+    /// A string starting at its opening quote, checked but left undecoded: its
+    /// text between the quotes and whether it has escapes
+    pub(crate) fn string(&mut self) -> Result<(&'a str, bool), SyntaxError> {
+        self.pos += 1;
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        let mut escaped = false;
+        loop {
+            match bytes.get(self.pos) {
+                None => {
+                    return Err(SyntaxError::new(
+                        ParseErrorKind::UnterminatedString,
+                        "unterminated string",
+                        None,
+                    ))
+                }
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok((&self.input[start..self.pos - 1], escaped));
+                }
+                Some(b'\\') => {
+                    escaped = true;
+                    let mut rest = bytes[self.pos + 1..].iter();
+                    let mut read = 0;
+                    let decoded = escape(
+                        &mut || {
+                            read += 1;
+                            rest.next().copied()
+                        },
+                        b'"',
+                    );
+                    // Errors point at the last byte read, like the stream's
+                    self.pos += read;
+                    decoded.map_err(|msg| self.err(ParseErrorKind::InvalidString, msg))?;
+                    self.pos += 1;
+                }
+                Some(c) if *c < 0x20 => {
+                    return Err(
+                        self.err(ParseErrorKind::InvalidString, "control character in string")
+                    )
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    pub(crate) fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    pub(crate) fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    pub(crate) fn eat(&mut self, c: u8) -> bool {
+        let matched = self.peek() == Some(c);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    /// An error at the current byte
+    pub(crate) fn err(&self, kind: ParseErrorKind, msg: &'static str) -> SyntaxError {
+        SyntaxError::new(kind, msg, Some(self.position()))
+    }
+
+    /// The line and column of the current byte, only worked out for errors
+    fn position(&self) -> Position {
+        let before = &self.input.as_bytes()[..self.pos.min(self.input.len())];
+        let line = before.iter().filter(|&&c| c == b'\n').count() + 1;
+        let line_start = before
+            .iter()
+            .rposition(|&c| c == b'\n')
+            .map_or(0, |i| i + 1);
+        let col = before[line_start..]
+            .iter()
+            .filter(|&&c| c & 0xc0 != 0x80)
+            .count()
+            + 1;
+        Position::at(line, col)
+    }
+}
@@ -0,0 +1,120 @@
+use crate::parse::JsonValue;
+
+/// Naming conventions for object keys
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Case {
+    /// `user_id`
+    Snake,
+    /// `USER_ID`
+    ScreamingSnake,
+    /// `user-id`
+    Kebab,
+    /// `userId`
+    Camel,
+    /// `UserId`
+    Pascal,
+}
+
+impl Case {
+    /// Convert `key` to this convention, e.g. `"HTTPServerName"` to `"http_server_name"`
+    /// Leading underscores are kept, so `_id` stays private-looking.
+    pub fn convert(&self, key: &str) -> String {
+        let rest = key.trim_start_matches('_');
+        let mut out = key[..key.len() - rest.len()].to_string();
+
+        for (i, word) in words(rest).iter().enumerate() {
+            let lower = word.to_lowercase();
+            match self {
+                Case::Snake | Case::ScreamingSnake | Case::Kebab if i > 0 => {
+                    out.push(if *self == Case::Kebab { '-' } else { '_' })
+                }
+                _ => (),
+            }
+            match self {
+                Case::Snake | Case::Kebab => out.push_str(&lower),
+                Case::ScreamingSnake => out.push_str(&word.to_uppercase()),
+                Case::Camel if i == 0 => out.push_str(&lower),
+                Case::Camel | Case::Pascal => {
+                    let mut chars = lower.chars();
+                    out.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+                    out.push_str(chars.as_str());
+                }
+            }
+        }
+        out
+    }
+}
+
+impl JsonValue {
+    /// Convert every object key in the tree to `case`
+    pub fn rename_keys(&mut self, case: Case) {
+        self.rename_keys_with(|key| case.convert(key));
+    }
+
+    /// Rename every object key in the tree with `rename`
+    pub fn rename_keys_with<F>(&mut self, mut rename: F)
+    where
+        F: FnMut(&str) -> String,
+    {
+        rename_all(self, &mut rename);
+    }
+}
+
+fn rename_all<F>(value: &mut JsonValue, rename: &mut F)
+where
+    F: FnMut(&str) -> String,
+{
+    match value {
+        JsonValue::Object(members) => {
+            for member in members {
+                rename_all(member, rename);
+            }
+        }
+        JsonValue::KeyedObject(key, value) => {
+            *key = rename(key).into();
+            rename_all(value, rename);
+        }
+        JsonValue::Arr(values) => {
+            for value in values {
+                rename_all(value, rename);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Split a key into words at separators and case changes
+/// An uppercase run ends before its last letter if a lowercase one follows, so
+/// `HTTPServer` is `HTTP` and `Server`.
+fn words(key: &str) -> Vec<String> {
+    let chars = key.chars().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut word = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' || c == '-' || c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
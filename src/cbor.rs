@@ -0,0 +1,482 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+
+#[derive(Debug)]
+pub struct CborError(pub String);
+
+impl fmt::Display for CborError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CborError {}
+
+/// Encode as CBOR (RFC 8949) with the shortest argument for every header
+/// Floats are always written as double precision so they read back unchanged.
+pub fn to_cbor(value: &JsonValue) -> Vec<u8> {
+    let mut out = vec![];
+    encode(value, &mut out);
+    out
+}
+
+fn encode(value: &JsonValue, out: &mut Vec<u8>) {
+    match value {
+        JsonValue::Null => out.push(0xf6),
+        JsonValue::Bool(b) => out.push(if *b { 0xf5 } else { 0xf4 }),
+        JsonValue::Int(i) if *i >= 0 => header(0, *i as u64, out),
+        JsonValue::Int(i) => header(1, !*i as u64, out),
+        JsonValue::Float(f, _) => {
+            out.push(0xfb);
+            out.extend_from_slice(&f.to_be_bytes());
+        }
+        JsonValue::Str(s) => {
+            header(3, s.len() as u64, out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        JsonValue::Arr(values) => {
+            header(4, values.len() as u64, out);
+            for value in values {
+                encode(value, out);
+            }
+        }
+        _ => {
+            let members = value.members().collect::<Vec<_>>();
+            header(5, members.len() as u64, out);
+            for (key, value) in members {
+                encode(&JsonValue::Str(key.into()), out);
+                encode(value, out);
+            }
+        }
+    }
+}
+
+/// Write the initial byte of a data item and its argument
+fn header(major: u8, arg: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    match arg {
+        0..=23 => out.push(major | arg as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, arg as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(arg as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(arg as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+}
+
+/// Decode one CBOR data item, converting to JSON as RFC 8949 section 6.1 suggests
+/// Byte strings become base64url strings without padding, or base64 or hex
+/// inside tags 22 and 23; other tags are dropped in favour of their content.
+/// Integer map keys become decimal strings, other non-string keys are errors.
+/// Undefined, other simple values, NaN and infinities become null, and
+/// integers beyond the range of `i64` become floats.
+pub fn from_cbor(bytes: &[u8]) -> Result<JsonValue, CborError> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    let value = decoder.item(0, Binary::Base64Url)?;
+    if decoder.pos < bytes.len() {
+        return Err(decoder.err("trailing bytes after the item"));
+    }
+    Ok(value)
+}
+
+/// Deeper nesting is rejected rather than risking the stack
+const MAX_DEPTH: usize = 512;
+
+/// How byte strings are written as text
+#[derive(Clone, Copy)]
+enum Binary {
+    Base64Url,
+    Base64,
+    Hex,
+}
+
+/// A decoded item, or the break that ends an indefinite-length item
+enum Item {
+    Value(JsonValue),
+    Break,
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn err(&self, msg: &str) -> CborError {
+        CborError(format!("CBOR error: {} at byte {}", msg, self.pos))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CborError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| self.err("unexpected end of input"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u64, CborError> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// The argument of an initial byte, `None` for an indefinite length
+    fn argument(&mut self, info: u8) -> Result<Option<u64>, CborError> {
+        match info {
+            0..=23 => Ok(Some(info as u64)),
+            24..=27 => self.uint(1 << (info - 24)).map(Some),
+            31 => Ok(None),
+            _ => Err(self.err(&format!("reserved additional info {}", info))),
+        }
+    }
+
+    fn item(&mut self, depth: usize, binary: Binary) -> Result<JsonValue, CborError> {
+        match self.next(depth, binary)? {
+            Item::Value(value) => Ok(value),
+            Item::Break => {
+                self.pos -= 1;
+                Err(self.err("unexpected break"))
+            }
+        }
+    }
+
+    fn next(&mut self, depth: usize, binary: Binary) -> Result<Item, CborError> {
+        if depth > MAX_DEPTH {
+            return Err(self.err("nesting too deep"));
+        }
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return self.simple(info);
+        }
+
+        let arg = self.argument(info)?;
+        let value = match (major, arg) {
+            (0, Some(n)) => {
+                i64::try_from(n).map_or(JsonValue::Float(n as f64, None), JsonValue::Int)
+            }
+            (1, Some(n)) => i64::try_from(n).map_or(JsonValue::Float(-1.0 - n as f64, None), |n| {
+                JsonValue::Int(-1 - n)
+            }),
+            (2, _) => {
+                let bytes = self.chunks(2, arg)?;
+                JsonValue::Str(encode_binary(&bytes, binary).into())
+            }
+            (3, _) => {
+                let start = self.pos;
+                let bytes = self.chunks(3, arg)?;
+                match String::from_utf8(bytes) {
+                    Ok(s) => JsonValue::Str(s.into()),
+                    Err(_) => {
+                        self.pos = start;
+                        return Err(self.err("text string is not valid UTF-8"));
+                    }
+                }
+            }
+            (4, _) => {
+                let mut values = vec![];
+                while arg.is_none_or(|len| (values.len() as u64) < len) {
+                    match self.next(depth + 1, binary)? {
+                        Item::Value(value) => values.push(value),
+                        Item::Break if arg.is_none() => break,
+                        Item::Break => return Err(self.err("unexpected break")),
+                    }
+                }
+                JsonValue::Arr(values.into())
+            }
+            (5, _) => {
+                let mut members = vec![];
+                while arg.is_none_or(|len| (members.len() as u64) < len) {
+                    let key = match self.next(depth + 1, binary)? {
+                        Item::Value(JsonValue::Int(i)) => i.to_string().into(),
+                        Item::Value(value) => match value.into_str() {
+                            Ok(key) => key,
+                            Err(other) => {
+                                return Err(self.err(&format!(
+                                    "map keys must be strings or integers, not {}",
+                                    other.type_name()
+                                )))
+                            }
+                        },
+                        Item::Break if arg.is_none() => break,
+                        Item::Break => return Err(self.err("unexpected break")),
+                    };
+                    members.push((key, self.item(depth + 1, binary)?));
+                }
+                JsonValue::from_members(members)
+            }
+            (6, Some(tag)) => {
+                let binary = match tag {
+                    21 => Binary::Base64Url,
+                    22 => Binary::Base64,
+                    23 => Binary::Hex,
+                    _ => binary,
+                };
+                self.item(depth + 1, binary)?
+            }
+            _ => return Err(self.err("indefinite length for an integer or tag")),
+        };
+        Ok(Item::Value(value))
+    }
+
+    /// Major type 7: floats, booleans, null and other simple values
+    fn simple(&mut self, info: u8) -> Result<Item, CborError> {
+        let value = match info {
+            20 => JsonValue::Bool(false),
+            21 => JsonValue::Bool(true),
+            24 => {
+                self.take(1)?;
+                JsonValue::Null
+            }
+            25 => float(half(self.uint(2)? as u16)),
+            26 => float(f32::from_bits(self.uint(4)? as u32) as f64),
+            27 => float(f64::from_bits(self.uint(8)?)),
+            28..=30 => return Err(self.err(&format!("reserved additional info {}", info))),
+            31 => return Ok(Item::Break),
+            // null, undefined and unassigned simple values
+            _ => JsonValue::Null,
+        };
+        Ok(Item::Value(value))
+    }
+
+    /// The content of a byte or text string, joining the chunks of an indefinite one
+    fn chunks(&mut self, major: u8, len: Option<u64>) -> Result<Vec<u8>, CborError> {
+        if let Some(len) = len {
+            let len = usize::try_from(len).map_err(|_| self.err("string too long"))?;
+            return Ok(self.take(len)?.to_vec());
+        }
+
+        let mut out = vec![];
+        loop {
+            let initial = self.take(1)?[0];
+            if initial == 0xff {
+                return Ok(out);
+            }
+            if initial >> 5 != major {
+                self.pos -= 1;
+                return Err(self.err("chunk of the wrong type in an indefinite string"));
+            }
+            match self.argument(initial & 0x1f)? {
+                Some(len) => {
+                    let len = usize::try_from(len).map_err(|_| self.err("string too long"))?;
+                    out.extend_from_slice(self.take(len)?);
+                }
+                None => return Err(self.err("nested indefinite string")),
+            }
+        }
+    }
+}
+
+/// NaN and infinities have no JSON form and are read as null
+fn float(f: f64) -> JsonValue {
+    if f.is_finite() {
+        JsonValue::Float(f, None)
+    } else {
+        JsonValue::Null
+    }
+}
+
+/// Widen an IEEE 754 half-precision float
+fn half(bits: u16) -> f64 {
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent as i32 - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+fn encode_binary(bytes: &[u8], binary: Binary) -> String {
+    const URL: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    const STANDARD: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let alphabet = match binary {
+        Binary::Hex => return bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+        Binary::Base64Url => URL,
+        Binary::Base64 => STANDARD,
+    };
+
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        if let Binary::Base64 = binary {
+            out.push_str(&"=="[..3 - chunk.len()]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::serialize::to_string;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn unhex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode(text: &str) -> Result<String, String> {
+        from_cbor(&unhex(text))
+            .map(|value| to_string(&value).unwrap())
+            .map_err(|e| e.0)
+    }
+
+    #[test]
+    fn encodes_known_vectors() {
+        // From RFC 8949 appendix A, except that floats are always doubles
+        let cases = [
+            ("0", "00"),
+            ("23", "17"),
+            ("24", "1818"),
+            ("100", "1864"),
+            ("1000", "1903e8"),
+            ("1000000", "1a000f4240"),
+            ("1000000000000", "1b000000e8d4a51000"),
+            ("-1", "20"),
+            ("-100", "3863"),
+            ("-1000", "3903e7"),
+            ("1.1", "fb3ff199999999999a"),
+            ("[false, true, null]", "83f4f5f6"),
+            (r#""""#, "60"),
+            (r#""IETF""#, "6449455446"),
+            (r#""ü""#, "62c3bc"),
+            ("[1, [2, 3], [4, 5]]", "8301820203820405"),
+            (r#"{"a": 1, "b": [2, 3]}"#, "a26161016162820203"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(hex(&to_cbor(&json(text))), expected, "{}", text);
+            assert_eq!(decode(expected).unwrap(), to_string(&json(text)).unwrap());
+        }
+
+        let items = format!("[{}]", ["1"; 25].join(","));
+        assert!(hex(&to_cbor(&json(&items))).starts_with("981901"));
+    }
+
+    #[test]
+    fn decodes_rfc_vectors() {
+        let cases = [
+            ("f93c00", "1.0"),
+            ("f93e00", "1.5"),
+            ("f97bff", "65504.0"),
+            ("f9c400", "-4.0"),
+            ("f90001", "5.960464477539063e-8"),
+            ("fa47c35000", "100000.0"),
+            ("f97c00", "null"),
+            ("f97e00", "null"),
+            ("f7", "null"),
+            ("f0", "null"),
+            ("f8ff", "null"),
+            (
+                "c074323031332d30332d32315432303a30343a30305a",
+                r#""2013-03-21T20:04:00Z""#,
+            ),
+            ("4401020304", r#""AQIDBA""#),
+            ("d64401020304", r#""AQIDBA==""#),
+            ("d74401020304", r#""01020304""#),
+            ("a201020304", r#"{"1":2,"3":4}"#),
+            ("5f42010243030405ff", r#""AQIDBAU""#),
+            ("7f657374726561646d696e67ff", r#""streaming""#),
+            ("9fff", "[]"),
+            ("9f018202039f0405ffff", "[1,[2,3],[4,5]]"),
+            ("bf61610161629f0203ffff", r#"{"a":1,"b":[2,3]}"#),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(decode(bytes).unwrap(), expected, "{}", bytes);
+        }
+
+        let max = from_cbor(&unhex("1bffffffffffffffff")).unwrap();
+        assert_eq!(max.as_f64(), Some(u64::MAX as f64));
+        let min = from_cbor(&unhex("3bffffffffffffffff")).unwrap();
+        assert_eq!(min.as_f64(), Some(-1.0 - u64::MAX as f64));
+    }
+
+    #[test]
+    fn rejects_malformed_items() {
+        let err = |text: &str| decode(text).unwrap_err();
+        assert_eq!(
+            err("1c"),
+            "CBOR error: reserved additional info 28 at byte 1"
+        );
+        assert_eq!(err("ff"), "CBOR error: unexpected break at byte 0");
+        assert_eq!(err("8201ff"), "CBOR error: unexpected break at byte 3");
+        assert_eq!(
+            err("1f"),
+            "CBOR error: indefinite length for an integer or tag at byte 1"
+        );
+        assert_eq!(
+            err("5f6161ff"),
+            "CBOR error: chunk of the wrong type in an indefinite string at byte 1"
+        );
+        assert_eq!(
+            err("5f5f"),
+            "CBOR error: nested indefinite string at byte 2"
+        );
+        assert_eq!(
+            err("a1f501"),
+            "CBOR error: map keys must be strings or integers, not boolean at byte 2"
+        );
+        assert_eq!(
+            err("62c328"),
+            "CBOR error: text string is not valid UTF-8 at byte 1"
+        );
+        assert_eq!(err("9f01"), "CBOR error: unexpected end of input at byte 2");
+        assert_eq!(
+            err("f6f6"),
+            "CBOR error: trailing bytes after the item at byte 1"
+        );
+    }
+
+    #[test]
+    fn rejects_nesting_beyond_max_depth() {
+        let nested = |depth: usize| {
+            let mut bytes = vec![0x81; depth];
+            bytes.push(0xf6);
+            bytes
+        };
+        assert!(from_cbor(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            from_cbor(&nested(MAX_DEPTH + 1)).unwrap_err().0,
+            format!("CBOR error: nesting too deep at byte {}", MAX_DEPTH + 1)
+        );
+        // Tags nest too, so a chain of them cannot exhaust the stack either
+        let mut tags = vec![0xc0; MAX_DEPTH + 1];
+        tags.push(0xf6);
+        assert!(from_cbor(&tags).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use crate::parse::JsonValue;
+
+/// Read a number out of a string such as `"42"`, `" -1.5 "` or `"1e3"`
+/// Integers that fit in an `i64` stay integers. Returns `None` for anything else,
+/// including `inf` and `NaN`.
+pub fn coerce_to_number(s: &str) -> Option<JsonValue> {
+    let s = s.trim();
+    if let Ok(i) = s.parse::<i64>() {
+        return Some(JsonValue::Int(i));
+    }
+    if s.contains(|c: char| c.is_alphabetic() && c != 'e' && c != 'E') {
+        return None;
+    }
+    let f = s.parse::<f64>().ok().filter(|f| f.is_finite())?;
+    Some(JsonValue::Float(f, None))
+}
+
+/// Read a boolean out of a string, ignoring case and surrounding whitespace
+/// Accepts `true`/`false`, `yes`/`no`, `on`/`off` and `1`/`0`.
+pub fn coerce_to_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Convert a string to the first of `types` it can represent, e.g. `"integer"`
+/// Used by `JsonValue::coerce`; gives `None` if no conversion applies.
+pub(crate) fn coerce_str(s: &str, types: &[String]) -> Option<JsonValue> {
+    types.iter().find_map(|t| match t.as_str() {
+        "integer" => match coerce_to_number(s)? {
+            JsonValue::Float(f, _) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                Some(JsonValue::Int(f as i64))
+            }
+            JsonValue::Float(_, _) => None,
+            n => Some(n),
+        },
+        "number" => coerce_to_number(s),
+        "boolean" => coerce_to_bool(s).map(JsonValue::Bool),
+        "null" => (s.trim() == "null").then_some(JsonValue::Null),
+        _ => None,
+    })
+}
@@ -0,0 +1,77 @@
+use std::cmp::Ordering;
+
+use crate::parse::JsonValue;
+
+/// Structural equality where numbers may differ by up to `epsilon`
+/// Integers and floats compare by value and object member order is ignored,
+/// e.g. `{"x": 0.1, "y": 2}` equals `{"y": 2.0, "x": 0.1000001}` with `epsilon = 1e-6`.
+pub fn approx_eq(a: &JsonValue, b: &JsonValue, epsilon: f64) -> bool {
+    match (a, b) {
+        (JsonValue::Str(a), JsonValue::Str(b)) => a == b,
+        (JsonValue::Bool(a), JsonValue::Bool(b)) => a == b,
+        (JsonValue::Null, JsonValue::Null) => true,
+        (JsonValue::Int(a), JsonValue::Int(b)) => a.abs_diff(*b) as f64 <= epsilon,
+        (JsonValue::Arr(a), JsonValue::Arr(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| approx_eq(a, b, epsilon))
+        }
+        _ if a.is_object() && b.is_object() => {
+            a.members().count() == b.members().count()
+                && a.members()
+                    .all(|(key, a)| b.get(key).is_some_and(|b| approx_eq(a, b, epsilon)))
+        }
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => a == b || (a - b).abs() <= epsilon,
+            _ => false,
+        },
+    }
+}
+
+/// Total order over JSON values, the one jq uses:
+/// null < false < true < numbers < strings < arrays < objects.
+/// Numbers compare by value regardless of int/float, arrays element-wise and
+/// objects first by their sorted key sets, then by the values of those keys.
+pub fn compare(a: &JsonValue, b: &JsonValue) -> Ordering {
+    fn rank(value: &JsonValue) -> u8 {
+        match value {
+            JsonValue::Null => 0,
+            JsonValue::Bool(false) => 1,
+            JsonValue::Bool(true) => 2,
+            JsonValue::Float(_, _) | JsonValue::Int(_) => 3,
+            JsonValue::Str(_) => 4,
+            JsonValue::Arr(_) => 5,
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty => 6,
+        }
+    }
+
+    match (a, b) {
+        (JsonValue::Int(a), JsonValue::Int(b)) => a.cmp(b),
+        (JsonValue::Str(a), JsonValue::Str(b)) => a.cmp(b),
+        (JsonValue::Arr(a), JsonValue::Arr(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|o| o.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+        _ if a.is_object() => {
+            let mut a = a.members().collect::<Vec<_>>();
+            let mut b = b.members().collect::<Vec<_>>();
+            a.sort_by_key(|(k, _)| *k);
+            b.sort_by_key(|(k, _)| *k);
+
+            let a_keys = a.iter().map(|(k, _)| *k);
+            let b_keys = b.iter().map(|(k, _)| *k);
+            a_keys.cmp(b_keys).then_with(|| {
+                a.iter()
+                    .zip(&b)
+                    .map(|((_, a), (_, b))| compare(a, b))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        },
+    }
+}
@@ -0,0 +1,201 @@
+//! `jsonp completions`: shell completion scripts generated from the usage table
+//! A command completes the flags its usage mentions, along with the flags no
+//! usage mentions, which apply to every command.
+
+use crate::{COMMANDS, FLAGS, SHORT_FLAGS};
+
+/// The completion script for `shell`, `None` for shells without one
+pub fn script(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+/// Command names in the order of the usage text, each once
+fn commands() -> Vec<&'static str> {
+    let mut names: Vec<&str> = vec![];
+    for (name, _) in COMMANDS {
+        if !names.contains(name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Long flags mentioned in a usage string, in order
+fn mentioned(usage: &str) -> Vec<&'static str> {
+    let words = usage
+        .split(|c: char| c.is_whitespace() || "[]|=".contains(c))
+        .map(
+            |word| match SHORT_FLAGS.iter().find(|(short, _)| *short == word) {
+                Some((_, long)) => *long,
+                None => word,
+            },
+        )
+        .collect::<Vec<_>>();
+    FLAGS
+        .iter()
+        .map(|(flag, _)| *flag)
+        .filter(|flag| words.contains(flag))
+        .collect()
+}
+
+/// Flags of one command, `None` for the invocation without a command
+fn flags(command: Option<&str>) -> Vec<&'static str> {
+    let usages = COMMANDS.iter().map(|(_, usage)| *usage).collect::<Vec<_>>();
+    let everywhere = usages
+        .iter()
+        .flat_map(|usage| mentioned(usage))
+        .collect::<Vec<_>>();
+    let own = COMMANDS
+        .iter()
+        .filter(|(name, _)| Some(*name) == command)
+        .flat_map(|(_, usage)| mentioned(usage))
+        .collect::<Vec<_>>();
+    FLAGS
+        .iter()
+        .map(|(flag, _)| *flag)
+        .filter(|flag| own.contains(flag) || !everywhere.contains(flag))
+        .collect()
+}
+
+/// Short spellings of the given flags
+fn shorts(flags: &[&str]) -> Vec<&'static str> {
+    SHORT_FLAGS
+        .iter()
+        .filter(|(_, long)| flags.contains(long))
+        .map(|(short, _)| *short)
+        .collect()
+}
+
+fn takes_value(flag: &str) -> bool {
+    FLAGS.iter().any(|(name, value)| *name == flag && *value)
+}
+
+fn bash() -> String {
+    let mut cases = String::new();
+    for command in commands() {
+        let mut words = flags(Some(command));
+        words.extend(shorts(&words));
+        cases.push_str(&format!(
+            "        {}) flags=\"{}\" ;;\n",
+            command,
+            words.join(" ")
+        ));
+    }
+    let mut global = flags(None);
+    global.extend(shorts(&global));
+    let valued = FLAGS
+        .iter()
+        .filter(|(_, value)| *value)
+        .map(|(flag, _)| *flag)
+        .collect::<Vec<_>>();
+
+    format!(
+        r#"_jsonp() {{
+    local cur prev flags
+    cur=${{COMP_WORDS[COMP_CWORD]}}
+    prev=${{COMP_WORDS[COMP_CWORD-1]}}
+    case "$prev" in
+        {valued}) return ;;
+    esac
+    if [[ $COMP_CWORD -eq 1 && $cur != -* ]]; then
+        COMPREPLY=($(compgen -W "{commands}" -- "$cur"))
+        return
+    fi
+    case "${{COMP_WORDS[1]}}" in
+{cases}        *) flags="{global}" ;;
+    esac
+    if [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W "$flags" -- "$cur"))
+    fi
+}}
+complete -o default -F _jsonp jsonp
+"#,
+        valued = valued.join("|"),
+        commands = commands().join(" "),
+        cases = cases,
+        global = global.join(" "),
+    )
+}
+
+fn zsh() -> String {
+    let mut cases = String::new();
+    for command in commands() {
+        let mut words = flags(Some(command));
+        words.extend(shorts(&words));
+        cases.push_str(&format!(
+            "        {}) flags=({}) ;;\n",
+            command,
+            words.join(" ")
+        ));
+    }
+    let mut global = flags(None);
+    global.extend(shorts(&global));
+
+    format!(
+        r#"#compdef jsonp
+
+_jsonp() {{
+    local -a flags
+    if (( CURRENT == 2 )) && [[ $PREFIX != -* ]]; then
+        compadd -- {commands}
+        _files
+        return
+    fi
+    case $words[2] in
+{cases}        *) flags=({global}) ;;
+    esac
+    if [[ $PREFIX == -* ]]; then
+        compadd -- $flags
+    else
+        _files
+    fi
+}}
+
+compdef _jsonp jsonp
+"#,
+        commands = commands().join(" "),
+        cases = cases,
+        global = global.join(" "),
+    )
+}
+
+fn fish() -> String {
+    let commands = commands();
+    let mut out = String::from("complete -c jsonp -n __fish_use_subcommand -a '");
+    out.push_str(&commands.join(" "));
+    out.push_str("'\n");
+
+    let line = |out: &mut String, condition: Option<&str>, flag: &str| {
+        out.push_str("complete -c jsonp");
+        if let Some(condition) = condition {
+            out.push_str(&format!(" -n '{}'", condition));
+        }
+        out.push_str(&format!(" -l {}", &flag[2..]));
+        if let Some((short, _)) = SHORT_FLAGS.iter().find(|(_, long)| *long == flag) {
+            out.push_str(&format!(" -s {}", &short[1..]));
+        }
+        if takes_value(flag) {
+            out.push_str(" -r");
+        }
+        out.push('\n');
+    };
+    for flag in flags(None) {
+        line(&mut out, None, flag);
+    }
+    let global = flags(None);
+    for command in commands {
+        let condition = format!("__fish_seen_subcommand_from {}", command);
+        for flag in flags(Some(command))
+            .into_iter()
+            .filter(|flag| !global.contains(flag))
+        {
+            line(&mut out, Some(&condition), flag);
+        }
+    }
+    out
+}
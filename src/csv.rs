@@ -0,0 +1,173 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::serialize::{to_string, LineEnding};
+
+#[derive(Debug)]
+pub struct CsvError(pub String);
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// How values nested inside the objects become cells
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flatten {
+    /// One column per top-level key, nested values written as compact JSON
+    Json,
+    /// One column per leaf, named by its dotted path, e.g. `user.name` or `tags[0]`
+    Paths,
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub flatten: Flatten,
+    pub delimiter: char,
+    pub line_ending: LineEnding,
+    /// The columns to write, in this order, instead of every column found
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            flatten: Flatten::Json,
+            delimiter: ',',
+            line_ending: LineEnding::Lf,
+            columns: None,
+        }
+    }
+}
+
+/// Convert an array of objects to CSV, see `to_csv_with`
+pub fn to_csv(value: &JsonValue) -> Result<String, CsvError> {
+    to_csv_with(value, &CsvOptions::default())
+}
+
+/// Convert an array of objects to CSV
+/// The header is the union of all columns in first-seen order unless `columns`
+/// are given; missing cells and nulls are left empty. Fields are quoted when they contain the delimiter,
+/// a quote or a line break (RFC 4180).
+pub fn to_csv_with(value: &JsonValue, options: &CsvOptions) -> Result<String, CsvError> {
+    let JsonValue::Arr(rows) = value else {
+        return Err(CsvError(
+            "CSV error: expected an array of objects".to_string(),
+        ));
+    };
+
+    let mut table = vec![];
+    for (i, row) in rows.iter().enumerate() {
+        if !row.is_object() {
+            return Err(CsvError(format!(
+                "CSV error: element {} is {}, not an object",
+                i,
+                row.type_name()
+            )));
+        }
+
+        let mut cells = vec![];
+        for (key, value) in row.members() {
+            match options.flatten {
+                Flatten::Json => cells.push((column_name(key, true), cell(value)?)),
+                Flatten::Paths => flatten(value, column_name(key, true), &mut cells)?,
+            }
+        }
+        table.push(cells);
+    }
+
+    let mut header: Vec<&String> = vec![];
+    match &options.columns {
+        Some(columns) => header.extend(columns),
+        None => {
+            for (column, _) in table.iter().flatten() {
+                if !header.contains(&column) {
+                    header.push(column);
+                }
+            }
+        }
+    }
+
+    let line_ending = match options.line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+    };
+    let mut out = String::new();
+    let header_cells = header.iter().map(|column| column.as_str());
+    write_record(&mut out, header_cells, options.delimiter, line_ending);
+    for cells in &table {
+        let record = header.iter().map(|column| {
+            cells
+                .iter()
+                .find(|(c, _)| c == *column)
+                .map_or("", |(_, cell)| cell.as_str())
+        });
+        write_record(&mut out, record, options.delimiter, line_ending);
+    }
+    Ok(out)
+}
+
+/// Add a cell for every leaf below `value`, named by its dotted path from the row
+fn flatten(
+    value: &JsonValue,
+    name: String,
+    cells: &mut Vec<(String, String)>,
+) -> Result<(), CsvError> {
+    match value {
+        JsonValue::Arr(values) if !values.is_empty() => {
+            for (i, v) in values.iter().enumerate() {
+                flatten(v, format!("{}[{}]", name, i), cells)?;
+            }
+        }
+        JsonValue::Object(_) | JsonValue::KeyedObject(_, _) => {
+            for (key, v) in value.members() {
+                flatten(v, format!("{}{}", name, column_name(key, false)), cells)?;
+            }
+        }
+        _ => cells.push((name, cell(value)?)),
+    }
+    Ok(())
+}
+
+/// The dotted path segment for `key`, bracketed if it would not parse back
+fn column_name(key: &str, first: bool) -> String {
+    if key.is_empty() || key.contains(['.', '[', ']', '"']) {
+        format!("[\"{}\"]", key)
+    } else if first {
+        key.to_string()
+    } else {
+        format!(".{}", key)
+    }
+}
+
+fn cell(value: &JsonValue) -> Result<String, CsvError> {
+    match value {
+        JsonValue::Str(s) => Ok(s.to_string()),
+        JsonValue::Null => Ok(String::new()),
+        value => to_string(value).map_err(|e| CsvError(e.0)),
+    }
+}
+
+fn write_record<'a>(
+    out: &mut String,
+    fields: impl Iterator<Item = &'a str>,
+    delimiter: char,
+    line_ending: &str,
+) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(delimiter);
+        }
+        if field.contains([delimiter, '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str(line_ending);
+}
@@ -0,0 +1,275 @@
+//! How failures are reported: as text for people, or with `--error-format json`
+//! as one JSON object per line on stderr, for editors and CI to read.
+//! Messages keep the layout `<file>: <Kind> error: <message> at line L column C`
+//! used throughout. Syntax errors and schema violations fill in the fields of
+//! the objects from what they know, other messages are picked apart.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use jsonp::parse::{JsonValue, ParseErrorKind, SyntaxError};
+use jsonp::serialize::to_string;
+
+/// Report a failure like `eprintln!`, in the chosen error format
+macro_rules! report {
+    ($($arg:tt)*) => {
+        $crate::diagnostic::Diagnostic::parse(format!($($arg)*)).emit()
+    };
+}
+
+static JSON: OnceLock<bool> = OnceLock::new();
+
+/// Report every further failure as JSON
+pub fn use_json() {
+    JSON.get_or_init(|| true);
+}
+
+/// Whether failures are reported as JSON, in which case other notes on stderr
+/// are left out so that every line of it parses
+pub fn is_json() -> bool {
+    JSON.get().copied().unwrap_or(false)
+}
+
+/// Where a document came from: a file, or one record of NDJSON in it
+#[derive(Clone, Copy)]
+pub struct Origin<'a> {
+    pub file: &'a str,
+    /// The line number of the record and the byte offset of its start
+    pub record: Option<(usize, usize)>,
+}
+
+impl<'a> Origin<'a> {
+    pub fn file(file: &'a str) -> Self {
+        Origin { file, record: None }
+    }
+}
+
+/// The file, followed by the line of the record as in `data.ndjson:3`
+impl fmt::Display for Origin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.record {
+            Some((line, _)) => write!(f, "{}:{}", self.file, line),
+            None => f.write_str(self.file),
+        }
+    }
+}
+
+pub struct Diagnostic {
+    /// The message as printed in the text format
+    text: String,
+    pub file: Option<String>,
+    /// Line and column, counting from 1
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// Byte offset into the file, known when its source is at hand
+    pub offset: Option<usize>,
+    /// The kind of error in lowercase, e.g. `syntax`, `schema` or `io`
+    pub code: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// A failure to parse `source`, the text of the document from `origin`
+    pub fn syntax(origin: Origin, err: &SyntaxError, source: &str) -> Self {
+        let code = match err.kind() {
+            ParseErrorKind::Io => "io",
+            _ => "syntax",
+        };
+        let at = err.position().map(|at| (at.line(), at.column()));
+        let mut diagnostic = Diagnostic::located(origin, code, err.message(), at, source);
+        diagnostic.text = format!("{}: {}", origin, err);
+        if code == "syntax" {
+            if let Some(offset) = diagnostic.offset {
+                let offset = offset - origin.record.map_or(0, |(_, start)| start);
+                diagnostic.suggestion = suggestion(source, offset).map(str::to_string);
+            }
+        }
+        diagnostic
+    }
+
+    /// A schema violation of the value at `at` in `source`, if it was found
+    pub fn schema(
+        origin: Origin,
+        message: String,
+        at: Option<(usize, usize)>,
+        source: &str,
+    ) -> Self {
+        let mut diagnostic = Diagnostic::located(origin, "schema", message, at, source);
+        diagnostic.text = match at {
+            // The name of an NDJSON record already has its line number
+            Some((_, column)) if origin.record.is_some() => {
+                format!("{}:{}: {}", origin, column, diagnostic.message)
+            }
+            Some((line, column)) => {
+                format!("{}:{}:{}: {}", origin, line, column, diagnostic.message)
+            }
+            None => format!("{}: {}", origin, diagnostic.message),
+        };
+        diagnostic
+    }
+
+    /// A diagnostic at a line and column of `source`, counted within the record
+    /// for NDJSON, or at its end without one
+    fn located(
+        origin: Origin,
+        code: &str,
+        message: String,
+        at: Option<(usize, usize)>,
+        source: &str,
+    ) -> Self {
+        let (line, column, offset) = match at {
+            Some((line, column)) => (Some(line), Some(column), offset_of(source, line, column)),
+            None if code == "syntax" => {
+                let line = source.split('\n').count();
+                let last = source.rsplit('\n').next().unwrap_or_default();
+                (
+                    Some(line),
+                    Some(last.chars().count() + 1),
+                    Some(source.len()),
+                )
+            }
+            None => (None, None, None),
+        };
+        let (first_line, start) = origin.record.unwrap_or((1, 0));
+        Diagnostic {
+            text: String::new(),
+            file: Some(origin.file.to_string()),
+            line: line.map(|line| first_line + line - 1),
+            column,
+            offset: offset.map(|offset| start + offset),
+            code: code.to_string(),
+            message,
+            suggestion: None,
+        }
+    }
+
+    /// Pick apart a message laid out as described at the top of this file
+    /// Parts that are missing stay unknown, a message without a kind has the
+    /// code `error`.
+    pub fn parse(text: String) -> Self {
+        let mut diagnostic = Diagnostic {
+            file: None,
+            line: None,
+            column: None,
+            offset: None,
+            code: "error".to_string(),
+            message: text.clone(),
+            suggestion: None,
+            text,
+        };
+
+        let mut rest = diagnostic.text.as_str();
+        // Names of inputs never contain spaces in the messages worth reading this way
+        if let Some((file, after)) = rest.split_once(": ") {
+            if !file.is_empty() && !file.contains(char::is_whitespace) {
+                let (file, line, column) = split_location(file);
+                diagnostic.file = Some(file.to_string());
+                diagnostic.line = line;
+                diagnostic.column = column;
+                rest = after;
+            }
+        }
+        if let Some((kind, after)) = rest.split_once(" error: ") {
+            if !kind.contains(char::is_whitespace) {
+                diagnostic.code = kind.to_lowercase();
+                rest = after;
+            }
+        }
+
+        let mut message = rest;
+        if let Some((before, at)) = rest.rsplit_once(" at line ") {
+            if let Some((line, column)) = at.split_once(" column ") {
+                if let (Ok(line), Ok(column)) = (line.parse::<usize>(), column.parse::<usize>()) {
+                    // Within a numbered line of NDJSON the line is always 1
+                    diagnostic.line = Some(diagnostic.line.map_or(line, |first| first + line - 1));
+                    diagnostic.column = Some(column);
+                    message = before;
+                }
+            }
+        }
+        diagnostic.message = message.to_string();
+        diagnostic
+    }
+
+    pub fn emit(&self) {
+        if !is_json() {
+            eprintln!("{}", self.text);
+            return;
+        }
+
+        let text = |s: &Option<String>| {
+            s.as_deref()
+                .map_or(JsonValue::Null, |s| JsonValue::Str(s.into()))
+        };
+        let number = |n: Option<usize>| n.map_or(JsonValue::Null, |n| JsonValue::Int(n as i64));
+        let members = [
+            ("file", text(&self.file)),
+            ("line", number(self.line)),
+            ("column", number(self.column)),
+            ("offset", number(self.offset)),
+            ("code", JsonValue::Str(self.code.as_str().into())),
+            ("message", JsonValue::Str(self.message.as_str().into())),
+            ("suggestion", text(&self.suggestion)),
+        ];
+        let json = JsonValue::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| JsonValue::KeyedObject(key.into(), Box::new(value)))
+                .collect(),
+        );
+        match to_string(&json) {
+            Ok(line) => eprintln!("{}", line),
+            Err(_) => eprintln!("{}", self.text),
+        }
+    }
+}
+
+/// Split `name:line:column` or `name:line` into its parts
+fn split_location(file: &str) -> (&str, Option<usize>, Option<usize>) {
+    let mut parts = file.rsplitn(3, ':').collect::<Vec<_>>();
+    parts.reverse();
+    let number = |part: &str| part.parse::<usize>().ok();
+    match parts.as_slice() {
+        [name, line, column] if number(line).is_some() && number(column).is_some() => {
+            (name, number(line), number(column))
+        }
+        [.., line] if number(line).is_some() => {
+            let name = &file[..file.len() - line.len() - 1];
+            (name, number(line), None)
+        }
+        _ => (file, None, None),
+    }
+}
+
+/// The byte offset of a character given by line and column, both from 1
+fn offset_of(source: &str, line: usize, column: usize) -> Option<usize> {
+    let start = if line == 1 {
+        0
+    } else {
+        source.match_indices('\n').nth(line - 2)?.0 + 1
+    };
+    let offset = source[start..]
+        .char_indices()
+        .nth(column.saturating_sub(1))
+        .map_or(source.len(), |(i, _)| start + i);
+    Some(offset)
+}
+
+/// A likely fix for a syntax error at `offset`
+fn suggestion(source: &str, offset: usize) -> Option<&'static str> {
+    if offset >= source.len() {
+        return Some("the document ends early, look for an unclosed bracket or string");
+    }
+    let rest = &source[offset..];
+    let before = source[..offset].trim_end();
+    if rest.starts_with("//") || rest.starts_with("/*") {
+        Some("comments are not JSON, --allow comments skips them")
+    } else if rest.starts_with('\'') {
+        Some("strings take double quotes, --allow single-quotes accepts single ones")
+    } else if (rest.starts_with('}') || rest.starts_with(']')) && before.ends_with(',') {
+        Some("remove the trailing comma, or --allow trailing-commas")
+    } else {
+        None
+    }
+}
@@ -0,0 +1,473 @@
+use crate::compare::approx_eq;
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+use crate::serialize::{to_string_pretty_with, SerializeError, SerializeOptions};
+
+/// One difference between two documents
+#[derive(Clone, Debug)]
+pub enum Change {
+    Added(JsonPointer, JsonValue),
+    Removed(JsonPointer, JsonValue),
+    /// The old value followed by the new one
+    Replaced(JsonPointer, JsonValue, JsonValue),
+}
+
+/// The changes that turn `old` into `new`, applicable in order
+/// Objects are compared by key and arrays by index; removed array elements
+/// are listed from the end so the indices of earlier ones stay valid.
+pub fn diff(old: &JsonValue, new: &JsonValue) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_into(old, new, &mut JsonPointer::default(), &mut changes);
+    changes
+}
+
+fn diff_into(old: &JsonValue, new: &JsonValue, path: &mut JsonPointer, out: &mut Vec<Change>) {
+    match (old, new) {
+        (JsonValue::Arr(a), JsonValue::Arr(b)) => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                path.push(i.to_string());
+                diff_into(a, b, path, out);
+                path.pop();
+            }
+            for i in (b.len()..a.len()).rev() {
+                out.push(Change::Removed(path.join(i.to_string()), a[i].clone()));
+            }
+            for (i, b) in b.iter().enumerate().skip(a.len()) {
+                out.push(Change::Added(path.join(i.to_string()), b.clone()));
+            }
+        }
+        _ if old.is_object() && new.is_object() => {
+            for (key, a) in old.members() {
+                if new.get(key).is_none() {
+                    out.push(Change::Removed(path.join(key), a.clone()));
+                }
+            }
+            for (key, b) in new.members() {
+                match old.get(key) {
+                    Some(a) => {
+                        path.push(key);
+                        diff_into(a, b, path, out);
+                        path.pop();
+                    }
+                    None => out.push(Change::Added(path.join(key), b.clone())),
+                }
+            }
+        }
+        _ if approx_eq(old, new, 0.0) => (),
+        _ => out.push(Change::Replaced(path.clone(), old.clone(), new.clone())),
+    }
+}
+
+/// The changes as an RFC 6902 JSON Patch document
+/// e.g. `[{"op": "replace", "path": "/version", "value": "2.0.0"}]`
+pub fn to_patch(changes: &[Change]) -> JsonValue {
+    let operation = |op: &str, path: &JsonPointer, value: Option<&JsonValue>| {
+        let mut members = vec![
+            ("op".to_string(), JsonValue::Str(op.into())),
+            ("path".to_string(), JsonValue::Str(path.to_string().into())),
+        ];
+        members.extend(value.map(|v| ("value".to_string(), v.clone())));
+        JsonValue::from_members(members)
+    };
+
+    JsonValue::Arr(
+        changes
+            .iter()
+            .map(|change| match change {
+                Change::Added(path, value) => operation("add", path, Some(value)),
+                Change::Removed(path, _) => operation("remove", path, None),
+                Change::Replaced(path, _, value) => operation("replace", path, Some(value)),
+            })
+            .collect(),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiffFormat {
+    /// `-`/`+` lines grouped into `@@` hunks, like `diff -u`
+    Unified,
+    /// Both documents in columns, changed lines marked between them
+    SideBySide,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffOptions {
+    pub format: DiffFormat,
+    /// Unchanged lines kept around each change in unified output
+    pub context: usize,
+    pub color: bool,
+    /// Total line width of side-by-side output
+    pub width: usize,
+    /// Names shown in the unified header
+    pub old_name: String,
+    pub new_name: String,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            format: DiffFormat::Unified,
+            context: 3,
+            color: false,
+            width: 160,
+            old_name: "a".to_string(),
+            new_name: "b".to_string(),
+        }
+    }
+}
+
+const REMOVED_COLOR: &str = "\x1b[31m";
+const ADDED_COLOR: &str = "\x1b[32m";
+const HUNK_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Render the differences between the pretty-printed documents
+/// Keys are sorted first so member order alone never shows up as a change.
+/// Gives an empty string when the documents print the same.
+pub fn render_diff(
+    old: &JsonValue,
+    new: &JsonValue,
+    options: &DiffOptions,
+) -> Result<String, SerializeError> {
+    let print = SerializeOptions {
+        sort_keys: true,
+        ..Default::default()
+    };
+    let old_text = to_string_pretty_with(old, &print)?;
+    let new_text = to_string_pretty_with(new, &print)?;
+    Ok(render_text_diff(&old_text, &new_text, options))
+}
+
+/// Render the differences between two texts line by line, e.g. a file and the
+/// same file formatted
+/// Gives an empty string when the lines are the same.
+pub fn render_text_diff(old: &str, new: &str, options: &DiffOptions) -> String {
+    let a = old.lines().collect::<Vec<_>>();
+    let b = new.lines().collect::<Vec<_>>();
+
+    let edits = line_diff(&a, &b);
+    if edits.iter().all(|e| *e == Edit::Equal) {
+        return String::new();
+    }
+
+    match options.format {
+        DiffFormat::Unified => unified(&a, &b, &edits, options),
+        DiffFormat::SideBySide => side_by_side(&a, &b, &edits, options),
+    }
+}
+
+fn paint(out: &mut String, color: Option<&str>, line: &str) {
+    match color {
+        Some(color) => {
+            out.push_str(color);
+            out.push_str(line);
+            out.push_str(RESET);
+        }
+        None => out.push_str(line),
+    }
+    out.push('\n');
+}
+
+fn unified(a: &[&str], b: &[&str], edits: &[Edit], options: &DiffOptions) -> String {
+    let color = |c| options.color.then_some(c);
+    let mut out = String::new();
+    paint(&mut out, None, &format!("--- {}", options.old_name));
+    paint(&mut out, None, &format!("+++ {}", options.new_name));
+
+    // Positions in both documents before each edit
+    let mut positions = vec![(0, 0)];
+    for edit in edits {
+        let (i, j) = positions[positions.len() - 1];
+        positions.push(match edit {
+            Edit::Equal => (i + 1, j + 1),
+            Edit::Delete => (i + 1, j),
+            Edit::Insert => (i, j + 1),
+        });
+    }
+
+    let changed = (0..edits.len())
+        .filter(|&e| edits[e] != Edit::Equal)
+        .collect::<Vec<_>>();
+    let mut start = 0;
+    while start < changed.len() {
+        // Extend the hunk while the next change is within reach of the context
+        let mut end = start;
+        while end + 1 < changed.len() && changed[end + 1] - changed[end] <= 2 * options.context {
+            end += 1;
+        }
+        let lo = changed[start].saturating_sub(options.context);
+        let hi = (changed[end] + options.context + 1).min(edits.len());
+        start = end + 1;
+
+        let (i, j) = positions[lo];
+        let (i_end, j_end) = positions[hi];
+        let range = |from: usize, len: usize| {
+            if len == 0 {
+                format!("{},0", from)
+            } else {
+                format!("{},{}", from + 1, len)
+            }
+        };
+        let header = format!("@@ -{} +{} @@", range(i, i_end - i), range(j, j_end - j));
+        paint(&mut out, color(HUNK_COLOR), &header);
+
+        for e in lo..hi {
+            let (i, j) = positions[e];
+            match edits[e] {
+                Edit::Equal => paint(&mut out, None, &format!(" {}", a[i])),
+                Edit::Delete => paint(&mut out, color(REMOVED_COLOR), &format!("-{}", a[i])),
+                Edit::Insert => paint(&mut out, color(ADDED_COLOR), &format!("+{}", b[j])),
+            }
+        }
+    }
+    out
+}
+
+fn side_by_side(a: &[&str], b: &[&str], edits: &[Edit], options: &DiffOptions) -> String {
+    let column = options.width.saturating_sub(3) / 2;
+    let cell = |line: &str| {
+        let mut line = line.chars().take(column).collect::<String>();
+        let len = line.chars().count();
+        line.extend(std::iter::repeat_n(' ', column - len));
+        line
+    };
+
+    let mut out = String::new();
+    let (mut i, mut j, mut e) = (0, 0, 0);
+    while e < edits.len() {
+        if edits[e] == Edit::Equal {
+            paint(
+                &mut out,
+                None,
+                format!("{}   {}", cell(a[i]), b[j]).trim_end(),
+            );
+            i += 1;
+            j += 1;
+            e += 1;
+            continue;
+        }
+
+        // Pair up the deletions and insertions of one run of changes
+        let run = edits[e..].iter().take_while(|e| **e != Edit::Equal);
+        let deletes = run.clone().filter(|e| **e == Edit::Delete).count();
+        let inserts = run.filter(|e| **e == Edit::Insert).count();
+        for row in 0..deletes.max(inserts) {
+            let left = (row < deletes).then(|| a[i + row]);
+            let right = (row < inserts).then(|| b[j + row]);
+            let (marker, color) = match (left, right) {
+                (Some(_), Some(_)) => ('|', None),
+                (Some(_), None) => ('<', Some(REMOVED_COLOR)),
+                _ => ('>', Some(ADDED_COLOR)),
+            };
+            let line = format!(
+                "{} {} {}",
+                cell(left.unwrap_or("")),
+                marker,
+                right.unwrap_or("")
+            );
+            paint(&mut out, color.filter(|_| options.color), line.trim_end());
+        }
+        i += deletes;
+        j += inserts;
+        e += deletes + inserts;
+    }
+    out
+}
+
+/// Shortest edit script between two line sequences (Myers' algorithm)
+/// Uses the linear space variant, which splits the sequences at the middle of
+/// an edit script and recurses on both halves, so memory stays proportional
+/// to the input however much the sequences differ.
+fn line_diff(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    edit_script(a, b, &mut edits);
+    // The halves of a script can interleave the deletions and insertions of a
+    // run of changes, which reads better with the deletions first
+    for run in edits.chunk_by_mut(|x, y| (*x == Edit::Equal) == (*y == Edit::Equal)) {
+        run.sort_by_key(|edit| *edit == Edit::Insert);
+    }
+    edits
+}
+
+fn edit_script(a: &[&str], b: &[&str], edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix));
+    if a.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Insert, b.len()));
+    } else if b.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Delete, a.len()));
+    } else {
+        // Both ends differ, so the script has at least two edits and both
+        // halves are shorter than it
+        let ((x, y), (u, v)) = middle_snake(a, b);
+        edit_script(&a[..x], &b[..y], edits);
+        edits.extend(std::iter::repeat_n(Edit::Equal, u - x));
+        edit_script(&a[u..], &b[v..], edits);
+    }
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+/// The start and end of the run of equal lines in the middle of a shortest
+/// edit script, found by searching from both ends until the paths overlap
+/// The backward search runs on the reversed sequences, so its diagonal `k`
+/// is `delta - k` going forward.
+fn middle_snake(a: &[&str], b: &[&str]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let mut forward = vec![0isize; 2 * max as usize + 3];
+    let mut backward = vec![0isize; 2 * max as usize + 3];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let start = (x, x - k);
+            while x < n && x - k < m && a[x as usize] == b[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+            if odd && (delta - k).abs() < d && x + backward[at(delta - k)] >= n {
+                let start = (start.0 as usize, start.1 as usize);
+                return (start, (x as usize, (x - k) as usize));
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let end = (n - x, m - (x - k));
+            while x < n && x - k < m && a[(n - 1 - x) as usize] == b[(m - 1 - (x - k)) as usize] {
+                x += 1;
+            }
+            backward[at(k)] = x;
+            if !odd && (delta - k).abs() <= d && x + forward[at(delta - k)] >= n {
+                let start = ((n - x) as usize, (m - (x - k)) as usize);
+                return (start, (end.0 as usize, end.1 as usize));
+            }
+        }
+    }
+    unreachable!("the searches meet once they cover the shortest script")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that `edits` turn `a` into `b`, returning how many changed a line
+    fn apply(a: &[&str], b: &[&str], edits: &[Edit]) -> usize {
+        let (mut x, mut y) = (0, 0);
+        for edit in edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(a[x], b[y]);
+                    x += 1;
+                    y += 1;
+                }
+                Edit::Delete => x += 1,
+                Edit::Insert => y += 1,
+            }
+        }
+        assert_eq!((x, y), (a.len(), b.len()));
+        edits.iter().filter(|e| **e != Edit::Equal).count()
+    }
+
+    /// The fewest inserts and deletes, by dynamic programming
+    fn distance(a: &[&str], b: &[&str]) -> usize {
+        let mut row = (0..=b.len()).collect::<Vec<_>>();
+        for (i, x) in a.iter().enumerate() {
+            let mut next = vec![i + 1; b.len() + 1];
+            for (j, y) in b.iter().enumerate() {
+                next[j + 1] = if x == y {
+                    row[j]
+                } else {
+                    row[j + 1].min(next[j]) + 1
+                };
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn finds_shortest_scripts() {
+        let mut seed = 0x2545_f491_u32;
+        let mut random = |n: u32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % n
+        };
+        let lines = ["a", "b", "c", "d"];
+        for _ in 0..2000 {
+            let mut sequence = |len: u32| {
+                (0..random(len))
+                    .map(|_| lines[random(4) as usize])
+                    .collect::<Vec<_>>()
+            };
+            let (a, b) = (sequence(12), sequence(12));
+            assert_eq!(
+                apply(&a, &b, &line_diff(&a, &b)),
+                distance(&a, &b),
+                "{:?} {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn puts_deletions_before_insertions() {
+        let old = "{\"a\":[1]}\n";
+        let new = "{\n  \"a\": [\n    1\n  ]\n}\n";
+        assert_eq!(
+            render_text_diff(old, new, &DiffOptions::default()),
+            "--- a\n+++ b\n@@ -1,1 +1,5 @@\n-{\"a\":[1]}\n+{\n+  \"a\": [\n+    1\n+  ]\n+}\n"
+        );
+
+        let a = ["x", "1", "2", "y", "3"];
+        let b = ["1", "x", "y", "4", "3", "5"];
+        let edits = line_diff(&a, &b);
+        for run in edits.chunk_by(|x, y| (*x == Edit::Equal) == (*y == Edit::Equal)) {
+            assert!(
+                run.is_sorted_by_key(|edit| *edit == Edit::Insert),
+                "{:?}",
+                edits
+            );
+        }
+        assert_eq!(apply(&a, &b, &edits), distance(&a, &b));
+    }
+
+    #[test]
+    fn diffs_unrelated_long_inputs() {
+        let a = (0..3_000)
+            .map(|i| if i % 2 == 0 { "a" } else { "b" })
+            .collect::<Vec<_>>();
+        let b = vec!["c"; 4_000];
+        assert_eq!(apply(&a, &b, &line_diff(&a, &b)), 7_000);
+    }
+}
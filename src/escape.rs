@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+
+/// The character the escape after a backslash stands for, reading its bytes
+/// with `next`
+/// Inside strings quoted with `'`, which only the lenient tokenizer takes, `\'`
+/// stands for the quote as well.
+pub(crate) fn escape(
+    next: &mut impl FnMut() -> Option<u8>,
+    quote: u8,
+) -> Result<char, &'static str> {
+    Ok(match next() {
+        Some(b'"') => '"',
+        Some(b'\\') => '\\',
+        Some(b'/') => '/',
+        Some(b'b') => '\u{08}',
+        Some(b'f') => '\u{0c}',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        Some(b'u') => return unicode_escape(next),
+        Some(c) if c == quote => c as char,
+        _ => return Err("invalid escape in string"),
+    })
+}
+
+/// Decode the `XXXX` of a `\uXXXX` escape, joining a surrogate pair
+fn unicode_escape(next: &mut impl FnMut() -> Option<u8>) -> Result<char, &'static str> {
+    const UNPAIRED: &str = "unpaired surrogate in string";
+    let high = hex4(next)?;
+    if !(0xd800..0xdc00).contains(&high) {
+        return char::from_u32(high).ok_or(UNPAIRED);
+    }
+    if next() != Some(b'\\') || next() != Some(b'u') {
+        return Err(UNPAIRED);
+    }
+    let low = hex4(next)?;
+    if !(0xdc00..0xe000).contains(&low) {
+        return Err(UNPAIRED);
+    }
+    char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)).ok_or(UNPAIRED)
+}
+
+fn hex4(next: &mut impl FnMut() -> Option<u8>) -> Result<u32, &'static str> {
+    let mut n = 0;
+    for _ in 0..4 {
+        let digit = next()
+            .and_then(|c| (c as char).to_digit(16))
+            .ok_or("invalid unicode escape in string")?;
+        n = n * 16 + digit;
+    }
+    Ok(n)
+}
+
+/// The text of a string between its quotes with its escapes decoded, borrowed
+/// when it has none
+/// Control characters must be escaped, as in JSON.
+pub(crate) fn unescape(raw: &str, quote: u8) -> Result<Cow<'_, str>, &'static str> {
+    let bytes = raw.as_bytes();
+    let Some(first) = bytes.iter().position(|&c| c == b'\\' || c < 0x20) else {
+        return Ok(Cow::Borrowed(raw));
+    };
+    let mut decoded = String::with_capacity(raw.len());
+    decoded.push_str(&raw[..first]);
+    let mut i = first;
+    while i < bytes.len() {
+        // Runs without escapes are copied whole, ending at ASCII bytes that
+        // never start or continue a longer character
+        let run = bytes[i..]
+            .iter()
+            .position(|&c| c == b'\\' || c < 0x20)
+            .map_or(bytes.len(), |len| i + len);
+        decoded.push_str(&raw[i..run]);
+        i = run;
+        match bytes.get(i) {
+            None => break,
+            Some(b'\\') => {
+                let mut rest = bytes[i + 1..].iter();
+                let mut read = 0;
+                let c = escape(
+                    &mut || {
+                        read += 1;
+                        rest.next().copied()
+                    },
+                    quote,
+                )?;
+                decoded.push(c);
+                i += 1 + read;
+            }
+            Some(_) => return Err("control character in string"),
+        }
+    }
+    Ok(Cow::Owned(decoded))
+}
+
+/// Check string text as `unescape` does, with the same errors, without decoding it
+pub(crate) fn check(raw: &str, quote: u8) -> Result<(), &'static str> {
+    let mut bytes = raw.bytes();
+    while let Some(c) = bytes.next() {
+        match c {
+            b'\\' => drop(escape(&mut || bytes.next(), quote)?),
+            c if c < 0x20 => return Err("control character in string"),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Decode the escapes of string text that was already checked, e.g. by
+/// `BorrowedParser::string`
+pub(crate) fn decode(raw: &str) -> String {
+    unescape(raw, b'"').map(Cow::into_owned).unwrap_or_default()
+}
+
+/// Whether the text after an opening quote ends with the closing one rather
+/// than an escaped quote
+pub(crate) fn closes(after_quote: &str, quote: u8) -> bool {
+    let bytes = after_quote.as_bytes();
+    let Some((&last, before)) = bytes.split_last() else {
+        return false;
+    };
+    let backslashes = before.iter().rev().take_while(|&&c| c == b'\\').count();
+    last == quote && backslashes % 2 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_every_escape() {
+        let decoded = unescape(
+            r#"\" \\ \/ \b \f \n \r \t \u0041 \u00e9 \ud83d\ude00"#,
+            b'"',
+        );
+        assert_eq!(
+            decoded.as_deref(),
+            Ok("\" \\ / \u{08} \u{0c} \n \r \t A é 😀")
+        );
+    }
+
+    #[test]
+    fn borrows_text_without_escapes() {
+        assert!(matches!(
+            unescape("plain é", b'"'),
+            Ok(Cow::Borrowed("plain é"))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        assert_eq!(unescape(r"\q", b'"'), Err("invalid escape in string"));
+        assert_eq!(unescape(r"\'", b'"'), Err("invalid escape in string"));
+        assert_eq!(
+            unescape(r"\u12", b'"'),
+            Err("invalid unicode escape in string")
+        );
+        assert_eq!(
+            unescape(r"\ud83d", b'"'),
+            Err("unpaired surrogate in string")
+        );
+        assert_eq!(
+            unescape(r"\ude00", b'"'),
+            Err("unpaired surrogate in string")
+        );
+        assert_eq!(unescape("a\nb", b'"'), Err("control character in string"));
+        assert_eq!(unescape("\\", b'"'), Err("invalid escape in string"));
+    }
+
+    #[test]
+    fn checks_like_it_decodes() {
+        for raw in [
+            r#"\" \\ \u0041 \ud83d\ude00"#,
+            r"\q",
+            r"\u12",
+            r"\ud83d",
+            "a\nb",
+            "\\",
+            r"ok \'",
+        ] {
+            assert_eq!(check(raw, b'"'), unescape(raw, b'"').map(drop), "{}", raw);
+        }
+        assert_eq!(check(r"\'", b'\''), Ok(()));
+    }
+
+    #[test]
+    fn single_quotes_escape_their_quote() {
+        assert_eq!(unescape(r"it\'s", b'\'').as_deref(), Ok("it's"));
+    }
+
+    #[test]
+    fn finds_the_closing_quote() {
+        assert!(closes(r#"a""#, b'"'));
+        assert!(closes(r#"a\\""#, b'"'));
+        assert!(!closes(r#"a\""#, b'"'));
+        assert!(!closes("a", b'"'));
+        assert!(!closes("", b'"'));
+    }
+}
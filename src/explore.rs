@@ -0,0 +1,423 @@
+//! `jsonp explore`: browse a document as a collapsible tree in the terminal
+//! Copying uses the OSC 52 escape sequence, which most terminal emulators
+//! forward to the system clipboard.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+
+use jsonp::parse::JsonValue;
+use jsonp::pointer::JsonPointer;
+use jsonp::serialize::to_string;
+
+use crate::terminal::{open_tty, size, Key, Keys, RawMode};
+
+const HELP: &str =
+    "j/k move  h/l fold  space toggle  / search  n/N next  p copy path  y copy value  q quit";
+
+/// Open the explorer on `json` until the user quits
+pub fn explore(json: &JsonValue, name: &str) -> Result<(), String> {
+    let io_err = |e: std::io::Error| format!("IO error: {}", e);
+    let mut tty = open_tty()?;
+
+    let _raw = RawMode::enable()?;
+    let mut explorer = Explorer::new(json, name);
+    // Alternate screen, hidden cursor
+    write!(tty, "\x1b[?1049h\x1b[?25l").map_err(io_err)?;
+    let result = explorer.run(&mut tty);
+    write!(tty, "\x1b[?25h\x1b[?1049l").map_err(io_err)?;
+    result.map_err(io_err)
+}
+
+/// A visible line of the tree
+struct Row<'a> {
+    depth: usize,
+    pointer: JsonPointer,
+    label: String,
+    value: &'a JsonValue,
+}
+
+struct Explorer<'a> {
+    root: &'a JsonValue,
+    name: String,
+    expanded: HashSet<JsonPointer>,
+    rows: Vec<Row<'a>>,
+    selected: usize,
+    scroll: usize,
+    /// The query being typed and the selection to go back to on escape
+    search: Option<(String, usize)>,
+    last_query: String,
+    message: String,
+}
+
+impl<'a> Explorer<'a> {
+    fn new(root: &'a JsonValue, name: &str) -> Self {
+        let mut explorer = Explorer {
+            root,
+            name: name.to_string(),
+            expanded: HashSet::from([JsonPointer::default()]),
+            rows: vec![],
+            selected: 0,
+            scroll: 0,
+            search: None,
+            last_query: String::new(),
+            message: String::new(),
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    fn run(&mut self, tty: &mut File) -> std::io::Result<()> {
+        let mut keys = Keys::default();
+        loop {
+            self.draw(tty)?;
+            let Some(key) = keys.next(tty)? else {
+                return Ok(());
+            };
+            if self.search.is_some() {
+                self.search_key(key);
+                continue;
+            }
+
+            self.message.clear();
+            match key {
+                Key::Char('q') | Key::Char('\x03') | Key::Escape => return Ok(()),
+                Key::Up | Key::Char('k') => self.select(self.selected.saturating_sub(1)),
+                Key::Down | Key::Char('j') => self.select(self.selected + 1),
+                Key::PageUp => self.select(self.selected.saturating_sub(self.height())),
+                Key::PageDown => self.select(self.selected + self.height()),
+                Key::Home | Key::Char('g') => self.select(0),
+                Key::End | Key::Char('G') => self.select(self.rows.len()),
+                Key::Right | Key::Char('l') => self.expand(),
+                Key::Left | Key::Char('h') => self.collapse(),
+                Key::Enter | Key::Char(' ') => self.toggle(),
+                Key::Char('/') => self.search = Some((String::new(), self.selected)),
+                Key::Char('n') => self.find(&self.last_query.clone(), self.selected, 1),
+                Key::Char('N') => self.find(&self.last_query.clone(), self.selected, -1),
+                Key::Char('p') => {
+                    let path = self.current().pointer.to_string();
+                    self.copy(tty, &path)?;
+                    self.message = format!("Copied path {}", display_pointer(&path));
+                }
+                Key::Char('y') => {
+                    let value = self.current().value;
+                    match to_string(value) {
+                        Ok(text) => {
+                            self.copy(tty, &text)?;
+                            self.message = "Copied value".to_string();
+                        }
+                        Err(err) => self.message = err.0,
+                    }
+                }
+                _ => self.message = HELP.to_string(),
+            }
+        }
+    }
+
+    fn search_key(&mut self, key: Key) {
+        let Some((mut query, origin)) = self.search.take() else {
+            return;
+        };
+        match key {
+            Key::Escape => {
+                self.select(origin);
+                return;
+            }
+            Key::Enter => {
+                self.last_query = query;
+                return;
+            }
+            Key::Backspace => {
+                query.pop();
+            }
+            Key::Char(c) if !c.is_control() => query.push(c),
+            _ => (),
+        }
+        // Incremental: every edit searches again from where the search started
+        self.find(&query, origin, 0);
+        self.search = Some((query, origin));
+    }
+
+    /// Select the node matching `query` at row `from` or, with a `step` of 1 or -1,
+    /// the next or previous one, revealing it if it is folded away
+    /// Searches the whole document in order, wrapping around at either end.
+    fn find(&mut self, query: &str, from: usize, step: isize) {
+        if query.is_empty() {
+            return;
+        }
+        let mut nodes = vec![];
+        collect(self.root, JsonPointer::default(), String::new(), &mut nodes);
+        let start = nodes
+            .iter()
+            .position(|(pointer, _, _)| *pointer == self.rows[from].pointer)
+            .unwrap_or(0);
+
+        let query = query.to_lowercase();
+        let matches = |(_, label, value): &(JsonPointer, String, &JsonValue)| {
+            label.to_lowercase().contains(&query)
+                || (!is_container(value) && summary(value).to_lowercase().contains(&query))
+        };
+        let len = nodes.len();
+        let order: Vec<usize> = if step < 0 {
+            (1..=len).map(|i| (start + len - i) % len).collect()
+        } else {
+            (0..len)
+                .map(|i| (start + step as usize + i) % len)
+                .collect()
+        };
+        let Some(found) = order.into_iter().find(|&i| matches(&nodes[i])) else {
+            self.message = format!("No match for '{}'", query);
+            return;
+        };
+
+        let pointer = nodes[found].0.clone();
+        let mut ancestor = pointer.clone();
+        while ancestor.pop().is_some() {
+            self.expanded.insert(ancestor.clone());
+        }
+        self.refresh();
+        if let Some(row) = self.rows.iter().position(|row| row.pointer == pointer) {
+            self.select(row);
+        }
+    }
+
+    fn expand(&mut self) {
+        let row = self.current();
+        if !is_container(row.value) {
+            return;
+        }
+        if self.expanded.insert(row.pointer.clone()) {
+            self.refresh();
+        } else if self
+            .rows
+            .get(self.selected + 1)
+            .is_some_and(|next| next.depth > self.rows[self.selected].depth)
+        {
+            self.select(self.selected + 1);
+        }
+    }
+
+    /// Fold the selected node, or move to its parent if it is folded already
+    fn collapse(&mut self) {
+        let pointer = self.current().pointer.clone();
+        if is_container(self.current().value) && self.expanded.remove(&pointer) {
+            self.refresh();
+            return;
+        }
+        let mut parent = pointer;
+        if parent.pop().is_some() {
+            if let Some(row) = self.rows.iter().position(|row| row.pointer == parent) {
+                self.select(row);
+            }
+        }
+    }
+
+    fn toggle(&mut self) {
+        let pointer = self.current().pointer.clone();
+        if !self.expanded.remove(&pointer) && is_container(self.current().value) {
+            self.expanded.insert(pointer);
+        }
+        self.refresh();
+    }
+
+    fn current(&self) -> &Row<'a> {
+        &self.rows[self.selected]
+    }
+
+    fn select(&mut self, row: usize) {
+        self.selected = row.min(self.rows.len() - 1);
+    }
+
+    /// Rebuild the visible rows after folding or unfolding
+    fn refresh(&mut self) {
+        let mut rows = vec![];
+        self.visible(
+            self.root,
+            JsonPointer::default(),
+            self.name.clone(),
+            0,
+            &mut rows,
+        );
+        self.rows = rows;
+        self.select(self.selected);
+    }
+
+    fn visible(
+        &self,
+        value: &'a JsonValue,
+        pointer: JsonPointer,
+        label: String,
+        depth: usize,
+        rows: &mut Vec<Row<'a>>,
+    ) {
+        let expanded = self.expanded.contains(&pointer);
+        rows.push(Row {
+            depth,
+            pointer: pointer.clone(),
+            label,
+            value,
+        });
+        if expanded {
+            for (key, child) in children(value) {
+                self.visible(child, pointer.join(key.clone()), key, depth + 1, rows);
+            }
+        }
+    }
+
+    /// Lines available for the tree, leaving two for the path and status
+    fn height(&self) -> usize {
+        size().0 - 2
+    }
+
+    fn draw(&mut self, tty: &mut File) -> std::io::Result<()> {
+        let (rows, cols) = size();
+        let height = rows - 2;
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+
+        let mut screen = String::from("\x1b[H");
+        for i in self.scroll..self.scroll + height {
+            screen.push_str("\x1b[2K");
+            if let Some(row) = self.rows.get(i) {
+                let marker = match (
+                    is_container(row.value),
+                    self.expanded.contains(&row.pointer),
+                ) {
+                    (false, _) => "  ",
+                    _ if children(row.value).is_empty() => "  ",
+                    (true, true) => "▾ ",
+                    (true, false) => "▸ ",
+                };
+                let line = format!(
+                    "{}{}{}: {}",
+                    "  ".repeat(row.depth),
+                    marker,
+                    printable(&row.label),
+                    summary(row.value)
+                );
+                let line = truncate(&line, cols);
+                if i == self.selected {
+                    screen.push_str(&format!("\x1b[7m{}\x1b[0m", line));
+                } else {
+                    screen.push_str(&line);
+                }
+            }
+            screen.push_str("\r\n");
+        }
+
+        let pointer = self.current().pointer.to_string();
+        let path = display_pointer(&pointer);
+        screen.push_str(&format!(
+            "\x1b[2K\x1b[1m{}\x1b[0m\r\n",
+            truncate(path, cols)
+        ));
+        let status = match &self.search {
+            Some((query, _)) => format!("/{}", query),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => HELP.to_string(),
+        };
+        screen.push_str(&format!("\x1b[2K{}", truncate(&printable(&status), cols)));
+        tty.write_all(screen.as_bytes())?;
+        tty.flush()
+    }
+
+    /// Put `text` on the clipboard through the terminal
+    fn copy(&self, tty: &mut File, text: &str) -> std::io::Result<()> {
+        write!(tty, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+    }
+}
+
+/// Every node in document order, with its pointer and label
+fn collect<'a>(
+    value: &'a JsonValue,
+    pointer: JsonPointer,
+    label: String,
+    nodes: &mut Vec<(JsonPointer, String, &'a JsonValue)>,
+) {
+    nodes.push((pointer.clone(), label, value));
+    for (key, child) in children(value) {
+        collect(child, pointer.join(key.clone()), key, nodes);
+    }
+}
+
+/// Members or elements of a container, keyed by name or index
+pub fn children(value: &JsonValue) -> Vec<(String, &JsonValue)> {
+    match value {
+        JsonValue::Object(members) => members
+            .iter()
+            .filter_map(|member| match member {
+                JsonValue::KeyedObject(key, value) => Some((key.to_string(), value.as_ref())),
+                _ => None,
+            })
+            .collect(),
+        JsonValue::KeyedObject(key, value) => vec![(key.to_string(), value.as_ref())],
+        JsonValue::Arr(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (i.to_string(), value))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn is_container(value: &JsonValue) -> bool {
+    matches!(
+        value,
+        JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Arr(_) | JsonValue::Empty
+    )
+}
+
+/// A one-line description of a value, e.g. `{3 keys}`, `[2 items]` or `"text"`
+fn summary(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Empty => "{}".to_string(),
+        JsonValue::Object(_) | JsonValue::KeyedObject(_, _) => {
+            format!("{{{}}}", count(children(value).len(), "key"))
+        }
+        JsonValue::Arr(values) if values.is_empty() => "[]".to_string(),
+        JsonValue::Arr(values) => format!("[{}]", count(values.len(), "item")),
+        _ => printable(&to_string(value).unwrap_or_else(|err| err.0)),
+    }
+}
+
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+fn display_pointer(pointer: &str) -> &str {
+    if pointer.is_empty() {
+        "(root)"
+    } else {
+        pointer
+    }
+}
+
+/// Replace control characters, which would upset the terminal
+fn printable(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_control() { '?' } else { c })
+        .collect()
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+        out.push_str(&"=="[..3 - chunk.len()]);
+    }
+    out
+}
@@ -0,0 +1,349 @@
+use std::collections::VecDeque;
+
+use crate::parse::{number_value, JsonValue, ParseErrorKind, SyntaxError};
+use crate::tape::{Cursor, Tape};
+
+/// A document as one vector of nodes that refer to each other by index, read
+/// through a `NodeRef`
+/// Nodes are laid out breadth first, so the children of a container are next
+/// to each other and any of them is found in one step. The tree owns its text
+/// and holds no pointers, so it can be sent to or shared between threads, or
+/// copied as plain data.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlatTree {
+    nodes: Vec<Node>,
+    /// The strings, keys and kept float lexemes, one after the other
+    text: String,
+}
+
+/// A value in a `FlatTree`, offsets being into its text
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Node {
+    Null,
+    True,
+    False,
+    Int(i64),
+    Float(f64),
+    /// A float kept as its lexeme, like `JsonValue::Float` keeps e.g. `1.50`
+    Lexeme {
+        start: u32,
+        len: u32,
+    },
+    /// A key or string value
+    Str {
+        start: u32,
+        len: u32,
+    },
+    /// The members are the `2 * len` nodes from `first`, each key followed by
+    /// its value
+    Object {
+        first: u32,
+        len: u32,
+    },
+    /// The elements are the `len` nodes from `first`
+    Array {
+        first: u32,
+        len: u32,
+    },
+}
+
+impl FlatTree {
+    /// Parse a document, with the same errors as `Tape::parse`
+    pub fn parse(input: &str) -> Result<Self, SyntaxError> {
+        lay_out(Tape::parse(input)?.root())
+    }
+
+    /// Copy an owned tree, which fails only for more than 4 GiB of nodes or text
+    pub fn from_json(value: &JsonValue) -> Result<Self, SyntaxError> {
+        lay_out(value)
+    }
+
+    /// The top level value
+    pub fn root(&self) -> NodeRef<'_> {
+        NodeRef { tree: self, at: 0 }
+    }
+
+    fn text(&self, start: u32, len: u32) -> &str {
+        &self.text[start as usize..(start + len) as usize]
+    }
+}
+
+/// Builds a `FlatTree` a level at a time from a value of type `S`
+struct Builder<S> {
+    nodes: Vec<Node>,
+    text: String,
+    /// Containers whose children are still to be laid out, along with their node
+    pending: VecDeque<(S, usize)>,
+}
+
+/// A tree that a `FlatTree` can be laid out from
+trait Source: Sized {
+    /// The node of a scalar, `None` for a container
+    fn scalar(&self, text: &mut String) -> Result<Option<Node>, SyntaxError>;
+
+    /// Add the children of a container with `Builder::key` and `Builder::child`,
+    /// returning its node
+    fn children(self, tree: &mut Builder<Self>) -> Result<Node, SyntaxError>;
+}
+
+fn lay_out<S: Source>(root: S) -> Result<FlatTree, SyntaxError> {
+    let mut tree = Builder {
+        nodes: vec![],
+        text: String::new(),
+        pending: VecDeque::new(),
+    };
+    tree.child(root)?;
+    while let Some((container, at)) = tree.pending.pop_front() {
+        tree.nodes[at] = container.children(&mut tree)?;
+    }
+    Ok(FlatTree {
+        nodes: tree.nodes,
+        text: tree.text,
+    })
+}
+
+impl<S: Source> Builder<S> {
+    /// The index the next node gets
+    fn next(&self) -> Result<u32, SyntaxError> {
+        u32::try_from(self.nodes.len()).map_err(|_| too_large())
+    }
+
+    /// Add a value, leaving the children of a container for later
+    fn child(&mut self, value: S) -> Result<(), SyntaxError> {
+        self.next()?;
+        match value.scalar(&mut self.text)? {
+            Some(node) => self.nodes.push(node),
+            None => {
+                self.pending.push_back((value, self.nodes.len()));
+                self.nodes.push(Node::Null);
+            }
+        }
+        Ok(())
+    }
+
+    fn key(&mut self, key: &str) -> Result<(), SyntaxError> {
+        self.next()?;
+        let (start, len) = span(&mut self.text, key)?;
+        self.nodes.push(Node::Str { start, len });
+        Ok(())
+    }
+}
+
+/// Append `s` to the text, returning where it is
+fn span(text: &mut String, s: &str) -> Result<(u32, u32), SyntaxError> {
+    let start = u32::try_from(text.len()).map_err(|_| too_large())?;
+    let len = u32::try_from(s.len()).map_err(|_| too_large())?;
+    start.checked_add(len).ok_or_else(too_large)?;
+    text.push_str(s);
+    Ok((start, len))
+}
+
+fn too_large() -> SyntaxError {
+    SyntaxError::new(
+        ParseErrorKind::TooLarge,
+        "document too large for a flat tree",
+        None,
+    )
+}
+
+impl Source for &JsonValue {
+    fn scalar(&self, text: &mut String) -> Result<Option<Node>, SyntaxError> {
+        Ok(Some(match self {
+            JsonValue::Null => Node::Null,
+            JsonValue::Bool(true) => Node::True,
+            JsonValue::Bool(false) => Node::False,
+            JsonValue::Int(i) => Node::Int(*i),
+            JsonValue::Float(f, None) => Node::Float(*f),
+            JsonValue::Float(_, Some(raw)) => {
+                let (start, len) = span(text, raw)?;
+                Node::Lexeme { start, len }
+            }
+            JsonValue::Str(s) => {
+                let (start, len) = span(text, s)?;
+                Node::Str { start, len }
+            }
+            _ => return Ok(None),
+        }))
+    }
+
+    fn children(self, tree: &mut Builder<Self>) -> Result<Node, SyntaxError> {
+        let first = tree.next()?;
+        if let JsonValue::Arr(values) = self {
+            for value in values.iter() {
+                tree.child(value)?;
+            }
+            let len = values.len() as u32;
+            return Ok(Node::Array { first, len });
+        }
+        let mut len = 0;
+        for (key, value) in self.members() {
+            tree.key(key)?;
+            tree.child(value)?;
+            len += 1;
+        }
+        Ok(Node::Object { first, len })
+    }
+}
+
+impl Source for Cursor<'_, '_> {
+    fn scalar(&self, text: &mut String) -> Result<Option<Node>, SyntaxError> {
+        if self.len().is_some() {
+            return Ok(None);
+        }
+        if let Some(s) = self.as_str() {
+            let (start, len) = span(text, &s)?;
+            return Ok(Some(Node::Str { start, len }));
+        }
+        (&self.to_json()).scalar(text)
+    }
+
+    fn children(self, tree: &mut Builder<Self>) -> Result<Node, SyntaxError> {
+        let (first, mut len) = (tree.next()?, 0);
+        if !self.is_object() {
+            for element in self.elements() {
+                tree.child(element)?;
+                len += 1;
+            }
+            return Ok(Node::Array { first, len });
+        }
+        for (key, value) in self.members() {
+            tree.key(&key)?;
+            tree.child(value)?;
+            len += 1;
+        }
+        Ok(Node::Object { first, len })
+    }
+}
+
+/// A node of a `FlatTree`, cheap to copy
+#[derive(Clone, Copy, Debug)]
+pub struct NodeRef<'t> {
+    tree: &'t FlatTree,
+    at: usize,
+}
+
+impl<'t> NodeRef<'t> {
+    fn node(&self) -> Node {
+        self.tree.nodes[self.at]
+    }
+
+    fn at(&self, at: u32) -> NodeRef<'t> {
+        NodeRef {
+            at: at as usize,
+            ..*self
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.node() == Node::Null
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            Node::True => Some(true),
+            Node::False => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The number if it is an integer, as `JsonValue::Int` would have it
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.node() {
+            Node::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Any number, integers included
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.node() {
+            Node::Int(i) => Some(i as f64),
+            Node::Float(f) => Some(f),
+            Node::Lexeme { start, len } => self.tree.text(start, len).parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'t str> {
+        match self.node() {
+            Node::Str { start, len } => Some(self.tree.text(start, len)),
+            _ => None,
+        }
+    }
+
+    /// Members of an object or elements of an array
+    pub fn len(&self) -> Option<usize> {
+        match self.node() {
+            Node::Object { len, .. } | Node::Array { len, .. } => Some(len as usize),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// The value of the first member named `key`
+    pub fn get(&self, key: &str) -> Option<NodeRef<'t>> {
+        self.members()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The element at `index`, found without going through the ones before it
+    pub fn index(&self, index: usize) -> Option<NodeRef<'t>> {
+        match self.node() {
+            Node::Array { first, len } if index < len as usize => {
+                Some(self.at(first + index as u32))
+            }
+            _ => None,
+        }
+    }
+
+    /// The elements of an array, none for anything else
+    pub fn elements(&self) -> impl Iterator<Item = NodeRef<'t>> + 't {
+        let (node, (first, len)) = (*self, self.children(false));
+        (first..first + len).map(move |at| node.at(at))
+    }
+
+    /// The keys and values of an object, none for anything else
+    pub fn members(&self) -> impl Iterator<Item = (&'t str, NodeRef<'t>)> + 't {
+        let (node, (first, len)) = (*self, self.children(true));
+        (0..len).map(move |i| {
+            let key = node.at(first + i * 2);
+            (key.as_str().unwrap_or_default(), node.at(first + i * 2 + 1))
+        })
+    }
+
+    /// Where the children of this container start and how many there are, none
+    /// unless it is an object if `object` or else an array
+    fn children(&self, object: bool) -> (u32, u32) {
+        match (self.node(), object) {
+            (Node::Object { first, len }, true) | (Node::Array { first, len }, false) => {
+                (first, len)
+            }
+            _ => (0, 0),
+        }
+    }
+
+    /// Copy the value into an owned tree
+    pub fn to_json(&self) -> JsonValue {
+        match self.node() {
+            Node::Null => JsonValue::Null,
+            Node::True => JsonValue::Bool(true),
+            Node::False => JsonValue::Bool(false),
+            Node::Int(i) => JsonValue::Int(i),
+            Node::Float(f) => JsonValue::Float(f, None),
+            Node::Lexeme { start, len } => {
+                number_value(self.tree.text(start, len)).unwrap_or(JsonValue::Null)
+            }
+            Node::Str { start, len } => JsonValue::Str(self.tree.text(start, len).into()),
+            Node::Array { .. } => JsonValue::Arr(self.elements().map(|e| e.to_json()).collect()),
+            Node::Object { .. } => JsonValue::from_members(
+                self.members()
+                    .map(|(key, value)| (key, value.to_json()))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hasher};
+
+/// How tables of keys are hashed, e.g. by the interner of an `Arena`
+/// Keys are short, for which the default SipHash costs more than the lookup
+/// itself. `resistant` is for input whose keys an attacker could pick to collide.
+#[derive(Clone, Debug, Default)]
+pub enum KeyHasher {
+    #[default]
+    Fast,
+    Resistant(RandomState),
+}
+
+impl KeyHasher {
+    /// SipHash with random keys, as `HashMap` uses by default
+    pub fn resistant() -> Self {
+        KeyHasher::Resistant(RandomState::new())
+    }
+}
+
+impl BuildHasher for KeyHasher {
+    type Hasher = KeyHash;
+
+    fn build_hasher(&self) -> KeyHash {
+        match self {
+            KeyHasher::Fast => KeyHash::Fast(FastHasher::default()),
+            KeyHasher::Resistant(state) => KeyHash::Resistant(state.build_hasher()),
+        }
+    }
+}
+
+/// The hasher `KeyHasher` builds
+pub enum KeyHash {
+    Fast(FastHasher),
+    Resistant(DefaultHasher),
+}
+
+impl Hasher for KeyHash {
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            KeyHash::Fast(hasher) => hasher.write(bytes),
+            KeyHash::Resistant(hasher) => hasher.write(bytes),
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        match self {
+            KeyHash::Fast(hasher) => hasher.write_u8(n),
+            KeyHash::Resistant(hasher) => hasher.write_u8(n),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        match self {
+            KeyHash::Fast(hasher) => hasher.finish(),
+            KeyHash::Resistant(hasher) => hasher.finish(),
+        }
+    }
+}
+
+/// The multiply and rotate hash of rustc (FxHash), taking eight bytes at a time
+/// Not randomized, so colliding keys are easy to find.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FastHasher(u64);
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl FastHasher {
+    fn add(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FastHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut rest = bytes;
+        while let Some((word, after)) = rest.split_first_chunk::<8>() {
+            self.add(u64::from_le_bytes(*word));
+            rest = after;
+        }
+        if let Some((word, after)) = rest.split_first_chunk::<4>() {
+            self.add(u32::from_le_bytes(*word) as u64);
+            rest = after;
+        }
+        for &byte in rest {
+            self.add(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.add(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        // The low bits of a product only depend on the low bits of its factors,
+        // while tables pick buckets by the low bits
+        self.0.rotate_left(26)
+    }
+}
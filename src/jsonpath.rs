@@ -0,0 +1,215 @@
+use std::fmt;
+
+use crate::array::slice_indices;
+use crate::parse::JsonValue;
+
+#[derive(Debug)]
+pub struct PathError(pub String);
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// A compiled JSONPath expression
+/// e.g., `$.users[*].name` or `$..price`
+/// Supported: `$`, `.name`, `['name']`, `.*`, `[*]`, `[i]` (negative from the
+/// end), `[start:end:step]`, unions such as `[0,2]` or `['a','b']` and the
+/// descendant operator `..`. Filter expressions are not supported, use `Query`.
+#[derive(Clone, Debug)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Clone, Debug)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Wildcard,
+}
+
+impl JsonPath {
+    /// Compile a path expression, which must start at the root `$`
+    pub fn compile(source: &str) -> Result<Self, PathError> {
+        let rest = source
+            .strip_prefix('$')
+            .ok_or_else(|| err(source, "expected '$' at the start"))?;
+        let mut chars = rest.chars().peekable();
+        let mut segments = vec![];
+
+        while let Some(c) = chars.next() {
+            let segment = match c {
+                '.' if chars.peek() == Some(&'.') => {
+                    chars.next();
+                    let selectors = match chars.peek() {
+                        Some('[') => {
+                            chars.next();
+                            bracket(source, &mut chars)?
+                        }
+                        _ => vec![dotted(source, &mut chars)?],
+                    };
+                    Segment::Descendant(selectors)
+                }
+                '.' => Segment::Child(vec![dotted(source, &mut chars)?]),
+                '[' => Segment::Child(bracket(source, &mut chars)?),
+                c => return Err(err(source, &format!("unexpected '{}'", c))),
+            };
+            segments.push(segment);
+        }
+        Ok(JsonPath { segments })
+    }
+
+    /// Every value the path matches in `root`, in document order
+    pub fn select<'a>(&self, root: &'a JsonValue) -> Vec<&'a JsonValue> {
+        let mut nodes = vec![root];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for node in nodes {
+                match segment {
+                    Segment::Child(selectors) => apply(selectors, node, &mut next),
+                    Segment::Descendant(selectors) => descend(selectors, node, &mut next),
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn err(source: &str, msg: &str) -> PathError {
+    PathError(format!("Path error: {} in '{}'", msg, source))
+}
+
+/// The name or `*` after a `.`
+fn dotted(source: &str, chars: &mut Chars) -> Result<Selector, PathError> {
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == '.' || c == '[' {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    match name.as_str() {
+        "" => Err(err(source, "expected a name after '.'")),
+        "*" => Ok(Selector::Wildcard),
+        _ => Ok(Selector::Name(name)),
+    }
+}
+
+/// The comma separated selectors of a `[...]`, after the opening bracket
+fn bracket(source: &str, chars: &mut Chars) -> Result<Vec<Selector>, PathError> {
+    let mut selectors = vec![];
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let selector = match chars.peek() {
+            Some(&quote @ ('\'' | '"')) => {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => break,
+                        Some('\\') => name.extend(chars.next()),
+                        Some(c) => name.push(c),
+                        None => return Err(err(source, "unterminated string")),
+                    }
+                }
+                Selector::Name(name)
+            }
+            Some('*') => {
+                chars.next();
+                Selector::Wildcard
+            }
+            None => return Err(err(source, "expected ']'")),
+            _ => {
+                let mut text = String::new();
+                while let Some(c) = chars.next_if(|c| !matches!(c, ',' | ']')) {
+                    text.push(c);
+                }
+                index_or_slice(source, text.trim())?
+            }
+        };
+        selectors.push(selector);
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(selectors),
+            _ => return Err(err(source, "expected ']'")),
+        }
+    }
+}
+
+fn index_or_slice(source: &str, text: &str) -> Result<Selector, PathError> {
+    let number = |part: &str| {
+        let part = part.trim();
+        if part.is_empty() {
+            return Ok(None);
+        }
+        part.parse::<i64>()
+            .map(Some)
+            .map_err(|_| err(source, &format!("invalid index '{}'", part)))
+    };
+
+    let parts = text.split(':').collect::<Vec<_>>();
+    match parts.as_slice() {
+        [index] => match number(index)? {
+            Some(index) => Ok(Selector::Index(index)),
+            None => Err(err(source, "empty brackets")),
+        },
+        [start, end] => Ok(Selector::Slice(number(start)?, number(end)?, None)),
+        [start, end, step] => match number(step)? {
+            Some(0) => Err(err(source, "slice step cannot be zero")),
+            step => Ok(Selector::Slice(number(start)?, number(end)?, step)),
+        },
+        _ => Err(err(source, &format!("invalid selector '{}'", text))),
+    }
+}
+
+fn apply<'a>(selectors: &[Selector], node: &'a JsonValue, out: &mut Vec<&'a JsonValue>) {
+    for selector in selectors {
+        match (selector, node) {
+            (Selector::Name(name), _) => out.extend(node.get(name)),
+            (Selector::Wildcard, JsonValue::Arr(values)) => out.extend(values),
+            (Selector::Wildcard, _) => out.extend(node.members().map(|(_, v)| v)),
+            (Selector::Index(i), JsonValue::Arr(values)) => {
+                let i = if *i < 0 { *i + values.len() as i64 } else { *i };
+                out.extend(usize::try_from(i).ok().and_then(|i| values.get(i)));
+            }
+            (Selector::Slice(start, end, step), JsonValue::Arr(values)) => {
+                let indices = slice_indices(values.len(), *start, *end, step.unwrap_or(1));
+                out.extend(indices.into_iter().flatten().map(|i| &values[i]));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Apply the selectors to `node` and each of its descendants
+fn descend<'a>(selectors: &[Selector], node: &'a JsonValue, out: &mut Vec<&'a JsonValue>) {
+    apply(selectors, node, out);
+    match node {
+        JsonValue::Arr(values) => {
+            for value in values {
+                descend(selectors, value, out);
+            }
+        }
+        _ => {
+            for (_, value) in node.members() {
+                descend(selectors, value, out);
+            }
+        }
+    }
+}
@@ -1,2 +1,45 @@
 pub mod tokenize;
 pub mod parse;
+pub mod serialize;
+pub mod digest;
+pub mod writer;
+pub mod stream;
+pub mod borrowed;
+pub mod tape;
+pub mod flat;
+pub mod arena;
+pub mod hash;
+pub mod metrics;
+pub mod ndjson;
+pub mod parallel;
+pub mod pointer;
+pub mod query;
+pub mod walk;
+pub mod stats;
+pub mod select;
+pub mod array;
+pub mod csv;
+pub mod schema;
+pub mod coerce;
+pub mod compare;
+pub mod diff;
+pub mod merge;
+pub mod redact;
+pub mod case;
+pub mod refs;
+pub mod template;
+pub mod jsonpath;
+pub mod patch;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+#[cfg(feature = "toml")]
+pub mod toml;
+pub mod msgpack;
+pub mod cbor;
+mod escape;
+mod value;
+mod regex;
+#[cfg(feature = "ryu")]
+mod ryu;
+
+pub use stream::stream_array;
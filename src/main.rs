@@ -1,42 +1,1868 @@
 use std::env;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, OnceLock};
 
-use jsonp::parse::Parser;
-use jsonp::tokenize::Tokenizer;
+use jsonp::cbor::{from_cbor, to_cbor};
+use jsonp::csv::{to_csv_with, CsvOptions, Flatten};
+use jsonp::diff::{diff, render_diff, render_text_diff, to_patch, DiffFormat, DiffOptions};
+use jsonp::digest::sha256;
+use jsonp::jsonpath::JsonPath;
+use jsonp::merge::{deep_merge, merge3, ArrayMerge};
+use jsonp::metrics::Metrics;
+use jsonp::msgpack::{from_msgpack, to_msgpack};
+use jsonp::ndjson::parse_lines;
+use jsonp::parallel::parse_parallel;
+use jsonp::parse::{JsonValue, Parser, SyntaxError};
+use jsonp::patch::apply_patch;
+use jsonp::pointer::JsonPointer;
+use jsonp::query::Query;
+use jsonp::schema::{self, Schema};
+use jsonp::serialize::{
+    to_string, to_string_pretty, to_string_pretty_with, to_string_with, SerializeOptions,
+};
+use jsonp::stream::JsonReader;
+use jsonp::tokenize::{LenientOptions, Tokenizer};
+#[cfg(feature = "toml")]
+use jsonp::toml::{from_toml, to_toml};
+use jsonp::walk::glob_matches;
+use jsonp::writer::JsonWriter;
 
-fn main() -> Result<(), ()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <json-file>", args[0]);
-        return Err(());
+mod completions;
+#[macro_use]
+mod diagnostic;
+mod explore;
+mod mmap;
+#[macro_use]
+mod output;
+mod repl;
+mod terminal;
+#[cfg(feature = "yaml")]
+use jsonp::yaml::{from_yaml, to_yaml};
+
+use crate::diagnostic::{Diagnostic, Origin};
+use crate::mmap::Source;
+
+/// Subcommands with their arguments, as shown in the usage text
+const COMMANDS: &[(&str, &str)] = &[
+    (
+        "fmt",
+        "[--ndjson | --stream] [--watch] [--check] [--indent N] [--sort-keys] [--recursive [--glob <pattern>]] [--jobs N] [<path>...]",
+    ),
+    (
+        "validate",
+        "[--ndjson | --stream] [--watch] [--schema <schema-file>] [--recursive [--glob <pattern>]] [--jobs N] [<path>...]",
+    ),
+    (
+        "compact",
+        "[--ndjson | --stream] [--sort-keys] [--recursive [--glob <pattern>]] [--jobs N] [<path>...]",
+    ),
+    ("query", "[--ndjson] [-r] <filter> [<json-file>]"),
+    ("get", "[--ndjson | --stream] [-r] <pointer> [<json-file>]"),
+    ("get", "[--ndjson] [-r] --path <jsonpath> [<json-file>]"),
+    (
+        "to-csv",
+        "[--at <pointer>] [--delimiter C] [--columns a,b] [--flatten json|paths] [<json-file>]",
+    ),
+    ("keys", "[--at <pointer>] [<json-file>]"),
+    ("len", "[--at <pointer>] [<json-file>]"),
+    ("type", "[--at <pointer>] [<json-file>]"),
+    ("stats", "[<json-file>]"),
+    ("canonicalize", "[--hash <sha256-file>] [<json-file>]"),
+    ("escape", "[<text-file>]"),
+    ("unescape", "[<json-file>]"),
+    ("schema", "[--ndjson] [--recursive [--glob <pattern>]] [<path>...]"),
+    ("set", "[--in-place] <pointer> <json-value> [<json-file>]"),
+    ("delete", "[--in-place] <pointer> [<json-file>]"),
+    ("patch", "[--in-place] <json-file> <patch-file>"),
+    (
+        "diff",
+        "[--format unified|side-by-side|rfc6902] <old-file> <new-file>",
+    ),
+    (
+        "merge",
+        "[--arrays replace|concat|index] <base-file> <override-file>...",
+    ),
+    ("merge3", "<base-file> <our-file> <their-file>"),
+    ("explore", "[<json-file>]"),
+    ("repl", "[<json-file>]"),
+    #[cfg(feature = "yaml")]
+    ("to-yaml", "[<json-file>]"),
+    #[cfg(feature = "yaml")]
+    ("from-yaml", "[<yaml-file>]"),
+    #[cfg(feature = "toml")]
+    ("to-toml", "[<json-file>]"),
+    #[cfg(feature = "toml")]
+    ("from-toml", "[<toml-file>]"),
+    ("to-msgpack", "[<json-file>]"),
+    ("from-msgpack", "[<msgpack-file>]"),
+    ("to-cbor", "[<json-file>]"),
+    ("from-cbor", "[<cbor-file>]"),
+    ("completions", "bash|zsh|fish"),
+];
+
+/// Flags and whether they take a value, e.g. `--indent 4` or `--indent=4`
+const FLAGS: &[(&str, bool)] = &[
+    ("--pretty", false),
+    ("--indent", true),
+    ("--minify", false),
+    ("--path", true),
+    ("--raw", false),
+    ("--in-place", false),
+    ("--format", true),
+    ("--arrays", true),
+    ("--at", true),
+    ("--color", true),
+    ("--sort-keys", false),
+    ("--delimiter", true),
+    ("--columns", true),
+    ("--flatten", true),
+    ("--ndjson", false),
+    ("--stream", false),
+    ("--watch", false),
+    ("--schema", true),
+    ("--lenient", false),
+    ("--allow", true),
+    ("--check", false),
+    ("--output", true),
+    ("--recursive", false),
+    ("--glob", true),
+    ("--jobs", true),
+    ("--error-format", true),
+    ("--hash", true),
+    ("--mmap", false),
+    ("--timing", false),
+    ("--parallel", false),
+];
+
+/// Single letter spellings of flags
+const SHORT_FLAGS: &[(&str, &str)] = &[("-r", "--raw"), ("-i", "--in-place"), ("-o", "--output")];
+
+/// Parsed command line: positional arguments and flags in the order given
+#[derive(Default)]
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            let arg = match SHORT_FLAGS.iter().find(|(short, _)| *short == arg) {
+                Some((_, long)) => long.to_string(),
+                None => arg,
+            };
+            if !arg.starts_with("--") {
+                parsed.positional.push(arg);
+                continue;
+            }
+
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let Some(&(_, takes_value)) = FLAGS.iter().find(|(flag, _)| *flag == name) else {
+                return Err(format!("Unknown flag '{}'", name));
+            };
+
+            let value = match (takes_value, inline) {
+                (true, Some(value)) => Some(value),
+                (true, None) => match args.next() {
+                    Some(value) => Some(value),
+                    None => return Err(format!("Flag '{}' needs a value", name)),
+                },
+                (false, None) => None,
+                (false, Some(_)) => return Err(format!("Flag '{}' does not take a value", name)),
+            };
+            parsed.flags.push((name, value));
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|(flag, _)| flag == name)
+    }
+
+    /// The value of the last occurrence of a flag
+    fn value(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(flag, _)| flag == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
+
+/// Why a command failed, after it was reported on stderr
+/// The discriminant is the exit status, the worst failure wins over several files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Failure {
+    /// Invalid input or usage, or documents that differ
+    Invalid = 1,
+    Io = 2,
+}
+
+fn main() -> ExitCode {
+    let result = run();
+    let result = match output::finish(result.is_ok()) {
+        Ok(()) => result,
+        Err(msg) => {
+            report!("{}", msg);
+            Err(Failure::Io)
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => ExitCode::from(failure as u8),
+    }
+}
+
+fn run() -> Result<(), Failure> {
+    let mut argv = env::args();
+    let program = argv.next().unwrap_or_else(|| "jsonp".to_string());
+    let args = match Args::parse(argv) {
+        Ok(args) => args,
+        Err(msg) => {
+            report!("{}", msg);
+            usage(&program);
+            return Err(Failure::Invalid);
+        }
+    };
+
+    match args.value("--error-format") {
+        None | Some("text") => (),
+        Some("json") => diagnostic::use_json(),
+        Some(other) => {
+            report!("Invalid error format '{}', expected text or json", other);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    if let Some(when) = args.value("--color") {
+        if !matches!(when, "always" | "auto" | "never") {
+            report!("Invalid color '{}', expected always, auto or never", when);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    let lenient = match lenient_options(&args) {
+        Ok(options) => options,
+        Err(msg) => {
+            report!("{}", msg);
+            return Err(Failure::Invalid);
+        }
+    };
+    LENIENT.get_or_init(|| lenient);
+    MMAP.get_or_init(|| args.flag("--mmap"));
+    TIMING.get_or_init(|| args.flag("--timing"));
+
+    let (command, rest) = match args.positional.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => ("", &[][..]),
+    };
+
+    if args.flag("--stream") {
+        let streams = match command {
+            "fmt" | "compact" | "validate" => true,
+            "get" => args.value("--path").is_none(),
+            _ => !COMMANDS.iter().any(|(name, _)| *name == command),
+        };
+        let unsupported = [
+            "--ndjson",
+            "--sort-keys",
+            "--schema",
+            "--lenient",
+            "--allow",
+            "--check",
+        ];
+        if !streams || unsupported.iter().any(|flag| args.flag(flag)) {
+            report!("--stream only works with fmt, compact, validate and get <pointer>, and not with --ndjson, --sort-keys, --schema, --lenient, --allow or --check");
+            return Err(Failure::Invalid);
+        }
+    }
+    if args.flag("--parallel") {
+        let unsupported = ["--ndjson", "--stream", "--lenient", "--allow", "--timing"];
+        if unsupported.iter().any(|flag| args.flag(flag)) {
+            report!("--parallel only parses strict JSON, and not with --ndjson, --stream, --lenient, --allow or --timing");
+            return Err(Failure::Invalid);
+        }
+        let jobs = jobs(&args)?;
+        PARALLEL.get_or_init(|| jobs);
+    }
+    let walked;
+    let rest = if args.flag("--recursive") {
+        if !matches!(command, "fmt" | "compact" | "validate" | "schema") {
+            report!("--recursive only works with fmt, compact, validate and schema");
+            return Err(Failure::Invalid);
+        }
+        walked = walk(rest, args.value("--glob"))?;
+        &walked[..]
+    } else if args.flag("--glob") {
+        report!("--glob picks the files found with --recursive, and needs it");
+        return Err(Failure::Invalid);
+    } else {
+        rest
+    };
+
+    if let Some(path) = args.value("--output") {
+        let writes = ["--watch", "--check", "--in-place"];
+        if matches!(command, "validate" | "explore" | "repl")
+            || writes.iter().any(|flag| args.flag(flag))
+        {
+            report!("--output does not work with validate, explore, repl, --watch, --check or --in-place");
+            return Err(Failure::Invalid);
+        }
+        if let Err(msg) = output::open(path) {
+            report!("{}", msg);
+            return Err(Failure::Io);
+        }
+    }
+    match (command, rest) {
+        ("fmt", paths) if args.flag("--watch") => {
+            watch(paths, |path| fmt(path, &args, args.flag("--minify")))
+        }
+        ("fmt", paths) => {
+            let minify = args.flag("--minify");
+            worst(&each_parallel(paths, jobs(&args)?, |path| {
+                fmt(path, &args, minify)
+            }))
+        }
+        ("compact", paths) => worst(&each_parallel(paths, jobs(&args)?, |path| {
+            fmt(path, &args, true)
+        })),
+        ("validate", paths) => {
+            let schema = load_schema(&args)?;
+            let validate = |path: &str| validate(path, &args, schema.as_ref());
+            if args.flag("--watch") {
+                return watch(paths, validate);
+            }
+            let results = each_parallel(paths, jobs(&args)?, validate);
+            if args.flag("--recursive") && !paths.is_empty() && !diagnostic::is_json() {
+                match results.iter().filter(|result| result.is_err()).count() {
+                    0 => eprintln!("All {} file(s) ok", paths.len()),
+                    failed => eprintln!("{} of {} file(s) failed", failed, paths.len()),
+                }
+            }
+            worst(&results)
+        }
+        ("query", [filter]) => query(filter, STDIN, &args),
+        ("query", [filter, path]) => query(filter, path, &args),
+        ("get", [] | [_]) if args.value("--path").is_some() => get(None, input(rest), &args),
+        ("get", [pointer]) if args.value("--path").is_none() => get(Some(pointer), STDIN, &args),
+        ("get", [pointer, path]) if args.value("--path").is_none() => {
+            get(Some(pointer), path, &args)
+        }
+        ("to-csv", [] | [_]) => json_to_csv(input(rest), &args),
+        ("keys" | "len" | "type", [] | [_]) => inspect(command, input(rest), &args),
+        ("stats", [] | [_]) => stats(input(rest)),
+        ("canonicalize", [] | [_]) => canonicalize(input(rest), &args),
+        ("escape", [] | [_]) => escape(input(rest)),
+        ("unescape", [] | [_]) => unescape(input(rest)),
+        ("schema", paths) => infer_schema(paths, &args),
+        ("set", [pointer, value]) => edit(pointer, Some(value), STDIN, &args),
+        ("set", [pointer, value, path]) => edit(pointer, Some(value), path, &args),
+        ("delete", [pointer]) => edit(pointer, None, STDIN, &args),
+        ("delete", [pointer, path]) => edit(pointer, None, path, &args),
+        ("patch", [path, patch_path]) => patch(path, patch_path, &args),
+        ("diff", [old, new]) => compare(old, new, &args),
+        ("merge", [_, _, ..]) => layer(rest, &args),
+        ("merge3", [base, ours, theirs]) => merge(base, ours, theirs),
+        ("repl", [] | [_]) => {
+            let path = input(rest);
+            let json = read_json(path)?;
+            // Lines come from the terminal, unless stdin is a script of them
+            let interactive = path == STDIN || std::io::stdin().is_terminal();
+            repl::repl(&json, use_color(&args), interactive).map_err(|msg| {
+                report!("{}", msg);
+                Failure::Io
+            })
+        }
+        ("explore", [] | [_]) => {
+            let path = input(rest);
+            let json = read_json(path)?;
+            explore::explore(&json, display_name(path)).map_err(|msg| {
+                report!("{}", msg);
+                Failure::Io
+            })
+        }
+        #[cfg(feature = "yaml")]
+        ("to-yaml", [] | [_]) => json_to_yaml(input(rest)),
+        #[cfg(feature = "yaml")]
+        ("from-yaml", [] | [_]) => yaml_to_json(input(rest), &args),
+        #[cfg(feature = "toml")]
+        ("to-toml", [] | [_]) => json_to_toml(input(rest)),
+        #[cfg(feature = "toml")]
+        ("from-toml", [] | [_]) => toml_to_json(input(rest), &args),
+        ("to-msgpack", [] | [_]) => write_binary(&to_msgpack(&read_json(input(rest))?)),
+        ("from-msgpack", [] | [_]) => msgpack_to_json(input(rest), &args),
+        ("to-cbor", [] | [_]) => write_binary(&to_cbor(&read_json(input(rest))?)),
+        ("from-cbor", [] | [_]) => cbor_to_json(input(rest), &args),
+        ("completions", [shell]) => match completions::script(shell) {
+            Some(script) => {
+                out!("{}", script);
+                Ok(())
+            }
+            None => {
+                report!("Invalid shell '{}', expected bash, zsh or fish", shell);
+                Err(Failure::Invalid)
+            }
+        },
+        _ if !COMMANDS.iter().any(|(name, _)| *name == command) => {
+            each(&args.positional, |path| show(path, &args))
+        }
+        _ => {
+            usage(&program);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// The path that reads standard input instead of a file
+const STDIN: &str = "-";
+
+/// Run a command on every input, standard input if there are none
+/// Keeps going after a file fails so that every problem is reported.
+fn each<F>(paths: &[String], mut command: F) -> Result<(), Failure>
+where
+    F: FnMut(&str) -> Result<(), Failure>,
+{
+    if paths.is_empty() {
+        return command(STDIN);
+    }
+    let mut worst = None;
+    for path in paths {
+        if let Err(failure) = command(path) {
+            worst = worst.max(Some(failure));
+        }
+    }
+    worst.map_or(Ok(()), Err)
+}
+
+/// Like `each` on `jobs` threads at once, giving the result for every input
+/// Output is written in the order of `paths` as each file is done with, while
+/// diagnostics are printed as they come.
+fn each_parallel<F>(paths: &[String], jobs: usize, command: F) -> Vec<Result<(), Failure>>
+where
+    F: Fn(&str) -> Result<(), Failure> + Sync,
+{
+    if paths.is_empty() {
+        return vec![command(STDIN)];
+    }
+    if jobs <= 1 {
+        return paths.iter().map(|path| command(path)).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.min(paths.len()) {
+            let (sender, next, command) = (sender.clone(), &next, &command);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(i) else {
+                    break;
+                };
+                let (result, bytes) = output::capture(|| command(path));
+                if sender.send((i, result, bytes)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut results = vec![None; paths.len()];
+        let mut pending = vec![None; paths.len()];
+        let mut written = 0;
+        for (i, result, bytes) in receiver {
+            results[i] = Some(result);
+            pending[i] = Some(bytes);
+            while let Some(bytes) = pending.get_mut(written).and_then(Option::take) {
+                let _ = output::stdout().write_all(&bytes);
+                written += 1;
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or(Err(Failure::Io)))
+            .collect()
+    })
+}
+
+/// The worst of several results
+fn worst(results: &[Result<(), Failure>]) -> Result<(), Failure> {
+    results
+        .iter()
+        .filter_map(|result| result.err())
+        .max()
+        .map_or(Ok(()), Err)
+}
+
+/// Threads to work on files with, chosen with `--jobs N`
+fn jobs(args: &Args) -> Result<usize, Failure> {
+    match args.value("--jobs").map(str::parse::<usize>) {
+        None => Ok(1),
+        Some(Ok(jobs)) if jobs > 0 => Ok(jobs),
+        Some(_) => {
+            report!(
+                "Invalid job count '{}', expected a positive number",
+                args.value("--jobs").unwrap_or_default()
+            );
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Replace the directories among `paths` with the files under them, sorted by
+/// name, and with a `glob` only those whose name matches
+/// Hidden files and directories are skipped, as are links to directories.
+fn walk(paths: &[String], glob: Option<&str>) -> Result<Vec<String>, Failure> {
+    let mut files = vec![];
+    let mut pending = paths
+        .iter()
+        .rev()
+        .map(|path| (std::path::PathBuf::from(path), true))
+        .collect::<Vec<_>>();
+    while let Some((path, named)) = pending.pop() {
+        if !named && path.is_symlink() && path.is_dir() {
+            continue;
+        }
+        if !path.is_dir() {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if named || glob.is_none_or(|glob| glob_matches(glob, &name)) {
+                files.push(path.to_string_lossy().into_owned());
+            }
+            continue;
+        }
+
+        let entries = std::fs::read_dir(&path).and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+        });
+        let mut entries = match entries {
+            Ok(entries) => entries,
+            Err(err) => {
+                report!("{}: IO error: {}", path.display(), err);
+                return Err(Failure::Io);
+            }
+        };
+        entries.retain(|entry| {
+            !entry
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .starts_with('.')
+        });
+        entries.sort();
+        pending.extend(entries.into_iter().rev().map(|entry| (entry, false)));
+    }
+
+    if files.is_empty() && !paths.is_empty() {
+        report!("No files found under {}", paths.join(", "));
+        return Err(Failure::Invalid);
+    }
+    Ok(files)
+}
+
+/// How often `--watch` looks for changes
+const WATCH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Run a command on every file, then again each time one of them changes
+/// There is no file notification API in std, so modification times are polled.
+/// Runs until interrupted.
+fn watch<F>(paths: &[String], mut command: F) -> Result<(), Failure>
+where
+    F: FnMut(&str) -> Result<(), Failure>,
+{
+    if paths.is_empty() || paths.iter().any(|path| path == STDIN) {
+        report!("--watch needs the files to watch, it cannot read stdin");
+        return Err(Failure::Invalid);
+    }
+
+    let modified = || {
+        paths
+            .iter()
+            .map(|path| {
+                // The length catches saves within the same timestamp tick
+                let metadata = std::fs::metadata(path).ok()?;
+                Some((metadata.modified().ok(), metadata.len()))
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut seen = modified();
+    loop {
+        let failed = paths.iter().filter(|path| command(path).is_err()).count();
+        if diagnostic::is_json() {
+            // Only the failures themselves are reported
+        } else if failed == 0 {
+            eprintln!("All {} file(s) ok, watching for changes", paths.len());
+        } else {
+            eprintln!(
+                "{} of {} file(s) failed, watching for changes",
+                failed,
+                paths.len()
+            );
+        }
+
+        loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let now = modified();
+            if now != seen {
+                seen = now;
+                break;
+            }
+        }
+    }
+}
+
+/// The single optional input of a command, standard input if it was left out
+fn input(rest: &[String]) -> &str {
+    rest.first().map_or(STDIN, |path| path.as_str())
+}
+
+/// How a path is named in diagnostics
+fn display_name(path: &str) -> &str {
+    if path == STDIN {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn usage(program: &str) {
+    eprintln!(
+        "Usage: {} [--pretty | --minify] [--indent N] [--color always|auto|never] [<json-file>...]",
+        program
+    );
+    eprintln!("Any command reading JSON also takes --lenient, or --allow comments,trailing-commas,single-quotes");
+    eprintln!("Any command printing a result also takes -o <file>, which is replaced once the result is complete");
+    eprintln!(
+        "Failures are reported as text, or with --error-format json as one JSON object per line"
+    );
+    eprintln!("With --timing, how long each document took to parse is printed on stderr");
+    eprintln!("With --ndjson, --jobs N also parses the records of each file on N threads");
+    eprintln!("With --parallel, --jobs N also splits each large document between N threads, as an experiment");
+    eprintln!("Files can be mapped into memory rather than read with --mmap, and must not change meanwhile");
+    eprintln!("set --in-place writes a value over the one it replaces and leaves the rest of the file alone; adding members or elements, delete and patch reformat the whole file");
+    for (command, args) in COMMANDS {
+        eprintln!("       {} {} {}", program, command, args);
+    }
+}
+
+/// Print a file as formatted JSON, colored when printing to a terminal
+fn show(path: &str, args: &Args) -> Result<(), Failure> {
+    if args.flag("--minify") {
+        return fmt(path, args, true);
+    }
+    if !use_color(args) || args.flag("--pretty") || args.value("--indent").is_some() {
+        return fmt(path, args, false);
+    }
+
+    let json = read_json(path)?;
+    match json.to_string_colored() {
+        Ok(text) => outln!("{}", text),
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a file to stdout, pretty or on a single line
+fn fmt(path: &str, args: &Args, compact: bool) -> Result<(), Failure> {
+    let mut options = serialize_options(args)?;
+    if args.flag("--stream") {
+        let stdout = std::io::BufWriter::new(output::stdout());
+        return stream(
+            path,
+            if compact {
+                JsonWriter::with_options(stdout, options)
+            } else {
+                JsonWriter::pretty(stdout, options)
+            },
+        );
+    }
+    let compact = compact || args.flag("--ndjson");
+    let check = args.flag("--check");
+    // With `--check` the output is compared to the input rather than printed,
+    // so it must not carry colors; only the diff is colored
+    if check {
+        options.color = false;
+    }
+    let mut original = String::new();
+    let mut formatted = String::new();
+    documents_with_source(path, args, |_, json, source| {
+        let text = if compact {
+            to_string_with(&json, &options)
+        } else {
+            to_string_pretty_with(&json, &options)
+        };
+        match text {
+            Ok(text) if check => {
+                original.push_str(source);
+                if args.flag("--ndjson") {
+                    original.push('\n');
+                }
+                formatted.push_str(&text);
+            }
+            Ok(text) => out!("{}", text),
+            Err(err) => {
+                report!("{}", err.0);
+                return Err(Failure::Invalid);
+            }
+        }
+        Ok(())
+    })?;
+    if original == formatted {
+        return Ok(());
+    }
+
+    let options = DiffOptions {
+        color: use_color(args),
+        old_name: display_name(path).to_string(),
+        new_name: format!("{} (formatted)", display_name(path)),
+        ..Default::default()
+    };
+    match render_text_diff(&original, &formatted, &options) {
+        // Only line endings or a final newline differ, which `lines` does not see
+        text if text.is_empty() => report!(
+            "{}: differs from its formatted form only in line endings",
+            display_name(path)
+        ),
+        text => out!("{}", text),
+    }
+    Err(Failure::Invalid)
+}
+
+/// Check that a document parses and, given a schema, that it satisfies it
+/// Violations are reported with the line and column of the offending value.
+fn validate(path: &str, args: &Args, schema: Option<&Schema>) -> Result<(), Failure> {
+    if args.flag("--stream") {
+        return stream(path, JsonWriter::new(std::io::sink()));
+    }
+    let Some(schema) = schema else {
+        return check(path, args);
+    };
+
+    documents_with_source(path, args, |name, json, source| {
+        let violations = schema.validate(&json);
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let pointers = violations
+            .iter()
+            .map(|violation| violation.instance_path.clone())
+            .collect::<Vec<_>>();
+        // Positions are only a help, so a document the reader rejects just goes without
+        let positions = JsonReader::new(source.as_bytes())
+            .locate(&pointers)
+            .unwrap_or_default();
+        for violation in &violations {
+            let message = format!(
+                "{}: {} (schema {})",
+                describe_pointer(&violation.instance_path),
+                violation.message,
+                describe_pointer(&violation.schema_path)
+            );
+            let at = positions.get(&violation.instance_path).copied();
+            Diagnostic::schema(name, message, at, source).emit();
+        }
+        Err(Failure::Invalid)
+    })
+}
+
+/// Compile the schema given with `--schema`, if any
+fn load_schema(args: &Args) -> Result<Option<Schema>, Failure> {
+    let Some(path) = args.value("--schema") else {
+        return Ok(None);
+    };
+    let json = read_json(path)?;
+    match Schema::compile(&json) {
+        Ok(schema) => Ok(Some(schema)),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// A pointer for diagnostics, where the empty root pointer would go unnoticed
+fn describe_pointer(pointer: &JsonPointer) -> String {
+    if pointer.tokens().is_empty() {
+        "(root)".to_string()
+    } else {
+        pointer.to_string()
+    }
+}
+
+/// Copy a document from its parse events into `writer` with `--stream`,
+/// without ever holding more than the stack of open containers
+fn stream<W: Write>(path: &str, mut writer: JsonWriter<W>) -> Result<(), Failure> {
+    let mut reader = JsonReader::new(open_input(path)?);
+    let result = loop {
+        match reader.next_event() {
+            Ok(Some(event)) => {
+                if let Err(err) = writer.event(&event) {
+                    break Err(err.0);
+                }
+            }
+            Ok(None) => break writer.finish().map(|_| ()).map_err(|err| err.0),
+            Err(err) => break Err(err.to_string()),
+        }
+    };
+    result.map_err(|msg| stream_failure(path, &msg))
+}
+
+/// Report an error met while streaming, which may be a read or write failure
+fn stream_failure(path: &str, msg: &str) -> Failure {
+    report!("{}: {}", display_name(path), msg);
+    if msg.starts_with("IO error") {
+        Failure::Io
+    } else {
+        Failure::Invalid
+    }
+}
+
+/// Output options shared by the formatting commands
+fn serialize_options(args: &Args) -> Result<SerializeOptions, Failure> {
+    let mut options = SerializeOptions {
+        trailing_newline: true,
+        color: use_color(args),
+        sort_keys: args.flag("--sort-keys"),
+        ..Default::default()
+    };
+    if let Some(indent) = args.value("--indent") {
+        match indent.parse::<usize>() {
+            Ok(n) => options.indent = " ".repeat(n),
+            Err(_) => {
+                report!("Invalid indent '{}', expected a number of spaces", indent);
+                return Err(Failure::Invalid);
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Run a jq-style filter over a file and print every result
+fn query(filter: &str, path: &str, args: &Args) -> Result<(), Failure> {
+    let query = match Query::compile(filter) {
+        Ok(query) => query,
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+
+    documents(path, args, |name, json| match query.eval(&json) {
+        Ok(results) => print_values(&results, args),
+        Err(err) => {
+            report!("{}: {}", name, err.0);
+            Err(Failure::Invalid)
+        }
+    })
+}
+
+/// Print the value at a pointer or dotted path, or every match of `--path`
+fn get(pointer: Option<&str>, path: &str, args: &Args) -> Result<(), Failure> {
+    match (pointer, args.value("--path")) {
+        (Some(pointer), _) => {
+            let pointer = match JsonPointer::from_path(pointer) {
+                Ok(pointer) => pointer,
+                Err(err) => {
+                    report!("{}", err.0);
+                    return Err(Failure::Invalid);
+                }
+            };
+            if args.flag("--stream") {
+                let mut reader = JsonReader::new(open_input(path)?);
+                return match reader.read_pointer(&pointer) {
+                    Ok(Some(value)) => print_values([&value], args),
+                    Ok(None) => {
+                        report!("{}: nothing at '{}'", display_name(path), pointer);
+                        Err(Failure::Invalid)
+                    }
+                    Err(err) => Err(stream_failure(path, &err.to_string())),
+                };
+            }
+            documents(path, args, |name, json| match pointer.get(&json) {
+                Some(value) => print_values([value], args),
+                None => {
+                    report!("{}: nothing at '{}'", name, pointer);
+                    Err(Failure::Invalid)
+                }
+            })
+        }
+        (None, Some(expr)) => {
+            let json_path = match JsonPath::compile(expr) {
+                Ok(json_path) => json_path,
+                Err(err) => {
+                    report!("{}", err.0);
+                    return Err(Failure::Invalid);
+                }
+            };
+            documents(path, args, |_, json| {
+                print_values(json_path.select(&json), args)
+            })
+        }
+        (None, None) => unreachable!("get is only dispatched with a pointer or --path"),
+    }
+}
+
+/// Print values one after another, strings without quotes for `--raw`
+/// With `--ndjson` every value takes exactly one line.
+fn print_values<'a>(
+    values: impl IntoIterator<Item = &'a JsonValue>,
+    args: &Args,
+) -> Result<(), Failure> {
+    let line = SerializeOptions {
+        color: use_color(args),
+        ..Default::default()
+    };
+    for value in values {
+        let text = match value {
+            JsonValue::Str(s) if args.flag("--raw") => Ok(s.to_string()),
+            _ if args.flag("--ndjson") => to_string_with(value, &line),
+            _ if use_color(args) => value.to_string_colored(),
+            _ => to_string_pretty(value),
+        };
+        match text {
+            Ok(text) => outln!("{}", text),
+            Err(err) => {
+                report!("{}", err.0);
+                return Err(Failure::Invalid);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the array of objects `--at` a pointer as CSV
+fn json_to_csv(path: &str, args: &Args) -> Result<(), Failure> {
+    let mut options = CsvOptions::default();
+    match args.value("--flatten").unwrap_or("json") {
+        "json" => options.flatten = Flatten::Json,
+        "paths" => options.flatten = Flatten::Paths,
+        other => {
+            report!("Unknown flattening '{}', expected json or paths", other);
+            return Err(Failure::Invalid);
+        }
+    }
+    if let Some(delimiter) = args.value("--delimiter") {
+        let mut chars = delimiter.chars();
+        options.delimiter = match (delimiter, chars.next(), chars.next()) {
+            ("\\t" | "tab", _, _) => '\t',
+            (_, Some(c), None) => c,
+            _ => {
+                report!("Invalid delimiter '{}', expected one character", delimiter);
+                return Err(Failure::Invalid);
+            }
+        };
+    }
+    options.columns = args
+        .value("--columns")
+        .map(|columns| columns.split(',').map(|c| c.trim().to_string()).collect());
+
+    let pointer = match JsonPointer::from_path(args.value("--at").unwrap_or("")) {
+        Ok(pointer) => pointer,
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    let json = read_json(path)?;
+    let Some(rows) = pointer.get(&json) else {
+        report!("{}: nothing at '{}'", display_name(path), pointer);
+        return Err(Failure::Invalid);
+    };
+    match to_csv_with(rows, &options) {
+        Ok(text) => out!("{}", text),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the sorted keys, the length or the type of the value `--at` a pointer
+/// Keys are printed one per line and strings without quotes, for shell scripts.
+fn inspect(command: &str, path: &str, args: &Args) -> Result<(), Failure> {
+    let pointer = match JsonPointer::from_path(args.value("--at").unwrap_or("")) {
+        Ok(pointer) => pointer,
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    let json = read_json(path)?;
+    let Some(value) = pointer.get(&json) else {
+        report!("{}: nothing at '{}'", display_name(path), pointer);
+        return Err(Failure::Invalid);
+    };
+
+    let filter = match command {
+        "keys" => "keys | .[]",
+        "len" => "length",
+        _ => "type",
+    };
+    let results = match Query::compile(filter).and_then(|query| query.eval(value)) {
+        Ok(results) => results,
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    for result in results {
+        match &result {
+            JsonValue::Str(s) => outln!("{}", s),
+            other => match to_string(other) {
+                Ok(text) => outln!("{}", text),
+                Err(err) => {
+                    report!("{}", err.0);
+                    return Err(Failure::Invalid);
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the RFC 8785 canonical form of a document, byte for byte without a
+/// trailing newline, and with `--hash` write its SHA-256 to a file
+/// The file reads like the output of `sha256sum`, naming the `--output` file so
+/// that `sha256sum -c` can check it.
+fn canonicalize(path: &str, args: &Args) -> Result<(), Failure> {
+    let json = read_json(path)?;
+    let text = match json.to_canonical_string() {
+        Ok(text) => text,
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    out!("{}", text);
+
+    if let Some(hash_path) = args.value("--hash") {
+        let digest = sha256(text.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        let name = args.value("--output").unwrap_or(STDIN);
+        write_atomic(hash_path, &format!("{}  {}\n", digest, name))?;
     }
+    Ok(())
+}
 
-    let source = match std::fs::read_to_string(args[1].as_str()) {
-        Ok(source) => source,
+/// Print text as a JSON string literal
+/// One trailing newline is taken for the end of the line rather than part of the
+/// text, so that `echo` output escapes as expected.
+fn escape(path: &str) -> Result<(), Failure> {
+    let source = read_source(path)?;
+    let text = source.strip_suffix('\n').unwrap_or(&source);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    match to_string(&JsonValue::Str(text.into())) {
+        Ok(literal) => {
+            outln!("{}", literal);
+            Ok(())
+        }
         Err(err) => {
-            eprintln!("IO error: {}", err);
-            return Err(());
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
         }
+    }
+}
+
+/// Print the text of a JSON string literal, which may be given without its quotes,
+/// as the inverse of `escape`
+fn unescape(path: &str) -> Result<(), Failure> {
+    let source = read_source(path)?;
+    let literal = source.trim();
+    // In an array, so that the reader, which stops after one value, gets to
+    // reject whatever follows the literal
+    let document = if literal.starts_with('"') {
+        format!("[{}]", literal)
+    } else {
+        format!("[\"{}\"]", literal)
     };
 
-    let mut tokenizer = Tokenizer::default();
-    let tokens = match tokenizer.tokenize(&source) {
-        Ok(toks) => toks,
+    match &JsonReader::new(document.as_bytes()).read_value() {
+        Ok(JsonValue::Arr(values)) => match &**values {
+            [JsonValue::Str(text)] => {
+                outln!("{}", text);
+                Ok(())
+            }
+            _ => {
+                report!("{}: expected a single string literal", display_name(path));
+                Err(Failure::Invalid)
+            }
+        },
+        Ok(_) => unreachable!("the document is an array"),
         Err(err) => {
-            eprintln!("Tokenizer error: {}", err);
-            return Err(());
+            report!("{}: {}", display_name(path), err);
+            Err(Failure::Invalid)
         }
+    }
+}
+
+/// Print what a document is made of, one figure per line
+fn stats(path: &str) -> Result<(), Failure> {
+    let stats = read_json(path)?.stats();
+    let largest = match &stats.largest_array {
+        Some((pointer, len)) if pointer.tokens().is_empty() => format!("{} items at the root", len),
+        Some((pointer, len)) => format!("{} items at {}", len, pointer),
+        None => "none".to_string(),
     };
+    for (label, figure) in [
+        ("nodes", stats.nodes().to_string()),
+        ("  objects", stats.objects.to_string()),
+        ("  arrays", stats.arrays.to_string()),
+        ("  strings", stats.strings.to_string()),
+        ("  integers", stats.integers.to_string()),
+        ("  floats", stats.floats.to_string()),
+        ("  booleans", stats.booleans.to_string()),
+        ("  nulls", stats.nulls.to_string()),
+        ("max depth", stats.max_depth.to_string()),
+        ("keys", stats.keys.to_string()),
+        ("largest array", largest),
+        ("string bytes", stats.string_bytes.to_string()),
+        ("duplicate keys", stats.duplicate_keys.len().to_string()),
+    ] {
+        outln!("{:<16}{}", label, figure);
+    }
+    for pointer in &stats.duplicate_keys {
+        outln!("  {}", pointer);
+    }
+    Ok(())
+}
+
+/// Print a JSON Schema that every document, or with `--ndjson` every line, satisfies
+/// Nothing is printed if any input fails, as the schema would be missing its samples.
+fn infer_schema(paths: &[String], args: &Args) -> Result<(), Failure> {
+    let options = serialize_options(args)?;
+    let mut samples = vec![];
+    each(paths, |path| {
+        documents(path, args, |_, json| {
+            samples.push(json);
+            Ok(())
+        })
+    })?;
 
-    let mut parser = Parser::new(tokens);
-    match parser.parse() {
-        Ok(json) => {
-            dbg!(json);
+    match to_string_pretty_with(&schema::infer(&samples), &options) {
+        Ok(text) => {
+            out!("{}", text);
+            Ok(())
         }
         Err(err) => {
-            eprintln!("{}", err.0);
-            return Err(())
+            report!("{}", err.0);
+            Err(Failure::Invalid)
         }
     }
+}
+
+/// Set the value at a pointer, or delete it without one, printing the document
+/// With `--in-place` the file is rewritten instead. A value that replaces one is
+/// written where the old one was, leaving the rest of the text alone; other
+/// edits reformat the file, keeping its indentation.
+fn edit(pointer: &str, value: Option<&str>, path: &str, args: &Args) -> Result<(), Failure> {
+    let in_place = args.flag("--in-place");
+    if in_place && path == STDIN {
+        report!("Cannot edit standard input in place");
+        return Err(Failure::Invalid);
+    }
+    let pointer = match JsonPointer::from_path(pointer) {
+        Ok(pointer) => pointer,
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    let value = match value {
+        Some(value) => Some(parse_value(value)?),
+        None => None,
+    };
+
+    let source = read_source(path)?;
+    let mut json = parse_json(display_name(path), &source)?;
+    let edited = match value {
+        Some(value) => json
+            .pointer_set(&pointer.to_string(), value)
+            .map(|old| old.is_some()),
+        None => json.pointer_remove(&pointer.to_string()).map(|_| false),
+    };
+    let replaced = match edited {
+        Ok(replaced) => replaced,
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+
+    if in_place {
+        match replaced.then(|| splice(&source, &pointer, &json)).flatten() {
+            Some(text) => write_atomic(path, &text),
+            None => write_json(path, &source, &json),
+        }
+    } else {
+        print_values([&json], args)
+    }
+}
+
+/// Apply a JSON Patch to a file, printing the result or with `--in-place`
+/// rewriting the file, which is left alone if any operation fails
+fn patch(path: &str, patch_path: &str, args: &Args) -> Result<(), Failure> {
+    let source = read_source(path)?;
+    let json = parse_json(display_name(path), &source)?;
+    let patch = read_json(patch_path)?;
+    let patched = match apply_patch(&json, &patch) {
+        Ok(patched) => patched,
+        Err(err) => {
+            report!("{}: {}", display_name(patch_path), err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+
+    if args.flag("--in-place") && path != STDIN {
+        write_json(path, &source, &patched)
+    } else {
+        print_values([&patched], args)
+    }
+}
+
+/// Write a document back over its file in the layout of its old `source`
+fn write_json(path: &str, source: &str, json: &JsonValue) -> Result<(), Failure> {
+    let options = layout_of(source);
+    let text = if source.trim().contains('\n') {
+        to_string_pretty_with(json, &options)
+    } else {
+        to_string_with(json, &options)
+    };
+    match text {
+        Ok(text) => write_atomic(path, &text),
+        Err(err) => {
+            report!("{}", err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// `source` with the value at `pointer` written over by the one in `json`,
+/// compact or indented like the old one, or `None` if it cannot be found
+fn splice(source: &str, pointer: &JsonPointer, json: &JsonValue) -> Option<String> {
+    let span = value_span(source, pointer)?;
+    let value = pointer.get(json)?;
+    let text = if source[span.clone()].contains('\n') {
+        let line = &source[source[..span.start].rfind('\n').map_or(0, |i| i + 1)..span.start];
+        let margin = &line[..line.len() - line.trim_start().len()];
+        let options = SerializeOptions {
+            trailing_newline: false,
+            ..layout_of(source)
+        };
+        let text = to_string_pretty_with(value, &options).ok()?;
+        text.replace('\n', &format!("\n{}", margin))
+    } else {
+        to_string(value).ok()?
+    };
 
+    let spliced = format!("{}{}{}", &source[..span.start], text, &source[span.end..]);
+    // Whatever the scan misread shows up as a different document
+    let tokenizer = Tokenizer::lenient(lenient());
+    let reread = Parser::new(tokenizer.tokens(&spliced)).parse().ok()?;
+    (to_string(&reread).ok()? == to_string(json).ok()?).then_some(spliced)
+}
+
+/// Where the value at `pointer` is written in `source`, found by skipping
+/// over the text of the members and elements before it
+fn value_span(source: &str, pointer: &JsonPointer) -> Option<std::ops::Range<usize>> {
+    let bytes = source.as_bytes();
+    let mut at = skip_blank(bytes, 0);
+    for token in pointer.tokens() {
+        let object = match bytes.get(at)? {
+            b'{' => true,
+            b'[' => false,
+            _ => return None,
+        };
+        at = skip_blank(bytes, at + 1);
+        let mut index = 0;
+        loop {
+            let found = if object {
+                let end = skip_value(bytes, at)?;
+                let key = &source[at..end];
+                at = skip_blank(bytes, end);
+                if bytes.get(at) != Some(&b':') {
+                    return None;
+                }
+                at = skip_blank(bytes, at + 1);
+                key_is(key, token)?
+            } else {
+                token.parse() == Ok(index)
+            };
+            if found {
+                break;
+            }
+            at = skip_blank(bytes, skip_value(bytes, at)?);
+            if bytes.get(at) != Some(&b',') {
+                return None;
+            }
+            at = skip_blank(bytes, at + 1);
+            index += 1;
+        }
+    }
+    Some(at..skip_value(bytes, at)?)
+}
+
+/// Whether the quoted key `text` reads as `key`
+fn key_is(text: &str, key: &str) -> Option<bool> {
+    let raw = text.get(1..text.len().checked_sub(1)?)?;
+    if !raw.contains('\\') {
+        return Some(raw == key);
+    }
+    let tokenizer = Tokenizer::lenient(lenient());
+    match &Parser::new(tokenizer.tokens(text)).parse().ok()? {
+        JsonValue::Str(text) => Some(**text == *key),
+        _ => None,
+    }
+}
+
+/// Skip whitespace and comments
+fn skip_blank(bytes: &[u8], mut at: usize) -> usize {
+    loop {
+        match (bytes.get(at), bytes.get(at + 1)) {
+            (Some(b' ' | b'\t' | b'\n' | b'\r'), _) => at += 1,
+            (Some(b'/'), Some(b'/')) => {
+                at = bytes[at..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |i| at + i)
+            }
+            (Some(b'/'), Some(b'*')) => {
+                at = bytes[at + 2..]
+                    .windows(2)
+                    .position(|w| w == b"*/")
+                    .map_or(bytes.len(), |i| at + i + 4)
+            }
+            _ => return at,
+        }
+    }
+}
+
+/// The end of the value whose text starts at `at`
+fn skip_value(bytes: &[u8], mut at: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    loop {
+        match *bytes.get(at)? {
+            quote @ (b'"' | b'\'') => {
+                at += 1;
+                while *bytes.get(at)? != quote {
+                    at += if bytes[at] == b'\\' { 2 } else { 1 };
+                }
+                at += 1;
+            }
+            b'{' | b'[' => {
+                depth += 1;
+                at += 1;
+            }
+            b'}' | b']' => {
+                depth = depth.checked_sub(1)?;
+                at += 1;
+            }
+            b'/' if depth > 0 => at = skip_blank(bytes, at).max(at + 1),
+            _ if depth > 0 => at += 1,
+            // A number or literal, which ends where the next token starts
+            _ => {
+                let len = bytes[at..]
+                    .iter()
+                    .position(|b| b",]}/ \t\n\r".contains(b))
+                    .unwrap_or(bytes.len() - at);
+                return Some(at + len);
+            }
+        }
+        if depth == 0 {
+            return Some(at);
+        }
+    }
+}
+
+/// Parse a JSON value given on the command line, which may be a bare scalar
+fn parse_value(text: &str) -> Result<JsonValue, Failure> {
+    parse_json("<value>", text)
+}
+
+/// Serialize options that reproduce the indentation of `source`
+fn layout_of(source: &str) -> SerializeOptions {
+    let indent = source
+        .lines()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+    SerializeOptions {
+        indent: indent.to_string(),
+        trailing_newline: source.ends_with('\n'),
+        ..Default::default()
+    }
+}
+
+/// Replace a file through a temporary file in the same directory, so readers
+/// never see it half written
+fn write_atomic(name: &str, text: &str) -> Result<(), Failure> {
+    let path = output::resolve(std::path::Path::new(name));
+    let tmp = output::temp_path(&path);
+
+    let written = std::fs::File::create(&tmp).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        output::replace(&file, &tmp, &path)
+    });
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&tmp);
+        report!("{}: IO error: {}", name, err);
+        return Err(Failure::Io);
+    }
     Ok(())
 }
+
+/// Print the differences between two files, failing if there are any
+/// `--format rfc6902` prints a JSON Patch instead of a diff of the text.
+fn compare(old_path: &str, new_path: &str, args: &Args) -> Result<(), Failure> {
+    let format = match args.value("--format").unwrap_or("unified") {
+        "unified" => Some(DiffFormat::Unified),
+        "side-by-side" => Some(DiffFormat::SideBySide),
+        "rfc6902" => None,
+        other => {
+            report!(
+                "Unknown diff format '{}', expected unified, side-by-side or rfc6902",
+                other
+            );
+            return Err(Failure::Invalid);
+        }
+    };
+    let old = read_json(old_path)?;
+    let new = read_json(new_path)?;
+
+    let Some(format) = format else {
+        let changes = diff(&old, &new);
+        print_values([&to_patch(&changes)], args)?;
+        return if changes.is_empty() {
+            Ok(())
+        } else {
+            Err(Failure::Invalid)
+        };
+    };
+    let options = DiffOptions {
+        format,
+        color: use_color(args),
+        old_name: old_path.to_string(),
+        new_name: new_path.to_string(),
+        ..Default::default()
+    };
+    match render_diff(&old, &new, &options) {
+        Ok(text) if text.is_empty() => Ok(()),
+        Ok(text) => {
+            out!("{}", text);
+            Err(Failure::Invalid)
+        }
+        Err(err) => {
+            report!("{}", err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Deep merge files in order, each overriding the ones before it
+fn layer(paths: &[String], args: &Args) -> Result<(), Failure> {
+    let arrays = match args.value("--arrays").unwrap_or("replace") {
+        "replace" => ArrayMerge::Replace,
+        "concat" => ArrayMerge::Concat,
+        "index" => ArrayMerge::ByIndex,
+        other => {
+            report!(
+                "Unknown array strategy '{}', expected replace, concat or index",
+                other
+            );
+            return Err(Failure::Invalid);
+        }
+    };
+
+    let mut merged = read_json(&paths[0])?;
+    for path in &paths[1..] {
+        merged = deep_merge(&merged, &read_json(path)?, arrays);
+    }
+    print_values([&merged], args)
+}
+
+/// Three-way merge that writes the result over `ours`, as git merge drivers do
+/// Fails if anything conflicts, listing the conflicting pointers.
+fn merge(base_path: &str, ours_path: &str, theirs_path: &str) -> Result<(), Failure> {
+    let base = read_json(base_path)?;
+    let ours = read_json(ours_path)?;
+    let theirs = read_json(theirs_path)?;
+    let merge = merge3(&base, &ours, &theirs);
+
+    let text = match to_string_pretty(&merge.value) {
+        Ok(text) => text,
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    };
+    write_atomic(ours_path, &(text + "\n"))?;
+
+    for conflict in &merge.conflicts {
+        report!("Merge conflict at '{}'", conflict.path);
+    }
+    if merge.conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::Invalid)
+    }
+}
+
+/// Print a JSON file as YAML
+#[cfg(feature = "yaml")]
+fn json_to_yaml(path: &str) -> Result<(), Failure> {
+    let json = read_json(path)?;
+    match to_yaml(&json) {
+        Ok(text) => out!("{}", text),
+        Err(err) => {
+            report!("{}", err.0);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a YAML file as JSON
+#[cfg(feature = "yaml")]
+fn yaml_to_json(path: &str, args: &Args) -> Result<(), Failure> {
+    let source = read_source(path)?;
+    match from_yaml(&source) {
+        Ok(json) => print_values([&json], args),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Print a JSON file as TOML
+#[cfg(feature = "toml")]
+fn json_to_toml(path: &str) -> Result<(), Failure> {
+    let json = read_json(path)?;
+    match to_toml(&json) {
+        Ok(text) => out!("{}", text),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            return Err(Failure::Invalid);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print a TOML file as JSON
+#[cfg(feature = "toml")]
+fn toml_to_json(path: &str, args: &Args) -> Result<(), Failure> {
+    let source = read_source(path)?;
+    match from_toml(&source) {
+        Ok(json) => print_values([&json], args),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Print a MessagePack file as JSON
+fn msgpack_to_json(path: &str, args: &Args) -> Result<(), Failure> {
+    let bytes = read_bytes(path)?;
+    match from_msgpack(&bytes) {
+        Ok(json) => print_values([&json], args),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Print a CBOR file as JSON
+fn cbor_to_json(path: &str, args: &Args) -> Result<(), Failure> {
+    let bytes = read_bytes(path)?;
+    match from_cbor(&bytes) {
+        Ok(json) => print_values([&json], args),
+        Err(err) => {
+            report!("{}: {}", display_name(path), err.0);
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Write an encoded document to stdout
+fn write_binary(bytes: &[u8]) -> Result<(), Failure> {
+    let mut stdout = output::stdout();
+    if let Err(err) = stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        report!("IO error: {}", err);
+        return Err(Failure::Io);
+    }
+    Ok(())
+}
+
+/// Run `command` on the document in a file, or with `--ndjson` on the document
+/// on each of its lines, as they are read
+/// A line that fails does not stop the rest; diagnostics name the line, as in
+/// `log.ndjson:12`, and the worst failure is returned.
+fn documents<F>(path: &str, args: &Args, mut command: F) -> Result<(), Failure>
+where
+    F: FnMut(Origin, JsonValue) -> Result<(), Failure>,
+{
+    documents_with_source(path, args, |name, json, _| command(name, json))
+}
+
+/// Check the documents of a file without building them
+fn check(path: &str, args: &Args) -> Result<(), Failure> {
+    if args.flag("--ndjson") || timing() {
+        return documents(path, args, |_, _| Ok(()));
+    }
+    let source = read_source(path)?;
+    let tokenizer = Tokenizer::lenient(lenient());
+    let checked = Parser::new(tokenizer.tokens(&source)).validate();
+    reported(Origin::file(display_name(path)), &source, checked)
+}
+
+/// Like `documents`, also passing the text each document was parsed from
+fn documents_with_source<F>(path: &str, args: &Args, mut command: F) -> Result<(), Failure>
+where
+    F: FnMut(Origin, JsonValue, &str) -> Result<(), Failure>,
+{
+    if !args.flag("--ndjson") {
+        let source = read_source(path)?;
+        let name = display_name(path);
+        return command(Origin::file(name), parse_json(name, &source)?, &source);
+    }
+
+    // With `--jobs N` records are parsed in batches on N threads, otherwise one
+    // at a time as they come in
+    let jobs = jobs(args)?;
+    let batch_len = if jobs > 1 { jobs * RECORD_BATCH } else { 1 };
+    let mut lines = numbered_lines(BufReader::new(open_input(path)?));
+
+    let mut worst = None;
+    loop {
+        let mut batch = vec![];
+        for (record, line) in lines.by_ref() {
+            let origin = Origin {
+                file: display_name(path),
+                record: Some(record),
+            };
+            match line {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => batch.push((origin, line)),
+                Err(err) => {
+                    report!("{}: IO error: {}", origin, err);
+                    return Err(Failure::Io);
+                }
+            }
+            if batch.len() == batch_len {
+                break;
+            }
+        }
+        if batch.is_empty() {
+            break;
+        }
+
+        let parsed = if timing() {
+            // Measured one at a time, so that each takes the time it is reported with
+            batch
+                .iter()
+                .map(|(origin, line)| timed_parse(&origin.to_string(), line))
+                .collect()
+        } else {
+            let texts: Vec<&str> = batch.iter().map(|(_, line)| line.as_str()).collect();
+            parse_lines(&texts, lenient(), jobs)
+        };
+        for (&(origin, ref line), parsed) in batch.iter().zip(parsed) {
+            let result =
+                reported(origin, line, parsed).and_then(|json| command(origin, json, line));
+            if let Err(failure) = result {
+                worst = worst.max(Some(failure));
+            }
+        }
+        // Flush each batch so that piped streams are not held back
+        let _ = output::stdout().flush();
+    }
+    worst.map_or(Ok(()), Err)
+}
+
+/// The lines of `reader` without their line endings, each with its line number
+/// and the byte offset it starts at
+fn numbered_lines(
+    mut reader: impl BufRead,
+) -> impl Iterator<Item = ((usize, usize), std::io::Result<String>)> {
+    let (mut number, mut start) = (0, 0);
+    std::iter::from_fn(move || {
+        let mut line = String::new();
+        let read = match reader.read_line(&mut line) {
+            Ok(0) => return None,
+            Ok(read) => read,
+            Err(err) => return Some(((number + 1, start), Err(err))),
+        };
+        number += 1;
+        let record = (number, start);
+        start += read;
+        let len = line.trim_end_matches('\n').trim_end_matches('\r').len();
+        line.truncate(len);
+        Some((record, Ok(line)))
+    })
+}
+
+/// Records given to each thread at once by `documents_with_source`
+const RECORD_BATCH: usize = 1024;
+
+/// Open a file, or stdin for `-`, to be read as it is needed
+fn open_input(path: &str) -> Result<Box<dyn Read>, Failure> {
+    if path == STDIN {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(Box::new(file)),
+        Err(err) => {
+            report!("{}: IO error: {}", display_name(path), err);
+            Err(Failure::Io)
+        }
+    }
+}
+
+/// Read and parse a JSON file, or stdin for `-`, reporting any failure on stderr
+/// Diagnostics start with the file name.
+fn read_json(path: &str) -> Result<JsonValue, Failure> {
+    let source = read_source(path)?;
+    parse_json(display_name(path), &source)
+}
+
+/// Read a binary file, or stdin for `-`
+fn read_bytes(path: &str) -> Result<Vec<u8>, Failure> {
+    let bytes = if path == STDIN {
+        let mut bytes = vec![];
+        std::io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        std::fs::read(path)
+    };
+    bytes.map_err(|err| {
+        report!("{}: IO error: {}", display_name(path), err);
+        Failure::Io
+    })
+}
+
+/// Whether to map files rather than read them, set once by `run`
+static MMAP: OnceLock<bool> = OnceLock::new();
+
+/// Read a file, or stdin for `-`, mapping the file with `--mmap`
+fn read_source(path: &str) -> Result<Source, Failure> {
+    let source = if path == STDIN {
+        std::io::read_to_string(std::io::stdin()).map(Source::Read)
+    } else if MMAP.get().copied().unwrap_or(false) {
+        std::fs::File::open(path).and_then(mmap::map)
+    } else {
+        std::fs::read_to_string(path).map(Source::Read)
+    };
+    source.map_err(|err| {
+        report!("{}: IO error: {}", display_name(path), err);
+        Failure::Io
+    })
+}
+
+/// Relaxations asked for with `--lenient` or `--allow`, set once by `run`
+static LENIENT: OnceLock<LenientOptions> = OnceLock::new();
+
+/// `--lenient` allows everything, `--allow` a comma separated list of relaxations
+fn lenient_options(args: &Args) -> Result<LenientOptions, String> {
+    if args.flag("--lenient") {
+        return Ok(LenientOptions::all());
+    }
+    let mut options = LenientOptions::default();
+    for name in args
+        .value("--allow")
+        .into_iter()
+        .flat_map(|list| list.split(','))
+    {
+        match name.trim() {
+            "comments" => options.comments = true,
+            "trailing-commas" => options.trailing_commas = true,
+            "single-quotes" => options.single_quotes = true,
+            other => {
+                return Err(format!(
+                    "Invalid relaxation '{}', expected comments, trailing-commas or single-quotes",
+                    other
+                ))
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Parse JSON text, naming where it came from in diagnostics
+fn parse_json(name: &str, source: &str) -> Result<JsonValue, Failure> {
+    let origin = Origin::file(name);
+    if timing() {
+        return reported(origin, source, timed_parse(name, source));
+    }
+    if let Some(&jobs) = PARALLEL.get() {
+        return reported(origin, source, parse_parallel(source, jobs));
+    }
+    let tokenizer = Tokenizer::lenient(lenient());
+    let mut parser = Parser::new(tokenizer.tokens(source));
+    reported(origin, source, parser.parse())
+}
+
+/// Threads to split each document between with `--parallel`, set once by `run`
+static PARALLEL: OnceLock<usize> = OnceLock::new();
+
+/// Whether to print how long parsing took, set once by `run`
+static TIMING: OnceLock<bool> = OnceLock::new();
+
+fn timing() -> bool {
+    TIMING.get().copied().unwrap_or(false)
+}
+
+/// Parse JSON text, printing what the parse took on stderr
+fn timed_parse(name: &str, source: &str) -> Result<JsonValue, SyntaxError> {
+    let (parsed, metrics) = Metrics::parse(source, lenient());
+    eprintln!("{}: {}", name, metrics);
+    parsed
+}
+
+/// The relaxations set by `run`
+fn lenient() -> LenientOptions {
+    LENIENT.get().copied().unwrap_or_default()
+}
+
+/// Report a failure to parse `source`, naming where it came from
+fn reported<T>(origin: Origin, source: &str, parsed: Result<T, SyntaxError>) -> Result<T, Failure> {
+    match parsed {
+        Ok(parsed) => Ok(parsed),
+        Err(err) => {
+            Diagnostic::syntax(origin, &err, source).emit();
+            Err(Failure::Invalid)
+        }
+    }
+}
+
+/// Whether to highlight output, as chosen with `--color always|auto|never`
+/// `auto`, the default, colors terminals unless `NO_COLOR` is set.
+fn use_color(args: &Args) -> bool {
+    match args.value("--color") {
+        Some("always") => true,
+        Some("never") => false,
+        _ => {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            output::is_terminal() && !no_color
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::compare::approx_eq;
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+
+/// A location both sides changed in different ways
+/// `None` means the value was absent on that side, e.g. a deleted member.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub path: JsonPointer,
+    pub base: Option<JsonValue>,
+    pub ours: Option<JsonValue>,
+    pub theirs: Option<JsonValue>,
+}
+
+/// The outcome of a three-way merge
+/// Conflicting locations keep our side in `value`.
+#[derive(Clone, Debug)]
+pub struct Merge {
+    pub value: JsonValue,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Merge the changes `ours` and `theirs` each made to `base`
+/// Objects merge member by member and arrays of unchanged length element by
+/// element; any other location changed on both sides is a conflict unless
+/// both made the same change.
+pub fn merge3(base: &JsonValue, ours: &JsonValue, theirs: &JsonValue) -> Merge {
+    let mut conflicts = vec![];
+    let value = merge_at(
+        Some(base),
+        Some(ours),
+        Some(theirs),
+        &mut JsonPointer::default(),
+        &mut conflicts,
+    );
+    Merge {
+        value: value.unwrap_or(JsonValue::Null),
+        conflicts,
+    }
+}
+
+fn same(a: Option<&JsonValue>, b: Option<&JsonValue>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.type_name() == b.type_name() && approx_eq(a, b, 0.0),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn merge_at(
+    base: Option<&JsonValue>,
+    ours: Option<&JsonValue>,
+    theirs: Option<&JsonValue>,
+    path: &mut JsonPointer,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<JsonValue> {
+    if same(ours, theirs) || same(base, theirs) {
+        return ours.cloned();
+    }
+    if same(base, ours) {
+        return theirs.cloned();
+    }
+
+    match (base, ours, theirs) {
+        (base, Some(o), Some(t)) if o.is_object() && t.is_object() => {
+            let base = base.filter(|b| b.is_object());
+            let mut keys = o.members().map(|(k, _)| k).collect::<Vec<_>>();
+            for (key, _) in t.members() {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+
+            let mut members = vec![];
+            for key in keys {
+                path.push(key);
+                let b = base.and_then(|b| b.get(key));
+                if let Some(value) = merge_at(b, o.get(key), t.get(key), path, conflicts) {
+                    members.push((key, value));
+                }
+                path.pop();
+            }
+            Some(JsonValue::from_members(members))
+        }
+        (Some(JsonValue::Arr(b)), Some(JsonValue::Arr(o)), Some(JsonValue::Arr(t)))
+            if b.len() == o.len() && b.len() == t.len() =>
+        {
+            let mut values = vec![];
+            for (i, ((b, o), t)) in b.iter().zip(o).zip(t).enumerate() {
+                path.push(i.to_string());
+                values.extend(merge_at(Some(b), Some(o), Some(t), path, conflicts));
+                path.pop();
+            }
+            Some(JsonValue::Arr(values.into()))
+        }
+        _ => {
+            conflicts.push(Conflict {
+                path: path.clone(),
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.cloned()
+        }
+    }
+}
+
+/// How `deep_merge` combines an array with the array that overrides it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArrayMerge {
+    /// The overriding array wins as a whole
+    Replace,
+    /// The overriding elements are appended
+    Concat,
+    /// Elements are merged by index, extra ones are kept from either side
+    ByIndex,
+}
+
+/// Layer `overlay` over `base`, e.g. a local config over the defaults
+/// Objects merge member by member, recursively; any other value in `overlay`
+/// replaces the one in `base`, arrays as chosen by `arrays`.
+pub fn deep_merge(base: &JsonValue, overlay: &JsonValue, arrays: ArrayMerge) -> JsonValue {
+    match (base, overlay) {
+        (base, overlay) if base.is_object() && overlay.is_object() => {
+            let mut members = base
+                .members()
+                .map(|(key, value)| (key, value.clone()))
+                .collect::<Vec<_>>();
+            for (key, value) in overlay.members() {
+                match members.iter_mut().find(|(k, _)| *k == key) {
+                    Some((_, existing)) => *existing = deep_merge(existing, value, arrays),
+                    None => members.push((key, value.clone())),
+                }
+            }
+            JsonValue::from_members(members)
+        }
+        (JsonValue::Arr(a), JsonValue::Arr(b)) => match arrays {
+            ArrayMerge::Replace => overlay.clone(),
+            ArrayMerge::Concat => JsonValue::Arr(a.iter().chain(b).cloned().collect()),
+            ArrayMerge::ByIndex => {
+                let mut values = a
+                    .iter()
+                    .zip(b)
+                    .map(|(a, b)| deep_merge(a, b, arrays))
+                    .collect::<Vec<_>>();
+                let longer = if a.len() > b.len() { a } else { b };
+                values.extend_from_slice(&longer[values.len()..]);
+                JsonValue::Arr(values.into())
+            }
+        },
+        _ => overlay.clone(),
+    }
+}
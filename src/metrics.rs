@@ -0,0 +1,95 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::arena::Arena;
+use crate::parse::{JsonValue, Parser, SyntaxError};
+use crate::tokenize::{LenientOptions, Position, Token, Tokenizer};
+
+/// What a parse took, as measured by `Metrics::parse`
+/// Tokens are produced as the parser asks for them, so the time spent producing
+/// them is counted as lexing and the rest as parsing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Metrics {
+    /// Length of the input in bytes
+    pub bytes: usize,
+    /// Tokens read, whitespace included
+    pub tokens: usize,
+    /// Allocations the arena made for the tree, see `Arena::allocations`
+    pub allocations: usize,
+    pub lex: Duration,
+    pub parse: Duration,
+}
+
+impl Metrics {
+    /// Parse `text` as `Parser` would, measuring the parse
+    pub fn parse(text: &str, options: LenientOptions) -> (Result<JsonValue, SyntaxError>, Metrics) {
+        let start = Instant::now();
+        let mut tokens = Timed {
+            tokens: Tokenizer::lenient(options).tokens(text),
+            count: 0,
+            spent: Duration::ZERO,
+        };
+        let mut parser = Parser::with_arena(&mut tokens, Arena::new());
+        let parsed = parser.parse();
+        let allocations = parser.into_arena().allocations();
+        let total = start.elapsed();
+
+        let metrics = Metrics {
+            bytes: text.len(),
+            tokens: tokens.count,
+            allocations,
+            lex: tokens.spent,
+            parse: total.saturating_sub(tokens.spent),
+        };
+        (parsed, metrics)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.lex + self.parse
+    }
+
+    /// Input read per second over the whole parse
+    pub fn bytes_per_sec(&self) -> f64 {
+        let secs = self.total().as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes in {:.3} ms ({:.1} MB/s), {} tokens, {} allocations, lex {:.3} ms, parse {:.3} ms",
+            self.bytes,
+            self.total().as_secs_f64() * 1e3,
+            self.bytes_per_sec() / 1e6,
+            self.tokens,
+            self.allocations,
+            self.lex.as_secs_f64() * 1e3,
+            self.parse.as_secs_f64() * 1e3,
+        )
+    }
+}
+
+/// Tokens counted along with the time taken to produce them
+struct Timed<I> {
+    tokens: I,
+    count: usize,
+    spent: Duration,
+}
+
+impl<'a, I: Iterator<Item = (Token<'a>, Position)>> Iterator for Timed<I> {
+    type Item = (Token<'a>, Position);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let token = self.tokens.next();
+        self.spent += start.elapsed();
+        self.count += token.is_some() as usize;
+        token
+    }
+}
@@ -0,0 +1,125 @@
+//! Input files mapped into memory for `--mmap`, without a library for it
+//! Parsing then reads the pages the kernel brings in on demand, so a large file
+//! takes no heap beyond the tree built from it. On other platforms than Unix
+//! files are read as usual.
+
+use std::fs::File;
+use std::ops::Deref;
+
+/// The text of a file, read into memory or mapped
+pub enum Source {
+    Read(String),
+    #[cfg(unix)]
+    Mapped(Mapped),
+}
+
+impl Deref for Source {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Source::Read(text) => text,
+            #[cfg(unix)]
+            Source::Mapped(mapped) => mapped.text(),
+        }
+    }
+}
+
+/// Map `file`, which must hold UTF-8 and must not change while mapped
+#[cfg(unix)]
+pub fn map(mut file: File) -> std::io::Result<Source> {
+    use std::io::{Error, ErrorKind, Read};
+    use std::os::fd::AsRawFd;
+
+    let len = file.metadata()?.len() as usize;
+    if len == 0 {
+        // Empty files cannot be mapped, and pipes or devices report no length
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        return Ok(Source::Read(text));
+    }
+    // SAFETY: a private read-only mapping of a file open for reading, checked for
+    // failure below. It stays valid after `file` is closed.
+    let addr = unsafe {
+        ffi::mmap(
+            std::ptr::null_mut(),
+            len,
+            ffi::PROT_READ,
+            ffi::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == ffi::MAP_FAILED {
+        return Err(Error::last_os_error());
+    }
+    let mapped = Mapped { addr, len };
+    if std::str::from_utf8(mapped.bytes()).is_err() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "stream did not contain valid UTF-8",
+        ));
+    }
+    Ok(Source::Mapped(mapped))
+}
+
+#[cfg(not(unix))]
+pub fn map(mut file: File) -> std::io::Result<Source> {
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut file, &mut text)?;
+    Ok(Source::Read(text))
+}
+
+/// A mapping of a whole file, unmapped when dropped
+#[cfg(unix)]
+pub struct Mapped {
+    addr: *mut ffi::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mapped {
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `addr` is the start of a readable mapping of `len` bytes, which
+        // lives as long as `self`
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.len) }
+    }
+
+    fn text(&self) -> &str {
+        // SAFETY: checked to be UTF-8 by `map`, and private mappings of files that
+        // are not written to do not change
+        unsafe { std::str::from_utf8_unchecked(self.bytes()) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `map` mapped, which nothing borrows anymore
+        unsafe {
+            ffi::munmap(self.addr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod ffi {
+    use std::ffi::c_int;
+    pub use std::ffi::c_void;
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
@@ -0,0 +1,379 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+
+#[derive(Debug)]
+pub struct MsgpackError(pub String);
+
+impl fmt::Display for MsgpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MsgpackError {}
+
+/// Encode as MessagePack, using the smallest encoding for every value
+/// Floats are always written as float 64 so they read back unchanged.
+pub fn to_msgpack(value: &JsonValue) -> Vec<u8> {
+    let mut out = vec![];
+    encode(value, &mut out);
+    out
+}
+
+fn encode(value: &JsonValue, out: &mut Vec<u8>) {
+    match value {
+        JsonValue::Null => out.push(0xc0),
+        JsonValue::Bool(b) => out.push(if *b { 0xc3 } else { 0xc2 }),
+        JsonValue::Int(i) => encode_int(*i, out),
+        JsonValue::Float(f, _) => {
+            out.push(0xcb);
+            out.extend_from_slice(&f.to_be_bytes());
+        }
+        JsonValue::Str(s) => {
+            encode_len(s.len(), 0xa0, 31, [0xd9, 0xda, 0xdb], out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        JsonValue::Arr(values) => {
+            encode_len(values.len(), 0x90, 15, [0, 0xdc, 0xdd], out);
+            for value in values {
+                encode(value, out);
+            }
+        }
+        _ => {
+            let members = value.members().collect::<Vec<_>>();
+            encode_len(members.len(), 0x80, 15, [0, 0xde, 0xdf], out);
+            for (key, value) in members {
+                encode(&JsonValue::Str(key.into()), out);
+                encode(value, out);
+            }
+        }
+    }
+}
+
+fn encode_int(i: i64, out: &mut Vec<u8>) {
+    match i {
+        0..=0x7f => out.push(i as u8),
+        -32..=-1 => out.push(i as i8 as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, i as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(i as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(i as u32).to_be_bytes());
+        }
+        0x1_0000_0000.. => {
+            out.push(0xcf);
+            out.extend_from_slice(&(i as u64).to_be_bytes());
+        }
+        -0x80..=-33 => out.extend_from_slice(&[0xd0, i as i8 as u8]),
+        -0x8000..=-0x81 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(i as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(i as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&i.to_be_bytes());
+        }
+    }
+}
+
+/// Write a length header: the fix form up to `fix_max`, else the 8, 16 or 32 bit
+/// form of `markers`, where a zero marker means the type has no 8 bit form
+fn encode_len(len: usize, fix: u8, fix_max: usize, markers: [u8; 3], out: &mut Vec<u8>) {
+    if len <= fix_max {
+        out.push(fix | len as u8);
+    } else if len <= 0xff && markers[0] != 0 {
+        out.extend_from_slice(&[markers[0], len as u8]);
+    } else if len <= 0xffff {
+        out.push(markers[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+/// Decode one MessagePack value
+/// Map keys must be strings. Unsigned integers above `i64::MAX` become floats;
+/// binary and extension types have no JSON counterpart and are errors.
+pub fn from_msgpack(bytes: &[u8]) -> Result<JsonValue, MsgpackError> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    let value = decoder.value(0)?;
+    if decoder.pos < bytes.len() {
+        return Err(decoder.err("trailing bytes after the value"));
+    }
+    Ok(value)
+}
+
+/// Deeper nesting is rejected rather than risking the stack
+const MAX_DEPTH: usize = 512;
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn err(&self, msg: &str) -> MsgpackError {
+        MsgpackError(format!("MessagePack error: {} at byte {}", msg, self.pos))
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], MsgpackError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| self.err("unexpected end of input"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// A big-endian unsigned integer of `n` bytes
+    fn uint(&mut self, n: usize) -> Result<u64, MsgpackError> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue, MsgpackError> {
+        if depth > MAX_DEPTH {
+            return Err(self.err("nesting too deep"));
+        }
+        let marker = self.take(1)?[0];
+        match marker {
+            0x00..=0x7f => Ok(JsonValue::Int(marker as i64)),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth),
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth),
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize),
+            0xc0 => Ok(JsonValue::Null),
+            0xc2 => Ok(JsonValue::Bool(false)),
+            0xc3 => Ok(JsonValue::Bool(true)),
+            0xc4..=0xc6 | 0xc7..=0xc9 | 0xd4..=0xd8 => {
+                self.pos -= 1;
+                Err(self.err(&format!(
+                    "{} values cannot be represented in JSON",
+                    if marker <= 0xc6 {
+                        "binary"
+                    } else {
+                        "extension"
+                    }
+                )))
+            }
+            0xca => Ok(float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(float(f64::from_bits(self.uint(8)?))),
+            0xcc..=0xcf => {
+                let n = self.uint(1 << (marker - 0xcc))?;
+                Ok(i64::try_from(n).map_or(JsonValue::Float(n as f64, None), JsonValue::Int))
+            }
+            0xd0 => Ok(JsonValue::Int(self.uint(1)? as i8 as i64)),
+            0xd1 => Ok(JsonValue::Int(self.uint(2)? as i16 as i64)),
+            0xd2 => Ok(JsonValue::Int(self.uint(4)? as i32 as i64)),
+            0xd3 => Ok(JsonValue::Int(self.uint(8)? as i64)),
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))? as usize;
+                self.str(len)
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(2 << (marker - 0xdc))? as usize;
+                self.array(len, depth)
+            }
+            0xde | 0xdf => {
+                let len = self.uint(2 << (marker - 0xde))? as usize;
+                self.map(len, depth)
+            }
+            0xe0..=0xff => Ok(JsonValue::Int(marker as i8 as i64)),
+            0xc1 => {
+                self.pos -= 1;
+                Err(self.err("invalid marker 0xc1"))
+            }
+        }
+    }
+
+    fn str(&mut self, len: usize) -> Result<JsonValue, MsgpackError> {
+        let start = self.pos;
+        let bytes = self.take(len)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(JsonValue::Str(s.into())),
+            Err(_) => {
+                self.pos = start;
+                Err(self.err("string is not valid UTF-8"))
+            }
+        }
+    }
+
+    fn array(&mut self, len: usize, depth: usize) -> Result<JsonValue, MsgpackError> {
+        // Every element takes at least a byte, so a bogus length fails early
+        if len > self.bytes.len() - self.pos {
+            return Err(self.err("unexpected end of input"));
+        }
+        let values = (0..len)
+            .map(|_| self.value(depth + 1))
+            .collect::<Result<_, _>>()?;
+        Ok(JsonValue::Arr(values))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<JsonValue, MsgpackError> {
+        if len > self.bytes.len() - self.pos {
+            return Err(self.err("unexpected end of input"));
+        }
+        let mut members = Vec::with_capacity(len);
+        for _ in 0..len {
+            let key = match self.value(depth + 1)?.into_str() {
+                Ok(key) => key,
+                Err(other) => {
+                    return Err(self.err(&format!(
+                        "map keys must be strings, not {}",
+                        other.type_name()
+                    )))
+                }
+            };
+            members.push((key, self.value(depth + 1)?));
+        }
+        Ok(JsonValue::from_members(members))
+    }
+}
+
+/// NaN and infinities have no JSON form and are read as null
+fn float(f: f64) -> JsonValue {
+    if f.is_finite() {
+        JsonValue::Float(f, None)
+    } else {
+        JsonValue::Null
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::serialize::to_string;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn unhex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode(text: &str) -> Result<String, String> {
+        from_msgpack(&unhex(text))
+            .map(|value| to_string(&value).unwrap())
+            .map_err(|e| e.0)
+    }
+
+    #[test]
+    fn encodes_known_vectors() {
+        let cases = [
+            ("null", "c0"),
+            ("[true, false]", "92c3c2"),
+            ("0", "00"),
+            ("127", "7f"),
+            ("-1", "ff"),
+            ("-32", "e0"),
+            ("128", "cc80"),
+            ("256", "cd0100"),
+            ("65536", "ce00010000"),
+            ("4294967296", "cf0000000100000000"),
+            ("-33", "d0df"),
+            ("-129", "d1ff7f"),
+            ("-32769", "d2ffff7fff"),
+            ("-9223372036854775808", "d38000000000000000"),
+            ("1.5", "cb3ff8000000000000"),
+            (r#""a""#, "a161"),
+            (r#"{"a": [1, 2]}"#, "81a16192"),
+        ];
+        for (text, expected) in cases {
+            let encoded = hex(&to_msgpack(&json(text)));
+            assert!(encoded.starts_with(expected), "{}: {}", text, encoded);
+            assert_eq!(decode(&encoded).unwrap(), to_string(&json(text)).unwrap());
+        }
+
+        let long = format!(r#""{}""#, "x".repeat(32));
+        assert!(hex(&to_msgpack(&json(&long))).starts_with("d920"));
+        let items = format!("[{}]", ["0"; 16].join(","));
+        assert!(hex(&to_msgpack(&json(&items))).starts_with("dc0010"));
+    }
+
+    #[test]
+    fn decodes_forms_it_does_not_write() {
+        assert_eq!(decode("ca3fc00000").unwrap(), "1.5");
+        assert_eq!(decode("cb7ff8000000000000").unwrap(), "null");
+        assert_eq!(decode("da000161").unwrap(), r#""a""#);
+        assert_eq!(decode("de0001a16101").unwrap(), r#"{"a":1}"#);
+        let max = from_msgpack(&unhex("cfffffffffffffffff")).unwrap();
+        assert_eq!(max.as_f64(), Some(u64::MAX as f64));
+    }
+
+    #[test]
+    fn rejects_what_json_cannot_hold() {
+        let err = |text: &str| decode(text).unwrap_err();
+        assert_eq!(
+            err("c40100"),
+            "MessagePack error: binary values cannot be represented in JSON at byte 0"
+        );
+        assert_eq!(
+            err("d40100"),
+            "MessagePack error: extension values cannot be represented in JSON at byte 0"
+        );
+        assert_eq!(
+            err("c1"),
+            "MessagePack error: invalid marker 0xc1 at byte 0"
+        );
+        assert_eq!(
+            err("810101"),
+            "MessagePack error: map keys must be strings, not number at byte 2"
+        );
+        assert_eq!(
+            err("a1ff"),
+            "MessagePack error: string is not valid UTF-8 at byte 1"
+        );
+        assert_eq!(
+            err("9201"),
+            "MessagePack error: unexpected end of input at byte 1"
+        );
+        assert_eq!(
+            err("cd01"),
+            "MessagePack error: unexpected end of input at byte 1"
+        );
+        assert_eq!(
+            err("c0c0"),
+            "MessagePack error: trailing bytes after the value at byte 1"
+        );
+    }
+
+    #[test]
+    fn rejects_nesting_beyond_max_depth() {
+        let nested = |depth: usize| {
+            let mut bytes = vec![0x91; depth];
+            bytes.push(0xc0);
+            bytes
+        };
+        assert!(from_msgpack(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            from_msgpack(&nested(MAX_DEPTH + 1)).unwrap_err().0,
+            format!(
+                "MessagePack error: nesting too deep at byte {}",
+                MAX_DEPTH + 1
+            )
+        );
+        let mut maps = b"\x81\xa1a".repeat(MAX_DEPTH + 1);
+        maps.push(0xc0);
+        assert!(from_msgpack(&maps).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use crate::parse::{JsonValue, Parser, SyntaxError};
+use crate::serialize::{to_vec, SerializeError};
+use crate::tokenize::{LenientOptions, Tokenizer};
+
+/// Parse each of `lines` as a document on up to `jobs` threads, giving the
+/// results in the order of the lines
+/// Every thread takes a run of consecutive lines, so that putting the results
+/// back in order is only appending them.
+pub fn parse_lines(
+    lines: &[&str],
+    options: LenientOptions,
+    jobs: usize,
+) -> Vec<Result<JsonValue, SyntaxError>> {
+    let parse = |line: &&str| Parser::new(Tokenizer::lenient(options).tokens(line)).parse();
+    if jobs <= 1 || lines.len() < 2 {
+        return lines.iter().map(parse).collect();
+    }
+
+    let per_thread = lines.len().div_ceil(jobs);
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = lines
+            .chunks(per_thread)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(parse).collect::<Vec<_>>()))
+            .collect();
+        threads
+            .into_iter()
+            .flat_map(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+/// Write each value as one compact line of newline-delimited JSON
+/// Compact output never contains a raw newline, so every line is one document.
+pub fn write_lines<'a, W: Write>(
+    mut writer: W,
+    values: impl Iterator<Item = &'a JsonValue>,
+) -> Result<(), SerializeError> {
+    for value in values {
+        let mut line = to_vec(value)?;
+        line.push(b'\n');
+        writer
+            .write_all(&line)
+            .map_err(|e| SerializeError(format!("IO error: {}", e)))?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| SerializeError(format!("IO error: {}", e)))
+}
@@ -0,0 +1,164 @@
+//! Where commands print their results: stdout, or with `--output` a temporary
+//! file that replaces the named one only once the command has succeeded, so an
+//! interrupted or failed run never leaves a truncated document behind.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Print to the output like `print!`
+macro_rules! out {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = write!($crate::output::stdout(), $($arg)*);
+    }};
+}
+
+/// Print a line to the output like `println!`
+macro_rules! outln {
+    ($($arg:tt)*) => {{
+        use std::io::Write as _;
+        let _ = writeln!($crate::output::stdout(), $($arg)*);
+    }};
+}
+
+struct Target {
+    /// The path as given, for messages
+    name: String,
+    path: PathBuf,
+    tmp: PathBuf,
+    file: BufWriter<File>,
+    /// The first write that failed, after which the file is never moved into place
+    error: Option<String>,
+}
+
+static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+
+thread_local! {
+    /// Output of the current thread held back by `capture`
+    static CAPTURED: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, collecting what it prints instead of writing it out, so that
+/// threads working on several files can have their output written in order
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+    CAPTURED.with_borrow_mut(|captured| *captured = Some(vec![]));
+    let result = f();
+    let bytes = CAPTURED.with_borrow_mut(|captured| captured.take().unwrap_or_default());
+    (result, bytes)
+}
+
+/// The file `path` names, following symbolic links so that replacing it
+/// keeps the link, or `path` itself for a file yet to be created
+pub fn resolve(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// A temporary file next to `path`, so that renaming it over `path` is atomic
+/// `path` should be resolved first, as the rename must stay in its directory.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.jsonp-tmp", name))
+}
+
+/// Move the written temporary `file` over `path`, taking on the permissions of
+/// the file it replaces
+/// Both the file and its directory are synced, so after a crash `path` holds
+/// either the old contents or the new ones.
+pub fn replace(file: &File, tmp: &Path, path: &Path) -> io::Result<()> {
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(tmp, metadata.permissions())?;
+    }
+    file.sync_all()?;
+    fs::rename(tmp, path)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Send all further output to a temporary file for `path`
+pub fn open(name: &str) -> Result<(), String> {
+    let path = resolve(Path::new(name));
+    let tmp = temp_path(&path);
+    let file = File::create(&tmp).map_err(|err| format!("{}: IO error: {}", name, err))?;
+    *lock() = Some(Target {
+        name: name.to_string(),
+        path,
+        tmp,
+        file: BufWriter::new(file),
+        error: None,
+    });
+    Ok(())
+}
+
+/// Move the output into place after a `complete` run, otherwise throw it away
+pub fn finish(complete: bool) -> Result<(), String> {
+    let Some(mut target) = lock().take() else {
+        return Ok(());
+    };
+    let flushed = target.file.flush().map_err(|err| err.to_string());
+    let written = match (complete, target.error.take().map_or(flushed, Err)) {
+        (true, Ok(())) => {
+            replace(target.file.get_ref(), &target.tmp, &target.path).map_err(|err| err.to_string())
+        }
+        (true, Err(msg)) => Err(msg),
+        (false, _) => Ok(()),
+    };
+    if !complete || written.is_err() {
+        let _ = fs::remove_file(&target.tmp);
+    }
+    written.map_err(|msg| format!("{}: IO error: {}", target.name, msg))
+}
+
+/// Whether the output is a terminal, as opposed to a pipe or a file
+pub fn is_terminal() -> bool {
+    lock().is_none() && io::stdout().is_terminal()
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Target>> {
+    TARGET
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Handle on the output, which is stdout unless `open` chose a file
+pub struct Output;
+
+pub fn stdout() -> Output {
+    Output
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let captured = CAPTURED.with_borrow_mut(|captured| {
+            captured.as_mut().map(|bytes| bytes.extend_from_slice(buf))
+        });
+        if captured.is_some() {
+            return Ok(buf.len());
+        }
+        let mut target = lock();
+        let Some(target) = target.as_mut() else {
+            return io::stdout().write(buf);
+        };
+        target.file.write(buf).inspect_err(|err| {
+            target.error.get_or_insert_with(|| err.to_string());
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if CAPTURED.with_borrow(|captured| captured.is_some()) {
+            return Ok(());
+        }
+        let mut target = lock();
+        let Some(target) = target.as_mut() else {
+            return io::stdout().flush();
+        };
+        target.file.flush().inspect_err(|err| {
+            target.error.get_or_insert_with(|| err.to_string());
+        })
+    }
+}
@@ -0,0 +1,233 @@
+use std::cell::Cell;
+use std::thread;
+
+use crate::parse::{JsonValue, Parser, SyntaxError};
+use crate::tokenize::Tokenizer;
+
+/// Inputs shorter than this are parsed on one thread, as they take less time to
+/// parse than the threads take to start
+pub(crate) const MIN_PARALLEL_LEN: usize = 1 << 20;
+
+/// Parse one large document on up to `jobs` threads, an experiment for inputs
+/// of hundreds of megabytes
+/// Stage one scans a chunk of the input on each thread for the quotes and
+/// brackets that tell where the members or elements of the top level container
+/// are. Stage two parses runs of them on each thread with `Parser` and joins
+/// the results, so valid documents give the tree `Parser` would. Only strict
+/// JSON is split, and anything the stages cannot make sense of is parsed again
+/// on one thread, so that errors are the same as `Parser`'s too.
+pub fn parse_parallel(input: &str, jobs: usize) -> Result<JsonValue, SyntaxError> {
+    let split = match jobs > 1 && input.len() >= MIN_PARALLEL_LEN {
+        true => split(input, jobs),
+        false => None,
+    };
+    let Some((object, runs)) = split.filter(|(_, runs)| runs.len() > 1) else {
+        return parse(input);
+    };
+
+    let parts = on_threads(runs, |run| parse_run(run, object));
+    let Some(parts) = parts.into_iter().collect::<Option<Vec<_>>>() else {
+        return parse(input);
+    };
+    let mut children = Vec::with_capacity(parts.iter().map(Vec::len).sum());
+    for part in parts {
+        children.extend(part);
+    }
+    Ok(match object {
+        true => JsonValue::Object(children.into_boxed_slice()),
+        false => JsonValue::Arr(children.into_boxed_slice()),
+    })
+}
+
+fn parse(input: &str) -> Result<JsonValue, SyntaxError> {
+    Parser::new(Tokenizer::new().tokens(input)).parse()
+}
+
+/// Parse a run of the members or elements of the top level container, `None`
+/// if it does not parse as it would as part of the whole
+/// The run is parsed as a container of its own, followed by one more child
+/// that is dropped: that child only parses as one of the container's if the run
+/// ends between two children, as the comma after it in the input needs. The
+/// container must also end with the input, rather than at a bracket in the run.
+fn parse_run(run: &str, object: bool) -> Option<Vec<JsonValue>> {
+    let (open, close) = match object {
+        true => ("{", r#", "": 0}"#),
+        false => ("[", ", 0]"),
+    };
+    let ended = Cell::new(false);
+    let tokens = Tokenizer::new()
+        .tokens(open)
+        .chain(Tokenizer::new().tokens(run))
+        .chain(Tokenizer::new().tokens(close))
+        .chain(std::iter::from_fn(|| {
+            ended.set(true);
+            None
+        }));
+    let mut parsed = Parser::new(tokens).parse().ok()?;
+    if !ended.get() {
+        return None;
+    }
+    let mut children = match &mut parsed {
+        JsonValue::Object(children) | JsonValue::Arr(children) => {
+            std::mem::take(children).into_vec()
+        }
+        _ => return None,
+    };
+    let sentinel = match &children.pop()? {
+        JsonValue::KeyedObject(key, value) => key.is_empty() && matches!(**value, JsonValue::Int(0)),
+        value => matches!(value, JsonValue::Int(0)),
+    };
+    sentinel.then_some(children)
+}
+
+/// Whether the top level container is an object, and the runs of its children
+/// to parse on each thread, `None` unless the input splits cleanly
+fn split(input: &str, jobs: usize) -> Option<(bool, Vec<&str>)> {
+    let bytes = input.as_bytes();
+    let start = bytes.iter().position(|&b| !is_whitespace(b))?;
+    let object = match bytes[start] {
+        b'{' => true,
+        b'[' => false,
+        _ => return None,
+    };
+
+    // Stage one: how each chunk moves the scan along, then where the children
+    // of the top level container end in it given the state it starts in
+    let chunk_len = bytes.len().div_ceil(jobs);
+    let chunks: Vec<_> = bytes.chunks(chunk_len).enumerate().collect();
+    let summaries = on_threads(chunks.clone(), |(_, chunk)| summarize(chunk));
+    let mut starts = Vec::with_capacity(chunks.len());
+    let (mut state, mut depth) = (State::Outside, 0);
+    for summary in &summaries {
+        starts.push((state, depth));
+        let (end, moved) = summary.ends[state as usize];
+        state = end;
+        depth += moved;
+    }
+    if state != State::Outside || depth != 0 {
+        return None;
+    }
+    let scans = on_threads(
+        chunks.into_iter().zip(starts).collect(),
+        |((i, chunk), start)| boundaries(chunk, i * chunk_len, start),
+    );
+
+    let end = scans.iter().find_map(|(_, close)| *close)?;
+    let closes = if object { b'}' } else { b']' };
+    if bytes[end] != closes || !bytes[end + 1..].iter().all(|&b| is_whitespace(b)) {
+        return None;
+    }
+    let commas: Vec<usize> = scans
+        .into_iter()
+        .flat_map(|(commas, _)| commas)
+        .take_while(|&comma| comma < end)
+        .collect();
+
+    // Stage two gets runs of about the same length, split at those commas
+    let mut runs = vec![];
+    let mut from = start + 1;
+    for k in 1..jobs {
+        let target = start + (end - start) * k / jobs;
+        let at = commas.partition_point(|&comma| comma < target.max(from));
+        if let Some(&comma) = commas.get(at) {
+            runs.push(&input[from..comma]);
+            from = comma + 1;
+        }
+    }
+    runs.push(&input[from..end]);
+    Some((object, runs))
+}
+
+/// Where a scan of the input is, as far as strings go
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Outside,
+    Inside,
+    /// Inside a string, right after a backslash
+    Escaped,
+}
+
+impl State {
+    fn after(self, b: u8) -> State {
+        match (self, b) {
+            (State::Outside, b'"') => State::Inside,
+            (State::Inside, b'"') => State::Outside,
+            (State::Inside, b'\\') => State::Escaped,
+            (State::Escaped, _) => State::Inside,
+            (state, _) => state,
+        }
+    }
+}
+
+/// What a chunk does to the scan: the state it ends in and how far it moves
+/// the depth, for each state it may start in
+struct Summary {
+    ends: [(State, i64); 3],
+}
+
+fn summarize(chunk: &[u8]) -> Summary {
+    let mut ends = [State::Outside, State::Inside, State::Escaped].map(|state| (state, 0));
+    for &b in chunk {
+        for (state, depth) in &mut ends {
+            if *state == State::Outside {
+                match b {
+                    b'{' | b'[' => *depth += 1,
+                    b'}' | b']' => *depth -= 1,
+                    _ => {}
+                }
+            }
+            *state = state.after(b);
+        }
+    }
+    Summary { ends }
+}
+
+/// The commas between children of the top level container in a chunk at
+/// `offset`, and where the container closes if it does in the chunk
+fn boundaries(
+    chunk: &[u8],
+    offset: usize,
+    (mut state, mut depth): (State, i64),
+) -> (Vec<usize>, Option<usize>) {
+    let (mut commas, mut close) = (vec![], None);
+    for (i, &b) in chunk.iter().enumerate() {
+        if state == State::Outside {
+            match b {
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 && close.is_none() {
+                        close = Some(offset + i);
+                    }
+                }
+                b',' if depth == 1 => commas.push(offset + i),
+                _ => {}
+            }
+        }
+        state = state.after(b);
+    }
+    (commas, close)
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// `f` of each of `items`, each on a thread of its own
+fn on_threads<T: Send, R: Send>(items: Vec<T>, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let f = &f;
+    thread::scope(|scope| {
+        let threads: Vec<_> = items
+            .into_iter()
+            .map(|item| scope.spawn(move || f(item)))
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
@@ -1,293 +1,1327 @@
+use std::borrow::{Borrow, Cow};
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::Peekable;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::arena::Arena;
+use crate::escape::{check, closes, unescape};
+use crate::hash::KeyHasher;
+use crate::serialize::format_float;
 use crate::tokenize::{Position, Token};
+use crate::writer::is_number;
 
 #[derive(Clone, Debug)]
 pub enum JsonValue {
-    Object(Vec<JsonValue>),
-    KeyedObject(String, Box<JsonValue>),
-    Float(f64),
+    Object(Box<[JsonValue]>),
+    /// A member of an object, whose key is shared with the other members of the
+    /// same name when it was parsed
+    KeyedObject(Key, Box<JsonValue>),
+    /// A float along with its source lexeme, kept only when re-serializing
+    /// the value would not reproduce it (e.g. `1.50`, `1e3` or `-0`)
+    /// The lexeme is boxed twice so that the rare float keeping one does not
+    /// widen every value.
+    Float(f64, Option<Box<Box<str>>>),
     Int(i64),
-    Str(String),
+    Str(Box<str>),
     Bool(bool),
-    Arr(Vec<JsonValue>),
+    Arr(Box<[JsonValue]>),
     Empty,
+    Null,
 }
 
-pub struct SyntaxError(pub String);
+// Every element of an array takes this much, so no variant may outgrow the
+// two words of a boxed slice or string.
+// Nor can a container keep a few values inline, as small vectors do: a value
+// holding values would be infinitely sized, so the children need a pointer
+// to the heap either way, and inline room would only widen every value.
+// Tiny containers are left to `Arena`, which reuses their allocations.
+#[cfg(target_pointer_width = "64")]
+const _: () = assert!(std::mem::size_of::<JsonValue>() == 24);
 
-#[derive(Clone, Debug)]
-pub struct Parser {
-    tokens: Vec<(Token, Position)>,
-    idx: usize,
+// Dropping a tree by the recursion of the generated drop glue would overflow
+// the stack on deeply nested documents, which the parser accepts, so the
+// children of nested containers are moved onto a heap stack and dropped
+// from there one by one, each with no children left.
+impl Drop for JsonValue {
+    fn drop(&mut self) {
+        if !has_nested(self) {
+            return;
+        }
+        let mut pending = vec![];
+        take_children(self, &mut pending);
+        while let Some(mut value) = pending.pop() {
+            if has_nested(&value) {
+                take_children(&mut value, &mut pending);
+            }
+        }
+    }
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<(Token, Position)>) -> Self {
-        Parser { tokens, idx: 0 }
+/// Whether `value` holds a container, so that dropping it would recurse
+fn has_nested(value: &JsonValue) -> bool {
+    let is_container = |value: &JsonValue| match value {
+        JsonValue::Object(_) | JsonValue::Arr(_) => true,
+        JsonValue::KeyedObject(_, value) => {
+            matches!(
+                **value,
+                JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Arr(_)
+            )
+        }
+        _ => false,
+    };
+    match value {
+        JsonValue::Object(values) | JsonValue::Arr(values) => values.iter().any(is_container),
+        JsonValue::KeyedObject(_, value) => is_container(value),
+        _ => false,
     }
+}
 
-    /// Parse a JSON document
-    pub fn parse(&mut self) -> Result<JsonValue, SyntaxError> {
-        self.remove_whitespace();
-        let (first_token, _) = self.current_token()?;
-        match first_token {
-            Token::LeftBracket => self.parse_array(),
-            Token::LeftCurly => self.parse_object(),
-            _ => Err(self.err("invalid JSON document")),
+/// Move the children of a container onto `pending`, emptying it
+fn take_children(value: &mut JsonValue, pending: &mut Vec<JsonValue>) {
+    match value {
+        JsonValue::Object(values) | JsonValue::Arr(values) => {
+            pending.append(&mut std::mem::take(values).into_vec());
         }
+        JsonValue::KeyedObject(_, value) => {
+            pending.push(std::mem::replace(&mut **value, JsonValue::Null));
+        }
+        _ => {}
     }
+}
 
-    /// Parse a literal object
-    fn parse_object(&mut self) -> Result<JsonValue, SyntaxError> {
-        // Assume {
-        self.assert_current(&[Token::LeftCurly])?;
-        self.next_token()?;
+/// Why a document failed to parse, e.g. `Syntax error: nesting too deep at
+/// line 3 column 12` once displayed
+/// Only what went wrong and where is kept, so that making one costs nothing
+/// even when it is dropped unread. The message is written out by `Display`.
+#[derive(Debug)]
+pub struct SyntaxError {
+    reason: Reason,
+    /// None for errors of the document as a whole, or its unexpected end
+    at: Option<Position>,
+}
 
-        if self.assert_current(&[Token::RightCurly]).is_ok() {
-            self.next_token()?;
-            return Ok(JsonValue::Empty);
+#[derive(Debug)]
+enum Reason {
+    /// e.g. `nesting too deep`
+    Message(ParseErrorKind, &'static str),
+    /// The current token was none of those expected
+    Expected(&'static [Token<'static>], Found),
+    /// A lexeme that is no number, e.g. `1.2.3`
+    InvalidNumber(Box<str>),
+    /// Reading the input failed, which is no syntax error
+    Io(std::io::Error),
+}
+
+/// A token as named in an error, keeping the text it shows of numbers and words
+#[derive(Debug)]
+enum Found {
+    Name(&'static str),
+    Text(Box<str>),
+}
+
+/// What kind of failure a `SyntaxError` is, to branch on rather than its message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// Something that cannot be where it is, e.g. a missing colon or `nul`
+    UnexpectedToken,
+    /// The input ended inside the document
+    UnexpectedEof,
+    /// A number that is malformed or does not fit, e.g. `1.2.3`
+    InvalidNumber,
+    /// A string without its closing quote
+    UnterminatedString,
+    /// A bad escape, control character or encoding inside a string
+    InvalidString,
+    /// Containers nested deeper than allowed
+    DepthExceeded,
+    /// More than whitespace after the document
+    TrailingCharacters,
+    /// A key given twice in one object, see `Parser::reject_duplicate_keys`
+    DuplicateKey,
+    /// A document too large for the form it is parsed into
+    TooLarge,
+    /// Reading the input failed
+    Io,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(kind: ParseErrorKind, message: &'static str, at: Option<Position>) -> Self {
+        SyntaxError {
+            reason: Reason::Message(kind, message),
+            at,
         }
+    }
 
-        let mut objs: Vec<JsonValue> = vec![];
-        while self
-            .assert_current(&[Token::RightCurly, Token::RightBracket])
-            .is_err()
-        {
-            // Expect a key or an empty object
-            self.assert_current(&[Token::Quote, Token::Comma])?;
-            let (next, _) = self.current_token()?;
-            let json = match next {
-                Token::Quote => self.parse_keyed_object(),
-                Token::Comma => break,
-                _ => Err(self.err("unterminated object")),
-            };
+    /// An error for the input ending inside the document
+    pub(crate) fn eof() -> Self {
+        SyntaxError::new(
+            ParseErrorKind::UnexpectedEof,
+            "unexpected end of file",
+            None,
+        )
+    }
+
+    pub(crate) fn invalid_number(num: impl Into<Box<str>>, at: Position) -> Self {
+        SyntaxError {
+            reason: Reason::InvalidNumber(num.into()),
+            at: Some(at),
+        }
+    }
+
+    pub(crate) fn io(err: std::io::Error) -> Self {
+        SyntaxError {
+            reason: Reason::Io(err),
+            at: None,
+        }
+    }
+
+    /// What kind of failure this is, for callers to match on
+    pub fn kind(&self) -> ParseErrorKind {
+        match self.reason {
+            Reason::Message(kind, _) => kind,
+            Reason::Expected(..) => ParseErrorKind::UnexpectedToken,
+            Reason::InvalidNumber(_) => ParseErrorKind::InvalidNumber,
+            Reason::Io(_) => ParseErrorKind::Io,
+        }
+    }
+
+    /// Where in the input the error is, unless it is about the input as a whole
+    pub fn position(&self) -> Option<Position> {
+        self.at
+    }
+
+    /// The message alone, without the kind of error or its position, e.g.
+    /// `invalid number '1.'`
+    pub fn message(&self) -> String {
+        self.reason.to_string()
+    }
+}
 
-            if !self.last_token() {
-                self.next_token()?;
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Reason::Io(err) => return write!(f, "IO error: {}", err),
+            reason => write!(f, "Syntax error: {}", reason)?,
+        }
+        match self.at {
+            Some(at) => write!(f, " at {}", at),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Io(err) => write!(f, "{}", err),
+            Reason::Message(_, message) => f.write_str(message),
+            Reason::Expected(expected, found) => {
+                f.write_str("expected ")?;
+                for (i, token) in expected.iter().enumerate() {
+                    let sep = if i > 0 { ", " } else { "" };
+                    write!(f, "{}{}", sep, token)?;
+                }
+                match found {
+                    Found::Name(name) => write!(f, " but got {}", name),
+                    Found::Text(text) => write!(f, " but got '{}'", text),
+                }
             }
+            Reason::InvalidNumber(num) => write!(f, "invalid number '{}'", num),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.reason {
+            Reason::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// The key of an object member, cheap to clone and compared by its text
+/// One pointer wide, unlike `Arc<str>`, so that a member takes no more room
+/// than any other value.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(Arc<Box<str>>);
+
+impl Deref for Key {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self
+    }
+}
+
+impl From<&str> for Key {
+    fn from(key: &str) -> Self {
+        Key(Arc::new(key.into()))
+    }
+}
+
+impl From<String> for Key {
+    fn from(key: String) -> Self {
+        Key(Arc::new(key.into_boxed_str()))
+    }
+}
+
+impl From<Box<str>> for Key {
+    fn from(key: Box<str>) -> Self {
+        Key(Arc::new(key))
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+/// Parses tokens as it takes them, from a vector or straight from the
+/// tokenizer with `Tokenizer::tokens`
+#[derive(Clone, Debug)]
+pub struct Parser<'a, I: Iterator<Item = (Token<'a>, Position)>> {
+    tokens: Peekable<I>,
+    /// The current token, never whitespace, `None` at the end
+    current: Option<(Token<'a>, Position)>,
+    arena: Arena,
+    /// Lengths of the last object and array parsed, which the next ones are
+    /// presized to since siblings tend to look alike, e.g. records
+    object_len: usize,
+    array_len: usize,
+    /// Whether values are only checked rather than built, see `validate`
+    checking: bool,
+    max_depth: usize,
+    reject_duplicates: bool,
+    /// The keys so far of each object being parsed, when rejecting duplicates,
+    /// and sets cleared for reuse, hashed like the arena's keys
+    keys: Vec<HashSet<Key, KeyHasher>>,
+    spare_keys: Vec<HashSet<Key, KeyHasher>>,
+}
+
+/// How deep documents may nest unless `Parser::max_depth` says otherwise
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+/// A container being parsed, held while the value of its current member or
+/// element is
+enum Frame {
+    /// The members so far, and the key of the one whose value is being parsed
+    Object(Vec<JsonValue>, Option<Key>),
+    Array(Vec<JsonValue>),
+}
+
+/// The containers a document is inside of, outermost first
+/// While checking they hold nothing, so only whether each is an object is
+/// kept, in bits that need no allocation up to `DEFAULT_MAX_DEPTH`.
+struct Stack {
+    checking: bool,
+    frames: Vec<Frame>,
+    objects: [u64; DEFAULT_MAX_DEPTH.div_ceil(64)],
+    len: usize,
+}
 
-            objs.push(json?);
+impl Stack {
+    fn new(checking: bool) -> Self {
+        Stack {
+            checking,
+            frames: vec![],
+            objects: [0; DEFAULT_MAX_DEPTH.div_ceil(64)],
+            len: 0,
         }
+    }
 
-        if objs.is_empty() {
-            Ok(JsonValue::Empty)
+    /// Whether the container at `depth`, from 0, is kept in the bits
+    fn in_bits(&self, depth: usize) -> bool {
+        self.checking && depth < self.objects.len() * 64
+    }
+
+    fn push(&mut self, frame: Frame) {
+        if self.in_bits(self.len) {
+            let (word, bit) = (self.len / 64, 1 << (self.len % 64));
+            match frame {
+                Frame::Object(..) => self.objects[word] |= bit,
+                Frame::Array(_) => self.objects[word] &= !bit,
+            }
         } else {
-            Ok(JsonValue::Object(objs))
+            self.frames.push(frame);
         }
+        self.len += 1;
     }
 
-    /// Parse a keyed object
-    /// e.g., "key": {}
-    fn parse_keyed_object(&mut self) -> Result<JsonValue, SyntaxError> {
-        let key = self.parse_key()?;
-        self.assert_current(&[Token::Colon])?;
-        self.next_token()?;
-        let (next, _) = self.current_token()?;
-        let json = match next {
-            Token::LeftCurly => self.parse_object(),
-            Token::Quote => self.parse_string_literal(),
-            Token::Char('t') | Token::Char('f') => self.parse_bool(),
-            Token::Digit(_) | Token::Minus => self.parse_number(),
-            Token::LeftBracket => self.parse_array(),
-            _ => Err(self.err("unexpected token while parsing object")),
+    fn pop(&mut self) -> Option<Frame> {
+        self.len = self.len.checked_sub(1)?;
+        if !self.in_bits(self.len) {
+            return self.frames.pop();
+        }
+        let (word, bit) = (self.len / 64, 1 << (self.len % 64));
+        Some(if self.objects[word] & bit != 0 {
+            Frame::Object(Vec::new(), None)
+        } else {
+            Frame::Array(Vec::new())
+        })
+    }
+}
+
+/// A value that is either done or a container that was opened
+enum Opened {
+    Value(JsonValue),
+    Frame(Frame),
+}
+
+/// Where parsing a container got to
+enum Step {
+    /// The container, which waits on the one opened for its current value
+    Descend(Frame, Frame),
+    Done(JsonValue),
+}
+
+/// Presizing never goes beyond this many values, so that one long array does
+/// not make every later one take as much room
+const MAX_PRESIZE: usize = 1024;
+
+impl<'a, I: Iterator<Item = (Token<'a>, Position)>> Parser<'a, I> {
+    pub fn new(tokens: impl IntoIterator<IntoIter = I>) -> Self {
+        Self::with_arena(tokens, Arena::new())
+    }
+
+    /// A parser building the tree from the allocations in `arena`
+    pub fn with_arena(tokens: impl IntoIterator<IntoIter = I>, arena: Arena) -> Self {
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            current: None,
+            arena,
+            object_len: 0,
+            array_len: 0,
+            checking: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            reject_duplicates: false,
+            keys: vec![],
+            spare_keys: vec![],
+        };
+        parser.advance();
+        parser
+    }
+
+    /// The arena, with what the parse left of it, to pass on to the next parser
+    pub fn into_arena(self) -> Arena {
+        self.arena
+    }
+
+    /// A parser for `tokens` that keeps the arena and how containers have been
+    /// sized so far, for parsing many small documents in a loop
+    /// Inputs that do not outlive the loop pass the arena on with `into_arena`
+    /// instead.
+    pub fn reset<'b, J>(self, tokens: impl IntoIterator<IntoIter = J>) -> Parser<'b, J>
+    where
+        J: Iterator<Item = (Token<'b>, Position)>,
+    {
+        let mut parser = Parser::with_arena(tokens, self.arena);
+        parser.object_len = self.object_len;
+        parser.array_len = self.array_len;
+        parser.max_depth = self.max_depth;
+        parser.reject_duplicates = self.reject_duplicates;
+        parser
+    }
+
+    /// Give a tree that is done with back to the arena, see `Arena::reclaim`
+    pub fn reclaim(&mut self, value: JsonValue) {
+        self.arena.reclaim(value);
+    }
+
+    /// Check that the tokens are a document `parse` accepts, with the same errors
+    /// Nothing is built, so a valid document is checked without allocating,
+    /// unless it nests deeper than `DEFAULT_MAX_DEPTH` or rejecting duplicate
+    /// keys keeps them.
+    pub fn validate(&mut self) -> Result<(), SyntaxError> {
+        self.checking = true;
+        let checked = self.parse().map(drop);
+        self.checking = false;
+        checked
+    }
+
+    /// Parse a JSON document
+    /// The containers being parsed are kept on a stack of their own rather than
+    /// the thread's, so that only `max_depth` limits how deep documents go.
+    pub fn parse(&mut self) -> Result<JsonValue, SyntaxError> {
+        let json = self.document()?;
+        if self.current.is_some() {
+            return Err(self.err(
+                ParseErrorKind::TrailingCharacters,
+                "unexpected data after the document",
+            ));
+        }
+        Ok(json)
+    }
+
+    fn document(&mut self) -> Result<JsonValue, SyntaxError> {
+        // Left over from a parse that failed
+        while !self.keys.is_empty() {
+            self.close_keys();
+        }
+        let mut frame = match self.value(0, "invalid JSON document")? {
+            Opened::Value(json) => return Ok(json),
+            Opened::Frame(frame) => frame,
         };
 
-        Ok(JsonValue::KeyedObject(key, Box::new(json?)))
+        let mut stack = Stack::new(self.checking);
+        // The value just parsed for `frame`, none when it was just opened
+        let mut done = None;
+        loop {
+            let depth = stack.len + 1;
+            let step = match frame {
+                Frame::Object(members, key) => self.resume_object(members, key, done, depth)?,
+                Frame::Array(values) => self.resume_array(values, done, depth)?,
+            };
+            (frame, done) = match step {
+                Step::Descend(parent, child) => {
+                    stack.push(parent);
+                    (child, None)
+                }
+                Step::Done(json) => match stack.pop() {
+                    Some(parent) => (parent, Some(json)),
+                    None => return Ok(json),
+                },
+            };
+        }
+    }
+
+    /// Reject documents nested deeper than `depth`, `DEFAULT_MAX_DEPTH` unless set
+    /// Parsing, serializing and dropping a tree take no stack per level, but
+    /// cloning it, `transform` and `query` recurse, so a tree deeper than the
+    /// default may overflow the stack in those.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Reject objects that give a key twice, which RFC 8259 leaves to the
+    /// parser, instead of keeping every member
+    pub fn reject_duplicate_keys(mut self, reject: bool) -> Self {
+        self.reject_duplicates = reject;
+        self
+    }
+
+    /// Start on an object at its `{`, done at once if empty
+    fn open_object(&mut self, depth: usize) -> Result<Opened, SyntaxError> {
+        self.check_depth(depth)?;
+        self.next_token()?;
+        if self.is_current(&[Token::RightCurly]) {
+            self.advance();
+            return Ok(Opened::Value(JsonValue::Empty));
+        }
+
+        if self.reject_duplicates {
+            let keys = self.spare_keys.pop();
+            let keys = keys.unwrap_or_else(|| HashSet::with_hasher(self.arena.hasher().clone()));
+            self.keys.push(keys);
+        }
+        let members = if self.checking {
+            Vec::new()
+        } else {
+            self.arena.vec(self.object_len)
+        };
+        Ok(Opened::Frame(Frame::Object(members, None)))
     }
 
-    /// Parse an array of json values
-    fn parse_array(&mut self) -> Result<JsonValue, SyntaxError> {
-        let mut arr: Vec<JsonValue> = vec![];
-        while self.current_token()?.0 != Token::RightBracket {
+    /// Parse the members of an object, from after `value` for `key` if given,
+    /// until the end or a member whose value is a container to descend into
+    fn resume_object(
+        &mut self,
+        mut members: Vec<JsonValue>,
+        key: Option<Key>,
+        value: Option<JsonValue>,
+        depth: usize,
+    ) -> Result<Step, SyntaxError> {
+        let mut done = false;
+        if let Some(json) = value {
+            members.extend(key.map(|key| self.member(key, json)));
+            done = self.end_child(&[Token::Comma, Token::RightCurly])?;
+        }
+        while !done {
+            // A member, e.g. "key": {}
+            let key = self.parse_key()?;
+            self.assert_current(&[Token::Colon])?;
             self.next_token()?;
-            let (next, _) = self.current_token()?;
-            let json = match next {
-                Token::LeftCurly => self.parse_object(),
-                Token::Quote => self.parse_string_literal(),
-                Token::Char('t') | Token::Char('f') => self.parse_bool(),
-                Token::Digit(_) | Token::Minus => self.parse_number(),
-                Token::LeftBracket => self.parse_array(),
-                Token::RightBracket => break,
-                _ => Err(self.err("unexpected token while parsing array")),
+            let json = match self.value(depth, "unexpected token while parsing object")? {
+                Opened::Value(json) => json,
+                Opened::Frame(child) => {
+                    return Ok(Step::Descend(Frame::Object(members, key), child));
+                }
             };
+            members.extend(key.map(|key| self.member(key, json)));
+            done = self.end_child(&[Token::Comma, Token::RightCurly])?;
+        }
+
+        self.close_keys();
+        if members.is_empty() {
+            self.arena.vec_back(members);
+            Ok(Step::Done(JsonValue::Empty))
+        } else {
+            self.object_len = members.len().min(MAX_PRESIZE);
+            Ok(Step::Done(JsonValue::Object(
+                self.arena.boxed_slice(members),
+            )))
+        }
+    }
 
-            arr.push(json?);
+    /// Done with the keys of the innermost object, if they were kept
+    fn close_keys(&mut self) {
+        if let Some(mut keys) = self.keys.pop() {
+            keys.clear();
+            self.spare_keys.push(keys);
         }
+    }
 
+    fn member(&mut self, key: Key, json: JsonValue) -> JsonValue {
+        self.arena.member(key, json)
+    }
+
+    /// Move past the comma or closing bracket after a member or element, one of
+    /// `expected`, returning whether it closed the container
+    fn end_child(&mut self, expected: &'static [Token<'static>]) -> Result<bool, SyntaxError> {
+        self.assert_current(expected)?;
+        if self.is_current(&[Token::Comma]) {
+            self.next_token()?;
+            return Ok(false);
+        }
+        self.advance();
+        Ok(true)
+    }
+
+    /// Start on an array at its `[`, done at once if empty
+    fn open_array(&mut self, depth: usize) -> Result<Opened, SyntaxError> {
+        self.check_depth(depth)?;
         self.next_token()?;
-        Ok(JsonValue::Arr(arr))
+        if self.is_current(&[Token::RightBracket]) {
+            self.advance();
+            return Ok(Opened::Value(JsonValue::Arr(Box::default())));
+        }
+
+        let values = if self.checking {
+            Vec::new()
+        } else {
+            self.arena.vec(self.array_len)
+        };
+        Ok(Opened::Frame(Frame::Array(values)))
+    }
+
+    /// Parse the elements of an array, from after `value` if given, until the
+    /// end or an element that is a container to descend into
+    fn resume_array(
+        &mut self,
+        mut values: Vec<JsonValue>,
+        value: Option<JsonValue>,
+        depth: usize,
+    ) -> Result<Step, SyntaxError> {
+        let mut done = false;
+        if let Some(json) = value {
+            if !self.checking {
+                values.push(json);
+            }
+            done = self.end_child(&[Token::Comma, Token::RightBracket])?;
+        }
+        while !done {
+            match self.value(depth, "unexpected token while parsing array")? {
+                Opened::Value(json) => {
+                    if !self.checking {
+                        values.push(json);
+                    }
+                }
+                Opened::Frame(child) => return Ok(Step::Descend(Frame::Array(values), child)),
+            }
+            done = self.end_child(&[Token::Comma, Token::RightBracket])?;
+        }
+
+        self.array_len = values.len().min(MAX_PRESIZE);
+        Ok(Step::Done(JsonValue::Arr(self.arena.boxed_slice(values))))
+    }
+
+    /// Parse the value at the current token inside a container at `depth`, or
+    /// open it if it is a container itself
+    fn value(&mut self, depth: usize, unexpected: &'static str) -> Result<Opened, SyntaxError> {
+        let (next, _) = self.current_token()?;
+        let json = match next {
+            Token::LeftCurly => return self.open_object(depth + 1),
+            Token::LeftBracket => return self.open_array(depth + 1),
+            Token::Quoted(_) => self.parse_string_literal(),
+            Token::Chars(s) if s.starts_with(['t', 'f']) => self.parse_bool(),
+            Token::Chars(s) if s.starts_with('n') => self.parse_null(),
+            Token::Number(_) => self.parse_number(),
+            _ => Err(self.err(ParseErrorKind::UnexpectedToken, unexpected)),
+        };
+        json.map(Opened::Value)
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<(), SyntaxError> {
+        if depth > self.max_depth {
+            return Err(self.err(ParseErrorKind::DepthExceeded, "nesting too deep"));
+        }
+        Ok(())
     }
 
     /// Parse a number, resulting in either a float or an integer
     fn parse_number(&mut self) -> Result<JsonValue, SyntaxError> {
+        let at = self.current_token()?.1;
         let num = self.digits_to_string();
-        if num.contains('.') {
-            match num.parse::<f64>() {
-                Ok(f) => Ok(JsonValue::Float(f)),
-                Err(_) => Err(self.err("failed to parse float")),
-            }
-        } else {
-            match num.parse::<i64>() {
-                Ok(i) => Ok(JsonValue::Int(i)),
-                Err(_) => Err(self.err("failed to parse integer")),
-            }
+        if !is_number(num) {
+            return Err(SyntaxError::invalid_number(num, at));
+        }
+        if self.checking {
+            return check_number(num)
+                .map(|()| JsonValue::Null)
+                .map_err(|msg| self.err(ParseErrorKind::InvalidNumber, msg));
         }
+        number_value(num).map_err(|msg| self.err(ParseErrorKind::InvalidNumber, msg))
     }
 
     /// Parse a string literal
     /// e.g., "foo": "bar"
     fn parse_string_literal(&mut self) -> Result<JsonValue, SyntaxError> {
-        self.assert_current(&[Token::Quote])?;
-        self.next_token()?;
-
-        let str = self.chars_to_string();
-
-        self.assert_current(&[Token::Quote])?;
-        self.next_token()?;
-
-        Ok(JsonValue::Str(str))
+        if self.checking {
+            return self.quoted(check).map(|()| JsonValue::Null);
+        }
+        let text = self.quoted_text()?;
+        Ok(JsonValue::Str(self.arena.string(&text)))
     }
 
     /// Parse a bool
     /// e.g. "field": true
     fn parse_bool(&mut self) -> Result<JsonValue, SyntaxError> {
+        let at = self.current_token()?.1;
         let str = self.chars_to_string();
         if str == "true" {
             Ok(JsonValue::Bool(true))
         } else if str == "false" {
             Ok(JsonValue::Bool(false))
         } else {
-            Err(self.err("failed to parse boolean"))
+            Err(self.literal_err(at, "failed to parse boolean"))
         }
     }
 
-    /// Parse a key (property name)
-    /// Consumes: `"key" :`, leaves next token as e.g., `{`
-    fn parse_key(&mut self) -> Result<String, SyntaxError> {
-        if self.assert_current(&[Token::Comma]).is_ok() {
-            self.next_token()?;
+    /// Parse a null
+    /// e.g. "field": null
+    fn parse_null(&mut self) -> Result<JsonValue, SyntaxError> {
+        let at = self.current_token()?.1;
+        if self.chars_to_string() == "null" {
+            Ok(JsonValue::Null)
+        } else {
+            Err(self.literal_err(at, "failed to parse null"))
         }
-        self.assert_current(&[Token::Quote])?;
-        self.next_token()?;
+    }
 
-        let key = self.chars_to_string();
+    /// A misspelled literal that started at `at`, reported at the token after
+    /// it, or at the literal itself when it ends the document
+    fn literal_err(&self, at: Position, msg: &'static str) -> SyntaxError {
+        let at = self.current.map_or(at, |(_, pos)| pos);
+        SyntaxError::new(ParseErrorKind::UnexpectedToken, msg, Some(at))
+    }
 
-        self.assert_current(&[Token::Quote])?;
-        self.next_token()?;
+    /// Parse a key (property name), `None` when only checking
+    /// Consumes: `"key"`, leaves next token as e.g., `:`
+    fn parse_key(&mut self) -> Result<Option<Key>, SyntaxError> {
+        let at = self.current_token()?.1;
+        if self.checking && self.keys.is_empty() {
+            return self.quoted(check).map(|()| None);
+        }
+        let text = self.quoted_text()?;
+        let Some(keys) = self.keys.last_mut() else {
+            return Ok(Some(self.arena.key(&text)));
+        };
+        if keys.contains(text.as_ref()) {
+            let kind = ParseErrorKind::DuplicateKey;
+            return Err(SyntaxError::new(kind, "duplicate key in object", Some(at)));
+        }
+        // The set holds the interned key, so it costs no allocation of its own
+        let key = self.arena.key(&text);
+        keys.insert(key.clone());
+        Ok((!self.checking).then_some(key))
+    }
 
-        Ok(key)
+    /// Whether the current token is one of `expected`, as `assert_current` would
+    /// find without making the error
+    fn is_current(&self, expected: &[Token]) -> bool {
+        let Some((curr, _)) = self.current else {
+            return false;
+        };
+        expected.iter().any(|ex| match (ex, curr) {
+            (Token::Quoted(_), Token::Quoted(_))
+            | (Token::Chars(_), Token::Chars(_))
+            | (Token::Number(_), Token::Number(_)) => true,
+            (a, b) => *a == b,
+        })
     }
 
     /// Assert that the current token is one of the expected ones
-    fn assert_current(&self, expected: &[Token]) -> Result<(), SyntaxError> {
-        let curr = self.current_token()?;
-
-        for ex in expected {
-            let mat = match (ex, curr.0) {
-                (Token::Char(_), Token::Char(_)) | (Token::Digit(_), Token::Digit(_)) => true,
-                (a, b) => *a == b,
-            };
-
-            if mat {
-                return Ok(());
-            }
+    fn assert_current(&self, expected: &'static [Token<'static>]) -> Result<(), SyntaxError> {
+        let (curr, at) = self.current_token()?;
+        if self.is_current(expected) {
+            return Ok(());
         }
 
-        let expected_list = expected
-            .iter()
-            .map(Token::to_string)
-            .collect::<Vec<_>>()
-            .join(", ");
+        let found = match curr {
+            Token::Number(text) | Token::Chars(text) => Found::Text(text.into()),
+            token => Found::Name(token.name()),
+        };
+        Err(SyntaxError {
+            reason: Reason::Expected(expected, found),
+            at: Some(at),
+        })
+    }
+
+    /// Consumes the string at the current position
+    fn quoted_text(&mut self) -> Result<Cow<'a, str>, SyntaxError> {
+        self.quoted(unescape)
+    }
 
-        Err(self.err(format!("expected {} but got {}", expected_list, curr.0).as_str()))
+    /// Read the current string token with `read`, given the text between its
+    /// quotes and the quote
+    fn quoted<T>(
+        &mut self,
+        read: impl FnOnce(&'a str, u8) -> Result<T, &'static str>,
+    ) -> Result<T, SyntaxError> {
+        self.assert_current(&[Token::Quoted("")])?;
+        let text = match self.current_token()?.0 {
+            Token::Quoted(quoted) => unquote(quoted, read),
+            _ => None,
+        };
+        // Running into the end of the input like the document does
+        let text = text.ok_or_else(|| {
+            SyntaxError::new(
+                ParseErrorKind::UnterminatedString,
+                "unexpected end of file",
+                None,
+            )
+        })?;
+        let text = text.map_err(|msg| self.err(ParseErrorKind::InvalidString, msg))?;
+        self.next_token()?;
+        Ok(text)
     }
 
-    /// Consumes char tokens from the current position.
+    /// Consumes the word at the current position, if any.
     /// Important: no assertions made here
-    fn chars_to_string(&mut self) -> String {
-        let key = self
-            .tokens
-            .iter()
-            .skip(self.idx)
-            .map_while(|(t, _)| match t {
-                Token::Char(c) => {
-                    self.idx += 1;
-                    Some(c)
-                }
-                _ => None,
-            })
-            .collect::<String>();
-        key
+    fn chars_to_string(&mut self) -> &'a str {
+        match self.current {
+            Some((Token::Chars(s), _)) => {
+                self.advance();
+                s
+            }
+            _ => "",
+        }
     }
 
-    /// Convert the expected incoming characters to a string representing a digit
-    fn digits_to_string(&mut self) -> String {
-        let digits = self
-            .tokens
-            .iter()
-            .skip(self.idx)
-            .map_while(|(t, _)| match t {
-                Token::Digit(c) => {
-                    self.idx += 1;
-                    Some(c)
-                }
-                Token::Minus => {
-                    self.idx += 1;
-                    Some(&'-')
-                }
-                Token::Dot => {
-                    self.idx += 1;
-                    Some(&'.')
-                }
-                _ => None,
-            })
-            .collect::<String>();
-        digits
+    /// Consumes the number at the current position, if any
+    fn digits_to_string(&mut self) -> &'a str {
+        match self.current {
+            Some((Token::Number(n), _)) => {
+                self.advance();
+                n
+            }
+            _ => "",
+        }
     }
 
-    /// Trim all of the whitespace since the parser does not care for it
-    fn remove_whitespace(&mut self) {
-        self.tokens = self
+    /// Skip whitespace since the parser does not care for it
+    fn skip_whitespace(&mut self) {
+        while self
             .tokens
-            .clone()
-            .into_iter()
-            .filter(|(x, _)| *x != Token::Whitespace && *x != Token::NewLine)
-            .collect();
+            .next_if(|(t, _)| *t == Token::Whitespace)
+            .is_some()
+        {}
+    }
+
+    /// Move past the current token and the whitespace after it
+    fn advance(&mut self) {
+        self.skip_whitespace();
+        self.current = self.tokens.next();
     }
 
     /// Consume the next token if it exists
     fn next_token(&mut self) -> Result<(), SyntaxError> {
         if self.end_of_tokens() {
-            Err(self.err("unterminated"))
+            Err(self.err(ParseErrorKind::UnexpectedEof, "unterminated"))
         } else {
-            self.idx += 1;
+            self.advance();
             Ok(())
         }
     }
 
     /// Get the current token if it exists
-    fn current_token(&self) -> Result<(Token, Position), SyntaxError> {
-        if self.end_of_tokens() {
-            Err(self.err("unexpected end of file"))
-        } else {
-            Ok(self.tokens[self.idx])
-        }
+    fn current_token(&self) -> Result<(Token<'a>, Position), SyntaxError> {
+        self.current.ok_or_else(SyntaxError::eof)
     }
 
-    fn err(&self, msg: &str) -> SyntaxError {
-        if self.end_of_tokens() {
+    fn err(&self, kind: ParseErrorKind, msg: &'static str) -> SyntaxError {
+        match self.current {
+            Some((_, pos)) => SyntaxError::new(kind, msg, Some(pos)),
             // A bit ugly, but allows current_token to crash
-            SyntaxError("Syntax error: unexpected end of file".to_string())
-        } else {
-            let (_, pos) = self.tokens[self.idx];
-            SyntaxError(format!("Syntax error: {} at {}", msg, pos))
+            None => SyntaxError::eof(),
         }
     }
 
     fn end_of_tokens(&self) -> bool {
-        self.tokens.len() <= self.idx
+        self.current.is_none()
+    }
+}
+
+/// The text between the quotes of a string as `read` takes it, e.g. decoded,
+/// `None` if the closing one is missing
+/// Only the lenient tokenizer quotes with `'`, inside which `\'` stands for the quote.
+fn unquote<'a, T>(
+    quoted: &'a str,
+    read: impl FnOnce(&'a str, u8) -> Result<T, &'static str>,
+) -> Option<Result<T, &'static str>> {
+    let quote = *quoted.as_bytes().first()?;
+    let inner = &quoted[1..];
+    closes(inner, quote).then(|| read(&inner[..inner.len() - 1], quote))
+}
+
+/// Whether `number_value` would take the lexeme, without building the value
+fn check_number(num: &str) -> Result<(), &'static str> {
+    if !is_number(num) {
+        return Err("invalid number");
+    }
+    num.parse::<f64>()
+        .map(drop)
+        .map_err(|_| "failed to parse float")
+}
+
+/// The value of a number lexeme, either a float or an integer
+/// Only lexemes of the JSON grammar are taken, so that a float can keep its own
+/// to write back out. Integers beyond `i64` become floats keeping their lexeme,
+/// like any other float that would not reproduce it.
+pub(crate) fn number_value(num: &str) -> Result<JsonValue, &'static str> {
+    if !is_number(num) {
+        return Err("invalid number");
+    }
+    if !num.contains(['.', 'e', 'E']) && num != "-0" {
+        if let Ok(i) = num.parse::<i64>() {
+            return Ok(JsonValue::Int(i));
+        }
+    }
+    match num.parse::<f64>() {
+        Ok(f) => {
+            let raw = (*format_float(f) != *num).then(|| Box::new(num.into()));
+            Ok(JsonValue::Float(f, raw))
+        }
+        Err(_) => Err("failed to parse float"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::borrowed::BorrowedValue;
+    use crate::flat::FlatTree;
+    use crate::parallel::{parse_parallel, MIN_PARALLEL_LEN};
+    use crate::serialize::to_string;
+    use crate::stream::JsonReader;
+    use crate::tape::Tape;
+    use crate::tokenize::{LenientOptions, Tokenizer};
+
+    fn parse(input: &str) -> Result<JsonValue, SyntaxError> {
+        Parser::new(Tokenizer::new().tokens(input)).parse()
+    }
+
+    fn compact(input: &str) -> String {
+        to_string(&parse(input).unwrap()).unwrap()
+    }
+
+    fn str_at<'v>(value: &'v JsonValue, pointer: &str) -> Option<&'v str> {
+        match value.pointer(pointer)? {
+            JsonValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn decodes_escapes() {
+        let value =
+            parse(r#"{"a":"x\"y","b":"\\ \/ \b\f\n\r\t","c":"\u0041\ud83d\ude00"}"#).unwrap();
+        assert_eq!(str_at(&value, "/a"), Some("x\"y"));
+        assert_eq!(str_at(&value, "/b"), Some("\\ / \u{08}\u{0c}\n\r\t"));
+        assert_eq!(str_at(&value, "/c"), Some("A😀"));
+    }
+
+    #[test]
+    fn decodes_escaped_keys() {
+        let value = parse(r#"{"a\"b":1}"#).unwrap();
+        assert!(value.pointer("/a\"b").is_some());
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        for input in [
+            r#"{"a":"x\"y"}"#,
+            r#"{"a":"x\ny"}"#,
+            r#"{"a":"back\\slash"}"#,
+            r#"["\u0001","tab\t","\"\\\""]"#,
+        ] {
+            let once = compact(input);
+            assert_eq!(once, input);
+            assert_eq!(compact(&once), once);
+        }
+    }
+
+    #[test]
+    fn decodes_like_the_stream() {
+        for input in [
+            r#"["x\"y","\\n","\u00e9\ud83d\ude00","\/"]"#,
+            r#"{"k\u0041":"v\tw"}"#,
+        ] {
+            let streamed = JsonReader::new(input.as_bytes()).read_value().unwrap();
+            assert_eq!(compact(input), to_string(&streamed).unwrap());
+        }
+    }
+
+    #[test]
+    fn rejects_bad_strings() {
+        for input in [r#"["\q"]"#, r#"["\ud800"]"#, "[\"a\nb\"]", r#"["\u12"]"#] {
+            let err = parse(input).err().unwrap();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidString, "{}", input);
+        }
+        let err = parse(r#"["a\"]"#).err().unwrap();
+        assert_eq!(err.kind(), ParseErrorKind::UnterminatedString);
+    }
+
+    #[test]
+    fn single_quotes_decode_too() {
+        let options = LenientOptions::all();
+        let value = Parser::new(Tokenizer::lenient(options).tokens(r"['it\'s', 'a\nb']"))
+            .parse()
+            .unwrap();
+        assert_eq!(to_string(&value).unwrap(), r#"["it's","a\nb"]"#);
+    }
+
+    /// Inputs that the RFC 8259 grammar rejects, and where the byte parsers
+    /// were stricter than `Parser` once
+    const INVALID: &[&str] = &[
+        "[1 2]",
+        r#"{"a":1]"#,
+        "[1]]",
+        "{} {}",
+        "[01]",
+        "[1.]",
+        r#"{"a":1,}"#,
+        "[1,]",
+        "[,1]",
+        "[1,,2]",
+        r#"{,"a":1}"#,
+        r#"{"a":1 "b":2}"#,
+        r#"{"a" 1}"#,
+        "[-]",
+        "[1e]",
+        "[.5]",
+        "[+1]",
+        "[tru]",
+        "[\"a\nb\"]",
+        "[",
+        "",
+        "tru",
+        "nul",
+        "1 2",
+        "01",
+        "-",
+        "\"a",
+    ];
+
+    const VALID: &[&str] = &[
+        r#"["x\"y"]"#,
+        "{\"a\":\r\n1}\r\n",
+        "[1.5e+3,-0,0.25,1E-2]",
+        r#"{"a":{"b":[1,{"c":2}]},"d":[]}"#,
+        "[[],{},[[]]]",
+        " [true,false,null] ",
+        "1",
+        " \"s\" ",
+        "null",
+        "[12345678901234567890,-9223372036854775809]",
+        "123456789012345678901234567890",
+    ];
+
+    /// Whether the stream reader reads the whole input as one document
+    fn streams(input: &str) -> bool {
+        let mut reader = JsonReader::new(input.as_bytes());
+        loop {
+            match reader.next_event() {
+                Ok(None) => return true,
+                Ok(Some(_)) => {}
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn validates(input: &str) -> bool {
+        Parser::new(Tokenizer::new().tokens(input))
+            .validate()
+            .is_ok()
+    }
+
+    #[test]
+    fn agrees_with_the_stream_on_invalid_input() {
+        for input in INVALID {
+            assert!(!validates(input), "validate took {:?}", input);
+            assert!(parse(input).is_err(), "parse took {:?}", input);
+            assert!(!streams(input), "the stream took {:?}", input);
+            assert!(
+                BorrowedValue::parse(input).is_err(),
+                "borrowed took {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_stream_on_valid_input() {
+        for input in VALID {
+            assert!(validates(input), "validate failed {:?}", input);
+            assert!(streams(input), "the stream failed {:?}", input);
+            assert!(
+                BorrowedValue::parse(input).is_ok(),
+                "borrowed failed {:?}",
+                input
+            );
+            let streamed = JsonReader::new(input.as_bytes()).read_value().unwrap();
+            assert_eq!(compact(input), to_string(&streamed).unwrap());
+        }
+    }
+
+    #[test]
+    fn other_parsers_agree_on_the_corpora() {
+        let parallel_agrees = |input: &str| {
+            let compact = |result: Result<JsonValue, SyntaxError>| {
+                result
+                    .map(|value| to_string(&value).unwrap())
+                    .map_err(|e| e.to_string())
+            };
+            let expected = compact(parse(input));
+            assert_eq!(
+                compact(parse_parallel(input, 4)),
+                expected,
+                "parallel on {:?}",
+                input
+            );
+        };
+        let check = |input: &str| {
+            let expected = parse(input).map(|value| to_string(&value).unwrap());
+            let tape = Tape::parse(input).map(|tape| to_string(&tape.root().to_json()).unwrap());
+            let flat =
+                FlatTree::parse(input).map(|tree| to_string(&tree.root().to_json()).unwrap());
+            assert_eq!(
+                tape.ok(),
+                expected.as_ref().ok().cloned(),
+                "tape on {:?}",
+                input
+            );
+            assert_eq!(
+                flat.ok(),
+                expected.as_ref().ok().cloned(),
+                "flat on {:?}",
+                input
+            );
+            parallel_agrees(input);
+        };
+        // Containers long enough that the parallel parser splits them
+        let wrap = |inputs: &[&str]| {
+            let copies = MIN_PARALLEL_LEN / inputs.iter().map(|i| i.len() + 8).sum::<usize>() + 1;
+            let elements = vec![inputs.join(","); copies].join(",");
+            let members = (0..copies * inputs.len())
+                .map(|i| format!("\"{}\":{}", i, inputs[i % inputs.len()]))
+                .collect::<Vec<_>>();
+            [
+                format!("[{}]", elements),
+                format!("{{{}}}", members.join(",")),
+            ]
+        };
+
+        for input in INVALID {
+            check(input);
+            wrap(&[input])
+                .iter()
+                .for_each(|input| parallel_agrees(input));
+        }
+        for input in VALID {
+            check(input);
+        }
+        wrap(VALID).iter().for_each(|input| parallel_agrees(input));
+    }
+
+    #[test]
+    fn keeps_integers_beyond_i64_as_floats() {
+        let value = parse("[9223372036854775807,9223372036854775808]").unwrap();
+        assert!(matches!(
+            value.pointer("/0"),
+            Some(JsonValue::Int(i64::MAX))
+        ));
+        match value.pointer("/1") {
+            Some(JsonValue::Float(f, Some(raw))) => {
+                assert_eq!(*f, 9223372036854775808.0);
+                assert_eq!(***raw, *"9223372036854775808");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            to_string(&value).unwrap(),
+            "[9223372036854775807,9223372036854775808]"
+        );
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        for input in ["{} {}", "[1]]", "[1] x"] {
+            let err = parse(input).err().unwrap();
+            assert_eq!(err.kind(), ParseErrorKind::TrailingCharacters, "{}", input);
+        }
+    }
+
+    #[test]
+    fn errors_keep_their_parts_apart() {
+        let err = parse("{\"a\": 1,\n  \"b\" 2}").err().unwrap();
+        let at = err.position().unwrap();
+        assert_eq!((at.line(), at.column()), (2, 7));
+        assert_eq!(err.message(), "expected COLON but got '2'");
+        assert_eq!(
+            err.to_string(),
+            "Syntax error: expected COLON but got '2' at line 2 column 7"
+        );
+        assert!(parse("[1,").err().unwrap().position().is_none());
+    }
+
+    #[test]
+    fn validates_deep_documents_like_it_parses() {
+        let nested = |depth: usize, close: &str| {
+            let open = (0..depth).map(|i| if i % 3 == 0 { r#"{"a":"# } else { "[" });
+            let close = (0..depth)
+                .rev()
+                .map(|i| if i % 3 == 0 { close } else { "]" });
+            open.chain(["\"\\u00e9\""]).chain(close).collect::<String>()
+        };
+        for depth in [1, 64, 65, 200, DEFAULT_MAX_DEPTH + 10] {
+            for close in ["}", "]"] {
+                let text = nested(depth, close);
+                let parsed = Parser::new(Tokenizer::new().tokens(&text))
+                    .max_depth(usize::MAX)
+                    .parse()
+                    .map(drop);
+                let checked = Parser::new(Tokenizer::new().tokens(&text))
+                    .max_depth(usize::MAX)
+                    .validate();
+                assert_eq!(checked.is_ok(), close == "}", "{} {}", depth, close);
+                let kind = |result: Result<(), SyntaxError>| result.err().map(|err| err.kind());
+                assert_eq!(kind(checked), kind(parsed), "{} {}", depth, close);
+            }
+        }
+    }
+
+    #[test]
+    fn handles_documents_deeper_than_the_stack() {
+        // Recursing once per level would overflow this stack many times over
+        let depth = 200_000;
+        let text = (0..depth)
+            .map(|i| if i % 2 == 0 { r#"{"a":"# } else { "[" })
+            .chain(["1"])
+            .chain((0..depth).rev().map(|i| if i % 2 == 0 { "}" } else { "]" }))
+            .collect::<String>();
+        std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || {
+                let json = Parser::new(Tokenizer::new().tokens(&text))
+                    .max_depth(depth)
+                    .parse()
+                    .unwrap();
+                assert_eq!(to_string(&json).unwrap(), text);
+                drop(json);
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn rejects_duplicate_keys_when_asked() {
+        let strict = |input: &str| {
+            Parser::new(Tokenizer::new().tokens(input))
+                .reject_duplicate_keys(true)
+                .parse()
+        };
+        let checks = |input: &str| {
+            Parser::new(Tokenizer::new().tokens(input))
+                .reject_duplicate_keys(true)
+                .validate()
+        };
+        for input in [
+            r#"{"a":1,"a":2}"#,
+            r#"[{"a":{"b":1,"b":1}}]"#,
+            r#"{"a":1,"\u0061":2}"#,
+        ] {
+            assert!(parse(input).is_ok(), "{}", input);
+            let err = strict(input).err().unwrap();
+            assert_eq!(err.kind(), ParseErrorKind::DuplicateKey, "{}", input);
+            assert_eq!(
+                checks(input).err().unwrap().kind(),
+                ParseErrorKind::DuplicateKey
+            );
+        }
+        let at = strict(r#"{"a":1, "a":2}"#)
+            .err()
+            .unwrap()
+            .position()
+            .unwrap();
+        assert_eq!(at.column(), 9);
+        for input in [r#"{"a":{"a":1},"b":{"a":2}}"#, r#"[{"a":1},{"a":1}]"#, "{}"] {
+            assert!(strict(input).is_ok(), "{}", input);
+            assert!(checks(input).is_ok(), "{}", input);
+        }
+
+        // With the arena's hasher, also once `reset` passes the arena on
+        let arena = Arena::with_hasher(KeyHasher::resistant());
+        let mut parser = Parser::with_arena(
+            Tokenizer::new().tokens(r#"[{"a":1,"b":2},{"b":1,"b":2}]"#),
+            arena,
+        )
+        .reject_duplicate_keys(true);
+        assert_eq!(
+            parser.parse().err().unwrap().kind(),
+            ParseErrorKind::DuplicateKey
+        );
+        let mut parser = parser.reset(Tokenizer::new().tokens(r#"[{"a":1,"b":2},{"b":1,"a":2}]"#));
+        assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn rejects_numbers_outside_the_grammar() {
+        for input in ["[01]", "[1.]", "[-]", "[1e+]", "[-01.5]"] {
+            let err = parse(input).err().unwrap();
+            assert_eq!(err.kind(), ParseErrorKind::InvalidNumber, "{}", input);
+        }
+        assert!(number_value("1.").is_err());
+        assert!(number_value("1.0e5").is_ok());
     }
 
-    fn last_token(&self) -> bool {
-        self.tokens.len() - 1 == self.idx
+    #[test]
+    fn takes_trailing_commas_only_when_lenient() {
+        let lenient =
+            |input| Parser::new(Tokenizer::lenient(LenientOptions::all()).tokens(input)).parse();
+        assert_eq!(to_string(&lenient("[1,2,]").unwrap()).unwrap(), "[1,2]");
+        assert_eq!(
+            to_string(&lenient(r#"{"a":1,}"#).unwrap()).unwrap(),
+            r#"{"a":1}"#
+        );
+        assert!(parse("[1,2,]").is_err());
     }
 }
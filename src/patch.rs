@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::compare::approx_eq;
+use crate::parse::JsonValue;
+use crate::pointer::PointerError;
+
+#[derive(Debug)]
+pub struct PatchError(pub String);
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Apply an RFC 6902 JSON Patch document, returning the patched copy of `doc`
+/// Operations run in order; if one fails, `doc` is left as it was and the error
+/// names the operation, e.g. `operation 2 (remove '/a/b')`.
+pub fn apply_patch(doc: &JsonValue, patch: &JsonValue) -> Result<JsonValue, PatchError> {
+    let JsonValue::Arr(operations) = patch else {
+        return Err(PatchError(
+            "Patch error: a patch must be an array of operations".to_string(),
+        ));
+    };
+
+    let mut doc = doc.clone();
+    let mut i = 0;
+    while i < operations.len() {
+        let appended = append_run(&mut doc, &operations[i..]);
+        if appended > 0 {
+            i += appended;
+            continue;
+        }
+        let operation = &operations[i];
+        apply(&mut doc, operation).map_err(|msg| {
+            let op = member(operation, "op").unwrap_or("?");
+            let path = member(operation, "path").unwrap_or("?");
+            PatchError(format!(
+                "Patch error: operation {} ({} '{}'): {}",
+                i, op, path, msg
+            ))
+        })?;
+        i += 1;
+    }
+    Ok(doc)
+}
+
+/// Append the values of a run of `add` operations on the same `.../-` path at
+/// once, returning how many were applied
+/// Each `pointer_add` copies the array, so these go through `pointer_extend`
+/// rather than making a long run quadratic. Anything but two or more appends
+/// to an array is left to `apply`.
+fn append_run(doc: &mut JsonValue, operations: &[JsonValue]) -> usize {
+    let Some(path) = operations.first().and_then(|op| member(op, "path")) else {
+        return 0;
+    };
+    let Some(parent) = path.strip_suffix("/-") else {
+        return 0;
+    };
+    let run = operations
+        .iter()
+        .take_while(|op| {
+            member(op, "op") == Some("add")
+                && member(op, "path") == Some(path)
+                && op.get("value").is_some()
+        })
+        .count();
+    if run < 2 {
+        return 0;
+    }
+    let values = operations[..run]
+        .iter()
+        .filter_map(|op| op.get("value"))
+        .cloned();
+    match doc.pointer_extend(parent, values) {
+        Ok(()) => run,
+        Err(_) => 0,
+    }
+}
+
+fn member<'a>(operation: &'a JsonValue, key: &str) -> Option<&'a str> {
+    match operation.get(key) {
+        Some(JsonValue::Str(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn apply(doc: &mut JsonValue, operation: &JsonValue) -> Result<(), String> {
+    let op = member(operation, "op").ok_or("missing \"op\"")?;
+    let path = member(operation, "path").ok_or("missing \"path\"")?;
+    let value = || operation.get("value").cloned().ok_or("missing \"value\"");
+    let from = || member(operation, "from").ok_or("missing \"from\"");
+
+    match op {
+        "add" => doc.pointer_add(path, value()?).map_err(pointer_msg),
+        "remove" => doc.pointer_remove(path).map(|_| ()).map_err(pointer_msg),
+        "replace" => {
+            if doc.pointer(path).is_none() {
+                return Err(format!("no value at '{}'", path));
+            }
+            doc.pointer_set(path, value()?)
+                .map(|_| ())
+                .map_err(pointer_msg)
+        }
+        "move" => {
+            let from = from()?;
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(format!("cannot move '{}' into itself", from));
+            }
+            let moved = doc.pointer_remove(from).map_err(pointer_msg)?;
+            doc.pointer_add(path, moved).map_err(pointer_msg)
+        }
+        "copy" => {
+            let from = from()?;
+            let copied = doc
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| format!("no value at '{}'", from))?;
+            doc.pointer_add(path, copied).map_err(pointer_msg)
+        }
+        "test" => {
+            let actual = doc
+                .pointer(path)
+                .ok_or_else(|| format!("no value at '{}'", path))?;
+            if approx_eq(actual, &value()?, 0.0) {
+                Ok(())
+            } else {
+                Err("test failed, the value differs".to_string())
+            }
+        }
+        other => Err(format!("unknown operation '{}'", other)),
+    }
+}
+
+fn pointer_msg(err: PointerError) -> String {
+    err.0.trim_start_matches("Pointer error: ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    fn patched(doc: &str, patch: &str) -> Result<String, String> {
+        apply_patch(&json(doc), &json(patch))
+            .map(|value| crate::serialize::to_string(&value).unwrap())
+            .map_err(|err| err.0)
+    }
+
+    #[test]
+    fn appends_runs_in_order() {
+        let patch = r#"[
+            {"op": "add", "path": "/a/-", "value": 1},
+            {"op": "add", "path": "/a/-", "value": [2]},
+            {"op": "add", "path": "/a/0", "value": 0},
+            {"op": "add", "path": "/a/-", "value": 3},
+            {"op": "add", "path": "/a/-", "value": 4},
+            {"op": "add", "path": "/b/-", "value": 5},
+            {"op": "add", "path": "/b/-", "value": 6}
+        ]"#;
+        assert_eq!(
+            patched(r#"{"a": [], "b": {}}"#, patch).as_deref(),
+            Ok(r#"{"a":[0,1,[2],3,4],"b":{"-":6}}"#)
+        );
+    }
+
+    #[test]
+    fn names_the_failed_operation_after_a_run() {
+        let patch = r#"[
+            {"op": "add", "path": "/a/-", "value": 1},
+            {"op": "add", "path": "/a/-", "value": 2},
+            {"op": "add", "path": "/a/-"}
+        ]"#;
+        assert_eq!(
+            patched(r#"{"a": []}"#, patch),
+            Err("Patch error: operation 2 (add '/a/-'): missing \"value\"".to_string())
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::value::edit_values;
+
+#[derive(Debug)]
+pub struct PointerError(pub String);
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PointerError {}
+
+/// A parsed JSON Pointer (RFC 6901), e.g. `/users/0/name`
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct JsonPointer {
+    tokens: Vec<String>,
+}
+
+impl JsonPointer {
+    /// Parse a pointer, decoding the `~1` and `~0` escapes
+    pub fn parse(pointer: &str) -> Result<Self, PointerError> {
+        if pointer.is_empty() {
+            return Ok(JsonPointer::default());
+        }
+
+        let Some(rest) = pointer.strip_prefix('/') else {
+            return Err(PointerError(format!(
+                "Pointer error: '{}' does not start with '/'",
+                pointer
+            )));
+        };
+
+        let tokens = rest
+            .split('/')
+            .map(|token| unescape(token).ok_or(token))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|token| {
+                PointerError(format!("Pointer error: invalid escape in '{}'", token))
+            })?;
+
+        Ok(JsonPointer { tokens })
+    }
+
+    /// Parse a dotted path such as `user.name`, `tags[0]` or `["a.b"].c`
+    /// Bracketed numbers become index tokens and quoted keys may contain any character.
+    /// Paths starting with `/` are read as JSON Pointers instead.
+    pub fn from_path(path: &str) -> Result<Self, PointerError> {
+        if path.starts_with('/') {
+            return JsonPointer::parse(path);
+        }
+
+        let err = |msg: &str| PointerError(format!("Pointer error: {} in path '{}'", msg, path));
+        let chars = path.chars().collect::<Vec<_>>();
+        let mut pointer = JsonPointer::default();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '.' if i == 0 || chars[i - 1] == ']' => i += 1,
+                '[' => {
+                    let close = chars[i..]
+                        .iter()
+                        .position(|&c| c == ']')
+                        .map(|p| p + i)
+                        .ok_or_else(|| err("unclosed '['"))?;
+                    let inner = chars[i + 1..close].iter().collect::<String>();
+                    if let Some(key) = inner
+                        .strip_prefix('"')
+                        .and_then(|inner| inner.strip_suffix('"'))
+                    {
+                        pointer.push(key);
+                    } else if parse_index(&inner).is_some() {
+                        pointer.push(inner);
+                    } else {
+                        return Err(err(&format!("invalid index '{}'", inner)));
+                    }
+                    i = close + 1;
+                    if i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                        return Err(err("expected '.' or '[' after ']'"));
+                    }
+                }
+                _ => {
+                    let end = chars[i..]
+                        .iter()
+                        .position(|&c| c == '.' || c == '[')
+                        .map_or(chars.len(), |p| p + i);
+                    if end == i {
+                        return Err(err("empty key"));
+                    }
+                    pointer.push(chars[i..end].iter().collect::<String>());
+                    i = end;
+                    if i < chars.len() && chars[i] == '.' {
+                        i += 1;
+                        if i == chars.len() {
+                            return Err(err("empty key"));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(pointer)
+    }
+
+    /// The unescaped reference tokens, outermost first
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Append a reference token, descending one level
+    pub fn push(&mut self, token: impl Into<String>) {
+        self.tokens.push(token.into());
+    }
+
+    /// A copy of the pointer extended by one reference token
+    pub fn join(&self, token: impl Into<String>) -> JsonPointer {
+        let mut pointer = self.clone();
+        pointer.push(token);
+        pointer
+    }
+
+    /// Remove the last reference token, ascending one level
+    pub fn pop(&mut self) -> Option<String> {
+        self.tokens.pop()
+    }
+
+    /// Resolve the pointer against `value`
+    pub fn get<'a>(&self, value: &'a JsonValue) -> Option<&'a JsonValue> {
+        self.tokens
+            .iter()
+            .try_fold(value, |value, token| child(value, token))
+    }
+
+    /// Resolve the pointer against `value`, allowing modification of the target
+    pub fn get_mut<'a>(&self, value: &'a mut JsonValue) -> Option<&'a mut JsonValue> {
+        self.tokens
+            .iter()
+            .try_fold(value, |value, token| child_mut(value, token))
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+impl JsonValue {
+    /// Look up a value by JSON Pointer, e.g. `value.pointer("/users/0/name")`
+    /// Returns `None` if the pointer is malformed or does not resolve.
+    pub fn pointer(&self, pointer: &str) -> Option<&JsonValue> {
+        JsonPointer::parse(pointer).ok()?.get(self)
+    }
+
+    /// Mutable counterpart of `pointer`
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut JsonValue> {
+        JsonPointer::parse(pointer).ok()?.get_mut(self)
+    }
+
+    /// Add a value following RFC 6902 `add` semantics
+    /// Object members are created or replaced, array elements are inserted at
+    /// the index (shifting the rest) and `-` appends. The parent must exist.
+    pub fn pointer_add(&mut self, pointer: &str, value: JsonValue) -> Result<(), PointerError> {
+        let pointer = JsonPointer::parse(pointer)?;
+        let Some((parent, last)) = split_parent(&pointer, self)? else {
+            *self = value;
+            return Ok(());
+        };
+
+        if let JsonValue::Arr(values) = parent {
+            let index = if last == "-" {
+                values.len()
+            } else {
+                array_index(last, values.len() + 1, &pointer)?
+            };
+            edit_values(values, |values| values.insert(index, value));
+            return Ok(());
+        }
+
+        set_member(parent, last, value, &pointer)?;
+        Ok(())
+    }
+
+    /// Set the value at `pointer`, returning the one it replaced
+    /// Existing targets are replaced, missing object members are created and
+    /// `-` appends to an array. The parent must exist.
+    pub fn pointer_set(
+        &mut self,
+        pointer: &str,
+        value: JsonValue,
+    ) -> Result<Option<JsonValue>, PointerError> {
+        let pointer = JsonPointer::parse(pointer)?;
+        let Some((parent, last)) = split_parent(&pointer, self)? else {
+            return Ok(Some(std::mem::replace(self, value)));
+        };
+
+        if let JsonValue::Arr(values) = parent {
+            if last == "-" {
+                edit_values(values, |values| values.push(value));
+                return Ok(None);
+            }
+            let index = array_index(last, values.len(), &pointer)?;
+            return Ok(Some(std::mem::replace(&mut values[index], value)));
+        }
+
+        set_member(parent, last, value, &pointer)
+    }
+
+    /// Append `values` to the array at `pointer` in a single edit
+    /// Arrays hold exactly their elements, so each `pointer_add` of `-` copies
+    /// the array; a run of appends made this way copies it once.
+    pub fn pointer_extend(
+        &mut self,
+        pointer: &str,
+        values: impl IntoIterator<Item = JsonValue>,
+    ) -> Result<(), PointerError> {
+        let parsed = JsonPointer::parse(pointer)?;
+        match parsed.get_mut(self) {
+            Some(JsonValue::Arr(array)) => {
+                edit_values(array, |array| array.extend(values));
+                Ok(())
+            }
+            Some(_) => Err(PointerError(format!(
+                "Pointer error: '{}' is not an array",
+                parsed
+            ))),
+            None => Err(PointerError(format!(
+                "Pointer error: no value at '{}'",
+                parsed
+            ))),
+        }
+    }
+
+    /// Remove the value at `pointer` and return it
+    pub fn pointer_remove(&mut self, pointer: &str) -> Result<JsonValue, PointerError> {
+        let pointer = JsonPointer::parse(pointer)?;
+        let Some((parent, last)) = split_parent(&pointer, self)? else {
+            return Err(PointerError(
+                "Pointer error: cannot remove the document root".to_string(),
+            ));
+        };
+
+        if let JsonValue::Arr(values) = parent {
+            let index = array_index(last, values.len(), &pointer)?;
+            return Ok(edit_values(values, |values| values.remove(index)));
+        }
+
+        let not_found = || PointerError(format!("Pointer error: no value at '{}'", pointer));
+        let members = object_members(parent).ok_or_else(not_found)?;
+        let index = members
+            .iter()
+            .position(|m| matches!(m, JsonValue::KeyedObject(key, _) if **key == *last))
+            .ok_or_else(not_found)?;
+        let removed = match edit_values(members, |members| members.remove(index)).into_member() {
+            Ok((_, value)) => value,
+            Err(other) => other,
+        };
+        if members.is_empty() {
+            *parent = JsonValue::Empty;
+        }
+        Ok(removed)
+    }
+}
+
+/// Resolve everything but the last token, returning the parent and that token
+/// `None` means the pointer addresses the document root.
+fn split_parent<'a, 'p>(
+    pointer: &'p JsonPointer,
+    value: &'a mut JsonValue,
+) -> Result<Option<(&'a mut JsonValue, &'p str)>, PointerError> {
+    let Some((last, parents)) = pointer.tokens.split_last() else {
+        return Ok(None);
+    };
+
+    let parent = parents
+        .iter()
+        .try_fold(value, |value, token| child_mut(value, token))
+        .ok_or_else(|| PointerError(format!("Pointer error: no parent for '{}'", pointer)))?;
+    Ok(Some((parent, last)))
+}
+
+/// Parse an index that must be below `bound`
+fn array_index(token: &str, bound: usize, pointer: &JsonPointer) -> Result<usize, PointerError> {
+    match parse_index(token) {
+        Some(index) if index < bound => Ok(index),
+        Some(_) => Err(PointerError(format!(
+            "Pointer error: index out of bounds in '{}'",
+            pointer
+        ))),
+        None => Err(PointerError(format!(
+            "Pointer error: '{}' is not an array index in '{}'",
+            token, pointer
+        ))),
+    }
+}
+
+/// Normalize any object representation into a member list that can be edited
+fn object_members(value: &mut JsonValue) -> Option<&mut Box<[JsonValue]>> {
+    match value {
+        JsonValue::Empty => *value = JsonValue::Object(Box::default()),
+        JsonValue::KeyedObject(_, _) => {
+            let member = std::mem::replace(value, JsonValue::Empty);
+            *value = JsonValue::Object(Box::new([member]));
+        }
+        _ => (),
+    }
+
+    match value {
+        JsonValue::Object(members) => Some(members),
+        _ => None,
+    }
+}
+
+/// Replace the member named `key` or append it, returning the previous value
+fn set_member(
+    parent: &mut JsonValue,
+    key: &str,
+    value: JsonValue,
+    pointer: &JsonPointer,
+) -> Result<Option<JsonValue>, PointerError> {
+    let Some(members) = object_members(parent) else {
+        return Err(PointerError(format!(
+            "Pointer error: parent of '{}' is not a container",
+            pointer
+        )));
+    };
+
+    for member in members.iter_mut() {
+        if let JsonValue::KeyedObject(k, v) = member {
+            if **k == *key {
+                return Ok(Some(std::mem::replace(v.as_mut(), value)));
+            }
+        }
+    }
+    let member = JsonValue::KeyedObject(key.into(), Box::new(value));
+    edit_values(members, |members| members.push(member));
+    Ok(None)
+}
+
+fn unescape(token: &str) -> Option<String> {
+    let mut out = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => out.push('~'),
+                Some('1') => out.push('/'),
+                _ => return None,
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+/// Parse an array index token: digits only, without leading zeros
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+fn child<'a>(value: &'a JsonValue, token: &str) -> Option<&'a JsonValue> {
+    match value {
+        JsonValue::Object(members) => members.iter().find_map(|m| match m {
+            JsonValue::KeyedObject(key, value) if **key == *token => Some(value.as_ref()),
+            _ => None,
+        }),
+        JsonValue::KeyedObject(key, value) if **key == *token => Some(value),
+        JsonValue::Arr(values) => values.get(parse_index(token)?),
+        _ => None,
+    }
+}
+
+fn child_mut<'a>(value: &'a mut JsonValue, token: &str) -> Option<&'a mut JsonValue> {
+    match value {
+        JsonValue::Object(members) => members.iter_mut().find_map(|m| match m {
+            JsonValue::KeyedObject(key, value) if **key == *token => Some(value.as_mut()),
+            _ => None,
+        }),
+        JsonValue::KeyedObject(key, value) if **key == *token => Some(value),
+        JsonValue::Arr(values) => values.get_mut(parse_index(token)?),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{JsonValue, Parser};
+    use crate::serialize::to_string;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    #[test]
+    fn extends_arrays_in_one_edit() {
+        let mut doc = json(r#"{"a": [1], "b": {}}"#);
+        doc.pointer_extend("/a", (2..5).map(JsonValue::Int))
+            .unwrap();
+        doc.pointer_add("/a/-", JsonValue::Int(5)).unwrap();
+        assert_eq!(to_string(&doc).unwrap(), r#"{"a":[1,2,3,4,5],"b":{}}"#);
+
+        let err = |pointer: &str| {
+            let mut doc = json(r#"{"a": [1], "b": {}}"#);
+            doc.pointer_extend(pointer, [JsonValue::Null])
+                .unwrap_err()
+                .0
+        };
+        assert_eq!(err("/b"), "Pointer error: '/b' is not an array");
+        assert_eq!(err("/c"), "Pointer error: no value at '/c'");
+    }
+}
@@ -0,0 +1,1068 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::compare::compare;
+use crate::parse::JsonValue;
+
+#[derive(Debug)]
+pub struct QueryError(pub String);
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A compiled jq-style filter
+/// e.g., `.items[] | select(.active) | .name`
+/// Supported: `.`, `..`, `.foo`, `."foo"`, `.[i]`, `.[a:b]`, `.[]`, `?`, `|`, `,`,
+/// `//`, `and`, `or`, comparisons, `+ - * / %`, literals, `[...]`, `{...}` and the
+/// functions `select(f)`, `map(f)`, `has(k)`, `length`, `keys`, `not`, `type`,
+/// `add`, `sort` and `empty`.
+#[derive(Clone, Debug)]
+pub struct Query {
+    filter: Filter,
+}
+
+impl Query {
+    /// Compile a filter expression
+    pub fn compile(source: &str) -> Result<Self, QueryError> {
+        let tokens = lex(source)?;
+        let mut parser = QueryParser { tokens, idx: 0 };
+        let filter = parser.parse_pipe()?;
+        if let Some(tok) = parser.peek() {
+            return Err(err(format!("unexpected {}", tok)));
+        }
+        Ok(Query { filter })
+    }
+
+    /// Run the filter against `input`, collecting every value it produces
+    pub fn eval(&self, input: &JsonValue) -> Result<Vec<JsonValue>, QueryError> {
+        eval(&self.filter, input)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Mod => "%",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ObjectKey {
+    Name(String),
+    Expr(Filter),
+}
+
+#[derive(Clone, Debug)]
+enum Filter {
+    Identity,
+    Recurse,
+    Field(Box<Filter>, String),
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Iterate(Box<Filter>),
+    Try(Box<Filter>),
+    Literal(JsonValue),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Binary(Box<Filter>, Op, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Alt(Box<Filter>, Box<Filter>),
+    Neg(Box<Filter>),
+    Array(Option<Box<Filter>>),
+    Object(Vec<(ObjectKey, Filter)>),
+    Call(String, Vec<Filter>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Dot,
+    DotDot,
+    Field(String),
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Punct(&'static str),
+}
+
+/// Tokens as written in the filter, quoted for error messages
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Dot => write!(f, "'.'"),
+            Tok::DotDot => write!(f, "'..'"),
+            Tok::Field(name) => write!(f, "'.{}'", name),
+            Tok::Ident(name) => write!(f, "'{}'", name),
+            Tok::Str(s) => write!(f, "string {:?}", s),
+            Tok::Num(n) => write!(f, "number {}", n),
+            Tok::Punct(p) => write!(f, "'{}'", p),
+        }
+    }
+}
+
+fn err(msg: impl Into<String>) -> QueryError {
+    QueryError(format!("Query error: {}", msg.into()))
+}
+
+const PUNCTS: [&str; 23] = [
+    "==", "!=", "<=", ">=", "//", "|", ",", "(", ")", "[", "]", "{", "}", ":", ";", "?", "+", "-",
+    "*", "/", "%", "<", ">",
+];
+
+fn lex(source: &str) -> Result<Vec<Tok>, QueryError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    let ident_start = |c: char| c.is_ascii_alphabetic() || c == '_';
+    let ident_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '.' {
+            if chars.get(i + 1) == Some(&'.') {
+                tokens.push(Tok::DotDot);
+                i += 2;
+            } else if chars.get(i + 1).is_some_and(|c| ident_start(*c)) {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && ident_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(Tok::Field(chars[start..i].iter().collect()));
+            } else {
+                tokens.push(Tok::Dot);
+                i += 1;
+            }
+        } else if ident_start(c) {
+            let start = i;
+            while i < chars.len() && ident_char(chars[i]) {
+                i += 1;
+            }
+            tokens.push(Tok::Ident(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text = chars[start..i].iter().collect::<String>();
+            let num = text
+                .parse()
+                .map_err(|_| err(format!("invalid number '{}'", text)))?;
+            tokens.push(Tok::Num(num));
+        } else if c == '"' {
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(err("unterminated string")),
+                    Some('"') => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => s.push('\n'),
+                            Some('t') => s.push('\t'),
+                            Some('r') => s.push('\r'),
+                            Some(c @ ('"' | '\\' | '/')) => s.push(*c),
+                            _ => return Err(err("invalid escape in string")),
+                        }
+                    }
+                    Some(c) => s.push(*c),
+                }
+                i += 1;
+            }
+            tokens.push(Tok::Str(s));
+            i += 1;
+        } else {
+            let rest = chars[i..].iter().take(2).collect::<String>();
+            let punct = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| err(format!("unexpected character '{}'", c)))?;
+            tokens.push(Tok::Punct(punct));
+            i += punct.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct QueryParser {
+    tokens: Vec<Tok>,
+    idx: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.idx)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let tok = self.tokens.get(self.idx).cloned();
+        self.idx += 1;
+        tok
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Punct(p)) if *p == punct) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), QueryError> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(err(format!("expected '{}'", punct)))
+        }
+    }
+
+    fn eat_ident(&mut self, name: &str) -> bool {
+        if matches!(self.peek(), Some(Tok::Ident(i)) if i == name) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_pipe(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_comma()?;
+        while self.eat("|") {
+            let rhs = self.parse_comma()?;
+            lhs = Filter::Pipe(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comma(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_alt()?;
+        while self.eat(",") {
+            let rhs = self.parse_alt()?;
+            lhs = Filter::Comma(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_alt(&mut self) -> Result<Filter, QueryError> {
+        let lhs = self.parse_or()?;
+        if self.eat("//") {
+            // Right associative, like jq
+            let rhs = self.parse_alt()?;
+            return Ok(Filter::Alt(Box::new(lhs), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_and()?;
+        while self.eat_ident("or") {
+            let rhs = self.parse_and()?;
+            lhs = Filter::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_comparison()?;
+        while self.eat_ident("and") {
+            let rhs = self.parse_comparison()?;
+            lhs = Filter::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Filter, QueryError> {
+        let lhs = self.parse_sum()?;
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        for (punct, op) in ops {
+            if self.eat(punct) {
+                let rhs = self.parse_sum()?;
+                return Ok(Filter::Binary(Box::new(lhs), op, Box::new(rhs)));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn parse_sum(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_product()?;
+        loop {
+            let op = if self.eat("+") {
+                Op::Add
+            } else if self.eat("-") {
+                Op::Sub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.parse_product()?;
+            lhs = Filter::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Filter, QueryError> {
+        let mut lhs = self.parse_postfix()?;
+        loop {
+            let op = if self.eat("*") {
+                Op::Mul
+            } else if self.eat("/") {
+                Op::Div
+            } else if self.eat("%") {
+                Op::Mod
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.parse_postfix()?;
+            lhs = Filter::Binary(Box::new(lhs), op, Box::new(rhs));
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Filter, QueryError> {
+        let mut term = self.parse_primary()?;
+        loop {
+            term = match self.peek() {
+                Some(Tok::Field(name)) => {
+                    let name = name.clone();
+                    self.idx += 1;
+                    Filter::Field(Box::new(term), name)
+                }
+                Some(Tok::Dot) => {
+                    self.idx += 1;
+                    match self.next() {
+                        Some(Tok::Str(name)) => Filter::Field(Box::new(term), name),
+                        Some(Tok::Punct("[")) => self.parse_brackets(term)?,
+                        _ => return Err(err("expected a field name after '.'")),
+                    }
+                }
+                Some(Tok::Punct("[")) => {
+                    self.idx += 1;
+                    self.parse_brackets(term)?
+                }
+                Some(Tok::Punct("?")) => {
+                    self.idx += 1;
+                    Filter::Try(Box::new(term))
+                }
+                _ => return Ok(term),
+            };
+        }
+    }
+
+    /// Parse the inside of `[...]` following a term, the `[` is already consumed
+    fn parse_brackets(&mut self, term: Filter) -> Result<Filter, QueryError> {
+        let term = Box::new(term);
+        if self.eat("]") {
+            return Ok(Filter::Iterate(term));
+        }
+
+        if self.eat(":") {
+            let end = self.parse_pipe()?;
+            self.expect("]")?;
+            return Ok(Filter::Slice(term, None, Some(Box::new(end))));
+        }
+
+        let index = self.parse_pipe()?;
+        if self.eat(":") {
+            let end = if self.eat("]") {
+                return Ok(Filter::Slice(term, Some(Box::new(index)), None));
+            } else {
+                self.parse_pipe()?
+            };
+            self.expect("]")?;
+            return Ok(Filter::Slice(
+                term,
+                Some(Box::new(index)),
+                Some(Box::new(end)),
+            ));
+        }
+
+        self.expect("]")?;
+        Ok(Filter::Index(term, Box::new(index)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Filter, QueryError> {
+        match self.next() {
+            Some(Tok::Dot) => match self.peek() {
+                Some(Tok::Str(name)) => {
+                    let name = name.clone();
+                    self.idx += 1;
+                    Ok(Filter::Field(Box::new(Filter::Identity), name))
+                }
+                Some(Tok::Punct("[")) => {
+                    self.idx += 1;
+                    self.parse_brackets(Filter::Identity)
+                }
+                _ => Ok(Filter::Identity),
+            },
+            Some(Tok::DotDot) => Ok(Filter::Recurse),
+            Some(Tok::Field(name)) => Ok(Filter::Field(Box::new(Filter::Identity), name)),
+            Some(Tok::Num(n)) => Ok(Filter::Literal(number(n))),
+            Some(Tok::Str(s)) => Ok(Filter::Literal(JsonValue::Str(s.into()))),
+            Some(Tok::Ident(name)) => match name.as_str() {
+                "true" => Ok(Filter::Literal(JsonValue::Bool(true))),
+                "false" => Ok(Filter::Literal(JsonValue::Bool(false))),
+                "null" => Ok(Filter::Literal(JsonValue::Null)),
+                _ => self.parse_call(name),
+            },
+            Some(Tok::Punct("(")) => {
+                let inner = self.parse_pipe()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Tok::Punct("[")) => {
+                if self.eat("]") {
+                    return Ok(Filter::Array(None));
+                }
+                let inner = self.parse_pipe()?;
+                self.expect("]")?;
+                Ok(Filter::Array(Some(Box::new(inner))))
+            }
+            Some(Tok::Punct("{")) => self.parse_object(),
+            Some(Tok::Punct("-")) => Ok(Filter::Neg(Box::new(self.parse_postfix()?))),
+            Some(tok) => Err(err(format!("unexpected {}", tok))),
+            None => Err(err("unexpected end of filter")),
+        }
+    }
+
+    fn parse_call(&mut self, name: String) -> Result<Filter, QueryError> {
+        let mut args = vec![];
+        if self.eat("(") {
+            args.push(self.parse_pipe()?);
+            while self.eat(";") {
+                args.push(self.parse_pipe()?);
+            }
+            self.expect(")")?;
+        }
+
+        let arity = match name.as_str() {
+            "length" | "keys" | "not" | "type" | "add" | "sort" | "empty" => 0,
+            "select" | "map" | "has" => 1,
+            _ => return Err(err(format!("unknown function '{}'", name))),
+        };
+        if args.len() != arity {
+            return Err(err(format!("{} takes {} argument(s)", name, arity)));
+        }
+        Ok(Filter::Call(name, args))
+    }
+
+    /// Parse an object construction, the `{` is already consumed
+    /// e.g., `{name, "id": .user.id, (.k): .v}`
+    fn parse_object(&mut self) -> Result<Filter, QueryError> {
+        let mut entries = vec![];
+        if self.eat("}") {
+            return Ok(Filter::Object(entries));
+        }
+
+        loop {
+            let key = match self.next() {
+                Some(Tok::Ident(name)) | Some(Tok::Str(name)) => ObjectKey::Name(name),
+                Some(Tok::Punct("(")) => {
+                    let key = self.parse_pipe()?;
+                    self.expect(")")?;
+                    ObjectKey::Expr(key)
+                }
+                _ => return Err(err("expected an object key")),
+            };
+
+            let value = if self.eat(":") {
+                self.parse_alt()?
+            } else {
+                match &key {
+                    ObjectKey::Name(name) => {
+                        Filter::Field(Box::new(Filter::Identity), name.clone())
+                    }
+                    ObjectKey::Expr(_) => return Err(err("expected ':' after object key")),
+                }
+            };
+            entries.push((key, value));
+
+            if self.eat("}") {
+                return Ok(Filter::Object(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+/// Integral results stay integers, like they were in the input
+fn number(n: f64) -> JsonValue {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        JsonValue::Int(n as i64)
+    } else {
+        JsonValue::Float(n, None)
+    }
+}
+
+fn truthy(value: &JsonValue) -> bool {
+    !matches!(value, JsonValue::Null | JsonValue::Bool(false))
+}
+
+fn eval(filter: &Filter, input: &JsonValue) -> Result<Vec<JsonValue>, QueryError> {
+    match filter {
+        Filter::Identity => Ok(vec![input.clone()]),
+        Filter::Recurse => {
+            let mut out = vec![];
+            recurse(input, &mut out);
+            Ok(out)
+        }
+        Filter::Field(target, name) => eval(target, input)?
+            .iter()
+            .map(|value| field(value, name))
+            .collect(),
+        Filter::Index(target, index) => {
+            let mut out = vec![];
+            for value in eval(target, input)? {
+                for index in eval(index, input)? {
+                    out.push(index_value(&value, &index)?);
+                }
+            }
+            Ok(out)
+        }
+        Filter::Slice(target, start, end) => {
+            let bound = |f: &Option<Box<Filter>>| -> Result<Option<f64>, QueryError> {
+                match f {
+                    None => Ok(None),
+                    Some(f) => match eval(f, input)?.first() {
+                        Some(JsonValue::Null) | None => Ok(None),
+                        Some(v) => v
+                            .as_f64()
+                            .map(Some)
+                            .ok_or_else(|| err("slice bounds must be numbers")),
+                    },
+                }
+            };
+            let (start, end) = (bound(start)?, bound(end)?);
+            eval(target, input)?
+                .iter()
+                .map(|value| slice(value, start, end))
+                .collect()
+        }
+        Filter::Iterate(target) => {
+            let mut out = vec![];
+            for value in eval(target, input)? {
+                match value.into_array() {
+                    Ok(values) => out.extend(values),
+                    Err(value) if value.is_object() => {
+                        out.extend(value.into_members().into_iter().map(|(_, v)| v))
+                    }
+                    Err(value) => {
+                        return Err(err(format!("cannot iterate over {}", value.type_name())))
+                    }
+                }
+            }
+            Ok(out)
+        }
+        Filter::Try(inner) => Ok(eval(inner, input).unwrap_or_default()),
+        Filter::Literal(value) => Ok(vec![value.clone()]),
+        Filter::Pipe(lhs, rhs) => {
+            let mut out = vec![];
+            for value in eval(lhs, input)? {
+                out.extend(eval(rhs, &value)?);
+            }
+            Ok(out)
+        }
+        Filter::Comma(lhs, rhs) => {
+            let mut out = eval(lhs, input)?;
+            out.extend(eval(rhs, input)?);
+            Ok(out)
+        }
+        Filter::Binary(lhs, op, rhs) => {
+            let mut out = vec![];
+            for r in eval(rhs, input)? {
+                for l in eval(lhs, input)? {
+                    out.push(binary(&l, *op, &r)?);
+                }
+            }
+            Ok(out)
+        }
+        Filter::And(lhs, rhs) | Filter::Or(lhs, rhs) => {
+            let is_and = matches!(filter, Filter::And(_, _));
+            let mut out = vec![];
+            for l in eval(lhs, input)? {
+                if truthy(&l) != is_and {
+                    // Short-circuit: `false and _` / `true or _`
+                    out.push(JsonValue::Bool(!is_and));
+                    continue;
+                }
+                for r in eval(rhs, input)? {
+                    out.push(JsonValue::Bool(truthy(&r)));
+                }
+            }
+            Ok(out)
+        }
+        Filter::Alt(lhs, rhs) => {
+            let values = eval(lhs, input)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(truthy)
+                .collect::<Vec<_>>();
+            if values.is_empty() {
+                eval(rhs, input)
+            } else {
+                Ok(values)
+            }
+        }
+        Filter::Neg(inner) => eval(inner, input)?
+            .iter()
+            .map(|value| match value {
+                JsonValue::Int(i) => Ok(JsonValue::Int(-i)),
+                JsonValue::Float(f, _) => Ok(JsonValue::Float(-f, None)),
+                value => Err(err(format!("cannot negate {}", value.type_name()))),
+            })
+            .collect(),
+        Filter::Array(inner) => {
+            let values = match inner {
+                Some(inner) => eval(inner, input)?,
+                None => vec![],
+            };
+            Ok(vec![JsonValue::Arr(values.into())])
+        }
+        Filter::Object(entries) => {
+            // Every combination of key and value outputs produces an object
+            let mut objects: Vec<Vec<(String, JsonValue)>> = vec![vec![]];
+            for (key, value) in entries {
+                let keys = match key {
+                    ObjectKey::Name(name) => vec![name.clone()],
+                    ObjectKey::Expr(key) => eval(key, input)?
+                        .into_iter()
+                        .map(|key| match key.into_str() {
+                            Ok(s) => Ok(s.into_string()),
+                            Err(key) => Err(err(format!(
+                                "object keys must be strings, got {}",
+                                key.type_name()
+                            ))),
+                        })
+                        .collect::<Result<_, _>>()?,
+                };
+                let values = eval(value, input)?;
+
+                let mut next = vec![];
+                for object in &objects {
+                    for key in &keys {
+                        for value in &values {
+                            let mut object = object.clone();
+                            object.push((key.clone(), value.clone()));
+                            next.push(object);
+                        }
+                    }
+                }
+                objects = next;
+            }
+            Ok(objects.into_iter().map(JsonValue::from_members).collect())
+        }
+        Filter::Call(name, args) => call(name, args, input),
+    }
+}
+
+fn recurse(value: &JsonValue, out: &mut Vec<JsonValue>) {
+    out.push(value.clone());
+    match value {
+        JsonValue::Arr(values) => values.iter().for_each(|v| recurse(v, out)),
+        value if value.is_object() => value.members().for_each(|(_, v)| recurse(v, out)),
+        _ => (),
+    }
+}
+
+fn field(value: &JsonValue, name: &str) -> Result<JsonValue, QueryError> {
+    match value {
+        JsonValue::Null => Ok(JsonValue::Null),
+        value if value.is_object() => Ok(value.get(name).cloned().unwrap_or(JsonValue::Null)),
+        value => Err(err(format!(
+            "cannot index {} with \"{}\"",
+            value.type_name(),
+            name
+        ))),
+    }
+}
+
+fn index_value(value: &JsonValue, index: &JsonValue) -> Result<JsonValue, QueryError> {
+    match (value, index) {
+        (JsonValue::Null, _) => Ok(JsonValue::Null),
+        (value, JsonValue::Str(name)) => field(value, name),
+        (JsonValue::Arr(values), index) if index.as_f64().is_some() => {
+            let i = index.as_f64().unwrap_or_default().floor() as i64;
+            let i = if i < 0 { i + values.len() as i64 } else { i };
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| values.get(i))
+                .cloned()
+                .unwrap_or(JsonValue::Null))
+        }
+        (value, index) => Err(err(format!(
+            "cannot index {} with {}",
+            value.type_name(),
+            index.type_name()
+        ))),
+    }
+}
+
+fn slice(value: &JsonValue, start: Option<f64>, end: Option<f64>) -> Result<JsonValue, QueryError> {
+    let range = |len: usize| {
+        let clamp = |i: f64| {
+            let i = if i < 0.0 { i + len as f64 } else { i };
+            i.clamp(0.0, len as f64) as usize
+        };
+        let start = start.map_or(0, clamp);
+        let end = end.map_or(len, clamp);
+        start..end.max(start)
+    };
+
+    match value {
+        JsonValue::Null => Ok(JsonValue::Null),
+        JsonValue::Arr(values) => Ok(JsonValue::Arr(values[range(values.len())].into())),
+        JsonValue::Str(s) => {
+            let chars = s.chars().collect::<Vec<_>>();
+            Ok(JsonValue::Str(chars[range(chars.len())].iter().collect()))
+        }
+        value => Err(err(format!("cannot slice {}", value.type_name()))),
+    }
+}
+
+fn binary(l: &JsonValue, op: Op, r: &JsonValue) -> Result<JsonValue, QueryError> {
+    let cmp = || compare(l, r);
+    match op {
+        Op::Eq => return Ok(JsonValue::Bool(cmp() == Ordering::Equal)),
+        Op::Ne => return Ok(JsonValue::Bool(cmp() != Ordering::Equal)),
+        Op::Lt => return Ok(JsonValue::Bool(cmp() == Ordering::Less)),
+        Op::Le => return Ok(JsonValue::Bool(cmp() != Ordering::Greater)),
+        Op::Gt => return Ok(JsonValue::Bool(cmp() == Ordering::Greater)),
+        Op::Ge => return Ok(JsonValue::Bool(cmp() != Ordering::Less)),
+        _ => (),
+    }
+
+    match (l, op, r) {
+        (JsonValue::Null, Op::Add, other) | (other, Op::Add, JsonValue::Null) => Ok(other.clone()),
+        (JsonValue::Int(a), _, JsonValue::Int(b)) => {
+            let exact = match op {
+                Op::Add => a.checked_add(*b),
+                Op::Sub => a.checked_sub(*b),
+                Op::Mul => a.checked_mul(*b),
+                Op::Div if *b != 0 && a % b == 0 => a.checked_div(*b),
+                Op::Mod if *b != 0 => a.checked_rem(*b),
+                _ => None,
+            };
+            match exact {
+                Some(i) => Ok(JsonValue::Int(i)),
+                None => float_op(*a as f64, op, *b as f64),
+            }
+        }
+        (l, _, r) if l.as_f64().is_some() && r.as_f64().is_some() => float_op(
+            l.as_f64().unwrap_or_default(),
+            op,
+            r.as_f64().unwrap_or_default(),
+        ),
+        (JsonValue::Str(a), Op::Add, JsonValue::Str(b)) => {
+            Ok(JsonValue::Str(format!("{}{}", a, b).into()))
+        }
+        (JsonValue::Str(a), Op::Div, JsonValue::Str(b)) => Ok(JsonValue::Arr(
+            a.split(&**b)
+                .map(|part| JsonValue::Str(part.into()))
+                .collect(),
+        )),
+        (JsonValue::Arr(a), Op::Add, JsonValue::Arr(b)) => {
+            Ok(JsonValue::Arr(a.iter().chain(b).cloned().collect()))
+        }
+        (JsonValue::Arr(a), Op::Sub, JsonValue::Arr(b)) => Ok(JsonValue::Arr(
+            a.iter()
+                .filter(|x| !b.iter().any(|y| compare(x, y) == Ordering::Equal))
+                .cloned()
+                .collect(),
+        )),
+        (l, Op::Add, r) if l.is_object() && r.is_object() => {
+            // Keys of the right-hand object win
+            let mut members = l
+                .members()
+                .filter(|(k, _)| r.get(k).is_none())
+                .map(|(k, v)| (k, v.clone()))
+                .collect::<Vec<_>>();
+            members.extend(r.members().map(|(k, v)| (k, v.clone())));
+            Ok(JsonValue::from_members(members))
+        }
+        (l, op, r) => Err(err(format!(
+            "cannot apply '{}' to {} and {}",
+            op,
+            l.type_name(),
+            r.type_name()
+        ))),
+    }
+}
+
+fn float_op(a: f64, op: Op, b: f64) -> Result<JsonValue, QueryError> {
+    let result = match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div if b == 0.0 => return Err(err("division by zero")),
+        Op::Div => a / b,
+        // Like jq, modulo works on the integer parts
+        Op::Mod => match (a.trunc() as i64, b.trunc() as i64) {
+            (_, 0) => return Err(err("division by zero")),
+            (a, b) => return Ok(JsonValue::Int(a.wrapping_rem(b))),
+        },
+        _ => unreachable!(),
+    };
+    Ok(JsonValue::Float(result, None))
+}
+
+fn call(name: &str, args: &[Filter], input: &JsonValue) -> Result<Vec<JsonValue>, QueryError> {
+    let value = match name {
+        "empty" => return Ok(vec![]),
+        "select" => {
+            let mut out = vec![];
+            for cond in eval(&args[0], input)? {
+                if truthy(&cond) {
+                    out.push(input.clone());
+                }
+            }
+            return Ok(out);
+        }
+        "map" => {
+            let mut out = vec![];
+            for value in eval(&Filter::Iterate(Box::new(Filter::Identity)), input)? {
+                out.extend(eval(&args[0], &value)?);
+            }
+            JsonValue::Arr(out.into())
+        }
+        "has" => {
+            let mut out = vec![];
+            for key in eval(&args[0], input)? {
+                let has = match (input, &key) {
+                    (JsonValue::Arr(values), key) if key.as_f64().is_some() => {
+                        let i = key.as_f64().unwrap_or_default();
+                        i >= 0.0 && (i as usize) < values.len()
+                    }
+                    (input, JsonValue::Str(key)) if input.is_object() => input.get(key).is_some(),
+                    (input, key) => {
+                        return Err(err(format!(
+                            "cannot check whether {} has a {} key",
+                            input.type_name(),
+                            key.type_name()
+                        )))
+                    }
+                };
+                out.push(JsonValue::Bool(has));
+            }
+            return Ok(out);
+        }
+        "length" => match input {
+            JsonValue::Null => JsonValue::Int(0),
+            JsonValue::Int(i) => JsonValue::Int(i.abs()),
+            JsonValue::Float(f, _) => JsonValue::Float(f.abs(), None),
+            JsonValue::Str(s) => JsonValue::Int(s.chars().count() as i64),
+            JsonValue::Arr(values) => JsonValue::Int(values.len() as i64),
+            JsonValue::Bool(_) => return Err(err("boolean has no length")),
+            object => JsonValue::Int(object.members().count() as i64),
+        },
+        "keys" => match input {
+            JsonValue::Arr(values) => {
+                JsonValue::Arr((0..values.len() as i64).map(JsonValue::Int).collect())
+            }
+            object if object.is_object() => {
+                let mut keys = object
+                    .members()
+                    .map(|(k, _)| Box::from(k))
+                    .collect::<Vec<_>>();
+                keys.sort();
+                JsonValue::Arr(keys.into_iter().map(JsonValue::Str).collect())
+            }
+            value => return Err(err(format!("{} has no keys", value.type_name()))),
+        },
+        "not" => JsonValue::Bool(!truthy(input)),
+        "type" => JsonValue::Str(input.type_name().into()),
+        "add" => match input {
+            JsonValue::Arr(values) => values
+                .iter()
+                .try_fold(JsonValue::Null, |acc, v| binary(&acc, Op::Add, v))?,
+            value => return Err(err(format!("cannot add up {}", value.type_name()))),
+        },
+        "sort" => match input {
+            JsonValue::Arr(values) => {
+                let mut values = values.clone();
+                values.sort_by(compare);
+                JsonValue::Arr(values)
+            }
+            value => return Err(err(format!("cannot sort {}", value.type_name()))),
+        },
+        _ => return Err(err(format!("unknown function '{}'", name))),
+    };
+    Ok(vec![value])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::serialize::to_string;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    /// The results of `filter` on `input`, compact and joined by spaces
+    fn run(filter: &str, input: &str) -> String {
+        let results = Query::compile(filter)
+            .and_then(|query| query.eval(&json(input)))
+            .unwrap_or_else(|err| panic!("{}: {}", filter, err));
+        results
+            .iter()
+            .map(|value| to_string(value).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn error(filter: &str, input: &str) -> String {
+        match Query::compile(filter).and_then(|query| query.eval(&json(input))) {
+            Ok(results) => panic!("{} gave {} results", filter, results.len()),
+            Err(err) => err.0,
+        }
+    }
+
+    const DOC: &str = r#"{"a":{"b":[1,2,3]},"x y":"s","users":[
+        {"name":"Ada","age":36,"active":true},
+        {"name":"Bob","age":17,"active":false}
+    ]}"#;
+
+    #[test]
+    fn follows_paths() {
+        assert_eq!(run(".", "[1]"), "[1]");
+        assert_eq!(run(".a.b", DOC), "[1,2,3]");
+        assert_eq!(run(r#"."x y""#, DOC), r#""s""#);
+        assert_eq!(run(r#".["x y"]"#, DOC), r#""s""#);
+        assert_eq!(run(".a.b[0], .a.b[-1], .a.b[5]", DOC), "1 3 null");
+        assert_eq!(run(".a.b[1:], .a.b[:-1]", DOC), "[2,3] [1,2]");
+        assert_eq!(run(".a.b[]", DOC), "1 2 3");
+        assert_eq!(run(".missing.deeper", DOC), "null");
+        assert_eq!(run("..", r#"{"a":[1]}"#), r#"{"a":[1]} [1] 1"#);
+        assert_eq!(run(".a[]?", "[1]"), "");
+        assert_eq!(run(r#""abc"[1:]"#, "null"), r#""bc""#);
+    }
+
+    #[test]
+    fn pipes_and_filters() {
+        assert_eq!(run(".users[] | .name", DOC), r#""Ada" "Bob""#);
+        assert_eq!(run(".users[] | select(.active) | .name", DOC), r#""Ada""#);
+        assert_eq!(run(".users | map(.age >= 18)", DOC), "[true,false]");
+        assert_eq!(
+            run(".users[] | {name, adult: .age > 17}", DOC),
+            r#"{"name":"Ada","adult":true} {"name":"Bob","adult":false}"#
+        );
+        assert_eq!(
+            run(r#"{(.users[].name): 1}"#, DOC),
+            r#"{"Ada":1} {"Bob":1}"#
+        );
+        assert_eq!(run("[.a.b[] | select(. != 2)]", DOC), "[1,3]");
+        assert_eq!(run(".missing // .a.b[0]", DOC), "1");
+        assert_eq!(
+            run("true and (false or 1), (null | not)", "null"),
+            "true true"
+        );
+        assert_eq!(run(".a | has(\"b\"), keys, length", DOC), r#"true ["b"] 1"#);
+        assert_eq!(
+            run(".a.b | add, sort, (.[0] | type)", DOC),
+            r#"6 [1,2,3] "number""#
+        );
+        assert_eq!(run("empty, 1", "null"), "1");
+    }
+
+    #[test]
+    fn does_arithmetic() {
+        assert_eq!(
+            run("1 + 2 * 3, (1 + 2) * 3, 7 % 3, 6 / 3", "null"),
+            "7 9 1 2"
+        );
+        assert_eq!(run("7 / 2, 0.5 + 1, -(.a.b[0])", DOC), "3.5 1.5 -1");
+        // Past `i64` the results are floats
+        assert_eq!(
+            run("9223372036854775807 + 1", "null"),
+            "9.223372036854776e18"
+        );
+        assert_eq!(
+            run(r#""a" + "b", "a,b" / ",""#, "null"),
+            r#""ab" ["a","b"]"#
+        );
+        assert_eq!(
+            run("[1,2,3] - [2], [1] + [2], null + 1", "null"),
+            "[1,3] [1,2] 1"
+        );
+        assert_eq!(
+            run(r#"{"a":1,"b":2} + {"b":3}"#, "null"),
+            r#"{"a":1,"b":3}"#
+        );
+    }
+
+    #[test]
+    fn reports_errors_in_words() {
+        assert_eq!(
+            error(".a * 2", r#"{"a":"s"}"#),
+            "Query error: cannot apply '*' to string and number"
+        );
+        assert_eq!(
+            error("{} - 1", "null"),
+            "Query error: cannot apply '-' to object and number"
+        );
+        assert_eq!(error("1 / 0", "null"), "Query error: division by zero");
+        assert_eq!(error(".[]", "1"), "Query error: cannot iterate over number");
+        assert_eq!(
+            error(".a", "[1]"),
+            "Query error: cannot index array with \"a\""
+        );
+        assert_eq!(
+            error("nope", "null"),
+            "Query error: unknown function 'nope'"
+        );
+        assert_eq!(
+            error("select", "null"),
+            "Query error: select takes 1 argument(s)"
+        );
+        assert_eq!(error(".a )", "null"), "Query error: unexpected ')'");
+        assert_eq!(error("1 2", "null"), "Query error: unexpected number 2");
+        assert_eq!(error("(1", "null"), "Query error: expected ')'");
+        assert_eq!(error("\"a", "null"), "Query error: unterminated string");
+        assert_eq!(
+            error(".a | ", "null"),
+            "Query error: unexpected end of filter"
+        );
+    }
+}
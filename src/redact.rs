@@ -0,0 +1,110 @@
+use crate::parse::JsonValue;
+use crate::pointer::{parse_index, JsonPointer, PointerError};
+use crate::value::edit_values;
+
+/// What happens to redacted values
+#[derive(Clone, Debug)]
+pub enum Redaction {
+    Replace(JsonValue),
+    Remove,
+}
+
+/// Replace every value matched by a pointer pattern with `"***"`, see `redact_with`
+pub fn redact(value: &mut JsonValue, patterns: &[&str]) -> Result<usize, PointerError> {
+    let mask = Redaction::Replace(JsonValue::Str("***".into()));
+    redact_with(value, patterns, &mask)
+}
+
+/// Redact the values matched by JSON Pointer patterns, e.g. `/users/*/password`
+/// A `*` token matches every member of an object or element of an array.
+/// Returns how many values were redacted.
+pub fn redact_with(
+    value: &mut JsonValue,
+    patterns: &[&str],
+    redaction: &Redaction,
+) -> Result<usize, PointerError> {
+    let mut count = 0;
+    for pattern in patterns {
+        let pointer = JsonPointer::parse(pattern)?;
+        match pointer.tokens() {
+            [] => {
+                return Err(PointerError(
+                    "Pointer error: cannot redact the document root".to_string(),
+                ))
+            }
+            tokens => count += redact_at(value, tokens, redaction),
+        }
+    }
+    Ok(count)
+}
+
+fn redact_at(value: &mut JsonValue, tokens: &[String], redaction: &Redaction) -> usize {
+    let (token, rest) = tokens.split_first().expect("tokens are not empty");
+    let matches = |key: &str| token == "*" || token == key;
+
+    if !rest.is_empty() {
+        return match value {
+            JsonValue::Arr(values) => values
+                .iter_mut()
+                .enumerate()
+                .filter(|(i, _)| token == "*" || parse_index(token) == Some(*i))
+                .map(|(_, v)| redact_at(v, rest, redaction))
+                .sum(),
+            JsonValue::Object(members) => members
+                .iter_mut()
+                .filter_map(|m| match m {
+                    JsonValue::KeyedObject(key, v) if matches(key) => Some(v),
+                    _ => None,
+                })
+                .map(|v| redact_at(v, rest, redaction))
+                .sum(),
+            JsonValue::KeyedObject(key, v) if matches(key) => redact_at(v, rest, redaction),
+            _ => 0,
+        };
+    }
+
+    match (value, redaction) {
+        (JsonValue::Arr(values), Redaction::Remove) => {
+            let before = values.len();
+            let mut i = 0;
+            edit_values(values, |values| {
+                values.retain(|_| {
+                    i += 1;
+                    !(token == "*" || parse_index(token) == Some(i - 1))
+                })
+            });
+            before - values.len()
+        }
+        (JsonValue::Arr(values), Redaction::Replace(mask)) => values
+            .iter_mut()
+            .enumerate()
+            .filter(|(i, _)| token == "*" || parse_index(token) == Some(*i))
+            .map(|(_, v)| *v = mask.clone())
+            .count(),
+        (value @ (JsonValue::Object(_) | JsonValue::KeyedObject(_, _)), Redaction::Remove) => {
+            let members = value
+                .members()
+                .filter(|(key, _)| !matches(key))
+                .map(|(key, v)| (key, v.clone()))
+                .collect::<Vec<_>>();
+            let removed = value.members().count() - members.len();
+            if removed > 0 {
+                *value = JsonValue::from_members(members);
+            }
+            removed
+        }
+        (JsonValue::Object(members), Redaction::Replace(mask)) => members
+            .iter_mut()
+            .filter_map(|m| match m {
+                JsonValue::KeyedObject(key, v) if matches(key) => Some(v),
+                _ => None,
+            })
+            .map(|v| **v = mask.clone())
+            .count(),
+        (JsonValue::KeyedObject(key, v), Redaction::Replace(mask)) if matches(key) => {
+            **v = mask.clone();
+            1
+        }
+        _ => 0,
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+
+#[derive(Debug)]
+pub struct RefError(pub String);
+
+impl fmt::Display for RefError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for RefError {}
+
+/// Inline every internal reference such as `{"$ref": "#/definitions/Foo"}`
+/// An object with a `$ref` is replaced by the referenced value as a whole, and
+/// references inside that value are resolved too. References to other
+/// documents are left as they are; unresolvable and cyclic references are errors.
+pub fn resolve_refs(value: &JsonValue) -> Result<JsonValue, RefError> {
+    resolve(value, value, &mut vec![])
+}
+
+fn resolve(
+    value: &JsonValue,
+    root: &JsonValue,
+    stack: &mut Vec<String>,
+) -> Result<JsonValue, RefError> {
+    match value {
+        JsonValue::Arr(values) => values
+            .iter()
+            .map(|v| resolve(v, root, stack))
+            .collect::<Result<_, _>>()
+            .map(JsonValue::Arr),
+        _ if value.is_object() => {
+            if let Some(JsonValue::Str(reference)) = value.get("$ref") {
+                if let Some(fragment) = reference.strip_prefix('#') {
+                    return resolve_ref(reference, fragment, root, stack);
+                }
+            }
+
+            let members = value
+                .members()
+                .map(|(key, v)| Ok((key, resolve(v, root, stack)?)))
+                .collect::<Result<_, _>>()?;
+            Ok(JsonValue::from_members(members))
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+fn resolve_ref(
+    reference: &str,
+    fragment: &str,
+    root: &JsonValue,
+    stack: &mut Vec<String>,
+) -> Result<JsonValue, RefError> {
+    if stack.iter().any(|r| r == reference) {
+        return Err(RefError(format!(
+            "Reference error: '{}' refers to itself through {}",
+            reference,
+            stack.join(" -> ")
+        )));
+    }
+
+    let fragment = percent_decode(fragment).ok_or_else(|| {
+        RefError(format!(
+            "Reference error: invalid escape in '{}'",
+            reference
+        ))
+    })?;
+    let target = JsonPointer::parse(&fragment)
+        .map_err(|_| RefError(format!("Reference error: '{}' is not a pointer", reference)))?
+        .get(root)
+        .ok_or_else(|| RefError(format!("Reference error: '{}' does not resolve", reference)))?;
+
+    stack.push(reference.to_string());
+    let resolved = resolve(target, root, stack);
+    stack.pop();
+    resolved
+}
+
+/// Decode the `%XX` escapes of a URI fragment
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
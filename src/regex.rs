@@ -0,0 +1,480 @@
+/// A small regular expression matcher for schema `pattern`s
+/// Supports literals, `.`, `[...]` classes, `\d \w \s` and their negations,
+/// `^ $`, groups with `|`, and the quantifiers `* + ? {n} {n,} {n,m}`.
+#[derive(Clone, Debug)]
+pub(crate) struct Regex {
+    program: Vec<Inst>,
+}
+
+type Seq = Vec<(Atom, usize, usize)>;
+
+#[derive(Clone, Debug)]
+enum Atom {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Group(Vec<Seq>),
+}
+
+/// An instruction of the compiled pattern, which runs on every position of the
+/// text at once rather than backtracking
+#[derive(Clone, Debug)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Jump(usize),
+    /// Continue at both targets
+    Split(usize, usize),
+    Match,
+}
+
+/// Counted repetitions are expanded, so `(a{1000}){1000}` is refused instead
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Regex {
+    pub(crate) fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = RegexParser {
+            chars: pattern.chars().collect(),
+            idx: 0,
+        };
+        let root = parser.parse_alt()?;
+        if parser.idx < parser.chars.len() {
+            return Err(format!("unmatched ')' in '{}'", pattern));
+        }
+        let mut compiler = Compiler { program: vec![] };
+        compiler.alt(&root)?;
+        compiler.emit(Inst::Match)?;
+        Ok(Regex {
+            program: compiler.program,
+        })
+    }
+
+    /// Whether the pattern matches anywhere in `text`
+    /// Runs in time linear in the text, without recursing on it.
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text = text.chars().collect::<Vec<_>>();
+        let mut threads = vec![];
+        let mut next = vec![];
+        let mut added = vec![usize::MAX; self.program.len()];
+        let mut stack = vec![];
+        for pos in 0..=text.len() {
+            // A match may start at any position
+            let program = &self.program;
+            add_thread(
+                program,
+                &mut threads,
+                &mut added,
+                &mut stack,
+                (0, pos, text.len()),
+            );
+            for &pc in &threads {
+                let c = text.get(pos).copied();
+                let consumed = match &program[pc] {
+                    Inst::Match => return true,
+                    Inst::Char(expected) => c == Some(*expected),
+                    Inst::Any => c.is_some_and(|c| c != '\n'),
+                    Inst::Class(items, negated) => {
+                        c.is_some_and(|c| items.iter().any(|item| item.matches(c)) != *negated)
+                    }
+                    _ => false,
+                };
+                if consumed {
+                    add_thread(
+                        program,
+                        &mut next,
+                        &mut added,
+                        &mut stack,
+                        (pc + 1, pos + 1, text.len()),
+                    );
+                }
+            }
+            std::mem::swap(&mut threads, &mut next);
+            next.clear();
+        }
+        false
+    }
+}
+
+struct RegexParser {
+    chars: Vec<char>,
+    idx: usize,
+}
+
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.idx).copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unexpected end of pattern")?;
+        self.idx += 1;
+        Ok(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alt(&mut self) -> Result<Vec<Seq>, String> {
+        let mut alt = vec![self.parse_seq()?];
+        while self.eat('|') {
+            alt.push(self.parse_seq()?);
+        }
+        Ok(alt)
+    }
+
+    fn parse_seq(&mut self) -> Result<Seq, String> {
+        let mut seq = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            let (min, max) = self.parse_quantifier()?;
+            if min > max {
+                return Err(format!("invalid repetition {{{},{}}}", min, max));
+            }
+            seq.push((atom, min, max));
+        }
+        Ok(seq)
+    }
+
+    fn parse_quantifier(&mut self) -> Result<(usize, usize), String> {
+        let bounds = match self.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => {
+                self.idx += 1;
+                let min = self.parse_count()?;
+                let max = if self.eat(',') {
+                    match self.peek() {
+                        Some('}') => usize::MAX,
+                        _ => self.parse_count()?,
+                    }
+                } else {
+                    min
+                };
+                if !self.eat('}') {
+                    return Err("expected '}' in repetition".to_string());
+                }
+                self.eat('?');
+                return Ok((min, max));
+            }
+            _ => return Ok((1, 1)),
+        };
+        self.idx += 1;
+        // Laziness does not change whether a pattern matches
+        self.eat('?');
+        Ok(bounds)
+    }
+
+    fn parse_count(&mut self) -> Result<usize, String> {
+        let start = self.idx;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.idx += 1;
+        }
+        let digits = self.chars[start..self.idx].iter().collect::<String>();
+        digits
+            .parse()
+            .map_err(|_| "expected a count in repetition".to_string())
+    }
+
+    fn parse_atom(&mut self) -> Result<Atom, String> {
+        match self.next()? {
+            '(' => {
+                if self.eat('?') && !self.eat(':') {
+                    return Err("only (?:...) groups are supported".to_string());
+                }
+                let alt = self.parse_alt()?;
+                if !self.eat(')') {
+                    return Err("unclosed '('".to_string());
+                }
+                Ok(Atom::Group(alt))
+            }
+            '[' => self.parse_class(),
+            '.' => Ok(Atom::Any),
+            '^' => Ok(Atom::Start),
+            '$' => Ok(Atom::End),
+            '\\' => match self.parse_escape()? {
+                ClassItem::Range(c, _) => Ok(Atom::Char(c)),
+                item => Ok(Atom::Class(vec![item], false)),
+            },
+            c @ ('*' | '+' | '?' | '{') => Err(format!("nothing to repeat before '{}'", c)),
+            c => Ok(Atom::Char(c)),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Atom, String> {
+        let negated = self.eat('^');
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Ok(']') if !first => break,
+                Ok(c) => c,
+                Err(_) => return Err("unclosed '['".to_string()),
+            };
+            first = false;
+
+            let item = if c == '\\' {
+                self.parse_escape()?
+            } else {
+                ClassItem::Range(c, c)
+            };
+            match item {
+                ClassItem::Range(lo, _)
+                    if self.peek() == Some('-') && self.chars.get(self.idx + 1) != Some(&']') =>
+                {
+                    self.idx += 1;
+                    let hi = match self.next()? {
+                        '\\' => match self.parse_escape()? {
+                            ClassItem::Range(hi, _) => hi,
+                            _ => return Err("invalid class range".to_string()),
+                        },
+                        hi => hi,
+                    };
+                    if hi < lo {
+                        return Err(format!("invalid class range {}-{}", lo, hi));
+                    }
+                    items.push(ClassItem::Range(lo, hi));
+                }
+                item => items.push(item),
+            }
+        }
+        Ok(Atom::Class(items, negated))
+    }
+
+    /// The character after a `\`, as a single-character range or a shorthand class
+    fn parse_escape(&mut self) -> Result<ClassItem, String> {
+        let c = match self.next()? {
+            'd' => return Ok(ClassItem::Digit(false)),
+            'D' => return Ok(ClassItem::Digit(true)),
+            'w' => return Ok(ClassItem::Word(false)),
+            'W' => return Ok(ClassItem::Word(true)),
+            's' => return Ok(ClassItem::Space(false)),
+            'S' => return Ok(ClassItem::Space(true)),
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            c if c.is_ascii_alphanumeric() => return Err(format!("unsupported escape '\\{}'", c)),
+            c => c,
+        };
+        Ok(ClassItem::Range(c, c))
+    }
+}
+
+/// Compiles the parsed pattern to a program for `Regex::is_match`
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err("pattern is too large once its repetitions are expanded".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    /// A split that tries the next instruction first, and its other branch once
+    /// patched
+    fn split(&mut self) -> Result<usize, String> {
+        let next = self.program.len() + 1;
+        self.emit(Inst::Split(next, 0))
+    }
+
+    /// Point the jump or split at `at` to `target`, as its other branch
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.program[at] {
+            Inst::Jump(to) | Inst::Split(_, to) => *to = target,
+            _ => unreachable!("only jumps and splits are patched"),
+        }
+    }
+
+    fn alt(&mut self, alt: &[Seq]) -> Result<(), String> {
+        let mut jumps = vec![];
+        for (i, seq) in alt.iter().enumerate() {
+            if i + 1 == alt.len() {
+                self.seq(seq)?;
+            } else {
+                let split = self.split()?;
+                self.seq(seq)?;
+                jumps.push(self.emit(Inst::Jump(0))?);
+                let next = self.program.len();
+                self.patch(split, next);
+            }
+        }
+        let end = self.program.len();
+        for jump in jumps {
+            self.patch(jump, end);
+        }
+        Ok(())
+    }
+
+    fn seq(&mut self, seq: &[(Atom, usize, usize)]) -> Result<(), String> {
+        for (atom, min, max) in seq {
+            for _ in 0..*min {
+                self.atom(atom)?;
+            }
+            if *max == usize::MAX {
+                let split = self.split()?;
+                self.atom(atom)?;
+                self.emit(Inst::Jump(split))?;
+                let end = self.program.len();
+                self.patch(split, end);
+            } else {
+                let mut splits = vec![];
+                for _ in *min..*max {
+                    let split = self.split()?;
+                    splits.push(split);
+                    self.atom(atom)?;
+                }
+                let end = self.program.len();
+                for split in splits {
+                    self.patch(split, end);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self, atom: &Atom) -> Result<(), String> {
+        let inst = match atom {
+            Atom::Char(c) => Inst::Char(*c),
+            Atom::Any => Inst::Any,
+            Atom::Class(items, negated) => Inst::Class(items.clone(), *negated),
+            Atom::Start => Inst::Start,
+            Atom::End => Inst::End,
+            Atom::Group(alt) => return self.alt(alt),
+        };
+        self.emit(inst).map(|_| ())
+    }
+}
+
+/// Follow the jumps, splits and assertions from `pc` at position `pos`, adding
+/// the instructions that consume a character or match to `threads`
+/// `added` holds the position each instruction was last added at, so every one
+/// is followed once per position, even in a loop that matches nothing.
+fn add_thread(
+    program: &[Inst],
+    threads: &mut Vec<usize>,
+    added: &mut [usize],
+    stack: &mut Vec<usize>,
+    (pc, pos, len): (usize, usize, usize),
+) {
+    stack.push(pc);
+    while let Some(pc) = stack.pop() {
+        if added[pc] == pos {
+            continue;
+        }
+        added[pc] = pos;
+        match program[pc] {
+            Inst::Jump(to) => stack.push(to),
+            Inst::Split(first, second) => {
+                stack.push(second);
+                stack.push(first);
+            }
+            Inst::Start if pos == 0 => stack.push(pc + 1),
+            Inst::End if pos == len => stack.push(pc + 1),
+            Inst::Start | Inst::End => {}
+            _ => threads.push(pc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn matches_anywhere_unless_anchored() {
+        assert!(is_match("b+", "abbc"));
+        assert!(!is_match("^b+", "abbc"));
+        assert!(is_match("^(?:ab|c){2}$", "abc"));
+        assert!(!is_match("^a{2,3}$", "aaaa"));
+        assert!(is_match(r"^\d{3}-\w+$", "123-abc_d"));
+        assert!(is_match("^[^a-c]?$", ""));
+    }
+
+    #[test]
+    fn ends_loops_that_match_nothing() {
+        assert!(is_match("^(a*)*b$", "aaab"));
+        assert!(!is_match("^(a*)*b$", "aaac"));
+        assert!(is_match("^(|a)+$", "aa"));
+    }
+
+    #[test]
+    fn matches_long_text_without_recursing() {
+        let text = "a".repeat(100_000);
+        assert!(is_match("^[a-z]*$", &text));
+        assert!(!is_match("^(a|aa)*b$", &text));
+        assert!(is_match("(?:a|b)+a{20}$", &text));
+    }
+
+    #[test]
+    fn matches_classes_and_escapes() {
+        assert!(is_match("^[a-cx-z]+$", "abzy"));
+        assert!(!is_match("^[a-cx-z]+$", "abd"));
+        assert!(is_match(r"^[\d.]+$", "1.25"));
+        assert!(is_match(r"^\D\W\S$", "a b"));
+        assert!(!is_match(r"^\D\W\S$", "1 b"));
+        assert!(is_match(r"^a\.b\*$", "a.b*"));
+        assert!(!is_match(r"^a\.b$", "axb"));
+        assert!(is_match("^.$", "é"));
+        assert!(is_match(r"^\t\s$", "\t\n"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let err = |pattern: &str| Regex::new(pattern).unwrap_err();
+        assert_eq!(err("a)"), "unmatched ')' in 'a)'");
+        assert_eq!(err("(a"), "unclosed '('");
+        assert_eq!(err("[a"), "unclosed '['");
+        assert_eq!(err("[z-a]"), "invalid class range z-a");
+        assert_eq!(err("*a"), "nothing to repeat before '*'");
+        assert_eq!(err("a{3,1}"), "invalid repetition {3,1}");
+        assert_eq!(err("(?=a)"), "only (?:...) groups are supported");
+        assert_eq!(err(r"\b"), "unsupported escape '\\b'");
+    }
+
+    #[test]
+    fn refuses_huge_repetitions() {
+        assert!(Regex::new("(a{1000}){1000}").is_err());
+        assert!(Regex::new("a{1000}").is_ok());
+    }
+}
@@ -0,0 +1,269 @@
+//! `jsonp repl`: evaluate expressions against a loaded document as they are typed
+//! Lines starting with `/` are JSON Pointers, lines starting with `$` are JSONPath
+//! expressions, and anything else is a query filter.
+
+use std::fs::File;
+use std::io::{BufRead, Write};
+
+use jsonp::jsonpath::JsonPath;
+use jsonp::parse::JsonValue;
+use jsonp::pointer::JsonPointer;
+use jsonp::query::Query;
+use jsonp::serialize::to_string_pretty;
+
+use crate::explore::children;
+use crate::terminal::{open_tty, Key, Keys, RawMode};
+
+const PROMPT: &str = "jsonp> ";
+
+const HELP: &str = "\
+/a/0/b     value at a JSON Pointer
+$.a[*].b   values selected by a JSONPath expression
+.a | keys  results of a query filter
+:quit      leave, as does ctrl-d on an empty line
+
+Up and down recall earlier lines, tab completes keys of the document.";
+
+/// Read expressions until the user quits, printing what each evaluates to
+/// With `interactive` lines are edited on the terminal, otherwise they are read
+/// from stdin one per line, which suits scripts.
+pub fn repl(json: &JsonValue, color: bool, interactive: bool) -> Result<(), String> {
+    let io_err = |e: std::io::Error| format!("IO error: {}", e);
+    if !interactive {
+        for line in std::io::stdin().lock().lines() {
+            let line = line.map_err(io_err)?;
+            match run_line(json, &line, color) {
+                Some(output) => println!("{}", output),
+                None => break,
+            }
+        }
+        return Ok(());
+    }
+
+    let mut tty = open_tty()?;
+    let _raw = RawMode::enable()?;
+    let mut editor = Editor {
+        history: vec![],
+        keys: Keys::default(),
+    };
+    while let Some(line) = editor.read_line(&mut tty, json).map_err(io_err)? {
+        let Some(output) = run_line(json, &line, color) else {
+            break;
+        };
+        if !output.is_empty() {
+            // Raw mode does not turn a newline into a carriage return and line feed
+            write!(tty, "{}\r\n", output.replace('\n', "\r\n")).map_err(io_err)?;
+        }
+    }
+    Ok(())
+}
+
+/// The output for one line, or `None` to quit
+fn run_line(json: &JsonValue, line: &str, color: bool) -> Option<String> {
+    let line = line.trim();
+    let output = match line {
+        "" => String::new(),
+        ":q" | ":quit" => return None,
+        ":help" => HELP.to_string(),
+        _ => match evaluate(json, line) {
+            Ok(values) => values
+                .iter()
+                .map(|value| {
+                    let text = if color {
+                        value.to_string_colored()
+                    } else {
+                        to_string_pretty(value)
+                    };
+                    text.unwrap_or_else(|err| err.0)
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(msg) => msg,
+        },
+    };
+    Some(output)
+}
+
+fn evaluate(json: &JsonValue, expr: &str) -> Result<Vec<JsonValue>, String> {
+    if expr.starts_with('/') {
+        let pointer = JsonPointer::parse(expr).map_err(|err| err.0)?;
+        match pointer.get(json) {
+            Some(value) => Ok(vec![value.clone()]),
+            None => Err(format!("nothing at '{}'", pointer)),
+        }
+    } else if expr.starts_with('$') {
+        let path = JsonPath::compile(expr).map_err(|err| err.0)?;
+        Ok(path.select(json).into_iter().cloned().collect())
+    } else {
+        let query = Query::compile(expr).map_err(|err| err.0)?;
+        query.eval(json).map_err(|err| err.0)
+    }
+}
+
+struct Editor {
+    history: Vec<String>,
+    keys: Keys,
+}
+
+impl Editor {
+    /// Edit a line on the terminal, `None` at the end of input
+    fn read_line(&mut self, tty: &mut File, json: &JsonValue) -> std::io::Result<Option<String>> {
+        let mut line: Vec<char> = vec![];
+        let mut cursor = 0;
+        // Position in the history, and the line being written before going back
+        let mut recall = self.history.len();
+        let mut draft = vec![];
+
+        loop {
+            let text = line.iter().collect::<String>();
+            let column = PROMPT.len() + cursor;
+            write!(tty, "\r\x1b[2K{}{}\r", PROMPT, text)?;
+            if column > 0 {
+                write!(tty, "\x1b[{}C", column)?;
+            }
+            tty.flush()?;
+
+            let Some(key) = self.keys.next(tty)? else {
+                return Ok(None);
+            };
+            match key {
+                Key::Enter => {
+                    write!(tty, "\r\n")?;
+                    if !text.trim().is_empty() && self.history.last() != Some(&text) {
+                        self.history.push(text.clone());
+                    }
+                    return Ok(Some(text));
+                }
+                Key::Char('\x04') if line.is_empty() => {
+                    write!(tty, "\r\n")?;
+                    return Ok(None);
+                }
+                Key::Char('\x03') => {
+                    write!(tty, "^C\r\n")?;
+                    line.clear();
+                    cursor = 0;
+                }
+                Key::Home | Key::Char('\x01') => cursor = 0,
+                Key::End | Key::Char('\x05') => cursor = line.len(),
+                Key::Char('\x15') => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Left => cursor = cursor.saturating_sub(1),
+                Key::Right => cursor = (cursor + 1).min(line.len()),
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Delete if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Up if recall > 0 => {
+                    if recall == self.history.len() {
+                        draft = line.clone();
+                    }
+                    recall -= 1;
+                    line = self.history[recall].chars().collect();
+                    cursor = line.len();
+                }
+                Key::Down if recall < self.history.len() => {
+                    recall += 1;
+                    line = match self.history.get(recall) {
+                        Some(previous) => previous.chars().collect(),
+                        None => draft.clone(),
+                    };
+                    cursor = line.len();
+                }
+                Key::Tab => {
+                    let before = line[..cursor].iter().collect::<String>();
+                    let (candidates, typed) = completions(json, &before);
+                    let insert = match candidates.as_slice() {
+                        [] => {
+                            write!(tty, "\x07")?;
+                            continue;
+                        }
+                        [only] => only.clone(),
+                        _ => {
+                            let common = common_prefix(&candidates);
+                            if common.chars().count() == typed {
+                                write!(tty, "\r\n{}\r\n", candidates.join("  "))?;
+                            }
+                            common
+                        }
+                    };
+                    for c in insert.chars().skip(typed) {
+                        line.insert(cursor, c);
+                        cursor += 1;
+                    }
+                }
+                Key::Char(c) if !c.is_control() => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Keys that could continue the word before the cursor, written as they would be
+/// typed, and how many characters of them were typed already
+/// The word up to its last `/` or `.` is resolved as a pointer or a dotted path,
+/// e.g. `/users/0/na` or `.users[0].na` both complete the keys of `/users/0`.
+fn completions(json: &JsonValue, before: &str) -> (Vec<String>, usize) {
+    let start = before
+        .rfind(|c: char| c.is_whitespace() || "|(,".contains(c))
+        .map_or(0, |i| i + 1);
+    let word = &before[start..];
+
+    let (parent, typed, is_pointer) = match word.rsplit_once('/') {
+        Some((parent, typed)) if word.starts_with('/') => (JsonPointer::parse(parent), typed, true),
+        _ => {
+            let path = word.strip_prefix('$').unwrap_or(word);
+            let Some((parent, typed)) = path.rsplit_once('.') else {
+                return (vec![], 0);
+            };
+            (
+                JsonPointer::from_path(parent.trim_start_matches('.')),
+                typed,
+                false,
+            )
+        }
+    };
+    let Ok(parent) = parent else {
+        return (vec![], 0);
+    };
+    let Some(value) = parent
+        .get(json)
+        .filter(|value| !matches!(value, JsonValue::Arr(_)))
+    else {
+        return (vec![], 0);
+    };
+
+    let candidates = children(value)
+        .into_iter()
+        .map(|(key, _)| {
+            if is_pointer {
+                key.replace('~', "~0").replace('/', "~1")
+            } else {
+                key
+            }
+        })
+        .filter(|key| key.starts_with(typed))
+        .collect();
+    (candidates, typed.chars().count())
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix = words[0].clone();
+    for word in &words[1..] {
+        let len = prefix
+            .chars()
+            .zip(word.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(c, _)| c.len_utf8())
+            .sum();
+        prefix.truncate(len);
+    }
+    prefix
+}
@@ -0,0 +1,241 @@
+use std::sync::OnceLock;
+
+// Shortest float digits with Ryū, as described in Ulf Adams' paper "Ryū: fast
+// float-to-string conversion" and written after its reference `d2d`. The tables
+// of powers of five are computed on first use rather than spelled out here.
+
+const MANTISSA_BITS: u32 = 52;
+const EXPONENT_BIAS: i32 = 1023;
+const POW5_INV_BITCOUNT: i32 = 125;
+const POW5_BITCOUNT: i32 = 125;
+const POW5_INV_TABLE_SIZE: usize = 342;
+const POW5_TABLE_SIZE: usize = 326;
+
+/// The shortest `m` and `e` such that `m * 10^e` reads back as the finite,
+/// positive double `f`
+pub(crate) fn shortest(f: f64) -> (u64, i32) {
+    let bits = f.to_bits();
+    let ieee_mantissa = bits & ((1 << MANTISSA_BITS) - 1);
+    let ieee_exponent = (bits >> MANTISSA_BITS) as i32;
+
+    // The value is `m2 * 2^e2`, less the two bits used for the bounds below
+    let (e2, m2) = match ieee_exponent {
+        0 => (1 - EXPONENT_BIAS - MANTISSA_BITS as i32 - 2, ieee_mantissa),
+        _ => (
+            ieee_exponent - EXPONENT_BIAS - MANTISSA_BITS as i32 - 2,
+            ieee_mantissa | (1 << MANTISSA_BITS),
+        ),
+    };
+    let accept_bounds = m2 % 2 == 0;
+
+    // The halfway points to the neighbouring doubles are `4 * m2 + 2` above and
+    // `4 * m2 - 1 - mm_shift` below, closer below at powers of two
+    let mv = 4 * m2;
+    let mm_shift = (ieee_mantissa != 0 || ieee_exponent <= 1) as u64;
+
+    let tables = tables();
+    let (mut vr, mut vp, mut vm, e10);
+    let mut vm_trailing_zeros = false;
+    let mut vr_trailing_zeros = false;
+    if e2 >= 0 {
+        let q = log10_pow2(e2) - (e2 > 3) as i32;
+        e10 = q;
+        let k = POW5_INV_BITCOUNT + pow5_bits(q) - 1;
+        let i = -e2 + q + k;
+        let mul = tables.pow5_inv[q as usize];
+        vr = mul_shift(mv, mul, i);
+        vp = mul_shift(mv + 2, mul, i);
+        vm = mul_shift(mv - 1 - mm_shift, mul, i);
+        if q <= 21 {
+            // Only then can one of the three be a multiple of 5^q
+            if mv % 5 == 0 {
+                vr_trailing_zeros = multiple_of_power_of_5(mv, q);
+            } else if accept_bounds {
+                vm_trailing_zeros = multiple_of_power_of_5(mv - 1 - mm_shift, q);
+            } else {
+                vp -= multiple_of_power_of_5(mv + 2, q) as u64;
+            }
+        }
+    } else {
+        let q = log10_pow5(-e2) - (-e2 > 1) as i32;
+        e10 = q + e2;
+        let i = -e2 - q;
+        let k = pow5_bits(i) - POW5_BITCOUNT;
+        let j = q - k;
+        let mul = tables.pow5[i as usize];
+        vr = mul_shift(mv, mul, j);
+        vp = mul_shift(mv + 2, mul, j);
+        vm = mul_shift(mv - 1 - mm_shift, mul, j);
+        if q <= 1 {
+            // `mv` has at least a trailing zero bit, so `vr` has one or more
+            // trailing zero digits and `vp` or `vm` may too
+            vr_trailing_zeros = true;
+            if accept_bounds {
+                vm_trailing_zeros = mm_shift == 1;
+            } else {
+                vp -= 1;
+            }
+        } else if q < 63 {
+            vr_trailing_zeros = multiple_of_power_of_2(mv, q);
+        }
+    }
+
+    // Drop digits while the bounds still differ, rounding `vr` on the last one
+    let mut removed = 0;
+    let output = if vm_trailing_zeros || vr_trailing_zeros {
+        // The rare case of exact values. Unlike the reference, ties round up
+        // rather than to even, as std's formatting does, so that the digits do
+        // not depend on the feature.
+        let mut last_removed = 0;
+        while vp / 10 > vm / 10 {
+            vm_trailing_zeros &= vm % 10 == 0;
+            last_removed = vr % 10;
+            vr /= 10;
+            vp /= 10;
+            vm /= 10;
+            removed += 1;
+        }
+        if vm_trailing_zeros {
+            while vm % 10 == 0 {
+                last_removed = vr % 10;
+                vr /= 10;
+                vm /= 10;
+                removed += 1;
+            }
+        }
+        let round_up = (vr == vm && (!accept_bounds || !vm_trailing_zeros)) || last_removed >= 5;
+        vr + round_up as u64
+    } else {
+        let mut round_up = false;
+        if vp / 100 > vm / 100 {
+            round_up = vr % 100 >= 50;
+            vr /= 100;
+            vp /= 100;
+            vm /= 100;
+            removed += 2;
+        }
+        while vp / 10 > vm / 10 {
+            round_up = vr % 10 >= 5;
+            vr /= 10;
+            vp /= 10;
+            vm /= 10;
+            removed += 1;
+        }
+        vr + (vr == vm || round_up) as u64
+    };
+    (output, e10 + removed)
+}
+
+/// `m * mul >> j`, for `j` of 64 or more
+fn mul_shift(m: u64, mul: u128, j: i32) -> u64 {
+    let low = m as u128 * (mul as u64) as u128;
+    let high = m as u128 * (mul >> 64);
+    (((low >> 64) + high) >> (j - 64)) as u64
+}
+
+/// The bits of `5^e`, or 1 for 0
+fn pow5_bits(e: i32) -> i32 {
+    ((e as u32 * 1217359) >> 19) as i32 + 1
+}
+
+/// `floor(log10(2^e))`
+fn log10_pow2(e: i32) -> i32 {
+    ((e as u32 * 78913) >> 18) as i32
+}
+
+/// `floor(log10(5^e))`
+fn log10_pow5(e: i32) -> i32 {
+    ((e as u32 * 732923) >> 20) as i32
+}
+
+fn multiple_of_power_of_5(mut value: u64, p: i32) -> bool {
+    let mut factor = 0;
+    while value.is_multiple_of(5) {
+        value /= 5;
+        factor += 1;
+    }
+    factor >= p
+}
+
+fn multiple_of_power_of_2(value: u64, p: i32) -> bool {
+    value & ((1 << p) - 1) == 0
+}
+
+/// Powers of five and their inverses, both to 125 bits
+struct Tables {
+    /// `5^i` cut or padded to its top 125 bits
+    pow5: Vec<u128>,
+    /// `2^(bits(5^i) - 1 + 125) / 5^i`, rounded up
+    pow5_inv: Vec<u128>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut pow5 = Vec::with_capacity(POW5_TABLE_SIZE);
+        let mut pow5_inv = Vec::with_capacity(POW5_INV_TABLE_SIZE);
+        // `5^i` in base 2^32, least significant limb first
+        let mut power = vec![1u32];
+        for i in 0..POW5_INV_TABLE_SIZE {
+            let bits = bit_length(&power);
+            if i < POW5_TABLE_SIZE {
+                pow5.push(match bits > POW5_BITCOUNT as usize {
+                    true => shift_right(&power, bits - POW5_BITCOUNT as usize),
+                    false => shift_right(&power, 0) << (POW5_BITCOUNT as usize - bits),
+                });
+            }
+
+            // 2^j, then divided by 5^13 at a time, the most that fits in a limb
+            let j = bits - 1 + POW5_INV_BITCOUNT as usize;
+            let mut inverse = vec![0u32; j / 32 + 1];
+            inverse[j / 32] = 1 << (j % 32);
+            let mut left = i;
+            while left > 0 {
+                let n = left.min(13);
+                divide(&mut inverse, 5u32.pow(n as u32));
+                left -= n;
+            }
+            pow5_inv.push(shift_right(&inverse, 0) + 1);
+
+            multiply(&mut power, 5);
+        }
+        Tables { pow5, pow5_inv }
+    })
+}
+
+fn bit_length(n: &[u32]) -> usize {
+    let top = n.iter().rposition(|&limb| limb != 0).unwrap_or(0);
+    top * 32 + (32 - n[top].leading_zeros() as usize)
+}
+
+/// The low 128 bits of `n >> shift`
+fn shift_right(n: &[u32], shift: usize) -> u128 {
+    let limb = |i: usize| n.get(i).copied().unwrap_or(0) as u64;
+    (0..4).fold(0, |out, word| {
+        let (at, bit) = (shift / 32 + word, shift % 32);
+        let bits = (limb(at) | limb(at + 1) << 32) >> bit;
+        out | ((bits as u32 as u128) << (word * 32))
+    })
+}
+
+fn multiply(n: &mut Vec<u32>, by: u32) {
+    let mut carry = 0;
+    for limb in n.iter_mut() {
+        let product = *limb as u64 * by as u64 + carry;
+        *limb = product as u32;
+        carry = product >> 32;
+    }
+    if carry > 0 {
+        n.push(carry as u32);
+    }
+}
+
+/// Divide in place, dropping the remainder
+fn divide(n: &mut [u32], by: u32) {
+    let mut rest = 0;
+    for limb in n.iter_mut().rev() {
+        let value = rest << 32 | *limb as u64;
+        *limb = (value / by as u64) as u32;
+        rest = value % by as u64;
+    }
+}
@@ -0,0 +1,740 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::coerce::coerce_str;
+use crate::compare::compare;
+use crate::parse::{JsonValue, Key};
+use crate::pointer::JsonPointer;
+use crate::regex::Regex;
+use crate::serialize::to_string;
+
+#[derive(Debug)]
+pub struct SchemaError(pub String);
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// A place where an instance does not satisfy its schema
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The offending value in the instance
+    pub instance_path: JsonPointer,
+    /// The keyword that rejected it, e.g. `/properties/age/minimum`
+    pub schema_path: JsonPointer,
+    pub message: String,
+}
+
+/// A compiled JSON Schema
+/// Supported keywords: `type`, `required`, `enum`, `minimum`, `maximum`,
+/// `exclusiveMinimum`, `exclusiveMaximum`, `minLength`, `maxLength`,
+/// `minItems`, `maxItems`, `pattern`, `items`, `properties`,
+/// `additionalProperties` and `$ref` to a pointer within the same document,
+/// e.g. `#/$defs/name`. Other keywords are ignored.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    root: Node,
+    /// Every `$ref` target, compiled once so recursive schemas terminate
+    defs: Vec<Node>,
+}
+
+/// The document being compiled and the targets referenced so far
+struct Refs<'a> {
+    document: &'a JsonValue,
+    targets: Vec<JsonPointer>,
+}
+
+impl Schema {
+    /// Compile a schema document, rejecting malformed keywords
+    pub fn compile(schema: &JsonValue) -> Result<Self, SchemaError> {
+        let mut refs = Refs {
+            document: schema,
+            targets: vec![],
+        };
+        let root = Node::compile(schema, JsonPointer::default(), &mut refs)?;
+
+        // Compiling a target can reference further targets
+        let mut defs = vec![];
+        while let Some(path) = refs.targets.get(defs.len()).cloned() {
+            let target = path.get(schema).expect("targets exist when referenced");
+            defs.push(Node::compile(target, path, &mut refs)?);
+        }
+
+        // A chain of references that comes back to itself never reaches a keyword
+        for start in 0..defs.len() {
+            let mut next = defs[start].reference;
+            for _ in 0..defs.len() {
+                match next {
+                    Some(i) if i == start => {
+                        return Err(SchemaError(format!(
+                            "Schema error: '$ref' refers to itself at '{}'",
+                            defs[start].path
+                        )))
+                    }
+                    Some(i) => next = defs[i].reference,
+                    None => break,
+                }
+            }
+        }
+
+        Ok(Schema { root, defs })
+    }
+
+    /// Check `instance` against the schema, collecting every violation
+    pub fn validate(&self, instance: &JsonValue) -> Vec<Violation> {
+        let mut violations = vec![];
+        self.root.validate(
+            instance,
+            &JsonPointer::default(),
+            &self.defs,
+            &mut violations,
+        );
+        violations
+    }
+}
+
+impl JsonValue {
+    /// Convert string-encoded numbers, booleans and nulls to the types `schema` expects
+    /// e.g. `{"age": "42"}` becomes `{"age": 42}` under `{"properties": {"age": {"type": "integer"}}}`.
+    /// Strings are only touched where the schema does not also allow a string.
+    pub fn coerce(self, schema: &Schema) -> JsonValue {
+        schema.root.coerce(self, &schema.defs)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Additional {
+    Allowed,
+    Schema(Box<Node>),
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    path: JsonPointer,
+    never: bool,
+    /// Index of the `$ref` target in `Schema::defs`
+    reference: Option<usize>,
+    types: Option<Vec<String>>,
+    required: Vec<String>,
+    enumeration: Option<Vec<JsonValue>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    pattern: Option<(String, Regex)>,
+    items: Option<Box<Node>>,
+    properties: Vec<(String, Node)>,
+    additional: Additional,
+}
+
+const TYPES: [&str; 7] = [
+    "null", "boolean", "object", "array", "number", "string", "integer",
+];
+
+impl Node {
+    fn compile(
+        schema: &JsonValue,
+        path: JsonPointer,
+        refs: &mut Refs,
+    ) -> Result<Self, SchemaError> {
+        let mut node = Node {
+            path,
+            never: false,
+            reference: None,
+            types: None,
+            required: vec![],
+            enumeration: None,
+            minimum: None,
+            maximum: None,
+            exclusive_minimum: None,
+            exclusive_maximum: None,
+            min_length: None,
+            max_length: None,
+            min_items: None,
+            max_items: None,
+            pattern: None,
+            items: None,
+            properties: vec![],
+            additional: Additional::Allowed,
+        };
+
+        match schema {
+            JsonValue::Bool(b) => {
+                node.never = !b;
+                return Ok(node);
+            }
+            _ if schema.is_object() => (),
+            _ => return Err(node.err("", "a schema must be an object or a boolean")),
+        }
+
+        for (keyword, value) in schema.members() {
+            let path = node.path.join(keyword);
+            match keyword {
+                "$ref" => {
+                    let JsonValue::Str(reference) = value else {
+                        return Err(node.err(keyword, "expected a string"));
+                    };
+                    let target = reference.strip_prefix('#').ok_or_else(|| {
+                        node.err(keyword, "only references within the schema are supported")
+                    })?;
+                    let target = JsonPointer::parse(target).map_err(|e| node.err(keyword, &e.0))?;
+                    if target.get(refs.document).is_none() {
+                        let msg = format!("'{}' does not point into the schema", reference);
+                        return Err(node.err(keyword, &msg));
+                    }
+                    node.reference = Some(match refs.targets.iter().position(|t| *t == target) {
+                        Some(index) => index,
+                        None => {
+                            refs.targets.push(target);
+                            refs.targets.len() - 1
+                        }
+                    });
+                }
+                "type" => {
+                    let types = match value {
+                        JsonValue::Str(t) => vec![t.to_string()],
+                        JsonValue::Arr(types) => types
+                            .iter()
+                            .map(|t| match t {
+                                JsonValue::Str(t) => Ok(t.to_string()),
+                                _ => Err(node.err(keyword, "types must be strings")),
+                            })
+                            .collect::<Result<_, _>>()?,
+                        _ => return Err(node.err(keyword, "expected a string or an array")),
+                    };
+                    if let Some(t) = types.iter().find(|t| !TYPES.contains(&t.as_str())) {
+                        return Err(node.err(keyword, &format!("unknown type '{}'", t)));
+                    }
+                    node.types = Some(types);
+                }
+                "required" => {
+                    node.required = match value {
+                        JsonValue::Arr(keys) => keys
+                            .iter()
+                            .map(|k| match k {
+                                JsonValue::Str(k) => Ok(k.to_string()),
+                                _ => Err(node.err(keyword, "property names must be strings")),
+                            })
+                            .collect::<Result<_, _>>()?,
+                        _ => return Err(node.err(keyword, "expected an array")),
+                    }
+                }
+                "enum" => match value {
+                    JsonValue::Arr(values) => node.enumeration = Some(values.to_vec()),
+                    _ => return Err(node.err(keyword, "expected an array")),
+                },
+                "minimum" => node.minimum = Some(node.number(keyword, value)?),
+                "maximum" => node.maximum = Some(node.number(keyword, value)?),
+                "exclusiveMinimum" => node.exclusive_minimum = Some(node.number(keyword, value)?),
+                "exclusiveMaximum" => node.exclusive_maximum = Some(node.number(keyword, value)?),
+                "minLength" => node.min_length = Some(node.count(keyword, value)?),
+                "maxLength" => node.max_length = Some(node.count(keyword, value)?),
+                "minItems" => node.min_items = Some(node.count(keyword, value)?),
+                "maxItems" => node.max_items = Some(node.count(keyword, value)?),
+                "pattern" => match value {
+                    JsonValue::Str(pattern) => {
+                        let regex = Regex::new(pattern).map_err(|e| node.err(keyword, &e))?;
+                        node.pattern = Some((pattern.to_string(), regex));
+                    }
+                    _ => return Err(node.err(keyword, "expected a string")),
+                },
+                "items" => node.items = Some(Box::new(Node::compile(value, path, refs)?)),
+                "properties" => {
+                    if !value.is_object() {
+                        return Err(node.err(keyword, "expected an object"));
+                    }
+                    for (name, schema) in value.members() {
+                        let schema = Node::compile(schema, path.join(name), refs)?;
+                        node.properties.push((name.to_string(), schema));
+                    }
+                }
+                "additionalProperties" => {
+                    node.additional =
+                        Additional::Schema(Box::new(Node::compile(value, path, refs)?))
+                }
+                _ => (),
+            }
+        }
+
+        Ok(node)
+    }
+
+    fn number(&self, keyword: &str, value: &JsonValue) -> Result<f64, SchemaError> {
+        value
+            .as_f64()
+            .ok_or_else(|| self.err(keyword, "expected a number"))
+    }
+
+    fn count(&self, keyword: &str, value: &JsonValue) -> Result<usize, SchemaError> {
+        match value {
+            JsonValue::Int(i) if *i >= 0 => Ok(*i as usize),
+            _ => Err(self.err(keyword, "expected a non-negative integer")),
+        }
+    }
+
+    fn err(&self, keyword: &str, msg: &str) -> SchemaError {
+        let mut path = self.path.clone();
+        if !keyword.is_empty() {
+            path.push(keyword);
+        }
+        SchemaError(format!("Schema error: {} at '{}'", msg, path))
+    }
+
+    fn coerce(&self, value: JsonValue, defs: &[Node]) -> JsonValue {
+        let value = match self.reference {
+            Some(target) => defs[target].coerce(value, defs),
+            None => value,
+        };
+        match value {
+            JsonValue::Str(ref s) => match &self.types {
+                Some(types) if !types.iter().any(|t| t == "string") => {
+                    coerce_str(s, types).unwrap_or(value)
+                }
+                _ => value,
+            },
+            JsonValue::Arr(_) => match &self.items {
+                Some(items) => JsonValue::Arr(
+                    value
+                        .into_array()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|v| items.coerce(v, defs))
+                        .collect(),
+                ),
+                None => value,
+            },
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) => {
+                let members = value
+                    .members()
+                    .map(|(key, v)| {
+                        let v = match self.properties.iter().find(|(name, _)| name == key) {
+                            Some((_, schema)) => schema.coerce(v.clone(), defs),
+                            None => match &self.additional {
+                                Additional::Schema(schema) => schema.coerce(v.clone(), defs),
+                                Additional::Allowed => v.clone(),
+                            },
+                        };
+                        (key, v)
+                    })
+                    .collect();
+                JsonValue::from_members(members)
+            }
+            value => value,
+        }
+    }
+
+    fn validate(
+        &self,
+        instance: &JsonValue,
+        path: &JsonPointer,
+        defs: &[Node],
+        out: &mut Vec<Violation>,
+    ) {
+        if let Some(target) = self.reference {
+            defs[target].validate(instance, path, defs, out);
+        }
+
+        let mut fail = |keyword: &str, message: String| {
+            let schema_path = if keyword.is_empty() {
+                self.path.clone()
+            } else {
+                self.path.join(keyword)
+            };
+            out.push(Violation {
+                instance_path: path.clone(),
+                schema_path,
+                message,
+            });
+        };
+
+        if self.never {
+            fail("", "no value is allowed here".to_string());
+            return;
+        }
+
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| has_type(instance, t)) {
+                let expected = types.join(" or ");
+                let found = instance.type_name();
+                fail("type", format!("expected {}, found {}", expected, found));
+            }
+        }
+
+        if let Some(values) = &self.enumeration {
+            if !values.iter().any(|v| equal(v, instance)) {
+                fail("enum", "value is not one of the allowed values".to_string());
+            }
+        }
+
+        if let Some(n) = instance.as_f64() {
+            let text = || to_string(instance).unwrap_or_default();
+            if let Some(min) = self.minimum.filter(|min| n < *min) {
+                fail("minimum", format!("{} is less than {}", text(), min));
+            }
+            if let Some(max) = self.maximum.filter(|max| n > *max) {
+                fail("maximum", format!("{} is greater than {}", text(), max));
+            }
+            if let Some(min) = self.exclusive_minimum.filter(|min| n <= *min) {
+                let msg = format!("{} is not greater than {}", text(), min);
+                fail("exclusiveMinimum", msg);
+            }
+            if let Some(max) = self.exclusive_maximum.filter(|max| n >= *max) {
+                let msg = format!("{} is not less than {}", text(), max);
+                fail("exclusiveMaximum", msg);
+            }
+        }
+
+        if let JsonValue::Str(s) = instance {
+            let len = s.chars().count();
+            if let Some(min) = self.min_length.filter(|min| len < *min) {
+                fail("minLength", format!("string is shorter than {}", min));
+            }
+            if let Some(max) = self.max_length.filter(|max| len > *max) {
+                fail("maxLength", format!("string is longer than {}", max));
+            }
+            if let Some((pattern, regex)) = &self.pattern {
+                if !regex.is_match(s) {
+                    fail("pattern", format!("string does not match '{}'", pattern));
+                }
+            }
+        }
+
+        if let JsonValue::Arr(values) = instance {
+            if let Some(min) = self.min_items.filter(|min| values.len() < *min) {
+                fail("minItems", format!("array has fewer than {} items", min));
+            }
+            if let Some(max) = self.max_items.filter(|max| values.len() > *max) {
+                fail("maxItems", format!("array has more than {} items", max));
+            }
+        }
+
+        if instance.is_object() {
+            for key in &self.required {
+                if instance.get(key).is_none() {
+                    fail("required", format!("missing required property '{}'", key));
+                }
+            }
+        }
+
+        match instance {
+            JsonValue::Arr(values) => {
+                if let Some(items) = &self.items {
+                    for (i, value) in values.iter().enumerate() {
+                        items.validate(value, &path.join(i.to_string()), defs, out);
+                    }
+                }
+            }
+            _ if instance.is_object() => {
+                for (key, value) in instance.members() {
+                    let child = path.join(key);
+                    match self.properties.iter().find(|(name, _)| name == key) {
+                        Some((_, schema)) => schema.validate(value, &child, defs, out),
+                        None => match &self.additional {
+                            Additional::Schema(schema) if schema.never => out.push(Violation {
+                                instance_path: child,
+                                schema_path: schema.path.clone(),
+                                message: format!("property '{}' is not allowed", key),
+                            }),
+                            Additional::Schema(schema) => schema.validate(value, &child, defs, out),
+                            Additional::Allowed => (),
+                        },
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn has_type(value: &JsonValue, name: &str) -> bool {
+    match (name, value) {
+        ("integer", JsonValue::Int(_)) => true,
+        ("integer", JsonValue::Float(f, _)) => f.fract() == 0.0,
+        _ => value.type_name() == name,
+    }
+}
+
+/// Structural equality where `1` and `1.0` are the same number
+fn equal(a: &JsonValue, b: &JsonValue) -> bool {
+    a.type_name() == b.type_name() && compare(a, b) == Ordering::Equal
+}
+
+/// Infer a schema describing every sample, e.g. a set of API responses
+/// Types seen at the same location are merged, object members missing from
+/// some samples are left out of `required` and array items share one schema.
+pub fn infer(samples: &[JsonValue]) -> JsonValue {
+    let mut shape = Shape::default();
+    for sample in samples {
+        shape.add(sample);
+    }
+
+    let mut members: Vec<(Key, JsonValue)> = vec![(
+        "$schema".into(),
+        JsonValue::Str("https://json-schema.org/draft/2020-12/schema".into()),
+    )];
+    members.extend(shape.to_schema().into_members());
+    JsonValue::from_members(members)
+}
+
+/// Everything observed at one location across the samples
+#[derive(Default)]
+struct Shape {
+    null: bool,
+    boolean: bool,
+    integer: bool,
+    number: bool,
+    string: bool,
+    /// Number of arrays seen, and the merged shape of their elements
+    arrays: usize,
+    items: Option<Box<Shape>>,
+    /// Number of objects seen, and each member with how often it was present
+    objects: usize,
+    properties: Vec<(String, usize, Shape)>,
+}
+
+impl Shape {
+    fn add(&mut self, value: &JsonValue) {
+        match value {
+            JsonValue::Null => self.null = true,
+            JsonValue::Bool(_) => self.boolean = true,
+            JsonValue::Int(_) => self.integer = true,
+            JsonValue::Float(_, _) => self.number = true,
+            JsonValue::Str(_) => self.string = true,
+            JsonValue::Arr(values) => {
+                self.arrays += 1;
+                for value in values {
+                    self.items.get_or_insert_with(Box::default).add(value);
+                }
+            }
+            _ => {
+                self.objects += 1;
+                for (key, value) in value.members() {
+                    let index = match self.properties.iter().position(|(k, _, _)| k == key) {
+                        Some(index) => index,
+                        None => {
+                            self.properties.push((key.to_string(), 0, Shape::default()));
+                            self.properties.len() - 1
+                        }
+                    };
+                    let (_, count, shape) = &mut self.properties[index];
+                    *count += 1;
+                    shape.add(value);
+                }
+            }
+        }
+    }
+
+    fn to_schema(&self) -> JsonValue {
+        let mut types = vec![];
+        let flags = [
+            ("null", self.null),
+            ("boolean", self.boolean),
+            ("object", self.objects > 0),
+            ("array", self.arrays > 0),
+            ("number", self.number),
+            ("integer", self.integer && !self.number),
+            ("string", self.string),
+        ];
+        for (name, seen) in flags {
+            if seen {
+                types.push(JsonValue::Str(name.into()));
+            }
+        }
+
+        let mut members = vec![];
+        match types.len() {
+            0 => (),
+            1 => members.push(("type".to_string(), types.remove(0))),
+            _ => members.push(("type".to_string(), JsonValue::Arr(types.into()))),
+        }
+
+        if self.objects > 0 {
+            let properties = self
+                .properties
+                .iter()
+                .map(|(key, _, shape)| (key.clone(), shape.to_schema()))
+                .collect();
+            members.push((
+                "properties".to_string(),
+                JsonValue::from_members(properties),
+            ));
+
+            let required = self
+                .properties
+                .iter()
+                .filter(|(_, count, _)| *count == self.objects)
+                .map(|(key, _, _)| JsonValue::Str(key.as_str().into()))
+                .collect::<Vec<_>>();
+            if !required.is_empty() {
+                members.push(("required".to_string(), JsonValue::Arr(required.into())));
+            }
+        }
+
+        if let Some(items) = &self.items {
+            members.push(("items".to_string(), items.to_schema()));
+        }
+
+        JsonValue::from_members(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    /// Each violation as `instance path -> schema path`
+    fn violations(schema: &str, instance: &str) -> Vec<String> {
+        let schema = Schema::compile(&json(schema)).unwrap();
+        schema
+            .validate(&json(instance))
+            .iter()
+            .map(|v| format!("{} -> {}", v.instance_path, v.schema_path))
+            .collect()
+    }
+
+    fn compile_err(schema: &str) -> String {
+        Schema::compile(&json(schema)).unwrap_err().0
+    }
+
+    #[test]
+    fn checks_type() {
+        assert!(violations(r#"{"type": "string"}"#, r#""a""#).is_empty());
+        assert_eq!(violations(r#"{"type": "string"}"#, "1"), [" -> /type"]);
+        assert!(violations(r#"{"type": ["null", "integer"]}"#, "2.0").is_empty());
+        assert_eq!(violations(r#"{"type": "integer"}"#, "2.5"), [" -> /type"]);
+        let schema = Schema::compile(&json(r#"{"type": "array"}"#)).unwrap();
+        assert_eq!(
+            schema.validate(&json("{}"))[0].message,
+            "expected array, found object"
+        );
+        assert!(compile_err(r#"{"type": "text"}"#).contains("unknown type 'text'"));
+    }
+
+    #[test]
+    fn checks_required() {
+        let schema = r#"{"required": ["a", "b"]}"#;
+        assert!(violations(schema, r#"{"a": 1, "b": null}"#).is_empty());
+        assert_eq!(violations(schema, "{}"), [" -> /required", " -> /required"]);
+        assert!(violations(schema, "[]").is_empty());
+        assert!(compile_err(r#"{"required": [1]}"#).contains("must be strings"));
+    }
+
+    #[test]
+    fn checks_properties() {
+        let schema = r#"{
+            "properties": {"age": {"type": "integer"}, "a/b": {"type": "null"}},
+            "additionalProperties": false
+        }"#;
+        assert!(violations(schema, r#"{"age": 3}"#).is_empty());
+        assert_eq!(
+            violations(schema, r#"{"age": "3", "a/b": 1, "x": 1}"#),
+            [
+                "/age -> /properties/age/type",
+                "/a~1b -> /properties/a~1b/type",
+                "/x -> /additionalProperties",
+            ]
+        );
+        let extra = r#"{"properties": {}, "additionalProperties": {"type": "string"}}"#;
+        assert_eq!(
+            violations(extra, r#"{"x": 1}"#),
+            ["/x -> /additionalProperties/type"]
+        );
+    }
+
+    #[test]
+    fn checks_items() {
+        let schema = r#"{"items": {"minimum": 0}, "minItems": 1, "maxItems": 2}"#;
+        assert!(violations(schema, "[0, 1]").is_empty());
+        assert_eq!(violations(schema, "[1, -1]"), ["/1 -> /items/minimum"]);
+        assert_eq!(
+            violations(schema, "[-1, -2, 3]"),
+            [
+                " -> /maxItems",
+                "/0 -> /items/minimum",
+                "/1 -> /items/minimum"
+            ]
+        );
+        assert_eq!(violations(schema, "[]"), [" -> /minItems"]);
+    }
+
+    #[test]
+    fn checks_enum() {
+        let schema = r#"{"enum": ["red", 1, {"a": [null]}]}"#;
+        assert!(violations(schema, r#""red""#).is_empty());
+        assert!(violations(schema, "1.0").is_empty());
+        assert!(violations(schema, r#"{"a": [null]}"#).is_empty());
+        assert_eq!(violations(schema, r#""1""#), [" -> /enum"]);
+        assert_eq!(violations(schema, r#"{"a": []}"#), [" -> /enum"]);
+    }
+
+    #[test]
+    fn checks_minimum_and_maximum() {
+        let schema = r#"{"minimum": 1, "maximum": 2.5}"#;
+        assert!(violations(schema, "1").is_empty());
+        assert!(violations(schema, "2.5").is_empty());
+        assert_eq!(violations(schema, "0.5"), [" -> /minimum"]);
+        assert_eq!(violations(schema, "3"), [" -> /maximum"]);
+        assert!(violations(schema, r#""3""#).is_empty());
+
+        let exclusive = r#"{"exclusiveMinimum": 1, "exclusiveMaximum": 2}"#;
+        assert!(violations(exclusive, "1.5").is_empty());
+        assert_eq!(violations(exclusive, "1"), [" -> /exclusiveMinimum"]);
+        assert_eq!(violations(exclusive, "2"), [" -> /exclusiveMaximum"]);
+        assert!(compile_err(r#"{"minimum": "1"}"#).contains("expected a number"));
+    }
+
+    #[test]
+    fn checks_pattern() {
+        let schema = r#"{"pattern": "^[a-z]+-\\d+$", "minLength": 3, "maxLength": 5}"#;
+        assert!(violations(schema, r#""ab-12""#).is_empty());
+        assert_eq!(violations(schema, r#""ab12""#), [" -> /pattern"]);
+        assert_eq!(violations(schema, r#""abc-123""#), [" -> /maxLength"]);
+        assert!(violations(r#"{"pattern": "é"}"#, r#""café""#).is_empty());
+        assert!(compile_err(r#"{"pattern": "(a"}"#).contains("/pattern"));
+    }
+
+    #[test]
+    fn follows_refs() {
+        let schema = r##"{
+            "$defs": {"positive": {"type": "integer", "minimum": 1}},
+            "properties": {"n": {"$ref": "#/$defs/positive"}}
+        }"##;
+        assert!(violations(schema, r#"{"n": 2}"#).is_empty());
+        assert_eq!(
+            violations(schema, r#"{"n": 0}"#),
+            ["/n -> /$defs/positive/minimum"]
+        );
+
+        let tree = r##"{
+            "type": "object",
+            "properties": {"children": {"items": {"$ref": "#"}}}
+        }"##;
+        let instance = r#"{"children": [{"children": []}, {"children": [1]}]}"#;
+        assert_eq!(
+            violations(tree, instance),
+            ["/children/1/children/0 -> /type"]
+        );
+
+        let coerced = json(r#"{"n": "7"}"#).coerce(&Schema::compile(&json(schema)).unwrap());
+        assert_eq!(to_string(&coerced).unwrap(), r#"{"n":7}"#);
+
+        assert!(compile_err(r#"{"$ref": "other.json"}"#).contains("within the schema"));
+        assert!(compile_err(r##"{"$ref": "#/$defs/none"}"##).contains("does not point"));
+        let cycle = r##"{"$defs": {"a": {"$ref": "#/$defs/b"}, "b": {"$ref": "#/$defs/a"}}, "$ref": "#/$defs/a"}"##;
+        assert!(compile_err(cycle).contains("refers to itself"));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::parse::JsonValue;
+use crate::pointer::{parse_index, JsonPointer, PointerError};
+
+/// What `pluck` does with elements that lack the path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Misses {
+    Drop,
+    Null,
+}
+
+impl JsonValue {
+    /// Map an array to the values at a dotted path in each element, e.g. `pluck("user.name", Misses::Drop)`
+    /// Returns `None` for non-arrays or malformed paths.
+    pub fn pluck(&self, path: &str, misses: Misses) -> Option<JsonValue> {
+        let pointer = JsonPointer::from_path(path).ok()?;
+        let values = self
+            .as_array()?
+            .iter()
+            .filter_map(|v| match (pointer.get(v), misses) {
+                (Some(v), _) => Some(v.clone()),
+                (None, Misses::Drop) => None,
+                (None, Misses::Null) => Some(JsonValue::Null),
+            })
+            .collect();
+        Some(JsonValue::Arr(values))
+    }
+
+    /// Keep only the given dotted paths, e.g. `value.project(&["id", "user.name", "tags[0]"])`
+    /// The structure around each selected value is preserved; selected array
+    /// elements are kept in their original order but packed together.
+    pub fn project(&self, paths: &[&str]) -> Result<JsonValue, PointerError> {
+        let pointers = paths
+            .iter()
+            .map(|path| JsonPointer::from_path(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let tokens = pointers.iter().map(|p| p.tokens()).collect::<Vec<_>>();
+
+        Ok(project(self, &tokens).unwrap_or_else(|| match self {
+            JsonValue::Arr(_) => JsonValue::Arr(Box::default()),
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty => {
+                JsonValue::Empty
+            }
+            _ => JsonValue::Null,
+        }))
+    }
+}
+
+/// Project `value` onto the remaining tokens of each path, `None` if nothing matched
+fn project(value: &JsonValue, paths: &[&[String]]) -> Option<JsonValue> {
+    if paths.iter().any(|tokens| tokens.is_empty()) {
+        return Some(value.clone());
+    }
+
+    // The tails of the paths whose next token selects the child
+    let rest = |matches: &dyn Fn(&str) -> bool| {
+        paths
+            .iter()
+            .filter(|tokens| matches(&tokens[0]))
+            .map(|tokens| &tokens[1..])
+            .collect::<Vec<_>>()
+    };
+
+    match value {
+        JsonValue::Arr(values) => {
+            let values = values
+                .iter()
+                .enumerate()
+                .filter_map(|(i, v)| {
+                    let rest = rest(&|token| parse_index(token) == Some(i));
+                    if rest.is_empty() {
+                        None
+                    } else {
+                        project(v, &rest)
+                    }
+                })
+                .collect::<Vec<_>>();
+            (!values.is_empty()).then_some(JsonValue::Arr(values.into()))
+        }
+        _ if value.is_object() => {
+            let members = value
+                .members()
+                .filter_map(|(key, v)| {
+                    let rest = rest(&|token| token == key);
+                    if rest.is_empty() {
+                        None
+                    } else {
+                        Some((key, project(v, &rest)?))
+                    }
+                })
+                .collect::<Vec<_>>();
+            (!members.is_empty()).then(|| JsonValue::from_members(members))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,979 @@
+use std::fmt;
+use std::io::Write;
+
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+use crate::writer::is_number;
+
+#[derive(Debug)]
+pub struct SerializeError(pub String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+/// What to do with control characters (below U+0020) inside strings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ControlChars {
+    /// Escape them, e.g. as `\u0001`
+    Escape,
+    /// Refuse to serialize strings containing them
+    Error,
+}
+
+/// Line terminator used by pretty output
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Knobs for the compact and pretty serializers
+#[derive(Clone, Debug)]
+pub struct SerializeOptions {
+    /// Emit `\uXXXX` escapes for every character above U+007F
+    pub escape_non_ascii: bool,
+    /// Escape `<`, `>`, `&`, U+2028 and U+2029 so the output can be embedded
+    /// in a `<script>` tag or a JavaScript string literal
+    pub escape_html: bool,
+    /// Emit object members ordered by key instead of document order
+    pub sort_keys: bool,
+    /// Inserted once per nesting level in pretty output, e.g. `"  "` or `"\t"`
+    pub indent: String,
+    /// Column budget for pretty output: arrays and objects that fit within it
+    /// are kept on a single line, longer ones are broken up, with arrays of
+    /// scalars packed as many elements per line as fit
+    pub max_width: Option<usize>,
+    /// Highlight keys, strings, numbers, booleans and nulls with ANSI colors
+    pub color: bool,
+    pub control_chars: ControlChars,
+    /// Use the two-character escapes (`\n`, `\t`, ...) where JSON has one
+    /// instead of the `\u000a` form
+    pub short_escapes: bool,
+    /// End the document with a line ending, as most editors expect of files
+    pub trailing_newline: bool,
+    pub line_ending: LineEnding,
+    /// Quote strings with `'` instead of `"` in JSON5 output
+    pub single_quotes: bool,
+}
+
+impl Default for SerializeOptions {
+    fn default() -> Self {
+        SerializeOptions {
+            escape_non_ascii: false,
+            escape_html: false,
+            sort_keys: false,
+            indent: "  ".to_string(),
+            max_width: None,
+            color: false,
+            control_chars: ControlChars::Escape,
+            short_escapes: true,
+            trailing_newline: false,
+            line_ending: LineEnding::Lf,
+            single_quotes: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Compact,
+    Pretty,
+    /// Single-line pretty output, used to try containers against `max_width`
+    Inline,
+    Canonical,
+}
+
+/// Serialize a JSON value as compact text (no insignificant whitespace)
+pub fn to_string(value: &JsonValue) -> Result<String, SerializeError> {
+    to_string_with(value, &SerializeOptions::default())
+}
+
+/// Serialize a JSON value as compact text using the given options
+pub fn to_string_with(
+    value: &JsonValue,
+    options: &SerializeOptions,
+) -> Result<String, SerializeError> {
+    let mut out = vec![];
+    Serializer::new(&mut out, Mode::Compact, options.clone()).write_document(value)?;
+    Ok(into_string(out))
+}
+
+/// Append `value` as compact text to `out`, as a fragment of a larger document
+pub(crate) fn write_compact(
+    out: &mut Vec<u8>,
+    value: &JsonValue,
+    options: &SerializeOptions,
+) -> Result<(), SerializeError> {
+    Serializer::new(out, Mode::Compact, options.clone()).write_value(value)
+}
+
+/// Serialize a JSON value as compact UTF-8 bytes
+pub fn to_vec(value: &JsonValue) -> Result<Vec<u8>, SerializeError> {
+    let mut out = vec![];
+    Serializer::new(&mut out, Mode::Compact, SerializeOptions::default()).write_value(value)?;
+    Ok(out)
+}
+
+/// Serialize a JSON value as compact text straight into `writer`
+pub fn to_writer<W: Write>(mut writer: W, value: &JsonValue) -> Result<(), SerializeError> {
+    writer
+        .write_all(&to_vec(value)?)
+        .map_err(|e| SerializeError(format!("IO error: {}", e)))
+}
+
+/// Serialize only the subtree addressed by a JSON Pointer as compact text,
+/// e.g. `serialize_pointer(&value, "/data/items", writer)`
+pub fn serialize_pointer<W: Write>(
+    value: &JsonValue,
+    pointer: &str,
+    writer: W,
+) -> Result<(), SerializeError> {
+    let pointer = JsonPointer::parse(pointer).map_err(|e| SerializeError(e.0))?;
+    match pointer.get(value) {
+        Some(subtree) => to_writer(writer, subtree),
+        None => Err(SerializeError(format!(
+            "Serialize error: no value at '{}'",
+            pointer
+        ))),
+    }
+}
+
+/// Serialize a JSON value with one member or element per line
+pub fn to_string_pretty(value: &JsonValue) -> Result<String, SerializeError> {
+    to_string_pretty_with(value, &SerializeOptions::default())
+}
+
+/// Serialize a JSON value with one member or element per line using the given options
+pub fn to_string_pretty_with(
+    value: &JsonValue,
+    options: &SerializeOptions,
+) -> Result<String, SerializeError> {
+    let mut out = vec![];
+    Serializer::new(&mut out, Mode::Pretty, options.clone()).write_document(value)?;
+    Ok(into_string(out))
+}
+
+/// Serialize a JSON value as pretty JSON5, the dialect used by hand-edited configs
+/// Keys that are valid identifiers are left unquoted, every element and member is
+/// followed by a comma, and non-finite floats are written as `Infinity`/`NaN`.
+pub fn to_string_json5(value: &JsonValue) -> Result<String, SerializeError> {
+    to_string_json5_with(value, &SerializeOptions::default())
+}
+
+/// Serialize a JSON value as pretty JSON5 using the given options
+pub fn to_string_json5_with(
+    value: &JsonValue,
+    options: &SerializeOptions,
+) -> Result<String, SerializeError> {
+    let mut out = vec![];
+    let mut serializer = Serializer::new(&mut out, Mode::Pretty, options.clone());
+    serializer.json5 = true;
+    serializer.write_document(value)?;
+    Ok(into_string(out))
+}
+
+impl JsonValue {
+    /// Serialize as pretty JSON highlighted with ANSI colors for terminal output
+    pub fn to_string_colored(&self) -> Result<String, SerializeError> {
+        let options = SerializeOptions {
+            color: true,
+            ..SerializeOptions::default()
+        };
+        to_string_pretty_with(self, &options)
+    }
+
+    /// Serialize as canonical JSON according to RFC 8785 (JCS)
+    /// Keys are sorted by their UTF-16 code units, numbers use the ECMAScript
+    /// formatting rules and strings get the minimal escaping, so equal values
+    /// always produce byte-identical output.
+    /// Note: integers are formatted as IEEE 754 doubles, as the RFC mandates.
+    pub fn to_canonical_string(&self) -> Result<String, SerializeError> {
+        let mut out = vec![];
+        Serializer::new(&mut out, Mode::Canonical, SerializeOptions::default())
+            .write_value(self)?;
+        Ok(into_string(out))
+    }
+}
+
+const KEY_COLOR: &[u8] = b"\x1b[1;34m";
+const STRING_COLOR: &[u8] = b"\x1b[32m";
+const NUMBER_COLOR: &[u8] = b"\x1b[33m";
+const BOOL_COLOR: &[u8] = b"\x1b[35m";
+const NULL_COLOR: &[u8] = b"\x1b[90m";
+const RESET: &[u8] = b"\x1b[0m";
+
+fn color_of(value: &JsonValue) -> Option<&'static [u8]> {
+    match value {
+        JsonValue::Str(_) => Some(STRING_COLOR),
+        JsonValue::Float(_, _) | JsonValue::Int(_) => Some(NUMBER_COLOR),
+        JsonValue::Bool(_) => Some(BOOL_COLOR),
+        JsonValue::Null => Some(NULL_COLOR),
+        _ => None,
+    }
+}
+
+/// Whether `key` can be written bare in JSON5, i.e. is an ASCII identifier
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn is_scalar(value: &JsonValue) -> bool {
+    !matches!(
+        value,
+        JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Arr(_) | JsonValue::Empty
+    )
+}
+
+/// Count the characters that take up a column, i.e. skip UTF-8
+/// continuation bytes and ANSI color sequences
+fn char_count(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    let mut in_escape = false;
+    for b in bytes {
+        match b {
+            0x1b => in_escape = true,
+            b'm' if in_escape => in_escape = false,
+            _ if in_escape => (),
+            b if (*b as i8) >= -0x40 => count += 1,
+            _ => (),
+        }
+    }
+    count
+}
+
+/// The serializer only ever writes whole UTF-8 sequences
+fn into_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).expect("serializer output is valid UTF-8")
+}
+
+/// What is left to write of a container once it is opened
+enum Step<'v> {
+    /// An element or member, after a comma unless it is the first
+    Item {
+        first: bool,
+        key: Option<&'v str>,
+        value: &'v JsonValue,
+    },
+    /// The closing bracket
+    Close(u8),
+}
+
+struct Serializer<'a> {
+    out: &'a mut Vec<u8>,
+    mode: Mode,
+    options: SerializeOptions,
+    depth: usize,
+    /// Output length past which inline rendering gives up early
+    budget: usize,
+    json5: bool,
+}
+
+impl<'a> Serializer<'a> {
+    fn new(out: &'a mut Vec<u8>, mode: Mode, options: SerializeOptions) -> Self {
+        Serializer {
+            out,
+            mode,
+            options,
+            depth: 0,
+            budget: usize::MAX,
+            json5: false,
+        }
+    }
+
+    /// Write a top-level value, followed by the final newline if requested
+    fn write_document(&mut self, value: &JsonValue) -> Result<(), SerializeError> {
+        self.write_value(value)?;
+        if self.options.trailing_newline {
+            self.out
+                .extend_from_slice(self.options.line_ending.as_bytes());
+        }
+        Ok(())
+    }
+
+    /// Write `value`, taking nested containers from a heap stack of steps
+    /// rather than by recursion, so that deeply nested trees cannot overflow
+    /// the stack
+    fn write_value(&mut self, value: &JsonValue) -> Result<(), SerializeError> {
+        let mut steps = vec![];
+        self.open(value, &mut steps)?;
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Item { first, key, value } => {
+                    if !first {
+                        self.out.push(b',');
+                    }
+                    self.write_newline();
+                    if let Some(key) = key {
+                        self.write_key(key)?;
+                    }
+                    self.open(value, &mut steps)?;
+                }
+                Step::Close(bracket) => {
+                    self.write_trailing_comma();
+                    self.depth -= 1;
+                    self.write_newline();
+                    self.out.push(bracket);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a scalar or empty container whole, or open a container, leaving
+    /// its elements and closing bracket on `steps`
+    fn open<'v>(
+        &mut self,
+        value: &'v JsonValue,
+        steps: &mut Vec<Step<'v>>,
+    ) -> Result<(), SerializeError> {
+        if self.mode == Mode::Inline && self.out.len() > self.budget {
+            return Ok(());
+        }
+        if let (Mode::Pretty, Some(width)) = (self.mode, self.options.max_width) {
+            if self.write_inline(value, width)? {
+                return Ok(());
+            }
+        }
+
+        match value {
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) => {
+                let mut members = value.members().collect::<Vec<_>>();
+                if self.mode == Mode::Canonical {
+                    members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+                } else if self.options.sort_keys {
+                    members.sort_by_key(|(key, _)| *key);
+                }
+
+                if members.is_empty() {
+                    self.out.extend_from_slice(b"{}");
+                    return Ok(());
+                }
+                self.out.push(b'{');
+                self.depth += 1;
+                steps.push(Step::Close(b'}'));
+                steps.extend(
+                    members
+                        .into_iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, (key, value))| Step::Item {
+                            first: i == 0,
+                            key: Some(key),
+                            value,
+                        }),
+                );
+                return Ok(());
+            }
+            JsonValue::Arr(values) if !values.is_empty() => {
+                if let (Mode::Pretty, Some(width)) = (self.mode, self.options.max_width) {
+                    if values.iter().all(is_scalar) {
+                        return self.write_filled(values, width);
+                    }
+                }
+
+                self.out.push(b'[');
+                self.depth += 1;
+                steps.push(Step::Close(b']'));
+                steps.extend(
+                    values
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .map(|(i, value)| Step::Item {
+                            first: i == 0,
+                            key: None,
+                            value,
+                        }),
+                );
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let color = if self.options.color {
+            color_of(value)
+        } else {
+            None
+        };
+        if let Some(code) = color {
+            self.out.extend_from_slice(code);
+        }
+
+        let result = match value {
+            JsonValue::Float(f, raw) => match raw {
+                // Reuse the authored lexeme unless the value has since been changed,
+                // and only if it is a JSON number, as one built by hand may not be
+                Some(raw)
+                    if self.mode != Mode::Canonical
+                        && is_number(raw)
+                        && raw.parse::<f64>().map(f64::to_bits) == Ok(f.to_bits()) =>
+                {
+                    self.out.extend_from_slice(raw.as_bytes());
+                    Ok(())
+                }
+                _ => self.write_float(*f),
+            },
+            JsonValue::Int(i) => {
+                if self.mode == Mode::Canonical {
+                    self.write_float(*i as f64)
+                } else {
+                    write_int(self.out, *i);
+                    Ok(())
+                }
+            }
+            JsonValue::Str(s) => self.write_string(s),
+            JsonValue::Bool(b) => {
+                self.out
+                    .extend_from_slice(if *b { b"true" } else { b"false" });
+                Ok(())
+            }
+            JsonValue::Arr(_) => {
+                self.out.extend_from_slice(b"[]");
+                Ok(())
+            }
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty => {
+                self.out.extend_from_slice(b"{}");
+                Ok(())
+            }
+            JsonValue::Null => {
+                self.out.extend_from_slice(b"null");
+                Ok(())
+            }
+        };
+
+        if color.is_some() {
+            self.out.extend_from_slice(RESET);
+        }
+        result
+    }
+
+    /// Write the key of a member and the colon after it
+    fn write_key(&mut self, key: &str) -> Result<(), SerializeError> {
+        if self.options.color {
+            self.out.extend_from_slice(KEY_COLOR);
+        }
+        if self.json5 && is_identifier(key) {
+            self.out.extend_from_slice(key.as_bytes());
+        } else {
+            self.write_string(key)?;
+        }
+        if self.options.color {
+            self.out.extend_from_slice(RESET);
+        }
+        self.out.push(b':');
+        if self.mode == Mode::Pretty || self.mode == Mode::Inline {
+            self.out.push(b' ');
+        }
+        Ok(())
+    }
+
+    /// Write a non-empty container on one line if it fits in the rest of the line
+    fn write_inline(&mut self, value: &JsonValue, width: usize) -> Result<bool, SerializeError> {
+        let container = match value {
+            JsonValue::Object(members) | JsonValue::Arr(members) => !members.is_empty(),
+            JsonValue::KeyedObject(_, _) => true,
+            _ => false,
+        };
+        if !container {
+            return Ok(false);
+        }
+
+        let line_start = self
+            .out
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        let column = char_count(&self.out[line_start..]);
+        let available = width.saturating_sub(column);
+
+        // Any char is at most 4 bytes, so past this the line cannot fit anymore
+        let budget = available * 4;
+        let mut line = vec![];
+        let mut inline = Serializer::new(&mut line, Mode::Inline, self.options.clone());
+        inline.budget = budget;
+        inline.json5 = self.json5;
+        inline.write_value(value)?;
+
+        // Leave room for the comma that may follow
+        if line.len() <= budget && char_count(&line) < available {
+            self.out.extend_from_slice(&line);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Write an array of scalars packing as many elements per line as fit
+    fn write_filled(&mut self, values: &[JsonValue], width: usize) -> Result<(), SerializeError> {
+        self.out.push(b'[');
+        self.depth += 1;
+        self.write_newline();
+        let mut column = self.indent_width();
+        for (i, value) in values.iter().enumerate() {
+            let mut item = vec![];
+            let mut inline = Serializer::new(&mut item, Mode::Inline, self.options.clone());
+            inline.json5 = self.json5;
+            inline.write_value(value)?;
+            let len = char_count(&item);
+
+            if i > 0 {
+                self.out.push(b',');
+                if column + 2 + len < width {
+                    self.out.push(b' ');
+                    column += 2;
+                } else {
+                    self.write_newline();
+                    column = self.indent_width();
+                }
+            }
+            self.out.extend_from_slice(&item);
+            column += len;
+        }
+        self.write_trailing_comma();
+        self.depth -= 1;
+        self.write_newline();
+        self.out.push(b']');
+        Ok(())
+    }
+
+    /// JSON5 allows a comma after the last element, which keeps diffs of
+    /// multi-line containers to the lines that actually changed
+    fn write_trailing_comma(&mut self) {
+        if self.json5 && self.mode == Mode::Pretty {
+            self.out.push(b',');
+        }
+    }
+
+    /// Columns taken up by the indentation at the current depth, counting a tab as one
+    fn indent_width(&self) -> usize {
+        self.depth * self.options.indent.chars().count()
+    }
+
+    /// Break the line and indent to the current depth, in pretty mode only
+    fn write_newline(&mut self) {
+        match self.mode {
+            Mode::Pretty => {
+                self.out
+                    .extend_from_slice(self.options.line_ending.as_bytes());
+                for _ in 0..self.depth {
+                    self.out.extend_from_slice(self.options.indent.as_bytes());
+                }
+            }
+            Mode::Inline if self.out.last() == Some(&b',') => self.out.push(b' '),
+            _ => (),
+        }
+    }
+
+    fn write_float(&mut self, f: f64) -> Result<(), SerializeError> {
+        if !f.is_finite() && self.json5 {
+            let text: &[u8] = if f.is_nan() {
+                b"NaN"
+            } else if f > 0.0 {
+                b"Infinity"
+            } else {
+                b"-Infinity"
+            };
+            self.out.extend_from_slice(text);
+            return Ok(());
+        }
+        if !f.is_finite() {
+            return Err(SerializeError(format!(
+                "Serialize error: {} cannot be represented in JSON",
+                f
+            )));
+        }
+
+        let text = match self.mode {
+            Mode::Compact | Mode::Pretty | Mode::Inline => format_float(f),
+            Mode::Canonical => es_number(f),
+        };
+        self.out.extend_from_slice(text.as_bytes());
+        Ok(())
+    }
+
+    fn write_string(&mut self, s: &str) -> Result<(), SerializeError> {
+        let quote = if self.json5 && self.options.single_quotes {
+            b'\''
+        } else {
+            b'"'
+        };
+        write_quoted(self.out, s, &self.options, quote)
+    }
+}
+
+/// Write `s` as a quoted JSON string, escaping only what JSON and `options` require
+/// Runs of characters that need no escaping are copied over as-is.
+pub(crate) fn write_string(
+    out: &mut Vec<u8>,
+    s: &str,
+    options: &SerializeOptions,
+) -> Result<(), SerializeError> {
+    write_quoted(out, s, options, b'"')
+}
+
+fn write_quoted(
+    out: &mut Vec<u8>,
+    s: &str,
+    options: &SerializeOptions,
+    quote: u8,
+) -> Result<(), SerializeError> {
+    out.push(quote);
+    let mut run = 0;
+    for (i, c) in s.char_indices() {
+        if (c as u32) < 0x20 && options.control_chars == ControlChars::Error {
+            return Err(SerializeError(format!(
+                "Serialize error: control character U+{:04X} in string",
+                c as u32
+            )));
+        }
+
+        // An empty escape stands for the `\uXXXX` form
+        let escape: &[u8] = match c {
+            '"' if quote == b'"' => b"\\\"",
+            '\'' if quote == b'\'' => b"\\'",
+            '\\' => b"\\\\",
+            '\u{08}' if options.short_escapes => b"\\b",
+            '\u{0c}' if options.short_escapes => b"\\f",
+            '\n' if options.short_escapes => b"\\n",
+            '\r' if options.short_escapes => b"\\r",
+            '\t' if options.short_escapes => b"\\t",
+            c if (c as u32) < 0x20 => b"",
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' if options.escape_html => b"",
+            c if !c.is_ascii() && options.escape_non_ascii => b"",
+            _ => continue,
+        };
+
+        out.extend_from_slice(&s.as_bytes()[run..i]);
+        run = i + c.len_utf8();
+        if escape.is_empty() {
+            // Characters outside the BMP become a UTF-16 surrogate pair
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                write_unicode_escape(out, *unit);
+            }
+        } else {
+            out.extend_from_slice(escape);
+        }
+    }
+    out.extend_from_slice(&s.as_bytes()[run..]);
+    out.push(quote);
+    Ok(())
+}
+
+fn write_unicode_escape(out: &mut Vec<u8>, unit: u16) {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    out.extend_from_slice(b"\\u");
+    for shift in [12, 8, 4, 0] {
+        out.push(HEX[(unit >> shift) as usize & 0xf]);
+    }
+}
+
+/// Write the decimal digits of `i` without going through `fmt`
+pub(crate) fn write_int(out: &mut Vec<u8>, i: i64) {
+    let mut buf = [0; 20];
+    let mut pos = buf.len();
+    let mut n = i.unsigned_abs();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+
+    if i < 0 {
+        out.push(b'-');
+    }
+    out.extend_from_slice(&buf[pos..]);
+}
+
+/// Format a finite double the way the compact and pretty serializers print it
+/// This is the shortest text that parses back to the same value, always with a
+/// fraction or exponent so it reads back as a float, e.g. `2.0`, `0.1`, `1e300`
+pub(crate) fn format_float(f: f64) -> FloatText {
+    let mut text = FloatText::default();
+    if f.is_sign_negative() {
+        text.push(b"-");
+    }
+    if f == 0.0 {
+        text.push(b"0.0");
+        return text;
+    }
+
+    let (digits, n) = shortest_digits(f.abs());
+    let digits = digits.as_bytes();
+    let k = digits.len() as i32;
+    if k <= n && n <= 16 {
+        text.push(digits);
+        text.zeros(n - k);
+        text.push(b".0");
+    } else if 0 < n && n <= 16 {
+        let (int, frac) = digits.split_at(n as usize);
+        text.push(int);
+        text.push(b".");
+        text.push(frac);
+    } else if -5 < n && n <= 0 {
+        text.push(b"0.");
+        text.zeros(-n);
+        text.push(digits);
+    } else {
+        text.exponent(digits, n - 1, false);
+    }
+    text
+}
+
+/// Format a finite double the way ECMAScript's `Number.prototype.toString` does
+fn es_number(f: f64) -> FloatText {
+    let mut text = FloatText::default();
+    if f == 0.0 {
+        // Covers -0 as well
+        text.push(b"0");
+        return text;
+    }
+    if f < 0.0 {
+        text.push(b"-");
+    }
+
+    let (digits, n) = shortest_digits(f.abs());
+    let digits = digits.as_bytes();
+    let k = digits.len() as i32;
+    if k <= n && n <= 21 {
+        text.push(digits);
+        text.zeros(n - k);
+    } else if 0 < n && n <= 21 {
+        let (int, frac) = digits.split_at(n as usize);
+        text.push(int);
+        text.push(b".");
+        text.push(frac);
+    } else if -6 < n && n <= 0 {
+        text.push(b"0.");
+        text.zeros(-n);
+        text.push(digits);
+    } else {
+        text.exponent(digits, n - 1, true);
+    }
+    text
+}
+
+/// The text of a formatted float, kept on the stack as floats are formatted
+/// once for every one parsed or written
+/// Long enough for a sign, 17 digits, the zeros before them and an exponent.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct FloatText {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl FloatText {
+    fn push(&mut self, bytes: &[u8]) {
+        self.bytes[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn zeros(&mut self, n: i32) {
+        for _ in 0..n {
+            self.push(b"0");
+        }
+    }
+
+    fn uint(&mut self, mut n: u64) {
+        let mut buf = [0; 20];
+        let mut pos = buf.len();
+        loop {
+            pos -= 1;
+            buf[pos] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push(&buf[pos..]);
+    }
+
+    /// `digits` as `d.ddde<e>`, with a `+` for positive exponents if `plus`
+    fn exponent(&mut self, digits: &[u8], e: i32, plus: bool) {
+        self.push(&digits[..1]);
+        if digits.len() > 1 {
+            self.push(b".");
+            self.push(&digits[1..]);
+        }
+        self.push(b"e");
+        if e < 0 {
+            self.push(b"-");
+        } else if plus {
+            self.push(b"+");
+        }
+        self.uint(e.unsigned_abs() as u64);
+    }
+}
+
+impl std::ops::Deref for FloatText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // Only ASCII is ever pushed
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Write for FloatText {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        if self.len + s.len() > self.bytes.len() {
+            return Err(std::fmt::Error);
+        }
+        self.push(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// Split a finite, positive double into its shortest round-tripping decimal
+/// digits and the exponent `n` such that the value is `0.digits * 10^n`
+fn shortest_digits(f: f64) -> (FloatText, i32) {
+    let (mantissa, exp) = shortest(f);
+    let mut digits = FloatText::default();
+    digits.uint(mantissa);
+    (digits, exp + digits.len as i32)
+}
+
+#[cfg(feature = "ryu")]
+use crate::ryu::shortest;
+
+/// The shortest `m` and `e` such that `m * 10^e` reads back as the finite,
+/// positive double `f`
+#[cfg(not(feature = "ryu"))]
+fn shortest(f: f64) -> (u64, i32) {
+    // `{:e}` uses std's shortest mode (Grisu with a Dragon fallback), e.g. `1.2345e-7`
+    let mut sci = FloatText::default();
+    let _ = std::fmt::Write::write_fmt(&mut sci, format_args!("{:e}", f));
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let (mut m, mut k) = (0, 0);
+    for digit in mantissa.bytes().filter(u8::is_ascii_digit) {
+        m = m * 10 + (digit - b'0') as u64;
+        k += 1;
+    }
+    (m, exp.parse::<i32>().unwrap_or(0) - (k - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn float(f: f64, raw: &str) -> JsonValue {
+        JsonValue::Arr(vec![JsonValue::Float(f, Some(Box::new(raw.into())))].into())
+    }
+
+    #[test]
+    fn keeps_authored_lexemes() {
+        let value = Parser::new(Tokenizer::new().tokens("[1.50,1e3,-0]"))
+            .parse()
+            .unwrap();
+        assert_eq!(to_string(&value).unwrap(), "[1.50,1e3,-0]");
+    }
+
+    #[test]
+    fn strings_round_trip_through_every_parser() {
+        let mut text = (0u8..0x20).map(char::from).collect::<String>();
+        text.push_str("\"\\/'<>& é\u{7f}\u{2028}\u{2029}\u{ffff}😀");
+        let options = [
+            SerializeOptions::default(),
+            SerializeOptions {
+                escape_non_ascii: true,
+                escape_html: true,
+                ..Default::default()
+            },
+            SerializeOptions {
+                short_escapes: false,
+                ..Default::default()
+            },
+        ];
+        // The first key and the value of `k`, which both hold the text
+        let decoded = |value: &JsonValue| {
+            let object = value.pointer("/0").unwrap();
+            let key = object.members().next().unwrap().0;
+            assert_eq!(key, text);
+            match object.pointer("/k") {
+                Some(JsonValue::Str(s)) => s.to_string(),
+                other => panic!("no string in {:?}", other),
+            }
+        };
+        for options in options {
+            let member = JsonValue::KeyedObject(text.as_str().into(), Box::new(JsonValue::Null));
+            let value = JsonValue::Arr(
+                vec![JsonValue::Object(
+                    vec![
+                        member,
+                        JsonValue::KeyedObject(
+                            "k".into(),
+                            Box::new(JsonValue::Str(text.as_str().into())),
+                        ),
+                    ]
+                    .into(),
+                )]
+                .into(),
+            );
+            let mut writer = crate::writer::JsonWriter::with_options(vec![], options.clone());
+            writer.value(&value).unwrap();
+            let written = String::from_utf8(writer.finish().unwrap()).unwrap();
+            let json = to_string_with(&value, &options).unwrap();
+            assert_eq!(json, written);
+
+            let parsed = Parser::new(Tokenizer::new().tokens(&json)).parse().unwrap();
+            assert_eq!(decoded(&parsed), text);
+            assert_eq!(to_string_with(&parsed, &options).unwrap(), json);
+            let borrowed = crate::borrowed::BorrowedValue::parse(&json).unwrap();
+            assert_eq!(decoded(&borrowed.into_json()), text);
+            let tape = crate::tape::Tape::parse(&json).unwrap();
+            assert_eq!(decoded(&tape.root().to_json()), text);
+            let streamed = crate::stream::JsonReader::new(json.as_bytes())
+                .read_value()
+                .unwrap();
+            assert_eq!(to_string_with(&streamed, &options).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn canonicalizes_decoded_strings() {
+        let canonical = |text: &str| {
+            Parser::new(Tokenizer::new().tokens(text))
+                .parse()
+                .unwrap()
+                .to_canonical_string()
+                .unwrap()
+        };
+        let expected = r#"{"A":"A","a":"\u001f/\n"}"#;
+        assert_eq!(canonical(r#"{"a":"\u001f\/\n","A":"A"}"#), expected);
+        assert_eq!(
+            canonical(r#"{"a":"\u001F/\u000a","\u0041":"\u0041"}"#),
+            expected
+        );
+    }
+
+    #[test]
+    fn never_writes_a_lexeme_that_is_no_json_number() {
+        assert_eq!(to_string(&float(1.0, "1.")).unwrap(), "[1.0]");
+        assert_eq!(to_string(&float(1.0, "01")).unwrap(), "[1.0]");
+        assert_eq!(to_string(&float(1.0, " 1")).unwrap(), "[1.0]");
+        assert_eq!(to_string(&float(1.0, "1.0e0")).unwrap(), "[1.0e0]");
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashSet;
+
+use crate::hash::KeyHasher;
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+
+/// Shape of a document, as summarized by `JsonValue::stats`
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub integers: usize,
+    pub floats: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    /// Levels of nesting, 1 for a flat object or array
+    pub max_depth: usize,
+    /// Object members across the whole document
+    pub keys: usize,
+    /// The array with the most elements and its length
+    pub largest_array: Option<(JsonPointer, usize)>,
+    /// UTF-8 bytes of all string values, keys not included
+    pub string_bytes: usize,
+    /// Every member whose key already appeared earlier in the same object
+    pub duplicate_keys: Vec<JsonPointer>,
+}
+
+impl Stats {
+    /// All values, containers included
+    pub fn nodes(&self) -> usize {
+        self.objects
+            + self.arrays
+            + self.strings
+            + self.integers
+            + self.floats
+            + self.booleans
+            + self.nulls
+    }
+}
+
+impl JsonValue {
+    /// Count the values of a document by type, along with its depth, size and
+    /// any duplicate keys, e.g. to get a feel for a dump nobody documented
+    pub fn stats(&self) -> Stats {
+        self.stats_with_hasher(KeyHasher::default())
+    }
+
+    /// `stats`, finding duplicate keys through a table hashed with `hasher`, e.g.
+    /// `KeyHasher::resistant()` for documents from untrusted sources
+    pub fn stats_with_hasher(&self, hasher: KeyHasher) -> Stats {
+        let mut stats = Stats::default();
+        let mut seen = HashSet::with_hasher(hasher);
+        for (pointer, value) in self.all_paths() {
+            match value {
+                JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty => {
+                    stats.objects += 1;
+                    stats.max_depth = stats.max_depth.max(pointer.tokens().len() + 1);
+                    seen.clear();
+                    for (key, _) in value.members() {
+                        stats.keys += 1;
+                        if !seen.insert(key) {
+                            stats.duplicate_keys.push(pointer.join(key));
+                        }
+                    }
+                }
+                JsonValue::Arr(values) => {
+                    stats.arrays += 1;
+                    stats.max_depth = stats.max_depth.max(pointer.tokens().len() + 1);
+                    if stats
+                        .largest_array
+                        .as_ref()
+                        .is_none_or(|(_, len)| values.len() > *len)
+                    {
+                        stats.largest_array = Some((pointer, values.len()));
+                    }
+                }
+                JsonValue::Str(s) => {
+                    stats.strings += 1;
+                    stats.string_bytes += s.len();
+                }
+                JsonValue::Int(_) => stats.integers += 1,
+                JsonValue::Float(_, _) => stats.floats += 1,
+                JsonValue::Bool(_) => stats.booleans += 1,
+                JsonValue::Null => stats.nulls += 1,
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    #[test]
+    fn counts_values_and_duplicate_keys() {
+        let doc = json(r#"{"a": [1, 2.5, "xy"], "b": {"c": null, "c": true}, "a": []}"#);
+        for hasher in [KeyHasher::default(), KeyHasher::resistant()] {
+            let stats = doc.stats_with_hasher(hasher);
+            assert_eq!(stats.nodes(), 9);
+            assert_eq!((stats.objects, stats.arrays, stats.keys), (2, 2, 5));
+            assert_eq!(stats.max_depth, 2);
+            assert_eq!(stats.string_bytes, 2);
+            let duplicates = stats.duplicate_keys.iter().map(|p| p.to_string());
+            assert_eq!(duplicates.collect::<Vec<_>>(), ["/a", "/b/c"]);
+        }
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read};
+
+use crate::arena::Interner;
+use crate::escape::escape;
+use crate::parse::{number_value, JsonValue, Key, ParseErrorKind, SyntaxError};
+use crate::pointer::JsonPointer;
+use crate::tokenize::Position;
+use crate::writer::is_number;
+
+/// One step of a document as reported by [`JsonReader`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// The key of the next object member
+    Key(String),
+    Str(String),
+    /// A number as its JSON lexeme, e.g. `-1.5e3`
+    Number(String),
+    Bool(bool),
+    Null,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Container {
+    Object,
+    Array,
+}
+
+/// What may come next
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Document,
+    /// A key or the end of an empty object
+    FirstKey,
+    Key,
+    /// A value or the end of an empty array
+    FirstElement,
+    Value,
+    /// A comma or the end of the container
+    AfterValue,
+    Done,
+}
+
+/// Pull parser reporting a document as a sequence of events
+/// Only the stack of open containers is kept, so documents much larger than
+/// memory can be validated, or re-serialized by feeding a `JsonWriter`.
+pub struct JsonReader<R: Read> {
+    input: BufReader<R>,
+    stack: Vec<Container>,
+    state: State,
+    line: usize,
+    col: usize,
+    /// Where the last event began
+    start: (usize, usize),
+}
+
+/// Parse the elements of a document that is an array one at a time, e.g. a
+/// `[{...}, {...}, ...]` too large to hold whole
+pub fn stream_array<R: Read>(input: R) -> ArrayElements<R> {
+    ArrayElements {
+        reader: JsonReader::new(input),
+        keys: Interner::default(),
+        started: false,
+        done: false,
+    }
+}
+
+/// Iterator over the elements of an array, made by [`stream_array`]
+/// Only the element being read is held, with the keys shared across elements.
+/// An error, e.g. for a document that is not an array, is the last item.
+pub struct ArrayElements<R: Read> {
+    reader: JsonReader<R>,
+    keys: Interner,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> ArrayElements<R> {
+    /// The next element, or `None` once the array and the document end
+    fn element(&mut self) -> Result<Option<JsonValue>, SyntaxError> {
+        if !self.started {
+            self.started = true;
+            if self.reader.next_event()? != Some(Event::StartArray) {
+                return Err(self
+                    .reader
+                    .err(ParseErrorKind::UnexpectedToken, "expected an array"));
+            }
+        }
+        if self.reader.at_array_end()? {
+            self.reader.next_event()?;
+            // Anything after the array is an error
+            return self.reader.next_event().map(|_| None);
+        }
+        self.reader.read_value_with(&mut self.keys).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for ArrayElements<R> {
+    type Item = Result<JsonValue, SyntaxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let element = self.element().transpose();
+        self.done = !matches!(element, Some(Ok(_)));
+        element
+    }
+}
+
+/// A partially read container in [`JsonReader::read_value`]
+enum Partial {
+    Object(Vec<(Key, JsonValue)>, Option<Key>),
+    Array(Vec<JsonValue>),
+}
+
+impl<R: Read> JsonReader<R> {
+    pub fn new(input: R) -> Self {
+        JsonReader {
+            input: BufReader::new(input),
+            stack: vec![],
+            state: State::Document,
+            line: 1,
+            col: 0,
+            start: (1, 1),
+        }
+    }
+
+    /// Line and column where the last event began, counting from 1
+    /// For a `Key` event that is its opening quote.
+    pub fn position(&self) -> (usize, usize) {
+        self.start
+    }
+
+    /// The next event, or `None` once the document is complete
+    /// Errors are final: the reader should not be used after one.
+    pub fn next_event(&mut self) -> Result<Option<Event>, SyntaxError> {
+        loop {
+            self.skip_whitespace()?;
+            self.start = (self.line, self.col + 1);
+            let Some(c) = self.bump()? else {
+                return match self.state {
+                    State::Done => Ok(None),
+                    _ => Err(SyntaxError::eof()),
+                };
+            };
+
+            let event = match self.state {
+                State::Done => {
+                    return Err(self.err(
+                        ParseErrorKind::TrailingCharacters,
+                        "unexpected data after the document",
+                    ))
+                }
+                State::Document => self.value(c)?,
+                State::FirstKey if c == b'}' => self.close(),
+                State::FirstKey | State::Key => match c {
+                    b'"' => {
+                        let key = self.string()?;
+                        self.skip_whitespace()?;
+                        if self.bump()? != Some(b':') {
+                            return Err(self.err(
+                                ParseErrorKind::UnexpectedToken,
+                                "expected COLON after the key",
+                            ));
+                        }
+                        self.state = State::Value;
+                        Event::Key(key)
+                    }
+                    _ => {
+                        return Err(self.err(
+                            ParseErrorKind::UnexpectedToken,
+                            "expected QUOTE to start a key",
+                        ))
+                    }
+                },
+                State::FirstElement if c == b']' => self.close(),
+                State::FirstElement | State::Value => self.value(c)?,
+                State::AfterValue => match (self.stack.last(), c) {
+                    (Some(Container::Object), b',') => {
+                        self.state = State::Key;
+                        continue;
+                    }
+                    (Some(Container::Array), b',') => {
+                        self.state = State::Value;
+                        continue;
+                    }
+                    (Some(Container::Object), b'}') | (Some(Container::Array), b']') => {
+                        self.close()
+                    }
+                    (Some(Container::Object), _) => {
+                        return Err(self.err(
+                            ParseErrorKind::UnexpectedToken,
+                            "expected COMMA or RIGHT_CURLY",
+                        ))
+                    }
+                    _ => {
+                        return Err(self.err(
+                            ParseErrorKind::UnexpectedToken,
+                            "expected COMMA or RIGHT_BRACKET",
+                        ))
+                    }
+                },
+            };
+            return Ok(Some(event));
+        }
+    }
+
+    /// Read the next value whole, e.g. the subtree after a `Key` event
+    pub fn read_value(&mut self) -> Result<JsonValue, SyntaxError> {
+        self.read_value_with(&mut Interner::default())
+    }
+
+    /// `read_value` sharing keys with the values read before through `keys`
+    fn read_value_with(&mut self, keys: &mut Interner) -> Result<JsonValue, SyntaxError> {
+        let mut open: Vec<Partial> = vec![];
+        loop {
+            let event = self
+                .next_event()?
+                .ok_or_else(|| self.err(ParseErrorKind::UnexpectedToken, "expected a value"))?;
+            let value = match event {
+                Event::StartObject => {
+                    open.push(Partial::Object(vec![], None));
+                    continue;
+                }
+                Event::StartArray => {
+                    open.push(Partial::Array(vec![]));
+                    continue;
+                }
+                Event::Key(key) => {
+                    if let Some(Partial::Object(_, pending)) = open.last_mut() {
+                        *pending = Some(keys.intern(&key));
+                    }
+                    continue;
+                }
+                Event::EndObject | Event::EndArray => match open.pop() {
+                    Some(Partial::Object(members, _)) => JsonValue::from_members(members),
+                    Some(Partial::Array(values)) => JsonValue::Arr(values.into()),
+                    None => {
+                        return Err(self.err(ParseErrorKind::UnexpectedToken, "expected a value"))
+                    }
+                },
+                Event::Str(s) => JsonValue::Str(s.into()),
+                Event::Number(num) => number_value(&num)
+                    .map_err(|msg| self.err(ParseErrorKind::InvalidNumber, msg))?,
+                Event::Bool(b) => JsonValue::Bool(b),
+                Event::Null => JsonValue::Null,
+            };
+
+            match open.last_mut() {
+                None => return Ok(value),
+                Some(Partial::Object(members, pending)) => {
+                    members.push((pending.take().unwrap_or_default(), value))
+                }
+                Some(Partial::Array(values)) => values.push(value),
+            }
+        }
+    }
+
+    /// Read only the value at `pointer`, skipping over everything before it
+    /// Must be called before the first event; the rest of the document is left unread.
+    pub fn read_pointer(
+        &mut self,
+        pointer: &JsonPointer,
+    ) -> Result<Option<JsonValue>, SyntaxError> {
+        for token in pointer.tokens() {
+            match self.next_event()? {
+                Some(Event::StartObject) => loop {
+                    match self.next_event()? {
+                        Some(Event::Key(key)) if key == *token => break,
+                        Some(Event::Key(_)) => self.skip_value()?,
+                        _ => return Ok(None),
+                    }
+                },
+                Some(Event::StartArray) => {
+                    let is_index = token.bytes().all(|b| b.is_ascii_digit())
+                        && (token == "0" || !token.starts_with('0'));
+                    let Some(index) = token.parse::<usize>().ok().filter(|_| is_index) else {
+                        return Ok(None);
+                    };
+                    for _ in 0..index {
+                        if self.at_array_end()? {
+                            return Ok(None);
+                        }
+                        self.skip_value()?;
+                    }
+                    if self.at_array_end()? {
+                        return Ok(None);
+                    }
+                }
+                _ => return Ok(None),
+            }
+        }
+        self.read_value().map(Some)
+    }
+
+    /// Find the line and column where each value addressed by `pointers` begins,
+    /// reading the document once; pointers to nothing are left out
+    /// e.g. to point at the place of each schema violation.
+    pub fn locate(
+        &mut self,
+        pointers: &[JsonPointer],
+    ) -> Result<HashMap<JsonPointer, (usize, usize)>, SyntaxError> {
+        /// Where the next value goes: after an object key or at an array index
+        enum Slot {
+            Key(String),
+            Index(Option<usize>),
+        }
+
+        let wanted = pointers.iter().collect::<HashSet<_>>();
+        let mut found = HashMap::new();
+        let mut slots: Vec<Slot> = vec![];
+        while let Some(event) = self.next_event()? {
+            match event {
+                Event::Key(key) => {
+                    if let Some(slot) = slots.last_mut() {
+                        *slot = Slot::Key(key);
+                    }
+                    continue;
+                }
+                Event::EndObject | Event::EndArray => {
+                    slots.pop();
+                    continue;
+                }
+                _ => (),
+            }
+
+            if let Some(Slot::Index(index)) = slots.last_mut() {
+                *index = Some(index.map_or(0, |i| i + 1));
+            }
+            let mut pointer = JsonPointer::default();
+            for slot in &slots {
+                match slot {
+                    Slot::Key(key) => pointer.push(key.clone()),
+                    Slot::Index(index) => pointer.push(index.unwrap_or(0).to_string()),
+                }
+            }
+            if wanted.contains(&pointer) {
+                found.insert(pointer, self.start);
+            }
+
+            match event {
+                Event::StartObject => slots.push(Slot::Key(String::new())),
+                Event::StartArray => slots.push(Slot::Index(None)),
+                _ => (),
+            }
+        }
+        Ok(found)
+    }
+
+    /// Whether the next event would end the current array
+    fn at_array_end(&mut self) -> Result<bool, SyntaxError> {
+        self.skip_whitespace()?;
+        let in_array = matches!(self.state, State::FirstElement | State::AfterValue)
+            && self.stack.last() == Some(&Container::Array);
+        Ok(in_array && self.peek()? == Some(b']'))
+    }
+
+    /// Read past the next value without keeping it
+    pub fn skip_value(&mut self) -> Result<(), SyntaxError> {
+        let mut depth = 0usize;
+        loop {
+            match self
+                .next_event()?
+                .ok_or_else(|| self.err(ParseErrorKind::UnexpectedToken, "expected a value"))?
+            {
+                Event::StartObject | Event::StartArray => depth += 1,
+                Event::EndObject | Event::EndArray if depth == 0 => {
+                    return Err(self.err(ParseErrorKind::UnexpectedToken, "expected a value"))
+                }
+                Event::EndObject | Event::EndArray => depth -= 1,
+                Event::Key(_) => continue,
+                _ => (),
+            }
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Start the value whose first byte is `c`
+    fn value(&mut self, c: u8) -> Result<Event, SyntaxError> {
+        let event = match c {
+            b'{' => {
+                self.stack.push(Container::Object);
+                self.state = State::FirstKey;
+                return Ok(Event::StartObject);
+            }
+            b'[' => {
+                self.stack.push(Container::Array);
+                self.state = State::FirstElement;
+                return Ok(Event::StartArray);
+            }
+            b'"' => Event::Str(self.string()?),
+            b'-' | b'0'..=b'9' => Event::Number(self.number(c)?),
+            b't' => self.literal(b"rue", Event::Bool(true), "failed to parse boolean")?,
+            b'f' => self.literal(b"alse", Event::Bool(false), "failed to parse boolean")?,
+            b'n' => self.literal(b"ull", Event::Null, "failed to parse null")?,
+            _ => {
+                return Err(self.err(
+                    ParseErrorKind::UnexpectedToken,
+                    "unexpected character, expected a value",
+                ))
+            }
+        };
+        self.end_value();
+        Ok(event)
+    }
+
+    fn close(&mut self) -> Event {
+        let container = self.stack.pop();
+        self.end_value();
+        match container {
+            Some(Container::Object) => Event::EndObject,
+            _ => Event::EndArray,
+        }
+    }
+
+    fn end_value(&mut self) {
+        self.state = if self.stack.is_empty() {
+            State::Done
+        } else {
+            State::AfterValue
+        };
+    }
+
+    fn literal(
+        &mut self,
+        rest: &[u8],
+        event: Event,
+        msg: &'static str,
+    ) -> Result<Event, SyntaxError> {
+        for expected in rest {
+            if self.bump()? != Some(*expected) {
+                return Err(self.err(ParseErrorKind::UnexpectedToken, msg));
+            }
+        }
+        Ok(event)
+    }
+
+    fn number(&mut self, first: u8) -> Result<String, SyntaxError> {
+        let mut num = String::from(first as char);
+        while let Some(c) = self.peek()? {
+            if !matches!(c, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') {
+                break;
+            }
+            self.bump()?;
+            num.push(c as char);
+        }
+        if is_number(&num) {
+            Ok(num)
+        } else {
+            Err(SyntaxError::invalid_number(
+                num,
+                Position::at(self.line, self.col),
+            ))
+        }
+    }
+
+    /// Read the rest of a string after its opening quote, decoding escapes
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        let mut bytes = vec![];
+        loop {
+            match self.bump()? {
+                None => {
+                    return Err(SyntaxError::new(
+                        ParseErrorKind::UnterminatedString,
+                        "unterminated string",
+                        None,
+                    ))
+                }
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    // Reading failing midway is kept aside to return as it is
+                    let mut failed = None;
+                    let decoded = escape(
+                        &mut || match self.bump() {
+                            Ok(c) => c,
+                            Err(err) => {
+                                failed = Some(err);
+                                None
+                            }
+                        },
+                        b'"',
+                    );
+                    if let Some(err) = failed {
+                        return Err(err);
+                    }
+                    let decoded =
+                        decoded.map_err(|msg| self.err(ParseErrorKind::InvalidString, msg))?;
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(decoded.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(c) if c < 0x20 => {
+                    return Err(
+                        self.err(ParseErrorKind::InvalidString, "control character in string")
+                    )
+                }
+                Some(c) => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes)
+            .map_err(|_| self.err(ParseErrorKind::InvalidString, "string is not valid UTF-8"))
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), SyntaxError> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek()? {
+            self.bump()?;
+        }
+        Ok(())
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>, SyntaxError> {
+        let buf = self.input.fill_buf().map_err(SyntaxError::io)?;
+        Ok(buf.first().copied())
+    }
+
+    /// Consume the next byte, keeping track of its line and column
+    fn bump(&mut self) -> Result<Option<u8>, SyntaxError> {
+        let c = self.peek()?;
+        if let Some(c) = c {
+            self.input.consume(1);
+            if c == b'\n' {
+                self.line += 1;
+                self.col = 0;
+            } else if c & 0xc0 != 0x80 {
+                self.col += 1;
+            }
+        }
+        Ok(c)
+    }
+
+    /// An error at the last byte read
+    fn err(&self, kind: ParseErrorKind, msg: &'static str) -> SyntaxError {
+        SyntaxError::new(kind, msg, Some(Position::at(self.line, self.col)))
+    }
+}
@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+
+use crate::borrowed::{BorrowedParser, BorrowedValue, MAX_DEPTH};
+use crate::escape::decode;
+use crate::parse::{number_value, JsonValue, ParseErrorKind, SyntaxError};
+
+/// A document parsed into one flat vector of nodes, in the order they are
+/// written, read through a `Cursor`
+/// Containers record where they end, so whole subtrees are skipped in one step.
+/// Numbers and strings stay spans of the input until read.
+#[derive(Clone, Debug)]
+pub struct Tape<'a> {
+    input: &'a str,
+    nodes: Vec<Node>,
+}
+
+/// A value on the tape, offsets being into the input
+/// The members of an object are a key node followed by the value's nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Node {
+    Null,
+    True,
+    False,
+    /// A number checked to be valid but parsed when read
+    Number {
+        start: u32,
+        len: u32,
+    },
+    /// A key or string value, between its quotes
+    Str {
+        start: u32,
+        len: u32,
+        escaped: bool,
+    },
+    /// `end` is the index of the first node after the container
+    Object {
+        len: u32,
+        end: u32,
+    },
+    Array {
+        len: u32,
+        end: u32,
+    },
+}
+
+impl<'a> Tape<'a> {
+    /// Parse a document, which like for `BorrowedValue::parse` must be an object
+    /// or an array, with the same errors
+    pub fn parse(input: &'a str) -> Result<Self, SyntaxError> {
+        if u32::try_from(input.len()).is_err() {
+            return Err(SyntaxError::new(
+                ParseErrorKind::TooLarge,
+                "document too large for a tape",
+                None,
+            ));
+        }
+        let mut parser = BorrowedParser::new(input);
+        let mut nodes = vec![];
+        // The nodes of the containers not yet closed
+        let mut open: Vec<usize> = vec![];
+
+        loop {
+            parser.skip_whitespace();
+            let start = parser.pos;
+            match parser.peek() {
+                Some(c @ (b'{' | b'[')) => {
+                    if open.len() > MAX_DEPTH {
+                        return Err(parser.err(ParseErrorKind::DepthExceeded, "nesting too deep"));
+                    }
+                    parser.pos += 1;
+                    open.push(nodes.len());
+                    nodes.push(match c {
+                        b'{' => Node::Object { len: 0, end: 0 },
+                        _ => Node::Array { len: 0, end: 0 },
+                    });
+                    parser.skip_whitespace();
+                    if !parser.eat(if c == b'{' { b'}' } else { b']' }) {
+                        if c == b'{' {
+                            key(&mut parser, &mut nodes)?;
+                        }
+                        continue;
+                    }
+                    close(&mut nodes, open.pop());
+                }
+                Some(b'"') => {
+                    let (raw, escaped) = parser.string()?;
+                    nodes.push(Node::Str {
+                        start: start as u32 + 1,
+                        len: raw.len() as u32,
+                        escaped,
+                    });
+                }
+                Some(b'-' | b'0'..=b'9') => {
+                    parser.number()?;
+                    nodes.push(Node::Number {
+                        start: start as u32,
+                        len: (parser.pos - start) as u32,
+                    });
+                }
+                Some(b't') => {
+                    parser.literal("true", BorrowedValue::Null, "failed to parse boolean")?;
+                    nodes.push(Node::True);
+                }
+                Some(b'f') => {
+                    parser.literal("false", BorrowedValue::Null, "failed to parse boolean")?;
+                    nodes.push(Node::False);
+                }
+                Some(b'n') => {
+                    parser.literal("null", BorrowedValue::Null, "failed to parse null")?;
+                    nodes.push(Node::Null);
+                }
+                Some(_) => {
+                    return Err(parser.err(
+                        ParseErrorKind::UnexpectedToken,
+                        "unexpected character, expected a value",
+                    ))
+                }
+                None => {
+                    return Err(parser.err(ParseErrorKind::UnexpectedEof, "unexpected end of file"))
+                }
+            }
+
+            // A value is done: move on to the next one, closing containers on the way
+            loop {
+                let Some(&container) = open.last() else {
+                    parser.skip_whitespace();
+                    if parser.pos < input.len() {
+                        return Err(parser.err(
+                            ParseErrorKind::TrailingCharacters,
+                            "unexpected data after the document",
+                        ));
+                    }
+                    return Ok(Tape { input, nodes });
+                };
+                let object = matches!(nodes[container], Node::Object { .. });
+                if let Node::Object { len, .. } | Node::Array { len, .. } = &mut nodes[container] {
+                    *len += 1;
+                }
+                parser.skip_whitespace();
+                if parser.eat(b',') {
+                    if object {
+                        key(&mut parser, &mut nodes)?;
+                    }
+                    break;
+                }
+                if parser.eat(if object { b'}' } else { b']' }) {
+                    close(&mut nodes, open.pop());
+                    continue;
+                }
+                return Err(parser.err(
+                    ParseErrorKind::UnexpectedToken,
+                    if object {
+                        "expected COMMA or RIGHT_CURLY"
+                    } else {
+                        "expected COMMA or RIGHT_BRACKET"
+                    },
+                ));
+            }
+        }
+    }
+
+    /// The top level value
+    pub fn root(&self) -> Cursor<'_, 'a> {
+        Cursor { tape: self, at: 0 }
+    }
+
+    /// The index of the first node after the value at `at`
+    fn skip(&self, at: usize) -> usize {
+        match self.nodes[at] {
+            Node::Object { end, .. } | Node::Array { end, .. } => end as usize,
+            _ => at + 1,
+        }
+    }
+
+    fn text(&self, start: u32, len: u32) -> &'a str {
+        &self.input[start as usize..(start + len) as usize]
+    }
+}
+
+/// Parse the key of an object member along with its colon
+fn key(parser: &mut BorrowedParser, nodes: &mut Vec<Node>) -> Result<(), SyntaxError> {
+    parser.skip_whitespace();
+    if parser.peek() != Some(b'"') {
+        return Err(parser.err(
+            ParseErrorKind::UnexpectedToken,
+            "expected QUOTE to start a key",
+        ));
+    }
+    let start = parser.pos;
+    let (raw, escaped) = parser.string()?;
+    nodes.push(Node::Str {
+        start: start as u32 + 1,
+        len: raw.len() as u32,
+        escaped,
+    });
+    parser.skip_whitespace();
+    if !parser.eat(b':') {
+        return Err(parser.err(
+            ParseErrorKind::UnexpectedToken,
+            "expected COLON after the key",
+        ));
+    }
+    Ok(())
+}
+
+/// Record where the container just closed ends
+fn close(nodes: &mut [Node], container: Option<usize>) {
+    let after = nodes.len() as u32;
+    if let Some(Node::Object { end, .. } | Node::Array { end, .. }) =
+        container.map(|i| &mut nodes[i])
+    {
+        *end = after;
+    }
+}
+
+/// A position on a `Tape`, cheap to copy and to move around
+#[derive(Clone, Copy, Debug)]
+pub struct Cursor<'t, 'a> {
+    tape: &'t Tape<'a>,
+    at: usize,
+}
+
+impl<'t, 'a> Cursor<'t, 'a> {
+    fn node(&self) -> Node {
+        self.tape.nodes[self.at]
+    }
+
+    fn at(&self, at: usize) -> Cursor<'t, 'a> {
+        Cursor { at, ..*self }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.node() == Node::Null
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.node() {
+            Node::True => Some(true),
+            Node::False => Some(false),
+            _ => None,
+        }
+    }
+
+    /// The number if it is an integer, as `JsonValue::Int` would have it
+    pub fn as_i64(&self) -> Option<i64> {
+        match number_value(self.number()?) {
+            Ok(JsonValue::Int(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Any number, integers included
+    pub fn as_f64(&self) -> Option<f64> {
+        self.number()?.parse().ok()
+    }
+
+    fn number(&self) -> Option<&'a str> {
+        match self.node() {
+            Node::Number { start, len } => Some(self.tape.text(start, len)),
+            _ => None,
+        }
+    }
+
+    /// A string, borrowed unless it has escapes to decode
+    pub fn as_str(&self) -> Option<Cow<'a, str>> {
+        match self.node() {
+            Node::Str {
+                start,
+                len,
+                escaped,
+            } => {
+                let raw = self.tape.text(start, len);
+                Some(match escaped {
+                    false => Cow::Borrowed(raw),
+                    true => Cow::Owned(decode(raw)),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self.node(), Node::Object { .. })
+    }
+
+    /// Members of an object or elements of an array
+    pub fn len(&self) -> Option<usize> {
+        match self.node() {
+            Node::Object { len, .. } | Node::Array { len, .. } => Some(len as usize),
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// The value of the first member named `key`
+    pub fn get(&self, key: &str) -> Option<Cursor<'t, 'a>> {
+        self.members()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The element at `index`, skipping over the ones before it
+    pub fn index(&self, index: usize) -> Option<Cursor<'t, 'a>> {
+        self.elements().nth(index)
+    }
+
+    /// The elements of an array, none for anything else
+    pub fn elements(&self) -> impl Iterator<Item = Cursor<'t, 'a>> + 't {
+        let (tape, end) = (
+            self.tape,
+            self.end_of(matches!(self.node(), Node::Array { .. })),
+        );
+        let mut at = self.at + 1;
+        std::iter::from_fn(move || {
+            let element = (at < end).then_some(Cursor { tape, at })?;
+            at = tape.skip(at);
+            Some(element)
+        })
+    }
+
+    /// The keys and values of an object, none for anything else
+    pub fn members(&self) -> impl Iterator<Item = (Cow<'a, str>, Cursor<'t, 'a>)> + 't {
+        let (tape, end) = (
+            self.tape,
+            self.end_of(matches!(self.node(), Node::Object { .. })),
+        );
+        let mut at = self.at + 1;
+        std::iter::from_fn(move || {
+            let key = (at < end).then_some(Cursor { tape, at })?;
+            let value = key.at(at + 1);
+            at = tape.skip(at + 1);
+            Some((key.as_str().unwrap_or_default(), value))
+        })
+    }
+
+    /// Where the children of this container end, or that there are none
+    fn end_of(&self, container: bool) -> usize {
+        match container {
+            true => self.tape.skip(self.at),
+            false => 0,
+        }
+    }
+
+    /// Copy the value into an owned tree
+    pub fn to_json(&self) -> JsonValue {
+        match self.node() {
+            Node::Null => JsonValue::Null,
+            Node::True => JsonValue::Bool(true),
+            Node::False => JsonValue::Bool(false),
+            Node::Number { start, len } => {
+                number_value(self.tape.text(start, len)).unwrap_or(JsonValue::Null)
+            }
+            Node::Str { .. } => JsonValue::Str(self.as_str().unwrap_or_default().into()),
+            Node::Array { .. } => JsonValue::Arr(self.elements().map(|e| e.to_json()).collect()),
+            Node::Object { .. } => JsonValue::from_members(
+                self.members()
+                    .map(|(key, value)| (key.into_owned(), value.to_json()))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+use crate::serialize::to_string;
+
+#[derive(Debug)]
+pub struct TemplateError(pub String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Replace `${VAR}` placeholders in string values with variables from `env`
+/// e.g. `substitute_env(&config, &std::env::vars().collect())`.
+/// `${VAR:-default}` falls back to `default` when `VAR` is not set; other
+/// missing variables are errors.
+pub fn substitute_env(
+    template: &JsonValue,
+    env: &HashMap<String, String>,
+) -> Result<JsonValue, TemplateError> {
+    substitute(template, "${", "}", &mut |name| {
+        let (name, default) = match name.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (name, None),
+        };
+        match (env.get(name), default) {
+            (Some(value), _) => Ok(JsonValue::Str(value.as_str().into())),
+            (None, Some(default)) => Ok(JsonValue::Str(default.into())),
+            (None, None) => Err(err(format!("'{}' is not set", name))),
+        }
+    })
+}
+
+/// Replace `{{path}}` placeholders in string values with values from `data`
+/// Paths are dotted or JSON Pointers. A string that is exactly one placeholder
+/// takes the value as is, so `"{{limits}}"` can become an object; elsewhere
+/// values are spliced in as text.
+pub fn substitute_json(template: &JsonValue, data: &JsonValue) -> Result<JsonValue, TemplateError> {
+    substitute(template, "{{", "}}", &mut |path| {
+        let pointer = JsonPointer::from_path(path.trim()).map_err(|e| TemplateError(e.0))?;
+        pointer
+            .get(data)
+            .cloned()
+            .ok_or_else(|| err(format!("'{}' is not in the data", path.trim())))
+    })
+}
+
+type Lookup<'a> = dyn FnMut(&str) -> Result<JsonValue, TemplateError> + 'a;
+
+fn err(msg: String) -> TemplateError {
+    TemplateError(format!("Template error: {}", msg))
+}
+
+fn substitute(
+    value: &JsonValue,
+    open: &str,
+    close: &str,
+    lookup: &mut Lookup,
+) -> Result<JsonValue, TemplateError> {
+    match value {
+        JsonValue::Str(s) => substitute_str(s, open, close, lookup),
+        JsonValue::Arr(values) => values
+            .iter()
+            .map(|v| substitute(v, open, close, lookup))
+            .collect::<Result<_, _>>()
+            .map(JsonValue::Arr),
+        _ if value.is_object() => {
+            let members = value
+                .members()
+                .map(|(key, v)| Ok((key, substitute(v, open, close, lookup)?)))
+                .collect::<Result<_, _>>()?;
+            Ok(JsonValue::from_members(members))
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+fn substitute_str(
+    s: &str,
+    open: &str,
+    close: &str,
+    lookup: &mut Lookup,
+) -> Result<JsonValue, TemplateError> {
+    // A lone placeholder keeps the type of its value
+    if let Some(name) = s.strip_prefix(open).and_then(|s| s.strip_suffix(close)) {
+        if !name.contains(open) && !name.contains(close) {
+            return lookup(name);
+        }
+    }
+
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find(open) {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + open.len()..];
+        let end = after
+            .find(close)
+            .ok_or_else(|| err(format!("unclosed '{}' in '{}'", open, s)))?;
+        match lookup(&after[..end])?.into_str() {
+            Ok(value) => out.push_str(&value),
+            Err(value) => out.push_str(&to_string(&value).map_err(|e| TemplateError(e.0))?),
+        }
+        rest = &after[end + close.len()..];
+    }
+    out.push_str(rest);
+    Ok(JsonValue::Str(out.into()))
+}
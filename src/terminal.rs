@@ -0,0 +1,150 @@
+//! Raw mode and key input for the interactive commands, without a terminal library
+//! Raw mode is set through `stty`, which reads the settings of its stdin.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+/// The controlling terminal, for reading keys even when stdin is redirected
+pub fn open_tty() -> Result<File, String> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("IO error: no terminal to interact with: {}", e))
+}
+
+/// Restores the terminal settings saved by `enable` when dropped
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enable() -> Result<Self, String> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run `stty` on the controlling terminal, returning what it printed
+fn stty(args: &[&str]) -> Result<String, String> {
+    let tty = File::open("/dev/tty").map_err(|e| format!("IO error: {}", e))?;
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty))
+        .output()
+        .map_err(|e| format!("IO error: cannot run stty: {}", e))?;
+    if !output.status.success() {
+        return Err("IO error: stty failed to configure the terminal".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Rows and columns of the terminal
+pub fn size() -> (usize, usize) {
+    let size = stty(&["size"]).unwrap_or_default();
+    let mut numbers = size.split_whitespace().map(|n| n.parse().unwrap_or(0));
+    match (numbers.next(), numbers.next()) {
+        (Some(rows), Some(cols)) if rows > 2 && cols > 0 => (rows, cols),
+        _ => (24, 80),
+    }
+}
+
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Delete,
+    Tab,
+    Char(char),
+}
+
+/// Keys typed on the terminal, one at a time
+#[derive(Default)]
+pub struct Keys {
+    pending: VecDeque<Key>,
+}
+
+impl Keys {
+    /// The next key, reading from `tty` once those of the last read are used up
+    /// `None` at the end of input.
+    pub fn next(&mut self, tty: &mut File) -> std::io::Result<Option<Key>> {
+        let mut buf = [0; 64];
+        while self.pending.is_empty() {
+            let n = tty.read(&mut buf)?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.pending.extend(decode(&buf[..n]));
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Decode the keys in one read from the terminal
+/// A read holds whole escape sequences, and several keys when text is pasted.
+fn decode(mut bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    while let Some(&first) = bytes.first() {
+        let (key, len) = match bytes {
+            [0x1b, b'[' | b'O', rest @ ..] => {
+                // Parameters, then a final byte between `@` and `~`
+                let len = rest
+                    .iter()
+                    .position(|b| (0x40..=0x7e).contains(b))
+                    .map_or(bytes.len(), |i| i + 3);
+                let key = match &bytes[2..len] {
+                    b"A" => Some(Key::Up),
+                    b"B" => Some(Key::Down),
+                    b"C" => Some(Key::Right),
+                    b"D" => Some(Key::Left),
+                    b"H" | b"1~" => Some(Key::Home),
+                    b"F" | b"4~" => Some(Key::End),
+                    b"5~" => Some(Key::PageUp),
+                    b"6~" => Some(Key::PageDown),
+                    b"3~" => Some(Key::Delete),
+                    _ => None,
+                };
+                (key, len)
+            }
+            [0x1b, ..] => (Some(Key::Escape), 1),
+            [b'\r' | b'\n', ..] => (Some(Key::Enter), 1),
+            [0x7f | 0x08, ..] => (Some(Key::Backspace), 1),
+            [b'\t', ..] => (Some(Key::Tab), 1),
+            _ => {
+                let len = match first {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                }
+                .min(bytes.len());
+                let c = std::str::from_utf8(&bytes[..len])
+                    .ok()
+                    .and_then(|s| s.chars().next());
+                (c.map(Key::Char), len)
+            }
+        };
+        keys.extend(key);
+        bytes = &bytes[len..];
+    }
+    keys
+}
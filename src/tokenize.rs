@@ -3,55 +3,89 @@ use std::fmt::Display;
 
 pub struct Tokenizer {
     pos: Position,
+    options: LenientOptions,
 }
 
+/// Relaxations of the JSON grammar for hand-written files, e.g. JSONC configs
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LenientOptions {
+    /// Skip `// line` and `/* block */` comments
+    pub comments: bool,
+    /// Drop a comma after the last element or member
+    pub trailing_commas: bool,
+    /// Accept strings quoted with `'`, in which `\'` stands for the quote
+    pub single_quotes: bool,
+}
+
+impl LenientOptions {
+    /// Every relaxation turned on
+    pub fn all() -> Self {
+        LenientOptions {
+            comments: true,
+            trailing_commas: true,
+            single_quotes: true,
+        }
+    }
+}
+
+/// A token borrowing its text from the input
+/// Strings, numbers, words and whitespace are each one token spanning the whole
+/// run, so the number of tokens follows the structure of a document rather than
+/// its size.
 #[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Token {
-    Quote,
-    Digit(char),
+pub enum Token<'a> {
+    /// A string along with its quotes, the closing one missing if unterminated
+    Quoted(&'a str),
+    /// A number, e.g. `-1.5e3`, not necessarily a valid one
+    Number(&'a str),
     Dot,
     Comma,
     Colon,
-    Minus,
+    Plus,
     RightCurly,
     LeftCurly,
     RightBracket,
     LeftBracket,
-    Char(char),
-    NewLine,
+    /// A word like `true`
+    Chars(&'a str),
     Whitespace,
     NotSupported,
 }
 
-impl Token {
+impl Token<'_> {
     pub fn is_char(&self) -> bool {
-        match self {
-            Token::Char(_) => true,
-            _ => false,
-        }
+        matches!(self, Token::Chars(_))
     }
 }
 
-impl Display for Token {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let msg = match *self {
-            Self::Quote => "QUOTE",
-            Self::Digit(d) => &format!("'{}'", d),
+impl Token<'_> {
+    /// The name of the kind of token, which `Display` shows for all but numbers
+    /// and words, shown by their text
+    pub(crate) fn name(&self) -> &'static str {
+        match *self {
+            Self::Quoted(_) => "QUOTE",
+            Self::Number(_) => "NUMBER",
             Self::Dot => "DOT",
             Self::Comma => "COMMA",
             Self::Colon => "COLON",
-            Self::Minus=> "MINUS",
+            Self::Plus => "PLUS",
             Self::RightCurly => "RIGHT_CURLY",
             Self::LeftCurly => "LEFT_CURLY",
             Self::RightBracket => "RIGHT_BRACKET",
             Self::LeftBracket => "LEFT_BRACKET",
-            Self::Char(c) => &format!("'{}'", c),
-            Self::NewLine => "NEWLINE",
+            Self::Chars(_) => "WORD",
             Self::Whitespace => "WHITESPACE",
-            Self::NotSupported => unreachable!(),
-        };
+            Self::NotSupported => "UNSUPPORTED_CHARACTER",
+        }
+    }
+}
 
-        write!(f, "{}", msg)
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Number(s) | Self::Chars(s) => write!(f, "'{}'", s),
+            _ => f.write_str(self.name()),
+        }
     }
 }
 
@@ -61,12 +95,35 @@ pub struct Position {
     col: i32,
 }
 
+impl Position {
+    /// The position of the `col`th character of the line `line`, both from 1
+    pub(crate) fn at(line: usize, col: usize) -> Self {
+        Position {
+            line: line as i32,
+            col: col as i32,
+        }
+    }
+
+    /// The line, counting from 1
+    pub fn line(&self) -> usize {
+        self.line as usize
+    }
+
+    /// The column of the character within its line, counting from 1
+    pub fn column(&self) -> usize {
+        self.col as usize
+    }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "line {} column {}", self.line, self.col)
     }
 }
 
+/// The position of the first character is counted from here
+const START: Position = Position { line: 1, col: 0 };
+
 impl Default for Tokenizer {
     fn default() -> Self {
         Self::new()
@@ -75,65 +132,177 @@ impl Default for Tokenizer {
 
 impl Tokenizer {
     pub fn new() -> Self {
+        Self::lenient(LenientOptions::default())
+    }
+
+    /// A tokenizer that also accepts what `options` allows, producing tokens
+    /// that parse like those of the strict equivalent of the input
+    pub fn lenient(options: LenientOptions) -> Self {
         Self {
-            pos: Position { line: 1, col: 0 },
+            pos: START,
+            options,
         }
     }
 
+    /// Start positions over, for tokenizing the next input with the same tokenizer
+    pub fn reset(&mut self) {
+        self.pos = START;
+    }
+
     /// Map the characters in `file_contents` to JSON tokens
-    pub fn tokenize(&mut self, file_contents: &str) -> Result<Vec<(Token, Position)>, String> {
+    pub fn tokenize<'a>(
+        &mut self,
+        file_contents: &'a str,
+    ) -> Result<Vec<(Token<'a>, Position)>, String> {
         // FIXME: why is this a result if it never fails
-        let mut in_string = false;
-        Ok(file_contents
-            .chars()
-            .map(|c| {
-                self.next_char();
-                if in_string {
-                    if c == '\n' {
-                        self.new_line();
-                        (Token::NewLine, self.pos)
-                    } else if c == '"' {
-                        in_string = !in_string;
-                        (Token::Quote, self.pos)
-                    } else {
-                        (Token::Char(c), self.pos)
-                    }
-                } else {
-                    let token = match c {
-                        '"' => {
-                            in_string = !in_string;
-                            Token::Quote
-                        }
-                        ':' => Token::Colon,
-                        '-' => Token::Minus,
-                        '{' => Token::LeftCurly,
-                        '}' => Token::RightCurly,
-                        '[' => Token::LeftBracket,
-                        ']' => Token::RightBracket,
-                        ',' => Token::Comma,
-                        '.' => Token::Dot,
-                        ' ' | '\t' => Token::Whitespace,
-                        '\n' => {
-                            self.new_line();
-                            Token::NewLine
-                        }
-                        '0'..='9' => Token::Digit(c),
-                        'a'..='z' | 'A'..='Z' => Token::Char(c),
-                        _ => Token::NotSupported,
-                    };
-
-                    (token, self.pos)
+        // Tokens take 32 bytes each, so rather than reserving for the densest
+        // documents, room is made for one every eight bytes and grown past that
+        let mut tokens = Vec::with_capacity(file_contents.len() / 8);
+        let mut rest = file_contents;
+        while let Some(token) = self.next_token(&mut rest) {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    /// The tokens of `file_contents`, produced as they are read so that a parser
+    /// consuming them never holds more than the one at hand
+    pub fn tokens(self, file_contents: &str) -> Tokens<'_> {
+        Tokens {
+            tokenizer: self,
+            rest: file_contents,
+        }
+    }
+
+    /// Take the next token off the front of `rest`
+    fn next_token<'a>(&mut self, rest: &mut &'a str) -> Option<(Token<'a>, Position)> {
+        loop {
+            let c = rest.chars().next()?;
+            if c == '"' || (self.options.single_quotes && c == '\'') {
+                return Some(self.string(rest));
+            }
+            if self.options.comments && (rest.starts_with("//") || rest.starts_with("/*")) {
+                let len = comment_len(rest);
+                self.skip(&rest[..len]);
+                *rest = &rest[len..];
+                continue;
+            }
+
+            let (mut token, len) = match c {
+                ' ' | '\t' | '\n' | '\r' => (Token::Whitespace, whitespace_len(rest)),
+                '-' | '0'..='9' => {
+                    let len = run(rest, |c| matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'));
+                    (Token::Number(&rest[..len]), len)
+                }
+                'a'..='z' | 'A'..='Z' => {
+                    let len = run(rest, |c| c.is_ascii_alphabetic());
+                    (Token::Chars(&rest[..len]), len)
                 }
-            })
-            .collect())
+                _ => (Self::token(c), c.len_utf8()),
+            };
+            let pos = self.skip(&rest[..len]);
+            *rest = &rest[len..];
+            if token == Token::Comma && self.options.trailing_commas && self.closes(rest) {
+                token = Token::Whitespace;
+            }
+            return Some((token, pos));
+        }
+    }
+
+    /// Tokenize the string opened by the quote `rest` starts with
+    fn string<'a>(&mut self, rest: &mut &'a str) -> (Token<'a>, Position) {
+        let bytes = rest.as_bytes();
+        let quote = bytes[0];
+        // Quotes and backslashes are ASCII, so never inside a longer character
+        let mut i = 1;
+        while i < bytes.len() && bytes[i] != quote {
+            // A backslash escapes what follows it, the quote included
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        let (quoted, after) = rest.split_at((i + 1).min(bytes.len()));
+        *rest = after;
+        (Token::Quoted(quoted), self.skip(quoted))
+    }
+
+    /// Whether `rest` closes an object or array after whitespace and comments,
+    /// making a comma before it a trailing one
+    fn closes(&self, mut rest: &str) -> bool {
+        loop {
+            rest = &rest[whitespace_len(rest)..];
+            if !(self.options.comments && (rest.starts_with("//") || rest.starts_with("/*"))) {
+                return rest.starts_with(['}', ']']);
+            }
+            rest = &rest[comment_len(rest)..];
+        }
+    }
+
+    /// The token for a character that is a token by itself
+    fn token(c: char) -> Token<'static> {
+        match c {
+            ':' => Token::Colon,
+            '+' => Token::Plus,
+            '{' => Token::LeftCurly,
+            '}' => Token::RightCurly,
+            '[' => Token::LeftBracket,
+            ']' => Token::RightBracket,
+            ',' => Token::Comma,
+            '.' => Token::Dot,
+            _ => Token::NotSupported,
+        }
+    }
+
+    /// Move past `text`, returning the position of its first character
+    fn skip(&mut self, text: &str) -> Position {
+        let mut first = None;
+        for c in text.chars() {
+            self.next_char();
+            first.get_or_insert(self.pos);
+            if c == '\n' {
+                self.new_line();
+            }
+        }
+        first.unwrap_or(self.pos)
     }
 
     fn new_line(&mut self) {
         self.pos.line += 1;
-        self.pos.col = 1;
+        self.pos.col = 0;
     }
 
     fn next_char(&mut self) {
         self.pos.col += 1;
     }
 }
+
+/// Tokens read on demand from a string, see `Tokenizer::tokens`
+pub struct Tokens<'a> {
+    tokenizer: Tokenizer,
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token<'a>, Position);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tokenizer.next_token(&mut self.rest)
+    }
+}
+
+/// Length of the run of characters matching `f` that `text` starts with
+fn run(text: &str, f: impl Fn(char) -> bool) -> usize {
+    text.find(|c| !f(c)).unwrap_or(text.len())
+}
+
+fn whitespace_len(text: &str) -> usize {
+    run(text, |c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+}
+
+/// Length of the comment `text` starts with
+/// An unterminated block comment runs to the end of the input.
+fn comment_len(text: &str) -> usize {
+    if text.starts_with("//") {
+        text.find('\n').unwrap_or(text.len())
+    } else {
+        text[2..].find("*/").map_or(text.len(), |end| end + 4)
+    }
+}
@@ -0,0 +1,704 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+use crate::serialize::to_string;
+
+#[derive(Debug)]
+pub struct TomlError(pub String);
+
+impl fmt::Display for TomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TomlError {}
+
+/// Serialize an object as a TOML document
+/// Nested objects become `[tables]` and arrays of objects `[[arrays of tables]]`.
+/// TOML has no null and this writer keeps arrays to a single element type, so
+/// nulls and mixed arrays are errors naming their pointer.
+pub fn to_toml(value: &JsonValue) -> Result<String, TomlError> {
+    if !value.is_object() {
+        return Err(TomlError(format!(
+            "TOML error: a document must be an object, not {}",
+            value.type_name()
+        )));
+    }
+    let mut out = String::new();
+    write_table(value, &mut vec![], &JsonPointer::default(), &mut out)?;
+    Ok(out)
+}
+
+/// Write the members of a table: plain keys first, then subtables
+fn write_table(
+    table: &JsonValue,
+    keys: &mut Vec<String>,
+    pointer: &JsonPointer,
+    out: &mut String,
+) -> Result<(), TomlError> {
+    for (key, value) in table.members() {
+        if !is_table(value) && !is_table_array(value) {
+            let text = inline(value, &pointer.join(key))?;
+            out.push_str(&format!("{} = {}\n", key_text(key), text));
+        }
+    }
+
+    for (key, value) in table.members() {
+        keys.push(key_text(key));
+        let pointer = pointer.join(key);
+        if is_table(value) {
+            separate(out);
+            out.push_str(&format!("[{}]\n", keys.join(".")));
+            write_table(value, keys, &pointer, out)?;
+        } else if let (true, JsonValue::Arr(tables)) = (is_table_array(value), value) {
+            for (i, table) in tables.iter().enumerate() {
+                separate(out);
+                out.push_str(&format!("[[{}]]\n", keys.join(".")));
+                write_table(table, keys, &pointer.join(i.to_string()), out)?;
+            }
+        }
+        keys.pop();
+    }
+    Ok(())
+}
+
+/// A blank line between sections
+fn separate(out: &mut String) {
+    if !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+fn is_table(value: &JsonValue) -> bool {
+    value.is_object()
+}
+
+fn is_table_array(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Arr(values) => !values.is_empty() && values.iter().all(JsonValue::is_object),
+        _ => false,
+    }
+}
+
+fn key_text(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// A TOML basic string, whose escapes are those of JSON
+fn quote(s: &str) -> String {
+    to_string(&JsonValue::Str(s.into())).unwrap_or_default()
+}
+
+/// A value written on one line, with objects as inline tables
+fn inline(value: &JsonValue, pointer: &JsonPointer) -> Result<String, TomlError> {
+    let err = |msg: &str| TomlError(format!("TOML error: {} at '{}'", msg, pointer));
+    match value {
+        JsonValue::Null => Err(err("null cannot be represented")),
+        JsonValue::Str(s) => Ok(quote(s)),
+        JsonValue::Bool(b) => Ok(b.to_string()),
+        JsonValue::Int(i) => Ok(i.to_string()),
+        JsonValue::Float(_, _) => {
+            let text = to_string(value).map_err(|e| TomlError(e.0))?;
+            if text.contains(['.', 'e', 'E']) {
+                Ok(text)
+            } else {
+                Ok(text + ".0")
+            }
+        }
+        JsonValue::Arr(values) => {
+            if let Some(first) = values.first() {
+                if values.iter().any(|v| v.type_name() != first.type_name()) {
+                    return Err(err("arrays must not mix types"));
+                }
+            }
+            let items = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| inline(v, &pointer.join(i.to_string())))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("[{}]", items.join(", ")))
+        }
+        _ => {
+            let members = value
+                .members()
+                .map(|(key, v)| {
+                    let text = inline(v, &pointer.join(key))?;
+                    Ok(format!("{} = {}", key_text(key), text))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if members.is_empty() {
+                Ok("{}".to_string())
+            } else {
+                Ok(format!("{{ {} }}", members.join(", ")))
+            }
+        }
+    }
+}
+
+/// Parse a TOML document into a JSON object
+/// Dates and times have no JSON type and become strings; `inf` and `nan` are errors.
+pub fn from_toml(source: &str) -> Result<JsonValue, TomlError> {
+    let mut parser = TomlParser {
+        src: source,
+        pos: 0,
+    };
+    let mut root = Table::default();
+    let mut current: Vec<String> = vec![];
+
+    loop {
+        parser.skip_blank();
+        let Some(c) = parser.peek() else { break };
+        if c == '[' {
+            let array = parser.src[parser.pos..].starts_with("[[");
+            parser.pos += if array { 2 } else { 1 };
+            let keys = parser.keys()?;
+            let close = if array { "]]" } else { "]" };
+            if !parser.eat(close) {
+                return Err(parser.err(&format!("expected '{}'", close)));
+            }
+
+            let name = keys.join(".");
+            let (last, parents) = keys.split_last().expect("keys are not empty");
+            let parent = root.descend(parents).map_err(|msg| parser.err(&msg))?;
+            match (array, parent.find(last)) {
+                (true, None) => parent.entries.push((last.clone(), Item::Tables(vec![]))),
+                (true, Some(Item::Tables(_))) => (),
+                (false, None) => parent.entries.push((
+                    last.clone(),
+                    Item::Table(Table {
+                        explicit: true,
+                        ..Default::default()
+                    }),
+                )),
+                (false, Some(Item::Table(table))) if !table.explicit => table.explicit = true,
+                _ => return Err(parser.err(&format!("'{}' is already defined", name))),
+            }
+            if let Some(Item::Tables(tables)) = parent.find(last) {
+                tables.push(Table::default());
+            }
+            // Only now, so that errors about the header give its line
+            parser.end_of_line()?;
+            current = keys;
+        } else {
+            let keys = parser.keys()?;
+            parser.skip_spaces();
+            if !parser.eat("=") {
+                return Err(parser.err("expected '='"));
+            }
+            let value = parser.value()?;
+            let table = root.descend(&current).map_err(|msg| parser.err(&msg))?;
+            table.insert(&keys, value).map_err(|msg| parser.err(&msg))?;
+            parser.end_of_line()?;
+        }
+    }
+
+    Ok(root.into_json())
+}
+
+#[derive(Default)]
+struct Table {
+    entries: Vec<(String, Item)>,
+    /// Whether a `[header]` defined it, rather than a longer header or a dotted key
+    explicit: bool,
+}
+
+enum Item {
+    Value(JsonValue),
+    Table(Table),
+    Tables(Vec<Table>),
+}
+
+impl Table {
+    fn find(&mut self, key: &str) -> Option<&mut Item> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, item)| item)
+    }
+
+    /// The table at `keys`, creating missing ones; arrays of tables resolve to
+    /// their last element
+    fn descend(&mut self, keys: &[String]) -> Result<&mut Table, String> {
+        let Some((key, rest)) = keys.split_first() else {
+            return Ok(self);
+        };
+        if self.find(key).is_none() {
+            self.entries
+                .push((key.clone(), Item::Table(Table::default())));
+        }
+        match self.find(key) {
+            Some(Item::Table(table)) => table.descend(rest),
+            Some(Item::Tables(tables)) => match tables.last_mut() {
+                Some(table) => table.descend(rest),
+                None => Err(format!("'{}' is an empty array of tables", key)),
+            },
+            _ => Err(format!("'{}' is not a table", key)),
+        }
+    }
+
+    /// Insert a value at a dotted key
+    fn insert(&mut self, keys: &[String], value: JsonValue) -> Result<(), String> {
+        let (last, parents) = keys.split_last().expect("keys are not empty");
+        let table = self.descend(parents)?;
+        if table.find(last).is_some() {
+            return Err(format!("'{}' is already defined", keys.join(".")));
+        }
+        table.entries.push((last.clone(), Item::Value(value)));
+        Ok(())
+    }
+
+    fn into_json(self) -> JsonValue {
+        let members = self
+            .entries
+            .into_iter()
+            .map(|(key, item)| {
+                let value = match item {
+                    Item::Value(value) => value,
+                    Item::Table(table) => table.into_json(),
+                    Item::Tables(tables) => {
+                        JsonValue::Arr(tables.into_iter().map(Table::into_json).collect())
+                    }
+                };
+                (key, value)
+            })
+            .collect();
+        JsonValue::from_members(members)
+    }
+}
+
+struct TomlParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl TomlParser<'_> {
+    fn err(&self, msg: &str) -> TomlError {
+        let line = self.src[..self.pos].matches('\n').count() + 1;
+        TomlError(format!("TOML error: {} at line {}", msg, line))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.src[self.pos..].starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            let end = self.src[self.pos..]
+                .find('\n')
+                .unwrap_or(self.src.len() - self.pos);
+            self.pos += end;
+        }
+    }
+
+    /// Skip whitespace, newlines and comments
+    fn skip_blank(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            if !(self.eat("\n") || self.eat("\r\n")) {
+                break;
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_spaces();
+        self.skip_comment();
+        if self.peek().is_none() || self.eat("\n") || self.eat("\r\n") {
+            Ok(())
+        } else {
+            Err(self.err("expected the end of the line"))
+        }
+    }
+
+    /// A key, possibly dotted, e.g. `a."b.c".d`
+    fn keys(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut keys = vec![];
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return Err(self.err("expected a key"));
+                    }
+                    self.src[start..self.pos].to_string()
+                }
+            };
+            keys.push(key);
+            self.skip_spaces();
+            if !self.eat(".") {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, TomlError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') => self.basic_string().map(|s| JsonValue::Str(s.into())),
+            Some('\'') => self.literal_string().map(|s| JsonValue::Str(s.into())),
+            Some('[') => {
+                self.pos += 1;
+                let mut values = vec![];
+                loop {
+                    self.skip_blank();
+                    if self.eat("]") {
+                        return Ok(JsonValue::Arr(values.into()));
+                    }
+                    values.push(self.value()?);
+                    self.skip_blank();
+                    if !self.eat(",") && self.peek() != Some(']') {
+                        return Err(self.err("expected ',' or ']'"));
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut table = Table::default();
+                self.skip_spaces();
+                if self.eat("}") {
+                    return Ok(JsonValue::Empty);
+                }
+                loop {
+                    let keys = self.keys()?;
+                    self.skip_spaces();
+                    if !self.eat("=") {
+                        return Err(self.err("expected '='"));
+                    }
+                    let value = self.value()?;
+                    table.insert(&keys, value).map_err(|msg| self.err(&msg))?;
+                    self.skip_spaces();
+                    if self.eat("}") {
+                        return Ok(table.into_json());
+                    }
+                    if !self.eat(",") {
+                        return Err(self.err("expected ',' or '}'"));
+                    }
+                }
+            }
+            Some(_) => self.scalar(),
+            None => Err(self.err("expected a value")),
+        }
+    }
+
+    /// A boolean, number or date, read up to the next delimiter
+    fn scalar(&mut self) -> Result<JsonValue, TomlError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let date_time_space = c == ' '
+                && self.src[start..self.pos].len() == 10
+                && self.src[self.pos + 1..].starts_with(|c: char| c.is_ascii_digit());
+            if !(c.is_ascii_alphanumeric() || "+-_.:".contains(c) || date_time_space) {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text = &self.src[start..self.pos];
+
+        match text {
+            "true" => return Ok(JsonValue::Bool(true)),
+            "false" => return Ok(JsonValue::Bool(false)),
+            "inf" | "+inf" | "-inf" | "nan" | "+nan" | "-nan" => {
+                return Err(self.err(&format!("'{}' cannot be represented in JSON", text)))
+            }
+            _ => (),
+        }
+        let is_date = text.len() >= 8
+            && (text.as_bytes()[4] == b'-' || text.as_bytes()[2] == b':')
+            && text.starts_with(|c: char| c.is_ascii_digit());
+        if is_date {
+            return Ok(JsonValue::Str(text.into()));
+        }
+
+        let digits = text.replace('_', "");
+        let (sign, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+            if let Some(n) = unsigned.strip_prefix(prefix) {
+                return i64::from_str_radix(n, radix)
+                    .map(|i| JsonValue::Int(sign * i))
+                    .map_err(|_| self.err(&format!("invalid number '{}'", text)));
+            }
+        }
+        if let Ok(i) = digits.parse::<i64>() {
+            return Ok(JsonValue::Int(i));
+        }
+        match digits.parse::<f64>() {
+            Ok(f) if f.is_finite() && unsigned.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(JsonValue::Float(f, None))
+            }
+            _ => Err(self.err(&format!("invalid value '{}'", text))),
+        }
+    }
+
+    /// A `"basic"` or `"""multi-line"""` string with its escapes decoded
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        let multi = self.eat("\"\"\"");
+        if multi {
+            // A newline right after the opening quotes is trimmed
+            let _ = self.eat("\n") || self.eat("\r\n");
+        } else {
+            self.pos += 1;
+        }
+
+        let mut out = String::new();
+        loop {
+            if multi && self.src[self.pos..].starts_with("\"\"\"") {
+                // Up to two quotes may end the content right before the delimiter
+                while self.src[self.pos + 1..].starts_with("\"\"\"") {
+                    out.push('"');
+                    self.pos += 1;
+                }
+                self.pos += 3;
+                return Ok(out);
+            }
+            let Some(c) = self.peek() else {
+                return Err(self.err("unterminated string"));
+            };
+            if c == '\n' && !multi {
+                return Err(self.err("unterminated string"));
+            }
+            self.pos += c.len_utf8();
+            match c {
+                '"' if !multi => return Ok(out),
+                '\\' => {
+                    let Some(e) = self.peek() else {
+                        return Err(self.err("unterminated string"));
+                    };
+                    self.pos += e.len_utf8();
+                    match e {
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'r' => out.push('\r'),
+                        'b' => out.push('\u{8}'),
+                        'f' => out.push('\u{c}'),
+                        '"' | '\\' => out.push(e),
+                        'u' | 'U' => {
+                            let len = if e == 'u' { 4 } else { 8 };
+                            let hex = self.src.get(self.pos..self.pos + len).unwrap_or("");
+                            let c = u32::from_str_radix(hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.err("invalid unicode escape"))?;
+                            out.push(c);
+                            self.pos += len;
+                        }
+                        // A backslash at the end of a line trims the whitespace after it
+                        c if multi && c.is_whitespace() => {
+                            self.pos -= c.len_utf8();
+                            let rest = &self.src[self.pos..];
+                            let trimmed = rest.trim_start();
+                            if !rest[..rest.len() - trimmed.len()].contains('\n') {
+                                return Err(self.err("invalid escape"));
+                            }
+                            self.pos += rest.len() - trimmed.len();
+                        }
+                        _ => return Err(self.err(&format!("invalid escape '\\{}'", e))),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+
+    /// A `'literal'` or `'''multi-line'''` string, taken as written
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        let (delimiter, multi) = if self.eat("'''") {
+            let _ = self.eat("\n") || self.eat("\r\n");
+            ("'''", true)
+        } else {
+            self.pos += 1;
+            ("'", false)
+        };
+
+        let rest = &self.src[self.pos..];
+        let Some(mut end) = rest.find(delimiter) else {
+            return Err(self.err("unterminated string"));
+        };
+        if multi {
+            while rest[end + 1..].starts_with(delimiter) {
+                end += 1;
+            }
+        }
+        let text = &rest[..end];
+        if !multi && text.contains('\n') {
+            return Err(self.err("unterminated string"));
+        }
+        self.pos += end + delimiter.len();
+        Ok(text.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    fn toml(text: &str) -> Result<String, String> {
+        to_toml(&json(text)).map_err(|err| err.0)
+    }
+
+    /// Compact JSON of what a TOML document reads as
+    fn read(source: &str) -> Result<String, String> {
+        from_toml(source)
+            .map(|value| to_string(&value).unwrap())
+            .map_err(|err| err.0)
+    }
+
+    #[test]
+    fn writes_tables() {
+        let doc = r#"{"title":"x","server":{"host":"h","port":80,"tls":{"on":true}},"ratio":2.0,"point":{"x":1,"y":{}},"a b":[1,2]}"#;
+        assert_eq!(
+            toml(doc).unwrap(),
+            "title = \"x\"\nratio = 2.0\n\"a b\" = [1, 2]\n\n\
+             [server]\nhost = \"h\"\nport = 80\n\n[server.tls]\non = true\n\n\
+             [point]\nx = 1\n\n[point.y]\n"
+        );
+    }
+
+    #[test]
+    fn writes_arrays_of_tables() {
+        let doc = r#"{"products":[{"name":"a","tags":["x"]},{"name":"b","dims":{"w":1}}],"nested":[[{"k":1}]]}"#;
+        assert_eq!(
+            toml(doc).unwrap(),
+            "nested = [[{ k = 1 }]]\n\n\
+             [[products]]\nname = \"a\"\ntags = [\"x\"]\n\n\
+             [[products]]\nname = \"b\"\n\n[products.dims]\nw = 1\n"
+        );
+    }
+
+    #[test]
+    fn rejects_what_toml_cannot_hold() {
+        assert_eq!(
+            toml(r#"{"a":{"b":null}}"#).unwrap_err(),
+            "TOML error: null cannot be represented at '/a/b'"
+        );
+        assert_eq!(
+            toml(r#"{"a":[1,"x"]}"#).unwrap_err(),
+            "TOML error: arrays must not mix types at '/a'"
+        );
+        assert_eq!(
+            toml(r#"{"t":[{"a":[null]}]}"#).unwrap_err(),
+            "TOML error: null cannot be represented at '/t/0/a/0'"
+        );
+        assert_eq!(
+            toml("[1]").unwrap_err(),
+            "TOML error: a document must be an object, not array"
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let docs = [
+            r#"{"a":1,"b":{"c":[1,2],"d":{"e":"x\ny"}},"f":[{"g":true},{"g":false}]}"#,
+            r#"{"quoted key":-1.5,"big":1e300,"empty":{},"inline":[{"k":"v"}]}"#,
+        ];
+        for doc in docs {
+            let written = toml(doc).unwrap();
+            assert_eq!(
+                read(&written),
+                Ok(to_string(&json(doc)).unwrap()),
+                "{}",
+                written
+            );
+        }
+    }
+
+    #[test]
+    fn reads_toml() {
+        let source = "\
+# comment
+a.b = 1_000
+hex = 0xff
+date = 1979-05-27T07:32:00Z
+s = 'li\\teral'
+m = \"\"\"
+two
+lines\"\"\"
+inline = { x = [1, 2], y = \"\\u00e9\" }
+
+[[t]]
+n = 1
+[[t]]
+n = 2
+[t.sub]
+k = true
+";
+        assert_eq!(
+            read(source).unwrap(),
+            r#"{"a":{"b":1000},"hex":255,"date":"1979-05-27T07:32:00Z","s":"li\\teral","m":"two\nlines","inline":{"x":[1,2],"y":"é"},"t":[{"n":1},{"n":2,"sub":{"k":true}}]}"#
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        let error = |source: &str| read(source).unwrap_err();
+        assert_eq!(
+            error("a = 1\na = 2\n"),
+            "TOML error: 'a' is already defined at line 2"
+        );
+        assert_eq!(
+            error("[t]\n[t]\n"),
+            "TOML error: 't' is already defined at line 2"
+        );
+        assert_eq!(
+            error("a = 1\n[a.b]\n"),
+            "TOML error: 'a' is not a table at line 2"
+        );
+        assert_eq!(
+            error("a = inf\n"),
+            "TOML error: 'inf' cannot be represented in JSON at line 1"
+        );
+        assert_eq!(error("a 1\n"), "TOML error: expected '=' at line 1");
+        assert_eq!(
+            error("a = \"x\n"),
+            "TOML error: unterminated string at line 1"
+        );
+        assert_eq!(
+            error("a = 1 2\n"),
+            "TOML error: expected the end of the line at line 1"
+        );
+        assert_eq!(error("[t\n"), "TOML error: expected ']' at line 1");
+    }
+}
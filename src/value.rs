@@ -0,0 +1,126 @@
+use crate::parse::{JsonValue, Key};
+
+impl JsonValue {
+    /// Iterate the members of an object as `(key, value)` pairs
+    /// Yields nothing for values that are not objects.
+    pub(crate) fn members(&self) -> impl Iterator<Item = (&str, &JsonValue)> {
+        let members: &[JsonValue] = match self {
+            JsonValue::Object(members) => members,
+            JsonValue::KeyedObject(_, _) => std::slice::from_ref(self),
+            _ => &[],
+        };
+
+        members.iter().filter_map(|m| match m {
+            JsonValue::KeyedObject(key, value) => Some((key.as_ref(), value.as_ref())),
+            _ => None,
+        })
+    }
+
+    /// Build an object from its members, using `Empty` for `{}` like the parser does
+    pub(crate) fn from_members(members: Vec<(impl Into<Key>, JsonValue)>) -> JsonValue {
+        if members.is_empty() {
+            JsonValue::Empty
+        } else {
+            JsonValue::Object(
+                members
+                    .into_iter()
+                    .map(|(key, value)| JsonValue::KeyedObject(key.into(), Box::new(value)))
+                    .collect(),
+            )
+        }
+    }
+
+    /// Take an object apart into its `(key, value)` members
+    /// Gives nothing for values that are not objects. `JsonValue` implements
+    /// `Drop`, so a pattern cannot move the members out of it.
+    pub(crate) fn into_members(mut self) -> Vec<(Key, JsonValue)> {
+        match &mut self {
+            JsonValue::Object(members) => std::mem::take(members)
+                .into_vec()
+                .into_iter()
+                .filter_map(|m| m.into_member().ok())
+                .collect(),
+            JsonValue::KeyedObject(_, _) => self.into_member().into_iter().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The key and value of a member, or the value back if it is not one
+    pub(crate) fn into_member(mut self) -> Result<(Key, JsonValue), JsonValue> {
+        match &mut self {
+            JsonValue::KeyedObject(key, value) => Ok((
+                key.clone(),
+                std::mem::replace(&mut **value, JsonValue::Null),
+            )),
+            _ => Err(self),
+        }
+    }
+
+    /// The values of an array, or the value back if it is not one
+    pub(crate) fn into_array(mut self) -> Result<Vec<JsonValue>, JsonValue> {
+        match &mut self {
+            JsonValue::Arr(values) => Ok(std::mem::take(values).into_vec()),
+            _ => Err(self),
+        }
+    }
+
+    /// The text of a string, or the value back if it is not one
+    pub(crate) fn into_str(mut self) -> Result<Box<str>, JsonValue> {
+        match &mut self {
+            JsonValue::Str(s) => Ok(std::mem::take(s)),
+            _ => Err(self),
+        }
+    }
+
+    /// Look up the first member named `key`
+    pub(crate) fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.members().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    pub(crate) fn is_object(&self) -> bool {
+        matches!(
+            self,
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty
+        )
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Arr(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Float(f, _) => Some(*f),
+            JsonValue::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    /// The JSON type of the value, e.g. `"object"` or `"number"`
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            JsonValue::Object(_) | JsonValue::KeyedObject(_, _) | JsonValue::Empty => "object",
+            JsonValue::Float(_, _) | JsonValue::Int(_) => "number",
+            JsonValue::Str(_) => "string",
+            JsonValue::Bool(_) => "boolean",
+            JsonValue::Arr(_) => "array",
+            JsonValue::Null => "null",
+        }
+    }
+}
+
+/// Change the values of a container as a vector, boxing them back after
+/// Containers hold exactly their values, so every change of length reallocates;
+/// make a run of changes in one edit rather than one edit each.
+pub(crate) fn edit_values<T>(
+    values: &mut Box<[JsonValue]>,
+    edit: impl FnOnce(&mut Vec<JsonValue>) -> T,
+) -> T {
+    let mut vec = std::mem::take(values).into_vec();
+    let result = edit(&mut vec);
+    *values = vec.into_boxed_slice();
+    result
+}
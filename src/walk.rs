@@ -0,0 +1,164 @@
+use crate::parse::JsonValue;
+use crate::pointer::JsonPointer;
+
+/// Depth-first iterator over the values of a document and their pointers
+/// Created by `JsonValue::paths` and `JsonValue::all_paths`.
+pub struct Paths<'a> {
+    stack: Vec<(JsonPointer, &'a JsonValue)>,
+    containers: bool,
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = (JsonPointer, &'a JsonValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((pointer, value)) = self.stack.pop() {
+            let children = children(value);
+            if children.is_empty() {
+                return Some((pointer, value));
+            }
+
+            // Reversed so the first child is visited first
+            for (token, child) in children.into_iter().rev() {
+                let mut pointer = pointer.clone();
+                pointer.push(token);
+                self.stack.push((pointer, child));
+            }
+            if self.containers {
+                return Some((pointer, value));
+            }
+        }
+        None
+    }
+}
+
+impl JsonValue {
+    /// Iterate every leaf in document order, e.g. `("/users/0/name", "Ada")`
+    /// Scalars and empty containers are leaves.
+    pub fn paths(&self) -> Paths<'_> {
+        Paths {
+            stack: vec![(JsonPointer::default(), self)],
+            containers: false,
+        }
+    }
+
+    /// Like `paths`, but also yields each non-empty container before its children
+    pub fn all_paths(&self) -> Paths<'_> {
+        Paths {
+            stack: vec![(JsonPointer::default(), self)],
+            containers: true,
+        }
+    }
+
+    /// Collect every value, containers included, for which `predicate` holds
+    /// e.g. `value.find_all(|_, v| matches!(v, JsonValue::Str(s) if s.len() > 1024))`
+    pub fn find_all<F>(&self, mut predicate: F) -> Vec<(JsonPointer, &JsonValue)>
+    where
+        F: FnMut(&JsonPointer, &JsonValue) -> bool,
+    {
+        self.all_paths()
+            .filter(|(pointer, value)| predicate(pointer, value))
+            .collect()
+    }
+
+    /// Find every object member named `key`, at any depth
+    pub fn find_key(&self, key: &str) -> Vec<(JsonPointer, &JsonValue)> {
+        self.find_keys_by(|k| k == key)
+    }
+
+    /// Find every object member whose key matches a glob, e.g. `"*_id"`
+    /// `*` matches any run of characters and `?` a single one.
+    pub fn find_key_glob(&self, pattern: &str) -> Vec<(JsonPointer, &JsonValue)> {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+        self.find_keys_by(|k| glob_match(&pattern, &k.chars().collect::<Vec<_>>()))
+    }
+
+    fn find_keys_by<F>(&self, mut matches: F) -> Vec<(JsonPointer, &JsonValue)>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let mut found = vec![];
+        for (pointer, value) in self.all_paths() {
+            for (key, member) in value.members().filter(|(k, _)| matches(k)) {
+                let mut pointer = pointer.clone();
+                pointer.push(key);
+                found.push((pointer, member));
+            }
+        }
+        found
+    }
+
+    /// Rebuild the tree bottom-up, passing every value through `rewrite`
+    /// Return the value (possibly changed) to keep it or `None` to drop it,
+    /// e.g. `value.transform(|_, v| (!matches!(v, JsonValue::Null)).then_some(v))`.
+    /// Children are rewritten first and pointers refer to the original positions.
+    /// Returns `None` if the root itself is dropped.
+    pub fn transform<F>(self, mut rewrite: F) -> Option<JsonValue>
+    where
+        F: FnMut(&JsonPointer, JsonValue) -> Option<JsonValue>,
+    {
+        rebuild(self, &mut JsonPointer::default(), &mut rewrite)
+    }
+}
+
+fn rebuild<F>(value: JsonValue, pointer: &mut JsonPointer, rewrite: &mut F) -> Option<JsonValue>
+where
+    F: FnMut(&JsonPointer, JsonValue) -> Option<JsonValue>,
+{
+    let mut child = |token: String, value: JsonValue| {
+        pointer.push(token);
+        let value = rebuild(value, pointer, rewrite);
+        pointer.pop();
+        value
+    };
+
+    let value = match value.into_array() {
+        Ok(values) => JsonValue::Arr(
+            values
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, v)| child(i.to_string(), v))
+                .collect(),
+        ),
+        Err(value) if value.is_object() => {
+            let members = value
+                .into_members()
+                .into_iter()
+                .filter_map(|(k, v)| Some((k.clone(), child(k.to_string(), v)?)))
+                .collect();
+            JsonValue::from_members(members)
+        }
+        Err(scalar) => scalar,
+    };
+    rewrite(pointer, value)
+}
+
+/// The direct children of a container with their reference tokens
+fn children(value: &JsonValue) -> Vec<(String, &JsonValue)> {
+    match value {
+        JsonValue::Arr(values) => values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i.to_string(), v))
+            .collect(),
+        _ => value.members().map(|(k, v)| (k.to_string(), v)).collect(),
+    }
+}
+
+/// Whether `text` matches a glob in which `*` matches any run of characters and
+/// `?` a single one, e.g. `"*.json"` for file names
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    glob_match(
+        &pattern.chars().collect::<Vec<_>>(),
+        &text.chars().collect::<Vec<_>>(),
+    )
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
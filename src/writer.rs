@@ -0,0 +1,275 @@
+use std::io::Write;
+
+use crate::parse::JsonValue;
+use crate::serialize::{
+    format_float, write_compact, write_int, write_string, SerializeError, SerializeOptions,
+};
+use crate::stream::Event;
+
+#[derive(Clone, Copy, Debug)]
+enum Frame {
+    Object { first: bool, expect_key: bool },
+    Array { first: bool },
+}
+
+/// Incremental JSON serializer driven by parse events
+/// e.g., `start_object`, `key("a")`, `int(1)`, `end_object` writes `{"a":1}`
+/// Output is compact unless built with `pretty`, and written as soon as each
+/// event arrives, so documents can be transformed without ever materializing a tree.
+pub struct JsonWriter<W: Write> {
+    out: W,
+    options: SerializeOptions,
+    stack: Vec<Frame>,
+    done: bool,
+    pretty: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self::with_options(out, SerializeOptions::default())
+    }
+
+    pub fn with_options(out: W, options: SerializeOptions) -> Self {
+        JsonWriter {
+            out,
+            options,
+            stack: vec![],
+            done: false,
+            pretty: false,
+        }
+    }
+
+    /// Write one member or element per line, indented with `options.indent`
+    /// Keys are written in the order they arrive, `sort_keys` does not apply.
+    pub fn pretty(out: W, options: SerializeOptions) -> Self {
+        JsonWriter {
+            pretty: true,
+            ..Self::with_options(out, options)
+        }
+    }
+
+    pub fn start_object(&mut self) -> Result<(), SerializeError> {
+        self.begin_value()?;
+        self.stack.push(Frame::Object {
+            first: true,
+            expect_key: true,
+        });
+        self.emit(b"{")
+    }
+
+    pub fn end_object(&mut self) -> Result<(), SerializeError> {
+        match self.stack.last() {
+            Some(&Frame::Object {
+                expect_key: true,
+                first,
+            }) => {
+                self.stack.pop();
+                self.end_value();
+                self.close(first, b"}")
+            }
+            Some(Frame::Object { .. }) => Err(self.err("object member is missing its value")),
+            _ => Err(self.err("end_object outside of an object")),
+        }
+    }
+
+    pub fn start_array(&mut self) -> Result<(), SerializeError> {
+        self.begin_value()?;
+        self.stack.push(Frame::Array { first: true });
+        self.emit(b"[")
+    }
+
+    pub fn end_array(&mut self) -> Result<(), SerializeError> {
+        match self.stack.last() {
+            Some(&Frame::Array { first }) => {
+                self.stack.pop();
+                self.end_value();
+                self.close(first, b"]")
+            }
+            _ => Err(self.err("end_array outside of an array")),
+        }
+    }
+
+    /// Write the key of the next object member
+    pub fn key(&mut self, key: &str) -> Result<(), SerializeError> {
+        let first = match self.stack.last_mut() {
+            Some(Frame::Object { first, expect_key }) if *expect_key => {
+                *expect_key = false;
+                std::mem::replace(first, false)
+            }
+            _ => return Err(self.err("key outside of an object")),
+        };
+
+        let mut text = vec![];
+        if !first {
+            text.push(b',');
+        }
+        self.newline(&mut text);
+        write_string(&mut text, key, &self.options)?;
+        text.push(b':');
+        if self.pretty {
+            text.push(b' ');
+        }
+        self.emit(&text)
+    }
+
+    pub fn string(&mut self, s: &str) -> Result<(), SerializeError> {
+        let mut text = vec![];
+        write_string(&mut text, s, &self.options)?;
+        self.scalar(&text)
+    }
+
+    /// Write a number given as its JSON lexeme, e.g. `-1.5e3`
+    pub fn number(&mut self, lexeme: &str) -> Result<(), SerializeError> {
+        if !is_number(lexeme) {
+            return Err(self.err(&format!("'{}' is not a JSON number", lexeme)));
+        }
+        self.scalar(lexeme.as_bytes())
+    }
+
+    pub fn int(&mut self, i: i64) -> Result<(), SerializeError> {
+        let mut text = vec![];
+        write_int(&mut text, i);
+        self.scalar(&text)
+    }
+
+    pub fn float(&mut self, f: f64) -> Result<(), SerializeError> {
+        if !f.is_finite() {
+            return Err(self.err(&format!("{} cannot be represented in JSON", f)));
+        }
+        self.scalar(format_float(f).as_bytes())
+    }
+
+    pub fn bool(&mut self, b: bool) -> Result<(), SerializeError> {
+        self.scalar(if b { b"true" } else { b"false" })
+    }
+
+    pub fn null(&mut self) -> Result<(), SerializeError> {
+        self.scalar(b"null")
+    }
+
+    /// Write a whole value at once, e.g. a subtree that is already in memory
+    pub fn value(&mut self, value: &JsonValue) -> Result<(), SerializeError> {
+        let mut text = vec![];
+        write_compact(&mut text, value, &self.options)?;
+        self.scalar(&text)
+    }
+
+    /// Write an event read by a `JsonReader`
+    pub fn event(&mut self, event: &Event) -> Result<(), SerializeError> {
+        match event {
+            Event::StartObject => self.start_object(),
+            Event::EndObject => self.end_object(),
+            Event::StartArray => self.start_array(),
+            Event::EndArray => self.end_array(),
+            Event::Key(key) => self.key(key),
+            Event::Str(s) => self.string(s),
+            Event::Number(num) => self.number(num),
+            Event::Bool(b) => self.bool(*b),
+            Event::Null => self.null(),
+        }
+    }
+
+    /// Check that a complete document was written, flush, and hand back the sink
+    pub fn finish(mut self) -> Result<W, SerializeError> {
+        if !self.done || !self.stack.is_empty() {
+            return Err(self.err("incomplete document"));
+        }
+        if self.options.trailing_newline {
+            let line_ending = self.options.line_ending.as_bytes();
+            self.emit(line_ending)?;
+        }
+        self.out
+            .flush()
+            .map_err(|e| SerializeError(format!("IO error: {}", e)))?;
+        Ok(self.out)
+    }
+
+    fn scalar(&mut self, text: &[u8]) -> Result<(), SerializeError> {
+        self.begin_value()?;
+        self.end_value();
+        self.emit(text)
+    }
+
+    /// Check that a value may start here and write the separating comma
+    fn begin_value(&mut self) -> Result<(), SerializeError> {
+        match self.stack.last_mut() {
+            None if self.done => Err(self.err("document is already complete")),
+            None => Ok(()),
+            Some(Frame::Object {
+                expect_key: true, ..
+            }) => Err(self.err("expected a key before the value")),
+            Some(Frame::Object { expect_key, .. }) => {
+                *expect_key = true;
+                Ok(())
+            }
+            Some(Frame::Array { first }) => {
+                let mut text = vec![];
+                if !std::mem::replace(first, false) {
+                    text.push(b',');
+                }
+                self.newline(&mut text);
+                self.emit(&text)
+            }
+        }
+    }
+
+    /// Write the closing bracket, on its own line unless the container is empty
+    fn close(&mut self, empty: bool, bracket: &[u8]) -> Result<(), SerializeError> {
+        let mut text = vec![];
+        if !empty {
+            self.newline(&mut text);
+        }
+        text.extend_from_slice(bracket);
+        self.emit(&text)
+    }
+
+    /// Break the line and indent to the current depth, in pretty mode only
+    fn newline(&self, text: &mut Vec<u8>) {
+        if self.pretty {
+            text.extend_from_slice(self.options.line_ending.as_bytes());
+            for _ in 0..self.stack.len() {
+                text.extend_from_slice(self.options.indent.as_bytes());
+            }
+        }
+    }
+
+    fn end_value(&mut self) {
+        if self.stack.is_empty() {
+            self.done = true;
+        }
+    }
+
+    fn emit(&mut self, text: &[u8]) -> Result<(), SerializeError> {
+        self.out
+            .write_all(text)
+            .map_err(|e| SerializeError(format!("IO error: {}", e)))
+    }
+
+    fn err(&self, msg: &str) -> SerializeError {
+        SerializeError(format!("Serialize error: {}", msg))
+    }
+}
+
+/// Check `s` against the JSON number grammar
+pub(crate) fn is_number(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let (int, rest) = s.split_at(s.find(['.', 'e', 'E']).unwrap_or(s.len()));
+    let int_ok = int == "0" || (!int.starts_with('0') && all_digits(int));
+
+    let (frac, exp) = match rest.strip_prefix('.') {
+        Some(rest) => rest.split_at(rest.find(['e', 'E']).unwrap_or(rest.len())),
+        None => ("", rest),
+    };
+    let frac_ok = !rest.starts_with('.') || all_digits(frac);
+
+    let exp_ok = exp.is_empty() || {
+        let exp = &exp[1..];
+        all_digits(exp.strip_prefix(['+', '-']).unwrap_or(exp))
+    };
+
+    int_ok && frac_ok && exp_ok
+}
+
+fn all_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
@@ -0,0 +1,761 @@
+use std::fmt;
+
+use crate::parse::JsonValue;
+use crate::serialize::{to_string, SerializeError};
+
+#[derive(Debug)]
+pub struct YamlError(pub String);
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for YamlError {}
+
+/// Serialize as a block-style YAML document
+/// Strings are left unquoted where YAML would read them back as the same
+/// string, otherwise they are written as JSON strings, which YAML accepts.
+pub fn to_yaml(value: &JsonValue) -> Result<String, SerializeError> {
+    let mut out = String::new();
+    for line in block(value)? {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// The lines of a value, without the indentation of its parent
+fn block(value: &JsonValue) -> Result<Vec<String>, SerializeError> {
+    let mut lines = vec![];
+    match value {
+        JsonValue::Arr(values) if !values.is_empty() => {
+            for value in values {
+                let mut item = block(value)?.into_iter();
+                let first = item.next().unwrap_or_default();
+                lines.push(format!("- {}", first));
+                lines.extend(item.map(|line| format!("  {}", line)));
+            }
+        }
+        _ if value.is_object() && value.members().next().is_some() => {
+            for (key, value) in value.members() {
+                let key = scalar_text(&JsonValue::Str(key.into()))?;
+                if is_block(value) {
+                    lines.push(format!("{}:", key));
+                    lines.extend(block(value)?.into_iter().map(|line| format!("  {}", line)));
+                } else {
+                    lines.push(format!("{}: {}", key, scalar_text(value)?));
+                }
+            }
+        }
+        _ => lines.push(scalar_text(value)?),
+    }
+    Ok(lines)
+}
+
+/// Non-empty arrays and objects, which take lines of their own
+fn is_block(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Arr(values) => !values.is_empty(),
+        _ => value.members().next().is_some(),
+    }
+}
+
+fn scalar_text(value: &JsonValue) -> Result<String, SerializeError> {
+    match value {
+        JsonValue::Str(s) if is_plain(s) => Ok(s.to_string()),
+        JsonValue::Arr(_) => Ok("[]".to_string()),
+        _ if value.is_object() => Ok("{}".to_string()),
+        _ => to_string(value),
+    }
+}
+
+/// Whether a string can be written without quotes and still read back as itself
+fn is_plain(s: &str) -> bool {
+    let mut chars = s.chars();
+    let first_ok = chars
+        .next()
+        .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '/' || c == '.');
+    first_ok
+        && s.chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/' | ' '))
+        && !s.ends_with(' ')
+        && matches!(plain(s), JsonValue::Str(_))
+        && !is_yaml11_bool(s)
+}
+
+/// Words that YAML 1.1 readers, still common, take for booleans although the
+/// 1.2 core schema does not
+fn is_yaml11_bool(s: &str) -> bool {
+    matches!(
+        s.to_ascii_lowercase().as_str(),
+        "y" | "n" | "yes" | "no" | "on" | "off"
+    )
+}
+
+/// Parse a YAML document into JSON
+/// Supported: block mappings and sequences, flow `[...]` and `{...}`
+/// collections, plain, single and double quoted scalars, `|` and `>` block
+/// scalars and comments, with plain scalars resolved as in the YAML 1.2 core
+/// schema. Anchors, aliases, tags, complex keys and multiple documents are errors.
+pub fn from_yaml(source: &str) -> Result<JsonValue, YamlError> {
+    let mut lines = vec![];
+    for (i, raw) in source.lines().enumerate() {
+        let text = strip_comment(raw).trim_end();
+        let content = text.trim_start_matches(' ');
+        if content.starts_with('\t') {
+            return Err(err("tabs cannot indent", i + 1));
+        }
+        lines.push(Line {
+            indent: text.len() - content.len(),
+            text: content.to_string(),
+            raw,
+            number: i + 1,
+        });
+    }
+
+    let mut parser = YamlParser { lines, pos: 0 };
+    parser.skip_blank();
+    if parser.peek().is_some_and(|line| line.text == "---") {
+        parser.pos += 1;
+    }
+    let value = parser.node(0)?;
+
+    parser.skip_blank();
+    match parser.peek() {
+        None => Ok(value),
+        Some(line) if line.text == "..." => Ok(value),
+        Some(line) if is_marker(&line.text) => {
+            Err(err("multiple documents are not supported", line.number))
+        }
+        Some(line) => Err(err("unexpected content", line.number)),
+    }
+}
+
+fn err(msg: &str, line: usize) -> YamlError {
+    YamlError(format!("YAML error: {} at line {}", msg, line))
+}
+
+struct Line<'a> {
+    indent: usize,
+    /// The line without indentation and comment
+    text: String,
+    raw: &'a str,
+    number: usize,
+}
+
+struct YamlParser<'a> {
+    lines: Vec<Line<'a>>,
+    pos: usize,
+}
+
+impl YamlParser<'_> {
+    fn peek(&self) -> Option<&Line<'_>> {
+        self.lines.get(self.pos)
+    }
+
+    fn skip_blank(&mut self) {
+        while self.peek().is_some_and(|line| line.text.is_empty()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parse the node starting at the next line, if it is indented by at least `min`
+    fn node(&mut self, min: usize) -> Result<JsonValue, YamlError> {
+        self.skip_blank();
+        let Some(line) = self.peek() else {
+            return Ok(JsonValue::Null);
+        };
+        if line.indent < min {
+            return Ok(JsonValue::Null);
+        }
+
+        let (indent, number) = (line.indent, line.number);
+        if line.text == "-" || line.text.starts_with("- ") {
+            self.sequence(indent)
+        } else if split_key(&line.text, number)?.is_some() {
+            self.mapping(indent)
+        } else {
+            let text = self.flow_text()?;
+            inline(&text, number)
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<JsonValue, YamlError> {
+        let mut values = vec![];
+        loop {
+            self.skip_blank();
+            let Some(line) = self.peek() else { break };
+            if line.indent != indent || !(line.text == "-" || line.text.starts_with("- ")) {
+                break;
+            }
+
+            let rest = line.text[1..].trim_start().to_string();
+            let offset = line.text.len() - rest.len();
+            let number = line.number;
+            if rest.is_empty() {
+                self.pos += 1;
+                values.push(self.node(indent + 1)?);
+            } else if is_block_scalar(&rest) {
+                self.pos += 1;
+                values.push(self.block_scalar(&rest, indent, number)?);
+            } else {
+                // Parse the rest of the line as if it started a line of its own
+                let line = &mut self.lines[self.pos];
+                line.indent += offset;
+                line.text = rest;
+                values.push(self.node(indent + 1)?);
+            }
+        }
+        Ok(JsonValue::Arr(values.into()))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<JsonValue, YamlError> {
+        let mut members = vec![];
+        loop {
+            self.skip_blank();
+            let Some(line) = self.peek().filter(|line| !is_marker(&line.text)) else {
+                break;
+            };
+            if line.indent != indent {
+                if line.indent > indent {
+                    return Err(err("bad indentation", line.number));
+                }
+                break;
+            }
+            let number = line.number;
+            let Some((key, rest)) = split_key(&line.text, number)? else {
+                return Err(err("expected a mapping key", number));
+            };
+            self.pos += 1;
+
+            let value = if is_block_scalar(&rest) {
+                self.block_scalar(&rest, indent, number)?
+            } else if !rest.is_empty() {
+                // Flow collections may continue on the following lines
+                self.pos -= 1;
+                self.lines[self.pos].text = rest;
+                let text = self.flow_text()?;
+                inline(&text, number)?
+            } else {
+                self.skip_blank();
+                match self.peek() {
+                    // A sequence may sit at the indentation of its key
+                    Some(next)
+                        if next.indent == indent
+                            && (next.text == "-" || next.text.starts_with("- ")) =>
+                    {
+                        self.sequence(indent)?
+                    }
+                    _ => self.node(indent + 1)?,
+                }
+            };
+            members.push((key, value));
+        }
+        Ok(JsonValue::from_members(members))
+    }
+
+    /// The current line, joined with the following ones while a flow collection is open
+    fn flow_text(&mut self) -> Result<String, YamlError> {
+        let line = &self.lines[self.pos];
+        let number = line.number;
+        let mut text = line.text.clone();
+        self.pos += 1;
+        while depth(&text) > 0 {
+            match self.peek() {
+                Some(line) => {
+                    text.push(' ');
+                    text.push_str(&line.text);
+                    self.pos += 1;
+                }
+                None => return Err(err("unclosed flow collection", number)),
+            }
+        }
+        Ok(text)
+    }
+
+    /// A `|` (literal) or `>` (folded) scalar on the lines indented past `parent`
+    fn block_scalar(
+        &mut self,
+        header: &str,
+        parent: usize,
+        number: usize,
+    ) -> Result<JsonValue, YamlError> {
+        let folded = header.starts_with('>');
+        let chomp = header[1..].trim();
+        if !matches!(chomp, "" | "-" | "+") {
+            return Err(err("unsupported block scalar header", number));
+        }
+
+        let mut raw = vec![];
+        while let Some(line) = self.lines.get(self.pos) {
+            if !line.text.is_empty() && line.indent <= parent {
+                break;
+            }
+            raw.push(line.raw);
+            self.pos += 1;
+        }
+        let indent = raw
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start_matches(' ').len())
+            .min()
+            .unwrap_or(0);
+        let body = raw
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or(""))
+            .collect::<Vec<_>>();
+
+        // Folding joins adjacent lines with a space and turns each empty line
+        // into a line break; more indented lines are kept as they are
+        let mut text = String::new();
+        for (i, line) in body.iter().enumerate() {
+            text.push_str(line);
+            let folds = folded && !line.is_empty() && !line.starts_with(' ');
+            match body.get(i + 1) {
+                Some(next) if folds && next.is_empty() => (),
+                Some(next) if folds && !next.starts_with(' ') => text.push(' '),
+                _ => text.push('\n'),
+            }
+        }
+        match chomp {
+            "+" => (),
+            _ => {
+                let trimmed = text.trim_end_matches('\n').len();
+                text.truncate(trimmed);
+                if chomp.is_empty() && trimmed > 0 {
+                    text.push('\n');
+                }
+            }
+        }
+        Ok(JsonValue::Str(text.into()))
+    }
+}
+
+/// The `---` and `...` lines around documents
+fn is_marker(text: &str) -> bool {
+    text == "---" || text.starts_with("--- ") || text == "..."
+}
+
+fn is_block_scalar(text: &str) -> bool {
+    text.starts_with('|') || text.starts_with('>')
+}
+
+/// Cut a comment off a line, `#` only starts one outside quotes after whitespace
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') if prev.is_whitespace() => return &line[..i],
+            (None, '"' | '\'') if prev.is_whitespace() || "[{,:".contains(prev) => quote = Some(c),
+            (Some('"'), '\\') if prev == '\\' => {
+                prev = ' ';
+                continue;
+            }
+            (Some(q), c) if c == q && !(q == '"' && prev == '\\') => quote = None,
+            _ => (),
+        }
+        prev = c;
+    }
+    line
+}
+
+/// How many flow collections are still open at the end of `text`
+fn depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut prev = ' ';
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth -= 1,
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q && !(q == '"' && prev == '\\') => quote = None,
+            _ => (),
+        }
+        prev = if prev == '\\' && c == '\\' { ' ' } else { c };
+    }
+    depth
+}
+
+/// Split `key: value` into the key and the rest of the line
+/// `None` if the line is not a mapping entry.
+fn split_key(text: &str, number: usize) -> Result<Option<(String, String)>, YamlError> {
+    if text.starts_with("? ") {
+        return Err(err("complex keys are not supported", number));
+    }
+    if text.starts_with('[') || text.starts_with('{') {
+        return Ok(None);
+    }
+
+    let (key, after) = if text.starts_with('"') || text.starts_with('\'') {
+        let mut cursor = Cursor::new(text, number);
+        let key = cursor.quoted()?;
+        (key, &text[cursor.pos..])
+    } else {
+        let Some(colon) = text
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|&i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))
+        else {
+            return Ok(None);
+        };
+        (text[..colon].trim_end().to_string(), &text[colon..])
+    };
+
+    match after.trim_start().strip_prefix(':') {
+        Some(rest) if rest.is_empty() || rest.starts_with(' ') => {
+            Ok(Some((key, rest.trim().to_string())))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// A scalar or flow collection written on one line
+fn inline(text: &str, number: usize) -> Result<JsonValue, YamlError> {
+    let mut cursor = Cursor::new(text, number);
+    let value = cursor.flow(false)?;
+    cursor.skip_spaces();
+    if cursor.pos < text.len() {
+        return Err(err(
+            &format!("unexpected '{}'", &text[cursor.pos..]),
+            number,
+        ));
+    }
+    Ok(value)
+}
+
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+    number: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str, number: usize) -> Self {
+        Cursor {
+            text,
+            pos: 0,
+            number,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek() == Some(' ') {
+            self.pos += 1;
+        }
+    }
+
+    /// A value, stopping plain scalars at `,`, `]` and `}` inside collections
+    fn flow(&mut self, nested: bool) -> Result<JsonValue, YamlError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('[') => {
+                self.pos += 1;
+                let mut values = vec![];
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(JsonValue::Arr(values.into()));
+                    }
+                    values.push(self.flow(true)?);
+                    self.separator(']')?;
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut members = vec![];
+                loop {
+                    self.skip_spaces();
+                    if self.peek() == Some('}') {
+                        self.pos += 1;
+                        return Ok(JsonValue::from_members(members));
+                    }
+                    let key = match self.peek() {
+                        Some('"' | '\'') => self.quoted()?,
+                        _ => self.plain_text(&[':', ',', '}']).to_string(),
+                    };
+                    self.skip_spaces();
+                    let value = if self.peek() == Some(':') {
+                        self.pos += 1;
+                        self.flow(true)?
+                    } else {
+                        JsonValue::Null
+                    };
+                    members.push((key, value));
+                    self.separator('}')?;
+                }
+            }
+            Some('"' | '\'') => self.quoted().map(|s| JsonValue::Str(s.into())),
+            Some(c @ ('&' | '*' | '!')) => {
+                let what = match c {
+                    '&' => "anchors",
+                    '*' => "aliases",
+                    _ => "tags",
+                };
+                Err(err(&format!("{} are not supported", what), self.number))
+            }
+            _ => {
+                let stops: &[char] = if nested { &[',', ']', '}'] } else { &[] };
+                Ok(plain(self.plain_text(stops)))
+            }
+        }
+    }
+
+    /// Consume the `,` after a collection entry, or leave its closing bracket
+    fn separator(&mut self, close: char) -> Result<(), YamlError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some(',') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) if c == close => Ok(()),
+            _ => Err(err(&format!("expected ',' or '{}'", close), self.number)),
+        }
+    }
+
+    fn plain_text(&mut self, stops: &[char]) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let key_end = c == ':' && self.text[self.pos + 1..].starts_with([' ', ',', '}']);
+            if stops.contains(&c) && (c != ':' || key_end || self.pos + 1 == self.text.len()) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        self.text[start..self.pos].trim()
+    }
+
+    /// A single or double quoted scalar, with the escapes of double quoted ones decoded
+    fn quoted(&mut self) -> Result<String, YamlError> {
+        let quote = self.peek().expect("at a quote");
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.text[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\'' if quote == '\'' => {
+                    if self.text[self.pos + i + 1..].starts_with('\'') {
+                        chars.next();
+                        out.push('\'');
+                        continue;
+                    }
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '"' if quote == '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' if quote == '"' => {
+                    let escape = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some(c @ ('"' | '\\' | '/' | ' ')) => c,
+                        Some(c @ ('x' | 'u' | 'U')) => {
+                            let len = match c {
+                                'x' => 2,
+                                'u' => 4,
+                                _ => 8,
+                            };
+                            let hex = (0..len).filter_map(|_| chars.next()).map(|(_, c)| c);
+                            u32::from_str_radix(&hex.collect::<String>(), 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| err("invalid escape", self.number))?
+                        }
+                        _ => return Err(err("invalid escape", self.number)),
+                    };
+                    out.push(escape);
+                }
+                c => out.push(c),
+            }
+        }
+        Err(err("unterminated string", self.number))
+    }
+}
+
+/// Resolve a plain scalar through the YAML 1.2 core schema
+/// Infinities and NaN have no JSON counterpart and stay strings.
+fn plain(text: &str) -> JsonValue {
+    match text {
+        "" | "~" | "null" | "Null" | "NULL" => return JsonValue::Null,
+        "true" | "True" | "TRUE" => return JsonValue::Bool(true),
+        "false" | "False" | "FALSE" => return JsonValue::Bool(false),
+        _ => (),
+    }
+
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+        if let Ok(i) = text.parse::<i64>() {
+            return JsonValue::Int(i);
+        }
+    }
+    for (prefix, radix) in [("0x", 16), ("0o", 8)] {
+        if let Some(i) = text
+            .strip_prefix(prefix)
+            .and_then(|n| i64::from_str_radix(n, radix).ok())
+        {
+            return JsonValue::Int(i);
+        }
+    }
+
+    let number = digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'-' | b'+'));
+    match text.parse::<f64>() {
+        Ok(f) if number && f.is_finite() => JsonValue::Float(f, None),
+        _ => JsonValue::Str(text.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parser;
+    use crate::tokenize::Tokenizer;
+
+    fn json(text: &str) -> JsonValue {
+        Parser::new(Tokenizer::new().tokens(text)).parse().unwrap()
+    }
+
+    fn yaml(text: &str) -> String {
+        to_yaml(&json(text)).unwrap()
+    }
+
+    /// Compact JSON of what a YAML document reads as
+    fn read(source: &str) -> String {
+        to_string(&from_yaml(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_blocks() {
+        assert_eq!(
+            yaml(r#"{"a":1,"b":[true,null,{"c":"d"}],"e":{},"f":[]}"#),
+            "a: 1\nb:\n  - true\n  - null\n  - c: d\ne: {}\nf: []\n"
+        );
+        assert_eq!(yaml("[[1,2],3]"), "- - 1\n  - 2\n- 3\n");
+        assert_eq!(yaml("\"plain text\""), "plain text\n");
+    }
+
+    #[test]
+    fn quotes_strings_that_would_read_back_differently() {
+        let cases = [
+            ("yes", "\"yes\""),
+            ("No", "\"No\""),
+            ("on", "\"on\""),
+            ("1", "\"1\""),
+            ("-2.5", "\"-2.5\""),
+            ("null", "\"null\""),
+            ("~", "\"~\""),
+            ("true", "\"true\""),
+            (": ", "\": \""),
+            ("a: b", "\"a: b\""),
+            ("# x", "\"# x\""),
+            ("- x", "\"- x\""),
+            ("", "\"\""),
+            ("trailing ", "\"trailing \""),
+            ("two\nlines", "\"two\\nlines\""),
+            ("tab\there", "\"tab\\there\""),
+            ("quote\"", "\"quote\\\"\""),
+            ("yesterday", "yesterday"),
+            ("a.b/c-d", "a.b/c-d"),
+        ];
+        for (s, written) in cases {
+            let value = JsonValue::Str(s.into());
+            assert_eq!(
+                to_yaml(&value).unwrap(),
+                format!("{}\n", written),
+                "{:?}",
+                s
+            );
+            let key = JsonValue::from_members(vec![(s, JsonValue::Int(1))]);
+            assert_eq!(
+                to_yaml(&key).unwrap(),
+                format!("{}: 1\n", written),
+                "{:?}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let docs = [
+            r#"{"a":{"b":[1,-2.5,"x y"],"c":null},"d":[{"e":true},{"f":[]}]}"#,
+            r#"["yes","1",": ","two\nlines","\u00e9\u0001",{"":"empty key"}]"#,
+            r#"{"multi":"line one\nline two\n","nested":[[[]],{}]}"#,
+        ];
+        for doc in docs {
+            let written = yaml(doc);
+            assert_eq!(
+                read(&written),
+                to_string(&json(doc)).unwrap(),
+                "{}",
+                written
+            );
+        }
+    }
+
+    #[test]
+    fn reads_yaml() {
+        let source = "\
+---
+# a comment
+name: jsonp  # trailing
+count: 0x1f
+ratio: .5
+flags: [on, 'it''s', \"a\\tb\"]
+empty: ~
+text: |
+  kept
+  as is
+folded: >-
+  joined
+  lines
+list:
+  - a: 1
+    b: 2
+  - plain words
+...
+";
+        assert_eq!(
+            read(source),
+            r#"{"name":"jsonp","count":31,"ratio":0.5,"flags":["on","it's","a\tb"],"empty":null,"text":"kept\nas is\n","folded":"joined lines","list":[{"a":1,"b":2},"plain words"]}"#
+        );
+    }
+
+    #[test]
+    fn rejects_what_it_does_not_support() {
+        let error = |source: &str| from_yaml(source).err().unwrap().0;
+        assert_eq!(
+            error("a: 1\n---\nb: 2\n"),
+            "YAML error: multiple documents are not supported at line 2"
+        );
+        assert_eq!(
+            error("a:\n\t- 1\n"),
+            "YAML error: tabs cannot indent at line 2"
+        );
+        assert_eq!(
+            error("a: [1, 2\n"),
+            "YAML error: unclosed flow collection at line 1"
+        );
+        assert_eq!(
+            error("a: \"x\n"),
+            "YAML error: unterminated string at line 1"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! What parsing allocates, counted by this test's own global allocator
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use jsonp::parse::Parser;
+use jsonp::tokenize::Tokenizer;
+
+/// The system allocator, counting the allocations of each thread
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn validating_does_not_allocate() {
+    let nested = format!("{}1{}", "[{\"a\":".repeat(500), "}]".repeat(500));
+    for text in [
+        r#"{"a": [1, 2.5e3, -0, true, null], "b\n": {"c": "é\"\\"}}"#,
+        nested.as_str(),
+    ] {
+        let count = allocations(|| {
+            Parser::new(Tokenizer::new().tokens(text))
+                .validate()
+                .unwrap()
+        });
+        assert_eq!(count, 0, "{}", &text[..20]);
+    }
+}
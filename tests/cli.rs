@@ -0,0 +1,262 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Run the binary with `args`, giving it `input` on stdin
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jsonp"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+/// A file under the temporary directory holding `contents`, named for the test
+fn scratch(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("jsonp-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn validate_agrees_with_the_stream() {
+    let invalid = [
+        "[1 2]",
+        r#"{"a":1]"#,
+        "[1]]",
+        "{} {}",
+        "[01]",
+        "[1.]",
+        r#"{"a":1,}"#,
+        "1 2",
+        "nul",
+    ];
+    let valid = [
+        r#"{"a":"x\"y"}"#,
+        "{\"a\":\r\n1}\r\n",
+        "[12345678901234567890]",
+        "-12345678901234567890",
+        "1",
+        "\"s\"",
+        "null",
+    ];
+    for (inputs, code) in [(&invalid[..], 1), (&valid[..], 0)] {
+        for input in inputs {
+            let tree = run(&["validate"], input);
+            let stream = run(&["validate", "--stream"], input);
+            assert_eq!(tree.status.code(), Some(code), "validate {:?}", input);
+            assert_eq!(
+                stream.status.code(),
+                Some(code),
+                "validate --stream {:?}",
+                input
+            );
+        }
+    }
+}
+
+#[test]
+fn compact_keeps_integers_beyond_i64() {
+    let input = "[12345678901234567890, -9223372036854775809]";
+    let expected = "[12345678901234567890,-9223372036854775809]\n";
+    assert_eq!(stdout(&run(&["compact"], input)), expected);
+    assert_eq!(stdout(&run(&["compact", "--stream"], input)), expected);
+    assert_eq!(
+        stdout(&run(&["compact"], "12345678901234567890")),
+        "12345678901234567890\n"
+    );
+}
+
+#[test]
+fn compact_rejects_what_it_could_not_write_back() {
+    for input in ["[1.]", "[01]", "[1 2]"] {
+        let output = run(&["compact"], input);
+        assert_eq!(output.status.code(), Some(1), "{:?}", input);
+        assert_eq!(stdout(&output), "");
+    }
+}
+
+#[test]
+fn compact_decodes_escapes_like_the_stream() {
+    let input = r#"{"a":"x\ny","b":"q\"uote","c":"\u0041"}"#;
+    let expected = "{\"a\":\"x\\ny\",\"b\":\"q\\\"uote\",\"c\":\"A\"}\n";
+    assert_eq!(stdout(&run(&["compact"], input)), expected);
+    assert_eq!(stdout(&run(&["compact", "--stream"], input)), expected);
+}
+
+#[test]
+fn show_prints_json_without_a_subcommand() {
+    let output = run(&["--color", "never"], r#"{"a":[1,"A"]}"#);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "{\n  \"a\": [\n    1,\n    \"A\"\n  ]\n}\n"
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn set_in_place_keeps_escapes() {
+    let source = "{\n  \"a\": \"x\\\\y \\\"q\\\" \\u0001\",\n  \"b\": \"tab\\tline\\n\"\n}\n";
+    let path = scratch("set-in-place.json", source);
+    let output = run(
+        &[
+            "set",
+            "-i",
+            "/a",
+            r#""back\\slash""#,
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let expected = source.replace(r#""x\\y \"q\" \u0001""#, r#""back\\slash""#);
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+
+    // Editing again leaves the escapes of the strings alone
+    let output = run(&["set", "-i", "/c", "1", path.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    let expected = expected.replace("\\n\"\n}", "\\n\",\n  \"c\": 1\n}");
+    assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn set_in_place_writes_over_the_old_value_only() {
+    let source = "{\n    \"tags\": [1, 2, \"]\"],\n    \"k\\\"q\": {\"a\": [1,\n          2]},\n    \"n\": 0\n}\n";
+    let path = scratch("set-in-place-splice.json", source);
+    let set = |pointer: &str, value: &str| {
+        let output = run(&["set", "-i", pointer, value, path.to_str().unwrap()], "");
+        assert_eq!(output.status.code(), Some(0));
+        fs::read_to_string(&path).unwrap()
+    };
+
+    let expected = source.replace("[1, 2,", "[1, [3],");
+    assert_eq!(set("/tags/1", "[3]"), expected);
+    let expected = expected.replace("[1,\n          2]", "{\n        \"b\": true\n    }");
+    assert_eq!(set("/k\"q/a", r#"{"b": true}"#), expected);
+    assert_eq!(set("/n", "1.5"), expected.replace("\"n\": 0", "\"n\": 1.5"));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn canonicalize_does_not_depend_on_escapes() {
+    let escaped = run(&["canonicalize"], r#"{"b":"\u0041\/","a":"\u00e9"}"#);
+    let plain = run(&["canonicalize"], r#"{"b":"A/","a":"é"}"#);
+    assert_eq!(stdout(&escaped), r#"{"a":"é","b":"A/"}"#);
+    assert_eq!(stdout(&escaped), stdout(&plain));
+}
+
+#[cfg(unix)]
+#[test]
+fn replacing_a_file_keeps_its_link_and_permissions() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let target = scratch("link-target.json", "{\"a\": 1}\n");
+    fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+    let link = target.with_file_name(format!("jsonp-{}-link.json", std::process::id()));
+    let _ = fs::remove_file(&link);
+    symlink(&target, &link).unwrap();
+
+    let output = run(&["set", "-i", "/a", "2", link.to_str().unwrap()], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = run(&["compact", "-o", link.to_str().unwrap()], "[3]");
+    assert_eq!(output.status.code(), Some(0));
+
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read_to_string(&target).unwrap(), "[3]\n");
+    let mode = fs::metadata(&target).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    fs::remove_file(link).unwrap();
+    fs::remove_file(target).unwrap();
+}
+
+#[test]
+fn json_diagnostics_come_from_the_error() {
+    let path = scratch("with space.json", "{\"a\": 1,\n  \"b\": }\n");
+    let output = run(
+        &["validate", "--error-format", "json", path.to_str().unwrap()],
+        "",
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let expected = format!(
+        "{{\"file\":\"{}\",\"line\":2,\"column\":8,\"offset\":16,\"code\":\"syntax\",\
+         \"message\":\"unexpected token while parsing object\",\"suggestion\":null}}\n",
+        path.display()
+    );
+    assert_eq!(stderr, expected);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn ndjson_diagnostics_have_offsets_into_the_file() {
+    let input = "{\"a\":1}\r\n{\"a\": tru}\n\n[1,\n";
+    let output = run(&["validate", "--ndjson", "--error-format", "json"], input);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{}", stderr);
+    assert!(
+        lines[0].contains(r#""line":2,"column":10,"offset":18,"#),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].contains(r#""line":4,"column":4,"offset":24,"#),
+        "{}",
+        lines[1]
+    );
+    assert_eq!(&input[18..19], "}");
+}
+
+#[test]
+fn fmt_check_ignores_color() {
+    let path = scratch("check-color.json", "{\n  \"a\": [\n    1\n  ]\n}\n");
+    let output = run(
+        &[
+            "fmt",
+            "--check",
+            "--color",
+            "always",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stdout(&output));
+    assert_eq!(stdout(&output), "");
+
+    // Only the diff of a file that is not formatted is colored
+    fs::write(&path, "{\"a\":[1]}\n").unwrap();
+    let output = run(
+        &[
+            "fmt",
+            "--check",
+            "--color",
+            "always",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let diff = stdout(&output);
+    assert!(diff.contains("\x1b["), "{:?}", diff);
+    assert!(diff.contains("\"a\": ["), "{:?}", diff);
+    fs::remove_file(path).unwrap();
+}